@@ -41,6 +41,12 @@ module LocStrings =
         let Desc2 = "There is no in-game UI that displays these, so try alt-tab if you forget them."
         let Reload = "Load (or reload) modelmod managed code, configuration, and mods"
         let ReloadMods = "Load (or reload) mods only"
+        let DrawTrace = "Start/stop recording a draw call trace (replay it with trace_replay)"
+        let DisableMod = "Disable the most recently drawn mod (remembered across restarts)"
+        let EnableMod = "Re-enable the most recently disabled mod"
+        let NextPreset = "Apply the next saved variant preset"
+        let SavePreset = "Save the current variant selections as a preset"
+        let LoadStatus = "Write mod load progress to the log"
         let Toggle = "Toggle mod display"
         let ClearTex = "Clear the active texture list (will be rebuilt from scene textures)"
         let SelectNextTex = "Select Previous Texture"
@@ -89,10 +95,13 @@ module ProfileText =
             LocStrings.Input.ReloadMods; LocStrings.Input.Toggle
             LocStrings.Input.ClearTex
             LocStrings.Input.SelectNextTex; LocStrings.Input.SelectPrevTex; LocStrings.Input.DoSnapshot
-            LocStrings.Input.Reload ]
+            LocStrings.Input.Reload; LocStrings.Input.DrawTrace
+            LocStrings.Input.DisableMod; LocStrings.Input.EnableMod
+            LocStrings.Input.NextPreset; LocStrings.Input.SavePreset
+            LocStrings.Input.LoadStatus ]
 
-        let PunctKeys = [@"\"; "]"; ";"; ","; "."; "/"; "-"]
-        let FKeys = ["F1"; "F2"; "F6"; "F3"; "F4"; "F7"; "F10"]
+        let PunctKeys = [@"\"; "]"; ";"; ","; "."; "/"; "-"; "F9"; "'"; "Shift+'"; "["; "Shift+["; "Shift+F9"]
+        let FKeys = ["F1"; "F2"; "F6"; "F3"; "F4"; "F7"; "F10"; "F9"; "F5"; "Shift+F5"; "F8"; "Shift+F8"; "Shift+F9"]
 
         let Descriptions =
             let makeInputDesc keys =
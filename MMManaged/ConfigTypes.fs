@@ -133,6 +133,11 @@ module ConfigTypes =
 
         member x.IsPackedVec() = vecEncoding.Trim().ToLowerInvariant() = "packed"
         member x.IsOctaVec() = vecEncoding.Trim().ToLowerInvariant() = "octa"
+        /// Encodings that the native code re-encodes from packed after the mod data is written
+        member x.IsNativeVec() =
+            match vecEncoding.Trim().ToLowerInvariant() with
+            | "octa_snorm16" | "octa_unorm8" | "spheremap" | "xy_snorm16" | "qtangent" -> true
+            | _ -> false
         override x.ToString() =
             sprintf "[SnapshotProfile: %s; pos: %A; uv: %A, fliptangent: %A, vecencoding: %A, blendindexincolor1: %A, blendweightincolor2: %A, adjustblendweights: %A]" name posX uvX flipTangent vecEncoding blendIndexInColor1 blendWeightInColor2 adjustBlendWeights
 
@@ -165,6 +165,13 @@ module InteropTypes =
         /// matches any VB whose prim/vert counts match (default behavior).
         [<MarshalAs(UnmanagedType.U1)>]
         VBChecksumSet: bool
+        /// Full path to the mod's mmobj file, if native code can fill the mod's buffers from it
+        /// directly (see ModDBInterop.nativeFillMeshPath).  Empty otherwise.
+        [<MarshalAs(UnmanagedType.ByValTStr, SizeConst=8192)>]
+        MeshPath: string
+        /// Whether blend data comes from the mod mesh (WeightMode.Mod).
+        [<MarshalAs(UnmanagedType.U1)>]
+        MeshBlendFromMod: bool
     }
 
     /// Default value.  Also used as an error return value, since we don't throw exceptions accross interop.
@@ -191,6 +198,8 @@ module InteropTypes =
         DataAvailable = false
         VBChecksum = 0u
         VBChecksumSet = false
+        MeshPath = ""
+        MeshBlendFromMod = false
     }
 
     [<Struct>]
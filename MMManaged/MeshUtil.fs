@@ -535,6 +535,38 @@ map_Kd $$filename
         )
         found <> None
 
+    /// Returns the unique vertices used by the triangles (in the order they are first used),
+    /// and for each triangle vertex, its index in that list.  This is the data for an index
+    /// buffer; without one every triangle needs three vertices of its own.
+    let uniqueTriVerts (tris:IndexedTri[]) =
+        let seen = new Dictionary<PTNIndex,int>()
+        let verts = new ResizeArray<PTNIndex>()
+        let indices =
+            tris |> Array.collect (fun tri ->
+                tri.Verts |> Array.map (fun v ->
+                    match seen.TryGetValue v with
+                    | true, idx -> idx
+                    | _ ->
+                        let idx = verts.Count
+                        seen.Add(v, idx)
+                        verts.Add(v)
+                        idx))
+        verts.ToArray(), indices
+
+    /// Size of an index element for a mesh with the specified number of vertices.  16 bit
+    /// indices are used when they are big enough.
+    let indexElemSize (vertCount:int) = if vertCount <= int UInt16.MaxValue then 2 else 4
+
+    /// Convert indices to index buffer data with the specified element size.
+    let indexBytes (elemSize:int) (indices:int[]) =
+        let bytes:byte[] = Array.zeroCreate (indices.Length * elemSize)
+        use bw = new BinaryWriter(new MemoryStream(bytes))
+        match elemSize with
+        | 2 -> indices |> Array.iter (fun i -> bw.Write(uint16 i))
+        | 4 -> indices |> Array.iter (fun i -> bw.Write(uint32 i))
+        | n -> failwithf "Unsupported index size: %d" n
+        bytes
+
     /// Read a mesh file, determining the file type by extension.  Currently
     /// only obj/mmobj are supported.
     let readFrom(filename,modType,flags) =
@@ -213,6 +213,25 @@ module ModDBInterop =
         | 5 -> Deletion
         | _ -> failwithf "value cannot be converted into a mod type: %A" ival
 
+    /// The mod's mesh path if native code can fill its buffers straight from the mmobj file,
+    /// otherwise "".  The native fill only handles D3D11, the default read flags (reversed transforms,
+    /// "addx" blend weight adjustment) and blend data from the mod mesh; it falls back to fillModData
+    /// for anything else.
+    let private nativeFillMeshPath (dbmod:DBMod) =
+        let flags = dbmod.MeshReadFlags
+        let abw = flags.AdjustBlendWeights.Trim().ToLowerInvariant()
+        let blendInColor =
+            match dbmod.Profile with
+            | Some(p) -> p.BlendIndexInColor1 || p.BlendWeightInColor2
+            | None -> false
+        if CoreState.Context = "d3d11"
+            && flags.ReverseTransform
+            && (abw = "" || abw = ConfigTypes.AdjustBlendWeightsDefault)
+            && not blendInColor then
+            dbmod.MeshPath
+        else
+            ""
+
     /// Get the MeshRel mod at the specified index.
     let private getMeshRelationMod i =
         let moddb = State.Data.Moddb
@@ -240,14 +259,16 @@ module ModDBInterop =
         let modType = modTypeToInt meshrel.DBMod.Type
 
         let primType = 4 //D3DPT_TRIANGLELIST // TODO11
-        let vertCount = if meshrel.IsBuilt then modm.Value.Positions.Length else 0
+        // mods are indexed, so the vertex count is the number of unique vertices used by the
+        // triangles; each triangle vertex has an index.
+        let vertCount = if meshrel.IsBuilt then (fst (MeshUtil.uniqueTriVerts modm.Value.Triangles)).Length else 0
         let primCount = if meshrel.IsBuilt then modm.Value.Triangles.Length else 0
-        let indexCount = 0
+        let indexCount = primCount * 3
         let refPrimCount = meshrel.DBRef.PrimCount
         let refVertCount = meshrel.DBRef.VertCount
         let declSizeBytes = declSize
         let vertSizeBytes = vertSize
-        let indexElemSizeBytes = 0
+        let indexElemSizeBytes = if meshrel.IsBuilt then MeshUtil.indexElemSize vertCount else 0
 
         let mname = meshrel.DBMod.Name
         let parentModName =
@@ -293,6 +314,8 @@ module ModDBInterop =
             DataAvailable = meshrel.IsBuilt
             VBChecksum = vbChecksum
             VBChecksumSet = vbChecksumSet
+            MeshPath = nativeFillMeshPath meshrel.DBMod
+            MeshBlendFromMod = meshrel.DBMod.WeightMode = WeightMode.Mod
         }
 
     let emptyMod = InteropTypes.EmptyModData
@@ -823,9 +846,11 @@ module ModDBInterop =
                     let elStr = vertElsToString elements
                     "d3d11", [||], elements, VBDataDiskCache.hashString elStr, 0
 
-            // copy index data...someday
-            if (destIbSize > 0) then
-                failwith "Filling index data is not yet supported"
+            // native code decides whether to use an index buffer.  if it doesn't provide one, a
+            // separate vertex is written for each triangle vertex.
+            let indexed = destIbSize > 0
+            if indexed && md.IndexElemSizeBytes <> 2 && md.IndexElemSizeBytes <> 4 then
+                failwithf "Invalid index size: %d" md.IndexElemSizeBytes
 
             // Build the VBData cache signature.  All inputs that influence the
             // bytes fillModData would write must be folded in here so we don't
@@ -835,6 +860,7 @@ module ModDBInterop =
                 Ref = BinCacheTypes.mkSig meshrel.DBRef.MeshPath ModType.Reference meshrel.DBRef.MeshReadFlags
                 VertSizeBytes = vertSizeBytes
                 VbSize = destVbSize
+                IbSize = destIbSize
                 DeclSize = declSize
                 DeclHash = declHash
                 Context = context
@@ -883,6 +909,9 @@ module ModDBInterop =
                 if entry.Vb.Length <> destVbSize then
                     failwithf "Cached vb size %d does not match dest vb size %d" entry.Vb.Length destVbSize
                 destVbBw.Write(entry.Vb)
+                if entry.Ib.Length <> destIbSize then
+                    failwithf "Cached ib size %d does not match dest ib size %d" entry.Ib.Length destIbSize
+                destIbBw.Write(entry.Ib)
                 0
             | None ->
 
@@ -910,6 +939,9 @@ module ModDBInterop =
                 | Some(profile) when profile.IsPackedVec() ->
                     log.Info "encoding vectors with packed format (some formats only)"
                     DataWriters.encVec <- DataEncoding.PackedVectorV1.encode
+                | Some(profile) when profile.IsNativeVec() ->
+                    log.Info "encoding vectors with packed format, native code will convert to %A (some formats only)" profile.VecEncoding
+                    DataWriters.encVec <- DataEncoding.PackedVectorV1.encode
                 | Some (profile) ->
                     log.Warn "possible unknown vector encoding %A, using packed (some formats only)" profile.VecEncoding
                     DataWriters.encVec <- DataEncoding.PackedVectorV1.encode
@@ -919,9 +951,9 @@ module ModDBInterop =
 
                 // copy vertex data.  this is where most of the work happens.
                 if (destVbSize > 0) then
-                    // we aren't using an index list, so we'll fill the buffer with vertices representing all the primitives.
-
-                    // walk the mod triangle list: for each point on each triangle, write a unique entry into the vb.
+                    // if we have an index buffer, write each unique vertex once and fill the index
+                    // buffer with the triangles.  otherwise fill the buffer with vertices representing
+                    // all the primitives: for each point on each triangle, write a unique entry into the vb.
                     // the following bools control where data comes from (normally we don't want to change these except
                     // when debugging something)
 
@@ -934,9 +966,21 @@ module ModDBInterop =
                     // false: copy bin/tan from the nearest ref in raw binary data.  mostly for debug.
                     let computeBinormalTangent = true
 
-                    let srcVbSize = md.PrimCount * 3 * vertSizeBytes
+                    let uniqueVerts,indices =
+                        if indexed then MeshUtil.uniqueTriVerts modm.Triangles
+                        else [||],[||]
+
+                    let srcVbSize =
+                        if indexed then uniqueVerts.Length * vertSizeBytes
+                        else md.PrimCount * 3 * vertSizeBytes
                     if (destVbSize <> srcVbSize) then
-                        failwithf "VB size src/dest mismatch: src: %d, dest: %d (prims: %A, vert size: %A)" srcVbSize destVbSize md.PrimCount vertSizeBytes
+                        failwithf "VB size src/dest mismatch: src: %d, dest: %d (prims: %A, unique verts: %A, vert size: %A)" srcVbSize destVbSize md.PrimCount uniqueVerts.Length vertSizeBytes
+
+                    let ibBuffer =
+                        if indexed then MeshUtil.indexBytes md.IndexElemSizeBytes indices
+                        else [||]
+                    if (destIbSize <> ibBuffer.Length) then
+                        failwithf "IB size src/dest mismatch: src: %d, dest: %d (indices: %A, index size: %A)" ibBuffer.Length destIbSize indices.Length md.IndexElemSizeBytes
 
                     // Fill into a managed buffer (one extra memcpy at the end vs.
                     // writing straight to the unmanaged dest, in exchange for being
@@ -1227,21 +1271,24 @@ module ModDBInterop =
                     // Write the three triangle verts to the buffer.
                     let writeTriangle (tri:IndexedTri) = tri.Verts |> Array.iter writeVertex
 
-                    // Write all the triangles to the buffer.
+                    // Write all the triangles (or the unique verts, if indexed) to the buffer.
                     let maxTri = modm.Triangles.Length
                     let mutable currTri  = 0
                     let doload() = 
                         use sw = new Util.StopwatchTracker(sprintf "fill mod: %A: " md.ModName)
                         let progressSw = System.Diagnostics.Stopwatch.StartNew()
-                        modm.Triangles |> Array.iter 
-                            (fun tri 
-                                -> 
-                                // don't log this unless its going slowly
-                                if currTri % 1000 = 0 && progressSw.ElapsedMilliseconds >= 1000L then 
-                                    log.Warn "slow fill: mod %A; tri %d/%d (%d%%)" md.ModName  currTri maxTri (int (float currTri / float maxTri * float 100))
-                                currTri <- currTri + 1
-                                writeTriangle tri
-                            )
+                        if indexed then
+                            uniqueVerts |> Array.iter writeVertex
+                        else
+                            modm.Triangles |> Array.iter 
+                                (fun tri 
+                                    -> 
+                                    // don't log this unless its going slowly
+                                    if currTri % 1000 = 0 && progressSw.ElapsedMilliseconds >= 1000L then 
+                                        log.Warn "slow fill: mod %A; tri %d/%d (%d%%)" md.ModName  currTri maxTri (int (float currTri / float maxTri * float 100))
+                                    currTri <- currTri + 1
+                                    writeTriangle tri
+                                )
                     doload()
 
                     if int64 destVbSize <> bw.BaseStream.Position then
@@ -1250,11 +1297,12 @@ module ModDBInterop =
 
                     // copy the filled managed buffer to the unmanaged dest in one shot
                     destVbBw.Write(vbBuffer)
+                    destIbBw.Write(ibBuffer)
 
                     // refresh the VBData disk cache so future loads can blit directly
                     if useBinCache then
                         try
-                            VBDataDiskCache.save binCacheDir meshrel.DBMod.Name meshrel.DBRef.Name vbSig srcDeclData vbBuffer
+                            VBDataDiskCache.save binCacheDir meshrel.DBMod.Name meshrel.DBRef.Name vbSig srcDeclData vbBuffer ibBuffer
                         with e -> log.Error "%A" e
                 0
         with
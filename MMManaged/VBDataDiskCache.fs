@@ -12,7 +12,7 @@ open CoreTypes
 open BinCacheTypes
 
 /// Binary disk cache for the bytes that fillModData writes into the
-/// vertex buffer (and, for DX9, the bytes written into the vertex
+/// vertex and index buffers (and, for DX9, the bytes written into the vertex
 /// declaration buffer).  When available, used instead of manual fill process,
 /// which can be quite slow on an underpowered laptop especially when running
 /// in proton.
@@ -22,16 +22,17 @@ open BinCacheTypes
 module VBDataDiskCache =
     let private log() = Logging.getLogger("VBDataDiskCache")
     let private ser = FsPickler.CreateBinarySerializer()
-    let private cacheVersion = 1
+    let private cacheVersion = 2
 
     /// Identifies every input that can affect the bytes fillModData
-    /// writes into the destination vertex buffer.  If any of these change
+    /// writes into the destination vertex and index buffers.  If any of these change
     /// the cache entry must be discarded.
     type VBDataSig = {
         Mod: MeshSig
         Ref: MeshSig
         VertSizeBytes: int
         VbSize: int
+        IbSize: int
         DeclSize: int
         DeclHash: string
         Context: string
@@ -47,6 +48,7 @@ module VBDataDiskCache =
         Sig: VBDataSig
         Decl: byte[]
         Vb: byte[]
+        Ib: byte[]
     }
 
     let private sha256Hex (bytes: byte[]) =
@@ -86,11 +88,12 @@ module VBDataDiskCache =
             if e.Version <> cacheVersion then None
             elif e.Sig <> sg then None
             elif e.Vb.Length <> sg.VbSize then None
+            elif e.Ib.Length <> sg.IbSize then None
             elif e.Decl.Length <> sg.DeclSize then None
             else Some e
 
     let save (cacheDir: string) (modName: string) (refName: string)
-             (sg: VBDataSig) (decl: byte[]) (vb: byte[]) =
+             (sg: VBDataSig) (decl: byte[]) (vb: byte[]) (ib: byte[]) =
         let dir = Path.Combine(cacheDir, "VBData")
         Directory.CreateDirectory(dir) |> ignore
         let p = relPath cacheDir modName refName sg.DeclHash sg.Context sg.VertSizeBytes
@@ -104,6 +107,7 @@ module VBDataDiskCache =
                 Sig = sg
                 Decl = decl
                 Vb = vb
+                Ib = ib
             }
 
         // this uses a seperate thread now since I observed a stack size exception in one game (apparently fspickler can consume a lot of stack space).
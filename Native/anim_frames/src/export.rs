@@ -0,0 +1,161 @@
+//! Conversions of an anim frame file into formats that are easier to work with outside of
+//! ModelMod: a yaml timeline of the captured registers, and per-frame bone matrices.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::frame_file::AnimFrameFile;
+
+#[derive(Serialize, Debug)]
+pub struct TimelineFrame {
+    pub frame: usize,
+    /// Milliseconds since the first frame
+    pub time_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<[f32; 4]>,
+    pub registers: BTreeMap<u32, [f32; 4]>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Timeline {
+    pub version: u32,
+    pub frame_count: usize,
+    pub duration_ms: u64,
+    pub frames: Vec<TimelineFrame>,
+}
+
+fn frame_times_ms(file: &AnimFrameFile) -> Vec<u64> {
+    let start = file.frames.first().map(|f| f.snapped_at);
+    file.frames.iter().map(|f| {
+        start.and_then(|s| f.snapped_at.duration_since(s).ok())
+            .unwrap_or(Duration::from_secs(0))
+            .as_millis() as u64
+    }).collect()
+}
+
+pub fn timeline(file: &AnimFrameFile, version: u32) -> Timeline {
+    let times = frame_times_ms(file);
+    let frames = file.frames.iter().zip(times.iter()).enumerate().map(|(frame, (f, time_ms))| {
+        TimelineFrame {
+            frame,
+            time_ms: *time_ms,
+            transform: f.transform1,
+            registers: f.floats.clone(),
+        }
+    }).collect();
+    Timeline {
+        version,
+        frame_count: file.frames.len(),
+        duration_ms: times.last().copied().unwrap_or(0),
+        frames,
+    }
+}
+
+pub fn timeline_yaml(file: &AnimFrameFile, version: u32) -> Result<String, String> {
+    serde_yaml::to_string(&timeline(file, version)).map_err(|e| format!("Serialization error: {:?}", e))
+}
+
+/// How bone matrices are laid out in the constant registers.  Each register holds one row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoneLayout {
+    /// First register of the first bone
+    pub start_register: u32,
+    /// Registers per bone: 3 for 4x3 matrices (the bottom row is taken to be 0,0,0,1), or 4
+    pub registers_per_bone: u32,
+    /// Number of bones.  If None, bones are read until a register is missing.
+    pub count: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BoneFrame {
+    pub frame: usize,
+    pub time_ms: u64,
+    /// Row major 4x4 matrices, one per bone
+    pub bones: Vec<[[f32; 4]; 4]>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BoneFrames {
+    pub bone_count: usize,
+    pub frames: Vec<BoneFrame>,
+}
+
+/// Extract bone matrices from each frame.  Frames missing any of the registers for a bone
+/// stop at the previous bone, so if `count` is given and some frames don't have all of the
+/// bones, an error is returned rather than producing ragged output.
+pub fn bone_matrices(file: &AnimFrameFile, layout: BoneLayout) -> Result<BoneFrames, String> {
+    if layout.registers_per_bone != 3 && layout.registers_per_bone != 4 {
+        return Err(format!("registers per bone must be 3 or 4, not {}", layout.registers_per_bone));
+    }
+    let times = frame_times_ms(file);
+    let mut frames = vec![];
+    for (frame, (f, time_ms)) in file.frames.iter().zip(times.iter()).enumerate() {
+        let mut bones = vec![];
+        loop {
+            if layout.count.map(|c| bones.len() as u32 >= c).unwrap_or(false) {
+                break;
+            }
+            let first = layout.start_register + bones.len() as u32 * layout.registers_per_bone;
+            let mut mat = [[0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+            let rows = (0..layout.registers_per_bone)
+                .map(|r| f.floats.get(&(first + r)))
+                .collect::<Option<Vec<_>>>();
+            match rows {
+                Some(rows) => {
+                    for (r, row) in rows.into_iter().enumerate() {
+                        mat[r] = *row;
+                    }
+                    bones.push(mat);
+                },
+                None => break,
+            }
+        }
+        if let Some(count) = layout.count {
+            if bones.len() as u32 != count {
+                return Err(format!("frame {} only has {} of {} bones", frame, bones.len(), count));
+            }
+        }
+        frames.push(BoneFrame { frame, time_ms: *time_ms, bones });
+    }
+    let bone_count = frames.iter().map(|f| f.bones.len()).min().unwrap_or(0);
+    // without an explicit count, trim to the bones that every frame has
+    for f in frames.iter_mut() {
+        f.bones.truncate(bone_count);
+    }
+    Ok(BoneFrames { bone_count, frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_file::tests::test_file;
+
+    #[test]
+    fn test_timeline() {
+        let file = test_file();
+        let tl = timeline(&file, 1);
+        assert_eq!((tl.frame_count, tl.duration_ms), (2, 33));
+        assert_eq!(tl.frames[1].time_ms, 33);
+        let yaml = timeline_yaml(&file, 1).unwrap();
+        assert!(yaml.contains("duration_ms: 33"), "{}", yaml);
+        assert!(yaml.contains("transform:"), "{}", yaml);
+    }
+
+    #[test]
+    fn test_bone_matrices() {
+        let file = test_file();
+        // 8 registers: 2 bones of 3 registers from 1, leaving one over
+        let bf = bone_matrices(&file, BoneLayout { start_register: 1, registers_per_bone: 3, count: None }).unwrap();
+        assert_eq!(bf.bone_count, 2);
+        assert_eq!(bf.frames[1].bones[1], [[104.0, 0.0, 0.0, 1.0], [105.0, 0.0, 0.0, 1.0],
+            [106.0, 0.0, 0.0, 1.0], [0.0, 0.0, 0.0, 1.0]]);
+
+        let bf = bone_matrices(&file, BoneLayout { start_register: 0, registers_per_bone: 4, count: Some(2) }).unwrap();
+        assert_eq!(bf.frames[0].bones[1][3], [7.0, 0.0, 0.0, 1.0]);
+        assert!(bone_matrices(&file, BoneLayout { start_register: 0, registers_per_bone: 4, count: Some(3) })
+            .unwrap_err().contains("only has 2 of 3"));
+        assert!(bone_matrices(&file, BoneLayout { start_register: 0, registers_per_bone: 2, count: None }).is_err());
+    }
+}
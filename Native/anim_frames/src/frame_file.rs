@@ -0,0 +1,138 @@
+//! The `animframes_{p}p_{v}v.dat` file format.  The body is a bincode encoded `AnimFrameFile`.
+//! Files written since the header was added start with `MAGIC` and a version number; older
+//! files are just the body, and are read as version 0.  The first 8 bytes of an old file are
+//! the frame count, which can't be mistaken for the magic.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+pub const MAGIC: &[u8; 4] = b"MMAF";
+/// Version written by `AnimFrameFile::to_bytes`.  Increment this if `AnimFrame` changes, and
+/// keep the old layout around so that old captures can still be read.
+pub const VERSION: u32 = 1;
+
+/// Vertex shader constants captured for one mesh in one frame.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnimFrame {
+    pub snapped_at: SystemTime,
+    /// Float constant registers that were set, by register number
+    pub floats: BTreeMap<u32, [f32; 4]>,
+    /// Player transform (x, y, z, rotation) at the time of capture
+    pub transform1: Option<[f32; 4]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AnimFrameFile {
+    pub frames: Vec<AnimFrame>,
+}
+
+impl AnimFrameFile {
+    pub fn new() -> Self {
+        Self { frames: vec![] }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        let body = bincode::serialize(self).map_err(|e| format!("Serialization error: {:?}", e))?;
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Parse a file, returning its format version along with the frames.
+    pub fn from_bytes(data: &[u8]) -> Result<(u32, Self), String> {
+        let (version, body) = if data.starts_with(MAGIC) {
+            let version = data.get(4..8)
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .ok_or("truncated header")?;
+            (version, &data[8..])
+        } else {
+            (0, data)
+        };
+        match version {
+            0 | 1 => {
+                let file = bincode::deserialize(body).map_err(|e| format!("Deserialization error: {:?}", e))?;
+                Ok((version, file))
+            },
+            v => Err(format!("unsupported anim frame file version {} (newest supported is {})", v, VERSION)),
+        }
+    }
+
+    pub fn write_to_file(&self, name: &str) -> Result<(), String> {
+        let bytes = self.to_bytes()?;
+        std::fs::write(name, bytes).map_err(|e| format!("failed to write {}: {}", name, e))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<(u32, Self), String> {
+        let data = std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        Self::from_bytes(&data)
+    }
+
+    /// All registers set in any frame.
+    pub fn registers(&self) -> Vec<u32> {
+        let mut regs = self.frames.iter().flat_map(|f| f.floats.keys().copied()).collect::<Vec<_>>();
+        regs.sort_unstable();
+        regs.dedup();
+        regs
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    pub(crate) fn test_file() -> AnimFrameFile {
+        let frame = |ms: u64, base: f32| AnimFrame {
+            snapped_at: SystemTime::UNIX_EPOCH + Duration::from_millis(1_600_000_000_000 + ms),
+            floats: (0..8).map(|r| (r, [base + r as f32, 0.0, 0.0, 1.0])).collect(),
+            transform1: Some([1.0, 2.0, 3.0, 0.5]),
+        };
+        AnimFrameFile { frames: vec![frame(0, 0.0), frame(33, 100.0)] }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file = test_file();
+        let bytes = file.to_bytes().unwrap();
+        assert_eq!(&bytes[0..4], MAGIC);
+        let (version, read) = AnimFrameFile::from_bytes(&bytes).unwrap();
+        assert_eq!(version, VERSION);
+        assert_eq!(read, file);
+        assert_eq!(read.registers(), (0..8).collect::<Vec<_>>());
+
+        let mut bytes = bytes;
+        bytes[4] = 99;
+        assert!(AnimFrameFile::from_bytes(&bytes).unwrap_err().contains("version 99"));
+        assert!(AnimFrameFile::from_bytes(&bytes[0..6]).is_err());
+    }
+
+    #[test]
+    fn test_read_headerless() {
+        // the layout the hook wrote before the header was added, where the registers were
+        // constant_tracking's Vec4 struct
+        #[derive(Serialize)]
+        struct Vec4 { a: f32, b: f32, c: f32, d: f32 }
+        #[derive(Serialize)]
+        struct OldFrame { snapped_at: SystemTime, floats: BTreeMap<u32, Vec4>, transform1: Option<Vec4> }
+        #[derive(Serialize)]
+        struct OldFile { frames: Vec<OldFrame> }
+
+        let snapped_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let old = OldFile { frames: vec![OldFrame {
+            snapped_at,
+            floats: [(4, Vec4 { a: 1.0, b: 2.0, c: 3.0, d: 4.0 })].into_iter().collect(),
+            transform1: None,
+        }]};
+        let (version, read) = AnimFrameFile::from_bytes(&bincode::serialize(&old).unwrap()).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(read.frames, vec![AnimFrame {
+            snapped_at,
+            floats: [(4, [1.0, 2.0, 3.0, 4.0])].into_iter().collect(),
+            transform1: None,
+        }]);
+    }
+}
@@ -0,0 +1,10 @@
+//! Animation frame captures.  When snapshotting an animation the hook records the vertex shader
+//! constants of the animated meshes every frame, and writes them to `animframes_{p}p_{v}v.dat`
+//! files in the snapshot directory.  This crate has the file format, so that it can be read
+//! outside of the hook, and the conversions used by the `anim_frames` tool.
+
+mod export;
+mod frame_file;
+
+pub use crate::export::*;
+pub use crate::frame_file::*;
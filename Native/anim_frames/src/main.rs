@@ -0,0 +1,95 @@
+//! Reads the `animframes_{p}p_{v}v.dat` files written by animation snapshots.
+//!
+//! Usage: `anim_frames [--yaml <out.yaml>] [--bones <out.json> --bone-start R [--bone-regs 3|4] [--bone-count N]] <file.dat>`
+//!
+//! Without options, prints a summary of the capture.  `--yaml` writes a timeline of all the
+//! captured registers.  `--bones` writes json with a list of 4x4 row major bone matrices for
+//! each frame, read from registers starting at `--bone-start`, which can be loaded by the
+//! Blender scripts.  Bones are 3 registers each (4x3) unless `--bone-regs 4` is given, and are
+//! read until a register is missing unless `--bone-count` is given.
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use anim_frames::*;
+
+fn usage() -> ! {
+    eprintln!("usage: anim_frames [--yaml <out.yaml>] [--bones <out.json> --bone-start R [--bone-regs 3|4] [--bone-count N]] <file.dat>");
+    exit(1);
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut input: Option<PathBuf> = None;
+    let mut yaml_out: Option<PathBuf> = None;
+    let mut bones_out: Option<PathBuf> = None;
+    let mut bone_start: Option<u32> = None;
+    let mut bone_regs: u32 = 3;
+    let mut bone_count: Option<u32> = None;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--yaml" => {
+                i += 1;
+                yaml_out = Some(args.get(i).unwrap_or_else(|| usage()).into());
+            },
+            "--bones" => {
+                i += 1;
+                bones_out = Some(args.get(i).unwrap_or_else(|| usage()).into());
+            },
+            "--bone-start" => {
+                i += 1;
+                bone_start = Some(args.get(i).and_then(|r| r.parse().ok()).unwrap_or_else(|| usage()));
+            },
+            "--bone-regs" => {
+                i += 1;
+                bone_regs = args.get(i).and_then(|r| r.parse().ok()).unwrap_or_else(|| usage());
+            },
+            "--bone-count" => {
+                i += 1;
+                bone_count = Some(args.get(i).and_then(|r| r.parse().ok()).unwrap_or_else(|| usage()));
+            },
+            "-h" | "--help" => usage(),
+            a if a.starts_with("--") => {
+                eprintln!("unknown option: {}", a);
+                usage();
+            },
+            a if input.is_none() => input = Some(PathBuf::from(a)),
+            _ => usage(),
+        }
+        i += 1;
+    }
+    let input = input.unwrap_or_else(|| usage());
+    if bones_out.is_some() && bone_start.is_none() {
+        eprintln!("--bones requires --bone-start");
+        usage();
+    }
+
+    let (version, file) = AnimFrameFile::read_from_file(&input).unwrap_or_else(|e| fail(&e));
+    let tl = timeline(&file, version);
+    let regs = file.registers();
+    println!("{}: version {}, {} frames over {} ms", input.display(), version, tl.frame_count, tl.duration_ms);
+    match (regs.first(), regs.last()) {
+        (Some(first), Some(last)) => println!("{} registers set, c{} to c{}", regs.len(), first, last),
+        _ => println!("no registers set"),
+    }
+
+    if let Some(out) = yaml_out {
+        let yaml = timeline_yaml(&file, version).unwrap_or_else(|e| fail(&e));
+        std::fs::write(&out, yaml).unwrap_or_else(|e| fail(&format!("failed to write {}: {}", out.display(), e)));
+        println!("wrote timeline to {}", out.display());
+    }
+
+    if let (Some(out), Some(start_register)) = (bones_out, bone_start) {
+        let layout = BoneLayout { start_register, registers_per_bone: bone_regs, count: bone_count };
+        let bones = bone_matrices(&file, layout).unwrap_or_else(|e| fail(&e));
+        let json = serde_json::to_string(&bones).unwrap_or_else(|e| fail(&format!("Serialization error: {:?}", e)));
+        std::fs::write(&out, json).unwrap_or_else(|e| fail(&format!("failed to write {}: {}", out.display(), e)));
+        println!("wrote {} bones for {} frames to {}", bones.bone_count, bones.frames.len(), out.display());
+    }
+}
@@ -0,0 +1,188 @@
+//! D3D11 constant buffers.  DX11 has no constant registers to read back, instead the context
+//! hooks keep a copy of each constant buffer (when precopying data), and the buffers that are
+//! bound to the shaders at snapshot time are written out as binary files along with a yaml
+//! description.  If the shader's resource definitions are available, the buffers are labeled
+//! with their names and variable layouts.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use shared_dx::error::*;
+use shared_dx::util;
+
+use crate::constant_tracking::{vecToVec4, Vec4};
+
+/// Copy of a constant buffer that was bound to a shader slot.
+pub struct BoundConstantBuffer {
+    pub slot: u32,
+    pub data: Vec<u8>,
+}
+
+/// The constant buffers bound to a shader stage.
+#[derive(Default)]
+pub struct ConstantBufferGroup {
+    pub buffers: Vec<BoundConstantBuffer>,
+}
+
+#[derive(Serialize)]
+pub struct ConstantBufferVariable {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+    #[serde(rename = "type")]
+    pub var_type: String,
+}
+
+#[derive(Serialize)]
+pub struct ConstantBufferEntry {
+    pub slot: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub size: usize,
+    /// Name of the file with the raw buffer data, relative to the snapshot directory
+    pub file: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<ConstantBufferVariable>,
+    /// Buffer contents as float4 registers, see `buffer_registers`
+    pub floats: BTreeMap<u32, Vec4<f32>>,
+}
+
+#[derive(Serialize)]
+pub struct ConstantBufferFile {
+    pub buffers: Vec<ConstantBufferEntry>,
+}
+
+/// Split buffer contents into float4 registers (16 bytes each), like the DX9 float constants.
+/// A trailing partial register is dropped.
+pub fn buffer_registers(data:&[u8]) -> BTreeMap<u32, Vec4<f32>> {
+    let floats = data.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<_>>();
+    (0..floats.len() / 4)
+        .map(|reg| (reg as u32, vecToVec4(&floats, reg * 4)))
+        .collect()
+}
+
+/// Describe the buffers in `group`, naming them from `rdef` where possible.  `bin_prefix` is the
+/// file name prefix of the binary files, which are named `<bin_prefix><slot>.bin`.
+pub fn constant_buffer_file(group:&ConstantBufferGroup, rdef:Option<&dxbc::ResourceDefs>, bin_prefix:&str) -> ConstantBufferFile {
+    let buffers = group.buffers.iter().map(|buf| {
+        let cbuffer = rdef.and_then(|rdef| {
+            rdef.bindings.iter()
+                .find(|b| b.input_type == 0 && buf.slot >= b.bind_point && buf.slot < b.bind_point + b.bind_count.max(1))
+                .and_then(|b| rdef.constant_buffers.iter().find(|cb| cb.name == b.name))
+        });
+        let variables = cbuffer.map(|cb| cb.variables.iter().map(|v| ConstantBufferVariable {
+            name: v.name.clone(),
+            offset: v.offset,
+            size: v.size,
+            var_type: v.var_type.name(),
+        }).collect()).unwrap_or_default();
+
+        let floats = buffer_registers(&buf.data);
+
+        ConstantBufferEntry {
+            slot: buf.slot,
+            name: cbuffer.map(|cb| cb.name.clone()),
+            size: buf.data.len(),
+            file: format!("{}{}.bin", bin_prefix, buf.slot),
+            variables,
+            floats,
+        }
+    }).collect();
+    ConstantBufferFile { buffers }
+}
+
+fn write_group(snap_dir:&str, snap_prefix:&str, stage:&str, group:&ConstantBufferGroup, rdef:Option<&dxbc::ResourceDefs>) -> Result<()> {
+    use std::io::Write;
+
+    let bin_prefix = format!("{}_{}cb", snap_prefix, stage);
+    for buf in group.buffers.iter() {
+        let out = format!("{}/{}{}.bin", snap_dir, bin_prefix, buf.slot);
+        std::fs::File::create(&out)?.write_all(&buf.data)?;
+    }
+
+    let file = constant_buffer_file(group, rdef, &bin_prefix);
+    let s = serde_yaml::to_string(&file).map_err(|e| {
+        HookError::SerdeError(format!("Serialization error: {:?}", e))
+    })?;
+    let out = format!("{}/{}_{}const.yaml", snap_dir, snap_prefix, stage);
+    util::write_log_file(&format!("saving {} constant buffers to file: {}", group.buffers.len(), out));
+    std::fs::File::create(&out)?.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+/// Save the constant buffers bound to the vertex and pixel shaders.  The resource definitions
+/// come from the shaders saved with the snapshot, if they could be parsed.
+pub fn take_snapshot_d3d11(snap_dir:&str, snap_prefix:&str,
+    vconst:&Option<ConstantBufferGroup>, vs_rdef:Option<&dxbc::ResourceDefs>,
+    pconst:&Option<ConstantBufferGroup>, ps_rdef:Option<&dxbc::ResourceDefs>) {
+    if !crate::is_enabled() {
+        return;
+    }
+    if snap_dir == "" || snap_prefix == "" {
+        util::write_log_file(&format!("ERROR: no directory set, can't save shader constants"));
+        return;
+    }
+    vconst.as_ref().map(|vconst| {
+        write_group(snap_dir, snap_prefix, "v", vconst, vs_rdef).unwrap_or_else(|e| {
+            util::write_log_file(&format!("ERROR: failed to write vertex constants: {:?}", e));
+        });
+    });
+    pconst.as_ref().map(|pconst| {
+        write_group(snap_dir, snap_prefix, "p", pconst, ps_rdef).unwrap_or_else(|e| {
+            util::write_log_file(&format!("ERROR: failed to write pixel constants: {:?}", e));
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxbc::{ConstantBuffer, ResourceBinding, ResourceDefs, Variable, VariableType};
+
+    #[test]
+    fn test_constant_buffer_file() {
+        let floats:Vec<u8> = (0..20).flat_map(|f| (f as f32).to_le_bytes()).collect();
+        let group = ConstantBufferGroup {
+            buffers: vec![
+                BoundConstantBuffer { slot: 0, data: floats[0..64].to_vec() },
+                BoundConstantBuffer { slot: 3, data: floats.clone() },
+            ]
+        };
+        let rdef = ResourceDefs {
+            constant_buffers: vec![ConstantBuffer {
+                name: "Bones".to_owned(),
+                size: 64,
+                variables: vec![Variable {
+                    name: "boneMatrix".to_owned(),
+                    offset: 0,
+                    size: 64,
+                    var_type: VariableType { class: 3, base_type: 3, rows: 4, columns: 4, elements: 0 },
+                }],
+            }],
+            bindings: vec![ResourceBinding { name: "Bones".to_owned(), input_type: 0, dimension: 0,
+                bind_point: 0, bind_count: 1 }],
+            creator: "".to_owned(),
+        };
+
+        let file = constant_buffer_file(&group, Some(&rdef), "snap_vcb");
+        assert_eq!(file.buffers.len(), 2);
+        let cb = &file.buffers[0];
+        assert_eq!(cb.name.as_deref(), Some("Bones"));
+        assert_eq!(cb.file, "snap_vcb0.bin");
+        assert_eq!(cb.variables[0].var_type, "float4x4");
+        assert_eq!(cb.floats.len(), 4);
+        assert_eq!(cb.floats[&1], vecToVec4(&vec![4.0, 5.0, 6.0, 7.0], 0));
+        // not in the shader's bindings
+        let cb = &file.buffers[1];
+        assert_eq!(cb.name, None);
+        assert!(cb.variables.is_empty());
+        assert_eq!(cb.size, 80);
+        assert_eq!(cb.floats.len(), 5);
+
+        let yaml = serde_yaml::to_string(&file).unwrap();
+        assert!(yaml.contains("name: Bones"), "{}", yaml);
+        assert!(yaml.contains("type: float4x4"), "{}", yaml);
+    }
+}
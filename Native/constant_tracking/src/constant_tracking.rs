@@ -34,6 +34,11 @@ pub struct Vec4<T: Serialize> {
     d: T
 }
 
+impl<T: Copy + Serialize> Vec4<T> {
+    pub fn to_array(&self) -> [T; 4] {
+        [self.a, self.b, self.c, self.d]
+    }
+}
 
 pub fn vecToVec4<T>(vec:&Vec<T>, offset: usize) -> Vec4<T>
 where T: Copy + serde::Serialize {
@@ -124,6 +129,11 @@ where T: FromOffset<*const S> + serde::ser::Serialize + Copy
             }
         }
 
+    /// The value of a register, if it was set.
+    pub fn get(&self, reg: UINT) -> Option<T> {
+        self.list.get(reg as usize).copied().flatten()
+    }
+
     pub fn get_as_btree(&self) -> std::collections::BTreeMap<UINT, T>
     where T: Copy
     {
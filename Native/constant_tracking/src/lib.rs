@@ -2,5 +2,7 @@
 #![allow(clippy::all)]
 
 mod constant_tracking;
+mod constant_buffers;
 
 pub use crate::constant_tracking::*;
+pub use crate::constant_buffers::*;
@@ -84,7 +84,7 @@ struct CLRGlobalState {
     runtime_host: *mut ICLRRuntimeHost
 }
 
-const NATIVE_CODE_VERSION:i32 = 6;
+const NATIVE_CODE_VERSION:i32 = 7;
 
 static mut CLR_GLOBAL_STATE: CLRGlobalState = CLRGlobalState {
     runtime_host: null_mut()
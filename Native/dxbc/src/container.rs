@@ -0,0 +1,143 @@
+//! The DXBC container: a header followed by a list of chunks, each tagged with a fourcc
+//! (`ISGN` for the input signature, `SHEX` for the shader code and so on).
+
+pub const DXBC_MAGIC: &[u8; 4] = b"DXBC";
+/// magic, 16 byte checksum, version, total size, chunk count
+const HEADER_SIZE: usize = 32;
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    offset.checked_add(4).and_then(|end| data.get(offset..end))
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("offset {} out of range (size {})", offset, data.len()))
+}
+
+/// Read a null terminated string starting at `offset`.
+pub(crate) fn read_cstr(data: &[u8], offset: usize) -> Result<String, String> {
+    let tail = data.get(offset..)
+        .ok_or_else(|| format!("string offset {} out of range (size {})", offset, data.len()))?;
+    let end = tail.iter().position(|b| *b == 0)
+        .ok_or_else(|| format!("unterminated string at offset {}", offset))?;
+    Ok(String::from_utf8_lossy(&tail[..end]).into_owned())
+}
+
+/// The checksum in the container header.  The compiler computes it from the rest of the
+/// container, so it identifies the shader without hashing the bytecode.
+pub fn checksum(data: &[u8]) -> Option<[u8; 16]> {
+    if data.len() < HEADER_SIZE || &data[0..4] != DXBC_MAGIC {
+        return None;
+    }
+    let mut sum = [0_u8; 16];
+    sum.copy_from_slice(&data[4..20]);
+    Some(sum)
+}
+
+pub struct Chunk<'a> {
+    pub fourcc: [u8; 4],
+    pub data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).into_owned()
+    }
+}
+
+pub struct Container<'a> {
+    pub chunks: Vec<Chunk<'a>>,
+}
+
+impl<'a> Container<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE || &data[0..4] != DXBC_MAGIC {
+            return Err("not a DXBC container".to_owned());
+        }
+        let total = read_u32(data, 24)? as usize;
+        if total > data.len() {
+            return Err(format!("container size {} is larger than the data ({})", total, data.len()));
+        }
+        let data = &data[0..total];
+        let count = read_u32(data, 28)? as usize;
+        let mut chunks = Vec::with_capacity(count.min(64));
+        for i in 0..count {
+            let offset = read_u32(data, HEADER_SIZE + i * 4)? as usize;
+            let out_of_range = || format!("chunk {} (offset {}) is out of range", i, offset);
+            // offsets come from the file, so don't let them wrap on 32 bit
+            let size_pos = offset.checked_add(4).ok_or_else(out_of_range)?;
+            let size = read_u32(data, size_pos)? as usize;
+            let chunk_data = offset.checked_add(8)
+                .and_then(|start| Some(start..start.checked_add(size)?))
+                .and_then(|range| data.get(range))
+                .ok_or_else(|| format!("chunk {} (offset {}, size {}) is out of range", i, offset, size))?;
+            let mut fourcc = [0_u8; 4];
+            fourcc.copy_from_slice(&data[offset..size_pos]);
+            chunks.push(Chunk { fourcc, data: chunk_data });
+        }
+        Ok(Container { chunks })
+    }
+
+    /// The first chunk with one of the given fourccs.
+    pub fn chunk(&self, fourccs: &[&[u8; 4]]) -> Option<&Chunk<'a>> {
+        self.chunks.iter().find(|c| fourccs.iter().any(|f| c.fourcc == **f))
+    }
+}
+
+/// Build a container from chunks.  The checksum is left zeroed, since nothing here checks it.
+#[cfg(test)]
+pub(crate) fn build_container(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(DXBC_MAGIC);
+    out.extend_from_slice(&[0; 16]);
+    out.extend_from_slice(&1_u32.to_le_bytes());
+    let total_pos = out.len();
+    out.extend_from_slice(&0_u32.to_le_bytes());
+    out.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    let offsets_pos = out.len();
+    out.resize(offsets_pos + chunks.len() * 4, 0);
+    for (i, (fourcc, data)) in chunks.iter().enumerate() {
+        let offset = out.len() as u32;
+        out[offsets_pos + i * 4..offsets_pos + i * 4 + 4].copy_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(*fourcc);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    let total = out.len() as u32;
+    out[total_pos..total_pos + 4].copy_from_slice(&total.to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_container() {
+        let data = build_container(&[(b"ISGN", vec![1, 2, 3, 4]), (b"SHEX", vec![5; 8])]);
+        let c = Container::parse(&data).unwrap();
+        assert_eq!(c.chunks.len(), 2);
+        assert_eq!(c.chunks[0].name(), "ISGN");
+        assert_eq!(c.chunks[0].data, &[1, 2, 3, 4]);
+        assert_eq!(c.chunk(&[b"OSGN", b"SHEX"]).unwrap().data, &[5; 8]);
+        assert!(c.chunk(&[b"RDEF"]).is_none());
+
+        assert!(Container::parse(b"DXBD").is_err());
+        let mut summed = data.clone();
+        summed[4..20].copy_from_slice(&[7; 16]);
+        assert_eq!(checksum(&summed), Some([7; 16]));
+        assert_eq!(checksum(b"DXBD"), None);
+        // truncated
+        assert!(Container::parse(&data[0..data.len() - 1]).is_err());
+        // chunk offset out of range
+        let mut bad = data.clone();
+        bad[32..36].copy_from_slice(&1000_u32.to_le_bytes());
+        assert!(Container::parse(&bad).is_err());
+        // offset and size that would overflow when added
+        let mut bad = data.clone();
+        bad[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Container::parse(&bad).err().unwrap().contains("out of range"));
+        let mut bad = data.clone();
+        let size_pos = u32::from_le_bytes([bad[32], bad[33], bad[34], bad[35]]) as usize + 4;
+        bad[size_pos..size_pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Container::parse(&bad).err().unwrap().contains("size 4294967295"));
+        assert!(read_u32(&data, usize::MAX - 1).is_err());
+    }
+}
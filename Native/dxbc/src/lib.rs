@@ -0,0 +1,17 @@
+//! Parsing for compiled D3D11 shaders (DXBC containers).  DX9 shaders can be disassembled
+//! with d3dx, but for DX11 we only have the bytecode, so this reads the parts of it that are
+//! useful for snapshots: the input and output signatures (`ISGN`/`OSGN`), the constant buffers
+//! and resource bindings (`RDEF`) and the shader version (`SHEX`).  It has no platform
+//! dependencies so it can be tested anywhere; `testdata` has shaders compiled with fxc.
+
+mod container;
+mod rdef;
+mod shader_info;
+mod shex;
+mod signature;
+
+pub use crate::container::*;
+pub use crate::rdef::*;
+pub use crate::shader_info::*;
+pub use crate::shex::*;
+pub use crate::signature::*;
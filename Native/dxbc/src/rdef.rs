@@ -0,0 +1,239 @@
+//! Resource definitions (`RDEF` chunk): the constant buffers a shader uses, with their
+//! variables, and the resources (textures, samplers, buffers) bound to it.
+
+use crate::container::{read_cstr, read_u32, Chunk};
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    offset.checked_add(2).and_then(|end| data.get(offset..end))
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format!("offset {} out of range (size {})", offset, data.len()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableType {
+    pub class: u16,
+    pub base_type: u16,
+    pub rows: u16,
+    pub columns: u16,
+    pub elements: u16,
+}
+
+impl VariableType {
+    /// HLSL style name of the type, e.g. `float4x4` or `int2[4]`.
+    pub fn name(&self) -> String {
+        let base = match self.base_type {
+            0 => "void".to_owned(),
+            1 => "bool".to_owned(),
+            2 => "int".to_owned(),
+            3 => "float".to_owned(),
+            19 => "uint".to_owned(),
+            20 => "uint8".to_owned(),
+            39 => "double".to_owned(),
+            n => format!("type{}", n),
+        };
+        let name = match self.class {
+            0 => base,
+            1 => format!("{}{}", base, self.columns),
+            2 | 3 => format!("{}{}x{}", base, self.rows, self.columns),
+            5 => "struct".to_owned(),
+            _ => "object".to_owned(),
+        };
+        if self.elements > 0 {
+            format!("{}[{}]", name, self.elements)
+        } else {
+            name
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// Byte offset in the constant buffer
+    pub offset: u32,
+    pub size: u32,
+    pub var_type: VariableType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstantBuffer {
+    pub name: String,
+    pub size: u32,
+    pub variables: Vec<Variable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceBinding {
+    pub name: String,
+    pub input_type: u32,
+    pub dimension: u32,
+    pub bind_point: u32,
+    pub bind_count: u32,
+}
+
+impl ResourceBinding {
+    pub fn input_type_name(&self) -> &'static str {
+        match self.input_type {
+            0 => "cbuffer",
+            1 => "tbuffer",
+            2 => "texture",
+            3 => "sampler",
+            4 => "uav_rwtyped",
+            5 => "structured",
+            6 => "uav_rwstructured",
+            7 => "byteaddress",
+            8 => "uav_rwbyteaddress",
+            9 => "uav_append_structured",
+            10 => "uav_consume_structured",
+            11 => "uav_rwstructured_with_counter",
+            _ => "unknown",
+        }
+    }
+
+    pub fn dimension_name(&self) -> &'static str {
+        match self.dimension {
+            1 => "buffer",
+            2 => "texture1d",
+            3 => "texture1darray",
+            4 => "texture2d",
+            5 => "texture2darray",
+            6 => "texture2dms",
+            7 => "texture2dmsarray",
+            8 => "texture3d",
+            9 => "texturecube",
+            10 => "texturecubearray",
+            11 => "bufferex",
+            _ => "unknown",
+        }
+    }
+
+    /// The register this is bound to, e.g. `t0` for a texture.
+    pub fn register(&self) -> String {
+        let prefix = match self.input_type {
+            0 => "b",
+            3 => "s",
+            4 | 6 | 8 | 9 | 10 | 11 => "u",
+            _ => "t",
+        };
+        format!("{}{}", prefix, self.bind_point)
+    }
+
+    pub fn is_texture(&self) -> bool {
+        self.input_type == 2
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceDefs {
+    pub constant_buffers: Vec<ConstantBuffer>,
+    pub bindings: Vec<ResourceBinding>,
+    pub creator: String,
+}
+
+const BINDING_SIZE: usize = 32;
+const CBUFFER_SIZE: usize = 24;
+
+impl ResourceDefs {
+    pub fn parse(chunk: &Chunk) -> Result<Self, String> {
+        if &chunk.fourcc != b"RDEF" {
+            return Err(format!("{} is not a resource definition chunk", chunk.name()));
+        }
+        let data = chunk.data;
+        let cb_count = read_u32(data, 0)? as usize;
+        let cb_offset = read_u32(data, 4)? as usize;
+        let rb_count = read_u32(data, 8)? as usize;
+        let rb_offset = read_u32(data, 12)? as usize;
+        let major = data.get(17).copied().ok_or("truncated resource definitions")?;
+        let creator = read_cstr(data, read_u32(data, 24)? as usize)?;
+        // shader model 5 adds texture and sampler fields to the variables
+        let var_size = if major >= 5 { 40 } else { 24 };
+
+        let mut bindings = Vec::with_capacity(rb_count.min(128));
+        for i in 0..rb_count {
+            let off = rb_offset + i * BINDING_SIZE;
+            bindings.push(ResourceBinding {
+                name: read_cstr(data, read_u32(data, off)? as usize)?,
+                input_type: read_u32(data, off + 4)?,
+                dimension: read_u32(data, off + 12)?,
+                bind_point: read_u32(data, off + 20)?,
+                bind_count: read_u32(data, off + 24)?,
+            });
+        }
+
+        let mut constant_buffers = Vec::with_capacity(cb_count.min(128));
+        for i in 0..cb_count {
+            let off = cb_offset + i * CBUFFER_SIZE;
+            let var_count = read_u32(data, off + 4)? as usize;
+            let var_offset = read_u32(data, off + 8)? as usize;
+            let mut variables = Vec::with_capacity(var_count.min(256));
+            for v in 0..var_count {
+                let voff = var_offset + v * var_size;
+                let toff = read_u32(data, voff + 16)? as usize;
+                variables.push(Variable {
+                    name: read_cstr(data, read_u32(data, voff)? as usize)?,
+                    offset: read_u32(data, voff + 4)?,
+                    size: read_u32(data, voff + 8)?,
+                    var_type: VariableType {
+                        class: read_u16(data, toff)?,
+                        base_type: read_u16(data, toff + 2)?,
+                        rows: read_u16(data, toff + 4)?,
+                        columns: read_u16(data, toff + 6)?,
+                        elements: read_u16(data, toff + 8)?,
+                    },
+                });
+            }
+            constant_buffers.push(ConstantBuffer {
+                name: read_cstr(data, read_u32(data, off)? as usize)?,
+                size: read_u32(data, off + 12)?,
+                variables,
+            });
+        }
+
+        Ok(ResourceDefs { constant_buffers, bindings, creator })
+    }
+
+    /// Bindings for textures (not samplers or buffers).
+    pub fn textures(&self) -> impl Iterator<Item = &ResourceBinding> {
+        self.bindings.iter().filter(|b| b.is_texture())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::Container;
+
+    #[test]
+    fn test_rdef() {
+        let data = include_bytes!("../testdata/shape_vshader.cso");
+        let c = Container::parse(data).unwrap();
+        let rdef = ResourceDefs::parse(c.chunk(&[b"RDEF"]).unwrap()).unwrap();
+        assert!(rdef.creator.contains("HLSL"), "{}", rdef.creator);
+        assert_eq!(rdef.constant_buffers.len(), 2);
+        let cb = &rdef.constant_buffers[0];
+        assert_eq!((cb.name.as_str(), cb.size), ("MatrixBuffer", 64));
+        assert_eq!(cb.variables.len(), 1);
+        assert_eq!((cb.variables[0].name.as_str(), cb.variables[0].offset, cb.variables[0].size),
+            ("modelViewProjection", 0, 64));
+        assert_eq!(cb.variables[0].var_type.name(), "float4x4");
+        let cb = &rdef.constant_buffers[1];
+        assert_eq!(cb.name, "NormalMatrixBuffer");
+        assert_eq!(cb.variables[0].var_type.name(), "float3x3");
+        let regs = rdef.bindings.iter().map(|b| (b.name.as_str(), b.register())).collect::<Vec<_>>();
+        assert_eq!(regs, [("MatrixBuffer", "b0".to_owned()), ("NormalMatrixBuffer", "b2".to_owned())]);
+        assert_eq!(rdef.textures().count(), 0);
+
+        let data = include_bytes!("../testdata/shape_pshader.cso");
+        let c = Container::parse(data).unwrap();
+        let rdef = ResourceDefs::parse(c.chunk(&[b"RDEF"]).unwrap()).unwrap();
+        let tex = rdef.textures().collect::<Vec<_>>();
+        assert_eq!(tex.len(), 1);
+        assert_eq!((tex[0].name.as_str(), tex[0].register(), tex[0].dimension_name()),
+            ("diffuseTexture", "t0".to_owned(), "texture2d"));
+        let sampler = rdef.bindings.iter().find(|b| b.input_type_name() == "sampler").unwrap();
+        assert_eq!((sampler.name.as_str(), sampler.register()), ("samplerState", "s0".to_owned()));
+        let my_consts = rdef.constant_buffers.iter().find(|cb| cb.name == "MyConstants").unwrap();
+        let types = my_consts.variables.iter().map(|v| (v.name.as_str(), v.offset, v.var_type.name())).collect::<Vec<_>>();
+        assert_eq!(types, [("useTexture", 0, "bool".to_owned()), ("_padding2", 4, "float3".to_owned())]);
+    }
+}
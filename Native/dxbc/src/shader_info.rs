@@ -0,0 +1,130 @@
+//! Everything this crate knows how to read from a shader, gathered up for writing alongside
+//! snapshots.
+
+use std::fmt::Write;
+
+use crate::container::Container;
+use crate::rdef::ResourceDefs;
+use crate::shex::{shader_code, ShaderCode};
+use crate::signature::{input_signature, mask_string, output_signature, Signature};
+
+#[derive(Debug, Clone, Default)]
+pub struct ShaderInfo {
+    pub chunks: Vec<String>,
+    pub code: Option<ShaderCode>,
+    pub inputs: Option<Signature>,
+    pub outputs: Option<Signature>,
+    pub resources: Option<ResourceDefs>,
+}
+
+impl ShaderInfo {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let container = Container::parse(data)?;
+        let resources = container.chunk(&[b"RDEF"]).map(ResourceDefs::parse).transpose()?;
+        Ok(ShaderInfo {
+            chunks: container.chunks.iter().map(|c| c.name()).collect(),
+            code: shader_code(&container)?,
+            inputs: input_signature(&container)?,
+            outputs: output_signature(&container)?,
+            resources,
+        })
+    }
+
+    /// A yaml summary of the shader: its profile, signatures, constant buffers and resource
+    /// bindings.
+    pub fn to_yaml(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "chunks: [{}]", self.chunks.join(", "));
+        if let Some(code) = self.code.as_ref() {
+            let _ = writeln!(out, "profile: {}", code.profile());
+            let _ = writeln!(out, "instructions: {}", code.instruction_count);
+        }
+        write_signature_yaml(&mut out, "inputs", &self.inputs);
+        write_signature_yaml(&mut out, "outputs", &self.outputs);
+        if let Some(res) = self.resources.as_ref() {
+            if res.constant_buffers.is_empty() {
+                let _ = writeln!(out, "constant_buffers: []");
+            } else {
+                let _ = writeln!(out, "constant_buffers:");
+            }
+            for cb in res.constant_buffers.iter() {
+                let reg = res.bindings.iter().find(|b| b.input_type == 0 && b.name == cb.name)
+                    .map(|b| b.register()).unwrap_or_default();
+                let _ = writeln!(out, "  - name: {}", cb.name);
+                let _ = writeln!(out, "    register: {}", reg);
+                let _ = writeln!(out, "    size: {}", cb.size);
+                let _ = writeln!(out, "    variables:");
+                for v in cb.variables.iter() {
+                    let _ = writeln!(out, "      - {{ name: {}, offset: {}, size: {}, type: \"{}\" }}",
+                        v.name, v.offset, v.size, v.var_type.name());
+                }
+            }
+            if res.bindings.is_empty() {
+                let _ = writeln!(out, "resources: []");
+            } else {
+                let _ = writeln!(out, "resources:");
+            }
+            for b in res.bindings.iter() {
+                let _ = writeln!(out, "  - {{ name: {}, type: {}, register: {}, count: {}, dimension: {} }}",
+                    b.name, b.input_type_name(), b.register(), b.bind_count, b.dimension_name());
+            }
+        }
+        out
+    }
+}
+
+fn write_signature_yaml(out: &mut String, key: &str, sig: &Option<Signature>) {
+    match sig {
+        Some(sig) if !sig.elements.is_empty() => {
+            let _ = writeln!(out, "{}:", key);
+            for e in sig.elements.iter() {
+                let _ = writeln!(out, "  - {{ semantic: {}, index: {}, register: {}, mask: {}, type: {}, sysval: {} }}",
+                    e.semantic_name, e.semantic_index, e.register, mask_string(e.mask),
+                    e.component_type_name(), e.system_value_name());
+            }
+        },
+        _ => { let _ = writeln!(out, "{}: []", key); },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::build_container;
+    use crate::signature::build_signature;
+
+    #[test]
+    fn test_shader_info() {
+        let info = ShaderInfo::parse(include_bytes!("../testdata/shape_vshader.cso")).unwrap();
+        assert_eq!(info.chunks, ["RDEF", "ISGN", "OSGN", "SHEX", "STAT"]);
+        let inputs = info.inputs.as_ref().unwrap();
+        let names = inputs.elements.iter().map(|e| (e.semantic_name.as_str(), e.semantic_index))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("POSITION", 0), ("NORMAL", 0), ("BINORMAL", 0), ("TEXCOORD", 0)]);
+        assert_eq!(inputs.elements[1].component_type_name(), "int");
+        assert!(!inputs.has_skinning_inputs());
+        let outputs = info.outputs.as_ref().unwrap();
+        assert_eq!(outputs.elements[0].system_value_name(), "POSITION");
+        assert_eq!(outputs.elements.len(), 3);
+
+        let yaml = info.to_yaml();
+        assert!(yaml.contains("profile: vs_5_0\n"), "{}", yaml);
+        assert!(yaml.contains("  - name: NormalMatrixBuffer\n    register: b2\n    size: 48\n"), "{}", yaml);
+        assert!(yaml.contains("      - { name: modelViewProjection, offset: 0, size: 64, type: \"float4x4\" }\n"), "{}", yaml);
+
+        let yaml = ShaderInfo::parse(include_bytes!("../testdata/shape_pshader.cso")).unwrap().to_yaml();
+        assert!(yaml.contains("  - { name: diffuseTexture, type: texture, register: t0, count: 1, dimension: texture2d }\n"), "{}", yaml);
+        assert!(yaml.contains("  - { semantic: SV_Target, index: 0, register: 0, mask: xyzw, type: float, sysval: NONE }\n"), "{}", yaml);
+
+        // only the chunks that are there are written
+        let isgn = build_signature(&[("POSITION", 0, 0, 0, 0x7), ("TEXCOORD", 1, 0, 1, 0x3)]);
+        let data = build_container(&[(b"ISGN", isgn)]);
+        assert_eq!(ShaderInfo::parse(&data).unwrap().to_yaml(), "chunks: [ISGN]\n\
+            inputs:\n\
+            \x20 - { semantic: POSITION, index: 0, register: 0, mask: xyz, type: float, sysval: NONE }\n\
+            \x20 - { semantic: TEXCOORD, index: 1, register: 1, mask: xy, type: float, sysval: NONE }\n\
+            outputs: []\n");
+
+        assert!(ShaderInfo::parse(b"not a shader").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! The shader code chunk (`SHDR` for shader model 4, `SHEX` for 5).  Only the version header is
+//! decoded; the instructions are walked to count them, but not disassembled.
+
+use crate::container::{read_u32, Chunk, Container};
+
+pub const CODE_CHUNKS: &[&[u8; 4]] = &[b"SHEX", b"SHDR"];
+
+const OPCODE_CUSTOMDATA: u32 = 35;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderCode {
+    pub program_type: u32,
+    pub major: u32,
+    pub minor: u32,
+    /// Number of instructions, including declarations.
+    pub instruction_count: u32,
+}
+
+impl ShaderCode {
+    pub fn parse(chunk: &Chunk) -> Result<Self, String> {
+        if !CODE_CHUNKS.iter().any(|f| chunk.fourcc == **f) {
+            return Err(format!("{} is not a shader code chunk", chunk.name()));
+        }
+        let data = chunk.data;
+        let version = read_u32(data, 0)?;
+        let len = read_u32(data, 4)? as usize;
+        if len * 4 > data.len() {
+            return Err(format!("shader code length {} is larger than the chunk ({})", len * 4, data.len()));
+        }
+        let mut instruction_count = 0;
+        let mut pos = 2;
+        while pos < len {
+            let token = read_u32(data, pos * 4)?;
+            let ilen = if token & 0x7ff == OPCODE_CUSTOMDATA {
+                read_u32(data, pos * 4 + 4)? as usize
+            } else {
+                ((token >> 24) & 0x7f) as usize
+            };
+            if ilen == 0 {
+                return Err(format!("zero length instruction at dword {}", pos));
+            }
+            pos += ilen;
+            instruction_count += 1;
+        }
+        Ok(ShaderCode {
+            program_type: version >> 16,
+            major: (version >> 4) & 0xf,
+            minor: version & 0xf,
+            instruction_count,
+        })
+    }
+
+    /// Shader profile name, e.g. `vs_5_0`.
+    pub fn profile(&self) -> String {
+        let prefix = match self.program_type {
+            0 => "ps",
+            1 => "vs",
+            2 => "gs",
+            3 => "hs",
+            4 => "ds",
+            5 => "cs",
+            _ => "unknown",
+        };
+        format!("{}_{}_{}", prefix, self.major, self.minor)
+    }
+
+    pub fn is_vertex_shader(&self) -> bool {
+        self.program_type == 1
+    }
+}
+
+pub fn shader_code(container: &Container) -> Result<Option<ShaderCode>, String> {
+    container.chunk(CODE_CHUNKS).map(ShaderCode::parse).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shader_code() {
+        let c = Container::parse(include_bytes!("../testdata/shape_vshader.cso")).unwrap();
+        let code = shader_code(&c).unwrap().unwrap();
+        assert_eq!(code.profile(), "vs_5_0");
+        assert!(code.is_vertex_shader());
+        assert!(code.instruction_count > 10, "{}", code.instruction_count);
+
+        let c = Container::parse(include_bytes!("../testdata/shape_pshader.cso")).unwrap();
+        let code = shader_code(&c).unwrap().unwrap();
+        assert_eq!(code.profile(), "ps_5_0");
+    }
+}
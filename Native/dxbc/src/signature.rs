@@ -0,0 +1,198 @@
+//! Input and output signatures (`ISGN`/`OSGN` chunks and their later variants), which list
+//! the semantics that a shader reads and writes.
+
+use crate::container::{read_cstr, read_u32, Chunk, Container};
+
+pub const INPUT_SIGNATURE_CHUNKS: &[&[u8; 4]] = &[b"ISGN", b"ISG1"];
+pub const OUTPUT_SIGNATURE_CHUNKS: &[&[u8; 4]] = &[b"OSGN", b"OSG5", b"OSG1"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureElement {
+    pub semantic_name: String,
+    pub semantic_index: u32,
+    pub system_value: u32,
+    pub component_type: u32,
+    pub register: u32,
+    pub mask: u8,
+    /// For inputs, the components that are actually read.  For outputs, the components that
+    /// are never written.
+    pub rw_mask: u8,
+    pub stream: u32,
+}
+
+impl SignatureElement {
+    pub fn system_value_name(&self) -> &'static str {
+        match self.system_value {
+            0 => "NONE",
+            1 => "POSITION",
+            2 => "CLIP_DISTANCE",
+            3 => "CULL_DISTANCE",
+            4 => "RENDER_TARGET_ARRAY_INDEX",
+            5 => "VIEWPORT_ARRAY_INDEX",
+            6 => "VERTEX_ID",
+            7 => "PRIMITIVE_ID",
+            8 => "INSTANCE_ID",
+            9 => "IS_FRONT_FACE",
+            10 => "SAMPLE_INDEX",
+            64 => "TARGET",
+            65 => "DEPTH",
+            66 => "COVERAGE",
+            _ => "OTHER",
+        }
+    }
+
+    pub fn component_type_name(&self) -> &'static str {
+        match self.component_type {
+            1 => "uint",
+            2 => "int",
+            3 => "float",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Component mask as a swizzle, e.g. `xyz`.
+pub fn mask_string(mask: u8) -> String {
+    "xyzw".chars().enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .map(|(_, c)| c)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Signature {
+    pub elements: Vec<SignatureElement>,
+}
+
+impl Signature {
+    pub fn parse(chunk: &Chunk) -> Result<Self, String> {
+        // the later variants add a stream index before the name, and ISG1/OSG1 add a min
+        // precision value at the end
+        let (elem_size, has_stream) = match &chunk.fourcc {
+            b"ISGN" | b"OSGN" | b"PCSG" => (24, false),
+            b"OSG5" => (28, true),
+            b"ISG1" | b"OSG1" | b"PSG1" => (32, true),
+            _ => return Err(format!("{} is not a signature chunk", chunk.name())),
+        };
+        let data = chunk.data;
+        let count = read_u32(data, 0)? as usize;
+        let first = read_u32(data, 4)? as usize;
+        let mut elements = Vec::with_capacity(count.min(64));
+        for i in 0..count {
+            let mut off = first + i * elem_size;
+            let stream = if has_stream {
+                off += 4;
+                read_u32(data, off - 4)?
+            } else {
+                0
+            };
+            let name_offset = read_u32(data, off)? as usize;
+            let masks = read_u32(data, off + 20)?;
+            elements.push(SignatureElement {
+                semantic_name: read_cstr(data, name_offset)?,
+                semantic_index: read_u32(data, off + 4)?,
+                system_value: read_u32(data, off + 8)?,
+                component_type: read_u32(data, off + 12)?,
+                register: read_u32(data, off + 16)?,
+                mask: (masks & 0xff) as u8,
+                rw_mask: ((masks >> 8) & 0xff) as u8,
+                stream,
+            });
+        }
+        Ok(Signature { elements })
+    }
+
+    pub fn has_semantic(&self, name: &str) -> bool {
+        self.elements.iter().any(|e| e.semantic_name.eq_ignore_ascii_case(name))
+    }
+
+    /// Elements (as `NAME` + index, e.g. `TEXCOORD1`) that are not in `provided`, a list of
+    /// (semantic name, index) pairs such as the elements of a vertex layout.  System values
+    /// like `SV_VertexID` are generated by the pipeline, so they are never missing.
+    pub fn missing_inputs(&self, provided: &[(String, u32)]) -> Vec<String> {
+        self.elements.iter()
+            .filter(|e| e.system_value == 0)
+            .filter(|e| !provided.iter().any(|(name, index)|
+                name.eq_ignore_ascii_case(&e.semantic_name) && *index == e.semantic_index))
+            .map(|e| format!("{}{}", e.semantic_name, e.semantic_index))
+            .collect()
+    }
+
+    /// True if the signature has blend index or weight inputs, which means the shader most
+    /// likely does the skinning on the GPU.
+    pub fn has_skinning_inputs(&self) -> bool {
+        self.elements.iter().any(|e| {
+            let name = e.semantic_name.to_ascii_uppercase();
+            name.starts_with("BLENDINDICES") || name.starts_with("BLENDWEIGHT")
+        })
+    }
+}
+
+pub fn input_signature(container: &Container) -> Result<Option<Signature>, String> {
+    container.chunk(INPUT_SIGNATURE_CHUNKS).map(Signature::parse).transpose()
+}
+
+pub fn output_signature(container: &Container) -> Result<Option<Signature>, String> {
+    container.chunk(OUTPUT_SIGNATURE_CHUNKS).map(Signature::parse).transpose()
+}
+
+/// Build signature chunk data from (name, index, sysval, register, mask) tuples, in the
+/// `ISGN`/`OSGN` layout.
+#[cfg(test)]
+pub(crate) fn build_signature(elems: &[(&str, u32, u32, u32, u8)]) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(&(elems.len() as u32).to_le_bytes());
+    out.extend_from_slice(&8_u32.to_le_bytes());
+    let names_start = 8 + elems.len() * 24;
+    let mut names = vec![];
+    for (name, index, sysval, register, mask) in elems.iter() {
+        let name_offset = (names_start + names.len()) as u32;
+        names.extend_from_slice(name.as_bytes());
+        names.push(0);
+        for v in [name_offset, *index, *sysval, 3, *register, *mask as u32 | ((*mask as u32) << 8)] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    out.extend_from_slice(&names);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::build_container;
+
+    #[test]
+    fn test_signature() {
+        let isgn = build_signature(&[("POSITION", 0, 0, 0, 0x7), ("TEXCOORD", 0, 0, 1, 0x3),
+            ("BLENDINDICES", 0, 0, 2, 0xf)]);
+        let osgn = build_signature(&[("SV_Position", 0, 1, 0, 0xf)]);
+        let data = build_container(&[(b"RDEF", vec![0; 4]), (b"ISGN", isgn), (b"OSGN", osgn)]);
+        let c = Container::parse(&data).unwrap();
+
+        let inputs = input_signature(&c).unwrap().unwrap();
+        assert_eq!(inputs.elements.len(), 3);
+        assert_eq!(inputs.elements[1], SignatureElement { semantic_name: "TEXCOORD".to_owned(),
+            semantic_index: 0, system_value: 0, component_type: 3, register: 1, mask: 0x3,
+            rw_mask: 0x3, stream: 0 });
+        assert!(inputs.has_semantic("texcoord"));
+        assert!(inputs.has_skinning_inputs());
+        let outputs = output_signature(&c).unwrap().unwrap();
+        assert_eq!(outputs.elements[0].system_value_name(), "POSITION");
+        assert!(!outputs.has_skinning_inputs());
+
+        let provided = [("position".to_owned(), 0), ("TEXCOORD".to_owned(), 1)];
+        assert_eq!(inputs.missing_inputs(&provided), ["TEXCOORD0", "BLENDINDICES0"]);
+
+        // no signatures
+        let data = build_container(&[(b"SHEX", vec![0; 4])]);
+        let c = Container::parse(&data).unwrap();
+        assert_eq!(input_signature(&c).unwrap(), None);
+
+        // bad name offset
+        let mut isgn = build_signature(&[("POSITION", 0, 0, 0, 0x7)]);
+        isgn[8..12].copy_from_slice(&500_u32.to_le_bytes());
+        let data = build_container(&[(b"ISGN", isgn)]);
+        assert!(input_signature(&Container::parse(&data).unwrap()).is_err());
+    }
+}
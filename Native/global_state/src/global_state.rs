@@ -21,7 +21,7 @@ use fnv::FnvHashMap;
 use fnv::FnvHashSet;
 
 use types::interop;
-use types::native_mod;
+use types::d3ddata::ModD3DData;
 use types::d3dx;
 
 use snaplib::anim_snap_state::AnimSnapState;
@@ -48,31 +48,7 @@ pub enum RenderedPrimType {
     PrimCountVertSizeAndVBs(u32,u32,Vec<(u32,u32,u32)>)
 }
 
-/// Resolved state of a vertex buffer's CRC32 entry in `vb_checksums`.
-///
-/// A VB that is not present in the map at all is implicitly "pending" —
-/// the DX9 DIP hook will attempt to hash it the first time it sees it
-/// bound, and insert either `Checksum` or `NotPossible` depending on
-/// whether the Lock succeeded. DX11 hashes at create time using the
-/// supplied initial data and goes straight to `Checksum`.
-#[derive(Debug, Clone, Copy)]
-pub enum VBChecksumStatus {
-    /// Successfully computed CRC32 over the buffer's bytes.
-    Checksum(u32),
-    /// Buffer cannot be hashed (Lock failed). Don't retry.
-    NotPossible,
-}
-
-impl VBChecksumStatus {
-    /// Return the CRC if this entry has successfully been hashed.
-    /// `NotPossible` returns `None`.
-    pub fn checksum(&self) -> Option<u32> {
-        match self {
-            VBChecksumStatus::Checksum(c) => Some(*c),
-            VBChecksumStatus::NotPossible => None,
-        }
-    }
-}
+pub use mm_core::{VBChecksumStatus, ModsByNameMap, SelectedVariantMap, new_fnv_map};
 
 pub struct FrameMetrics {
     pub dip_calls: u32,
@@ -86,26 +62,10 @@ pub struct FrameMetrics {
     pub rendered_prims: Vec<RenderedPrimType>,
 }
 
-pub type LoadedModsMap = FnvHashMap<u32, Vec<native_mod::NativeModData>>;
-pub type ModsByNameMap = FnvHashMap<String,u32>;
-pub type SelectedVariantMap = FnvHashMap<u32, usize>;
-pub fn new_fnv_map<A,B> (capacity:usize) -> FnvHashMap<A,B> {
-    FnvHashMap::with_capacity_and_hasher(capacity, Default::default())
-}
-
-pub struct LoadedModState {
-    pub mods: LoadedModsMap,
-    pub mods_by_name: ModsByNameMap,
-    pub selected_variant: SelectedVariantMap,
-}
-
-// `LoadedModState` holds raw d3d resource pointers (textures, buffers) which
-// are not `Send` by default. We need `Send` so that the global `Mutex` can be
-// `Sync`, which lets multiple threads (render thread, deferred load thread)
-// access it through the lock. The same `unsafe impl Send` pattern is already
-// used for `LoadMsg` in `mod_load::load_thread`, where a cloned `NativeModData`
-// is shipped across threads.
-unsafe impl Send for LoadedModState {}
+pub type LoadedModsMap = mm_core::LoadedModsMap<ModD3DData>;
+/// The loaded mod state for the hook.  `Send` (needed for `LOADED_MODS`) comes from the
+/// `unsafe impl Send` on `ModD3DData`.
+pub type LoadedModState = mm_core::LoadedModState<ModD3DData>;
 
 pub struct ClrState {
     pub runtime_pointer: Option<u64>,
@@ -12,6 +12,18 @@ pub enum DebugModeCalledFns {
     Hook_ContextIASetVertexBuffers,
     Hook_ContextIASetInputLayout,
     Hook_ContextIASetPrimitiveTopology,
+    Hook_ContextDrawIndexedInstanced,
+    Hook_ContextDraw,
+    Hook_ContextPSSetConstantBuffers,
+    Hook_ContextUpdateSubresource,
+    Hook_ContextMap,
+    Hook_ContextUnmap,
+    Hook_ContextOMSetBlendState,
+    Hook_ContextOMSetDepthStencilState,
+    Hook_ContextRSSetState,
+    Hook_ContextPSSetSamplers,
+    Hook_ContextVSSetShader,
+    Hook_ContextPSSetShader,
     Last = 99
 }
 /// DebugMode is a special mode that is enabled by creating a file in the MMRoot called `DebugMode.txt`.
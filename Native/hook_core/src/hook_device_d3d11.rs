@@ -59,16 +59,20 @@ use winapi::um::d3d11::D3D11_CPU_ACCESS_READ;
 use winapi::um::d3d11::D3D11_TEXTURE2D_DESC;
 use winapi::um::d3d11::ID3D11Texture2D;
 use winapi::um::d3d11::{D3D11_APPEND_ALIGNED_ELEMENT, D3D11_BIND_INDEX_BUFFER,
-    D3D11_BIND_VERTEX_BUFFER, D3D11_BUFFER_DESC, D3D11_INPUT_ELEMENT_DESC,
-    D3D11_SUBRESOURCE_DATA, ID3D11Buffer, ID3D11DeviceVtbl, ID3D11InputLayout};
+    D3D11_BIND_VERTEX_BUFFER, D3D11_BIND_CONSTANT_BUFFER, D3D11_BUFFER_DESC, D3D11_INPUT_ELEMENT_DESC,
+    D3D11_SUBRESOURCE_DATA, ID3D11Buffer, ID3D11BufferVtbl, ID3D11DeviceVtbl, ID3D11InputLayout,
+    ID3D11ClassLinkage, ID3D11VertexShader, ID3D11PixelShader, D3D11_BLEND_DESC, ID3D11BlendState,
+    D3D11_RASTERIZER_DESC, ID3D11RasterizerState, D3D11_DEPTH_STENCIL_DESC, ID3D11DepthStencilState,
+    D3D11_SAMPLER_DESC, ID3D11SamplerState};
 use winapi::um::{d3dcommon::{D3D_DRIVER_TYPE, D3D_FEATURE_LEVEL},
     d3d11::{ID3D11Device, ID3D11DeviceContext, ID3D11DeviceContextVtbl}};
-use winapi::um::unknwnbase::IUnknown;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::shared::ntdef::ULONG;
 
 use crate::debugmode;
 use crate::debugmode::DebugModeCalledFns;
 use crate::hook_device::{load_d3d_lib, init_device_state_once, init_log};
-use shared_dx::{util::write_log_file,
+use shared_dx::{util::write_log_file, defs_dx11::OMSetBlendStateFn,
     types_dx11::{HookDirect3D11, HookDirect3D11Context, HookDirect3D11Device},
     types::{HookDeviceState, HookD3D11State, DX11Metrics, DevicePointer},
     error::*, dx11rs::{DX11RenderState, VertexFormat}};
@@ -80,6 +84,12 @@ use crate::hook_render_d3d11::*;
 
 static mut DEVICE_REALFN: RwLock<Option<HookDirect3D11Device>> = RwLock::new(None);
 
+/// Copies of buffer vtables with `Release` hooked, as (original vtable, copy) pairs.  Constant
+/// buffers are switched to one of these (the same way the context gets a vtable copy) so that
+/// their saved data can be dropped when they are released.  Only constant buffers use them, so
+/// the other buffers don't pay for the hook.  The copies are never freed.
+static CB_VTABLES: RwLock<Vec<(usize, usize)>> = RwLock::new(Vec::new());
+
 use global_state::{GLOBAL_STATE, GLOBAL_STATE_LOCK};
 
 type D3D11CreateDeviceFN = extern "system" fn (
@@ -359,15 +369,18 @@ pub unsafe fn apply_context_hooks(context:*mut ID3D11DeviceContext, first_hook:b
         iunknown.Release = hook_release;
         func_hooked += 1;
     }
-    // don't need this for now
-    // if (*vtbl).VSSetConstantBuffers as usize != hook_VSSetConstantBuffers as usize {
-    //     (*vtbl).VSSetConstantBuffers = hook_VSSetConstantBuffers;
-    //     func_hooked += 1;
-    // }
     if debugmode::draw_hook_enabled() && (*vtbl).DrawIndexed as usize != hook_draw_indexed as *const () as usize {
         (*vtbl).DrawIndexed = hook_draw_indexed;
         func_hooked += 1;
     }
+    if debugmode::draw_hook_enabled() && (*vtbl).DrawIndexedInstanced as usize != hook_draw_indexed_instanced as *const () as usize {
+        (*vtbl).DrawIndexedInstanced = hook_draw_indexed_instanced;
+        func_hooked += 1;
+    }
+    if debugmode::draw_hook_enabled() && (*vtbl).Draw as usize != hook_draw as *const () as usize {
+        (*vtbl).Draw = hook_draw;
+        func_hooked += 1;
+    }
     if (*vtbl).IASetVertexBuffers as usize != hook_IASetVertexBuffers as *const () as usize {
         (*vtbl).IASetVertexBuffers = hook_IASetVertexBuffers;
         func_hooked += 1;
@@ -384,6 +397,53 @@ pub unsafe fn apply_context_hooks(context:*mut ID3D11DeviceContext, first_hook:b
         (*vtbl).PSSetShaderResources = hook_PSSetShaderResources;
         func_hooked += 1;
     }
+    // bound shaders, constant buffers and states are only needed for snapshots
+    if GLOBAL_STATE.run_conf.precopy_data {
+        if (*vtbl).VSSetShader as usize != hook_VSSetShader as *const () as usize {
+            (*vtbl).VSSetShader = hook_VSSetShader;
+            func_hooked += 1;
+        }
+        if (*vtbl).PSSetShader as usize != hook_PSSetShader as *const () as usize {
+            (*vtbl).PSSetShader = hook_PSSetShader;
+            func_hooked += 1;
+        }
+        if (*vtbl).VSSetConstantBuffers as usize != hook_VSSetConstantBuffers as *const () as usize {
+            (*vtbl).VSSetConstantBuffers = hook_VSSetConstantBuffers;
+            func_hooked += 1;
+        }
+        if (*vtbl).PSSetConstantBuffers as usize != hook_PSSetConstantBuffers as *const () as usize {
+            (*vtbl).PSSetConstantBuffers = hook_PSSetConstantBuffers;
+            func_hooked += 1;
+        }
+        if (*vtbl).UpdateSubresource as usize != hook_UpdateSubresource as *const () as usize {
+            (*vtbl).UpdateSubresource = hook_UpdateSubresource;
+            func_hooked += 1;
+        }
+        if (*vtbl).Map as usize != hook_Map as *const () as usize {
+            (*vtbl).Map = hook_Map;
+            func_hooked += 1;
+        }
+        if (*vtbl).Unmap as usize != hook_Unmap as *const () as usize {
+            (*vtbl).Unmap = hook_Unmap;
+            func_hooked += 1;
+        }
+        if (*vtbl).OMSetBlendState as usize != hook_OMSetBlendState as *const () as usize {
+            (*vtbl).OMSetBlendState = std::mem::transmute(hook_OMSetBlendState as OMSetBlendStateFn);
+            func_hooked += 1;
+        }
+        if (*vtbl).OMSetDepthStencilState as usize != hook_OMSetDepthStencilState as *const () as usize {
+            (*vtbl).OMSetDepthStencilState = hook_OMSetDepthStencilState;
+            func_hooked += 1;
+        }
+        if (*vtbl).RSSetState as usize != hook_RSSetState as *const () as usize {
+            (*vtbl).RSSetState = hook_RSSetState;
+            func_hooked += 1;
+        }
+        if (*vtbl).PSSetSamplers as usize != hook_PSSetSamplers as *const () as usize {
+            (*vtbl).PSSetSamplers = hook_PSSetSamplers;
+            func_hooked += 1;
+        }
+    }
 
     if TRACK_REHOOK_TIME {
         let now = SystemTime::now();
@@ -505,8 +565,20 @@ pub unsafe fn unapply_device_hook(device:*mut ID3D11Device) -> Result<()> {
                 real_create_texture_2d: (hooks).real_create_texture_2d,
                 real_create_input_layout: hooks.real_create_input_layout,
                 real_query_interface: hooks.real_query_interface,
+                real_create_vertex_shader: hooks.real_create_vertex_shader,
+                real_create_pixel_shader: hooks.real_create_pixel_shader,
+                real_create_blend_state: hooks.real_create_blend_state,
+                real_create_rasterizer_state: hooks.real_create_rasterizer_state,
+                real_create_depth_stencil_state: hooks.real_create_depth_stencil_state,
+                real_create_sampler_state: hooks.real_create_sampler_state,
             };
             (*vtbl).CreateInputLayout = unhook.real_create_input_layout;
+            (*vtbl).CreateVertexShader = unhook.real_create_vertex_shader;
+            (*vtbl).CreatePixelShader = unhook.real_create_pixel_shader;
+            (*vtbl).CreateBlendState = unhook.real_create_blend_state;
+            (*vtbl).CreateRasterizerState = unhook.real_create_rasterizer_state;
+            (*vtbl).CreateDepthStencilState = unhook.real_create_depth_stencil_state;
+            (*vtbl).CreateSamplerState = unhook.real_create_sampler_state;
             (*vtbl).CreateBuffer = unhook.real_create_buffer;
             (*vtbl).CreateTexture2D = unhook.real_create_texture_2d;
             (*vtbl).parent.QueryInterface = unhook.real_query_interface;
@@ -556,6 +628,12 @@ pub unsafe fn apply_device_hook(device:*mut ID3D11Device) -> Result<()> {
         let real_create_texture_2d = (*vtbl).CreateTexture2D;
         let real_create_input_layout = (*vtbl).CreateInputLayout;
         let real_query_interface = (*vtbl).parent.QueryInterface;
+        let real_create_vertex_shader = (*vtbl).CreateVertexShader;
+        let real_create_pixel_shader = (*vtbl).CreatePixelShader;
+        let real_create_blend_state = (*vtbl).CreateBlendState;
+        let real_create_rasterizer_state = (*vtbl).CreateRasterizerState;
+        let real_create_depth_stencil_state = (*vtbl).CreateDepthStencilState;
+        let real_create_sampler_state = (*vtbl).CreateSamplerState;
 
         if real_create_buffer as usize == hook_CreateBuffer as *const () as usize {
             return Err(HookError::D3D11DeviceHookFailed(
@@ -573,12 +651,30 @@ pub unsafe fn apply_device_hook(device:*mut ID3D11Device) -> Result<()> {
             return Err(HookError::D3D11DeviceHookFailed(
                 format!("unable to hook QueryInterface due to missing real function")));
         }
+        if real_create_vertex_shader as usize == hook_CreateVertexShader as *const () as usize
+            || real_create_pixel_shader as usize == hook_CreatePixelShader as *const () as usize {
+            return Err(HookError::D3D11DeviceHookFailed(
+                format!("unable to hook Create*Shader due to missing real function")));
+        }
+        if real_create_blend_state as usize == hook_CreateBlendState as *const () as usize
+            || real_create_rasterizer_state as usize == hook_CreateRasterizerState as *const () as usize
+            || real_create_depth_stencil_state as usize == hook_CreateDepthStencilState as *const () as usize
+            || real_create_sampler_state as usize == hook_CreateSamplerState as *const () as usize {
+            return Err(HookError::D3D11DeviceHookFailed(
+                format!("unable to hook Create*State due to missing real function")));
+        }
 
         *lock = Some(HookDirect3D11Device {
             real_create_buffer,
             real_create_texture_2d,
             real_query_interface,
             real_create_input_layout,
+            real_create_vertex_shader,
+            real_create_pixel_shader,
+            real_create_blend_state,
+            real_create_rasterizer_state,
+            real_create_depth_stencil_state,
+            real_create_sampler_state,
         });
         write_log_file("device hook real funcs initialized");
     }
@@ -589,9 +685,15 @@ pub unsafe fn apply_device_hook(device:*mut ID3D11Device) -> Result<()> {
     // we don't copy the vtable.
     let old_prot = util::unprotect_memory(vtbl as *mut c_void, vsize)?;
     (*vtbl).CreateInputLayout = hook_CreateInputLayoutFn;
-    // don't need to hook create buffer if we aren't precoping data
+    // don't need to hook create buffer, the shaders or the states if we aren't precoping data
     if GLOBAL_STATE.run_conf.precopy_data {
         (*vtbl).CreateBuffer = hook_CreateBuffer;
+        (*vtbl).CreateVertexShader = hook_CreateVertexShader;
+        (*vtbl).CreatePixelShader = hook_CreatePixelShader;
+        (*vtbl).CreateBlendState = hook_CreateBlendState;
+        (*vtbl).CreateRasterizerState = hook_CreateRasterizerState;
+        (*vtbl).CreateDepthStencilState = hook_CreateDepthStencilState;
+        (*vtbl).CreateSamplerState = hook_CreateSamplerState;
     }
     if GLOBAL_STATE.run_conf.force_tex_cpu_read {
         (*vtbl).CreateTexture2D = hook_CreateTexture2D;
@@ -636,6 +738,16 @@ unsafe fn hook_d3d11(device:*mut ID3D11Device,_swapchain:*mut IDXGISwapChain, co
     let real_ia_set_input_layout = (*vtbl).IASetInputLayout;
     let real_ia_set_primitive_topology = (*vtbl).IASetPrimitiveTopology;
     let real_ps_set_shader_resources = (*vtbl).PSSetShaderResources;
+    let real_vs_set_shader = (*vtbl).VSSetShader;
+    let real_ps_set_shader = (*vtbl).PSSetShader;
+    let real_ps_setconstantbuffers = (*vtbl).PSSetConstantBuffers;
+    let real_update_subresource = (*vtbl).UpdateSubresource;
+    let real_map = (*vtbl).Map;
+    let real_unmap = (*vtbl).Unmap;
+    let real_om_set_blend_state: OMSetBlendStateFn = std::mem::transmute((*vtbl).OMSetBlendState);
+    let real_om_set_depth_stencil_state = (*vtbl).OMSetDepthStencilState;
+    let real_rs_set_state = (*vtbl).RSSetState;
+    let real_ps_set_samplers = (*vtbl).PSSetSamplers;
 
     // since we always make a copy of the vtable in the context at the moment, we don't search
     // for the real functions as we do in the device case, since a new context should always have
@@ -672,6 +784,16 @@ unsafe fn hook_d3d11(device:*mut ID3D11Device,_swapchain:*mut IDXGISwapChain, co
         real_ia_set_input_layout,
         real_ia_set_primitive_topology,
         real_ps_set_shader_resources,
+        real_vs_set_shader,
+        real_ps_set_shader,
+        real_ps_setconstantbuffers,
+        real_update_subresource,
+        real_map,
+        real_unmap,
+        real_om_set_blend_state,
+        real_om_set_depth_stencil_state,
+        real_rs_set_state,
+        real_ps_set_samplers,
     };
 
     Ok(HookDirect3D11 { context: hook_context })
@@ -981,6 +1103,63 @@ unsafe extern "system" fn hook_CreateInputLayoutFn(
     res
 }
 
+/// Switch the buffer to a copy of its vtable that has `hook_constant_buffer_Release`.
+unsafe fn install_constant_buffer_release_hook(buffer: *mut ID3D11Buffer) {
+    let orig = (*buffer).lpVtbl as usize;
+    let mut tables = match CB_VTABLES.write() {
+        Ok(t) => t,
+        Err(e) => {
+            write_log_file(&format!("error: constant buffer vtable lock failed: {}", e));
+            return;
+        }
+    };
+    if tables.iter().any(|(_orig, copy)| *copy == orig) {
+        return;
+    }
+    let copy = match tables.iter().find(|(o, _copy)| *o == orig) {
+        Some((_orig, copy)) => *copy,
+        None => {
+            let mut vtbl: ID3D11BufferVtbl = std::ptr::read((*buffer).lpVtbl);
+            vtbl.parent.parent.parent.Release = hook_constant_buffer_Release;
+            let copy = Box::into_raw(Box::new(vtbl)) as usize;
+            write_log_file(&format!("constant buffer vtbl {:x} replaced with hooked copy {:x}", orig, copy));
+            tables.push((orig, copy));
+            copy
+        }
+    };
+    (*buffer).lpVtbl = copy as *const ID3D11BufferVtbl;
+}
+
+/// Drops the saved data of a constant buffer when its last reference is released, since
+/// the pointer can be reused for a different buffer after that.
+unsafe extern "system" fn hook_constant_buffer_Release(THIS: *mut IUnknown) -> ULONG {
+    let copy = (*THIS).lpVtbl as usize;
+    let orig = CB_VTABLES.read().ok()
+        .and_then(|tables| tables.iter().find(|(_orig, c)| *c == copy).map(|(o, _copy)| *o));
+    let orig = match orig {
+        Some(o) => o as *const IUnknownVtbl,
+        None => {
+            // "should never happen", only hooked vtables point here
+            write_log_file(&format!("OOPS hook_constant_buffer_Release: no original vtable for {:x}", copy));
+            return 0;
+        }
+    };
+
+    let rc = ((*orig).Release)(THIS);
+    if rc == 0 {
+        let buf = THIS as usize;
+        let tracked = dev_state_d3d11_read()
+            .map_or(false, |(_lck, ds)| ds.rs.device_constant_buffer_data.contains_key(&buf));
+        if tracked {
+            if let Some((_lck, ds)) = dev_state_d3d11_write() {
+                ds.rs.device_constant_buffer_data.remove(&buf);
+                ds.rs.mapped_constant_buffers.remove(&buf);
+            }
+        }
+    }
+    rc
+}
+
 unsafe extern "system" fn hook_CreateBuffer(
     THIS: *mut ID3D11Device,
     pDesc: *const D3D11_BUFFER_DESC,
@@ -1007,8 +1186,9 @@ unsafe extern "system" fn hook_CreateBuffer(
     // But that doesn't work, at least for index buffers.  And since no other usage allows reading the
     // buffer from the CPU, unlike in DX9, this appears to be a one-way memory chute.
     // So we need to make a copy of the data in case we need it later.
-    // Note at this time we don't hook Map so if the code uses that to write to it again we'll
-    // miss that (in theory not possible with D3D11_USAGE_IMMUTABLE though)
+    // Note at this time we don't track Map for vertex and index buffers so if the code uses that
+    // to write to it again we'll miss that (in theory not possible with D3D11_USAGE_IMMUTABLE though).
+    // Constant buffers are the exception, the context hooks keep those copies up to date.
 
     let res = (dev_realfn.real_create_buffer)(
         THIS,
@@ -1017,7 +1197,21 @@ unsafe extern "system" fn hook_CreateBuffer(
         ppBuffer
     );
 
-    if res == 0 && ppBuffer != null_mut() && (*ppBuffer) != null_mut() {
+    if res == 0 && ppBuffer != null_mut() && (*ppBuffer) != null_mut() && !pDesc.is_null()
+        && (*pDesc).BindFlags & D3D11_BIND_CONSTANT_BUFFER != 0 {
+        // constant buffers are usually created without data and filled in later, so always
+        // keep a copy
+        let vlen = (*pDesc).ByteWidth as usize;
+        let mut dest_v:Vec<u8> = vec![0; vlen];
+        if !pInitialData.is_null() && !(*pInitialData).pSysMem.is_null() {
+            std::ptr::copy_nonoverlapping::<u8>((*pInitialData).pSysMem as *const u8, dest_v.as_mut_ptr(), vlen);
+        }
+        dev_state_d3d11_write()
+        .map(|(_lock,ds)| {
+            ds.rs.device_constant_buffer_data.insert(*ppBuffer as usize, dest_v);
+        });
+        install_constant_buffer_release_hook(*ppBuffer);
+    } else if res == 0 && ppBuffer != null_mut() && (*ppBuffer) != null_mut() {
         // if its an index buffer with data, we need to copy it out
         let is_ib = (*pDesc).BindFlags & D3D11_BIND_INDEX_BUFFER != 0;
         let is_vb = (*pDesc).BindFlags & D3D11_BIND_VERTEX_BUFFER != 0;
@@ -1048,6 +1242,92 @@ unsafe extern "system" fn hook_CreateBuffer(
     res
 }
 
+// Shader objects don't give back their bytecode, so when precopying data for snapshots we
+// keep a copy of it keyed by the shader pointer.  Shaders are never removed from the map,
+// but if a pointer is reused for a new shader its entry is replaced.
+macro_rules! impl_create_shader_hook {
+    ($name:ident, $shadertype:ident, $realfn:ident) => {
+        unsafe extern "system" fn $name(
+            THIS: *mut ID3D11Device,
+            pShaderBytecode: *const c_void,
+            BytecodeLength: SIZE_T,
+            pClassLinkage: *mut ID3D11ClassLinkage,
+            ppShader: *mut *mut $shadertype,
+        ) -> HRESULT {
+            let realfn = match get_device_realfn() {
+                Ok(lock) => lock.as_ref().map(|dev| dev.$realfn),
+                Err(_) => None,
+            };
+            let realfn = match realfn {
+                Some(f) => f,
+                None => {
+                    write_log_file(&format!("Error: {} returning E_FAIL due to missing realfn", stringify!($name)));
+                    return E_FAIL;
+                }
+            };
+
+            let res = (realfn)(THIS, pShaderBytecode, BytecodeLength, pClassLinkage, ppShader);
+
+            if res == 0 && !ppShader.is_null() && !(*ppShader).is_null()
+                && !pShaderBytecode.is_null() && BytecodeLength > 0 {
+                let code = std::slice::from_raw_parts(pShaderBytecode as *const u8, BytecodeLength).to_vec();
+                dev_state_d3d11_write().map(|(_lock,ds)| {
+                    ds.rs.device_shader_bytecode.insert(*ppShader as usize, code);
+                });
+            }
+
+            res
+        }
+    };
+}
+
+impl_create_shader_hook!(hook_CreateVertexShader, ID3D11VertexShader, real_create_vertex_shader);
+impl_create_shader_hook!(hook_CreatePixelShader, ID3D11PixelShader, real_create_pixel_shader);
+
+// Like shaders, state objects can't be read back from the context without also hooking the
+// getters, so when precopying data we save the description of each one as it is created and
+// the context hooks track which ones are bound.
+macro_rules! impl_create_state_hook {
+    ($name:ident, $desctype:ident, $statetype:ident, $realfn:ident, $map:ident) => {
+        unsafe extern "system" fn $name(
+            THIS: *mut ID3D11Device,
+            pDesc: *const $desctype,
+            ppState: *mut *mut $statetype,
+        ) -> HRESULT {
+            let realfn = match get_device_realfn() {
+                Ok(lock) => lock.as_ref().map(|dev| dev.$realfn),
+                Err(_) => None,
+            };
+            let realfn = match realfn {
+                Some(f) => f,
+                None => {
+                    write_log_file(&format!("Error: {} returning E_FAIL due to missing realfn", stringify!($name)));
+                    return E_FAIL;
+                }
+            };
+
+            let res = (realfn)(THIS, pDesc, ppState);
+
+            if res == 0 && !pDesc.is_null() && !ppState.is_null() && !(*ppState).is_null() {
+                dev_state_d3d11_write().map(|(_lock,ds)| {
+                    ds.rs.$map.insert(*ppState as usize, *pDesc);
+                });
+            }
+
+            res
+        }
+    };
+}
+
+impl_create_state_hook!(hook_CreateBlendState, D3D11_BLEND_DESC, ID3D11BlendState,
+    real_create_blend_state, device_blend_states);
+impl_create_state_hook!(hook_CreateRasterizerState, D3D11_RASTERIZER_DESC, ID3D11RasterizerState,
+    real_create_rasterizer_state, device_rasterizer_states);
+impl_create_state_hook!(hook_CreateDepthStencilState, D3D11_DEPTH_STENCIL_DESC, ID3D11DepthStencilState,
+    real_create_depth_stencil_state, device_depth_stencil_states);
+impl_create_state_hook!(hook_CreateSamplerState, D3D11_SAMPLER_DESC, ID3D11SamplerState,
+    real_create_sampler_state, device_sampler_states);
+
 /// Compute and cache the CRC32 of a DX11 vertex buffer's contents.
 /// The bytes are looked up from `device_vertex_buffer_data` (populated by
 /// `hook_CreateBuffer`). No-op if the VB is unknown or already hashed.
@@ -1302,6 +1582,42 @@ pub mod tests {
         E_FAIL
     }
 
+    pub unsafe extern "system" fn dummy_create_vertex_shader(
+        _ik: *mut ID3D11Device,
+        _pShaderBytecode: *const winapi::ctypes::c_void,
+        _BytecodeLength: usize,
+        _pClassLinkage: *mut ID3D11ClassLinkage,
+        _ppVertexShader: *mut *mut ID3D11VertexShader,
+    ) -> winapi::shared::winerror::HRESULT {
+        E_FAIL
+    }
+
+    pub unsafe extern "system" fn dummy_create_pixel_shader(
+        _ik: *mut ID3D11Device,
+        _pShaderBytecode: *const winapi::ctypes::c_void,
+        _BytecodeLength: usize,
+        _pClassLinkage: *mut ID3D11ClassLinkage,
+        _ppPixelShader: *mut *mut ID3D11PixelShader,
+    ) -> winapi::shared::winerror::HRESULT {
+        E_FAIL
+    }
+
+    macro_rules! impl_dummy_create_state {
+        ($name:ident, $desctype:ident, $statetype:ident) => {
+            pub unsafe extern "system" fn $name(
+                _ik: *mut ID3D11Device,
+                _pDesc: *const $desctype,
+                _ppState: *mut *mut $statetype,
+            ) -> winapi::shared::winerror::HRESULT {
+                E_FAIL
+            }
+        };
+    }
+    impl_dummy_create_state!(dummy_create_blend_state, D3D11_BLEND_DESC, ID3D11BlendState);
+    impl_dummy_create_state!(dummy_create_rasterizer_state, D3D11_RASTERIZER_DESC, ID3D11RasterizerState);
+    impl_dummy_create_state!(dummy_create_depth_stencil_state, D3D11_DEPTH_STENCIL_DESC, ID3D11DepthStencilState);
+    impl_dummy_create_state!(dummy_create_sampler_state, D3D11_SAMPLER_DESC, ID3D11SamplerState);
+
     pub unsafe extern "system" fn dummy_create_input_layout(
         _ik: *mut ID3D11Device,
         _pInputElementDescs: *const D3D11_INPUT_ELEMENT_DESC,
@@ -1389,6 +1705,12 @@ pub mod tests {
                 real_create_texture_2d: dummy_create_texture_2d,
                 real_create_input_layout: dummy_create_input_layout,
                 real_query_interface: dummy_query_interface,
+                real_create_vertex_shader: dummy_create_vertex_shader,
+                real_create_pixel_shader: dummy_create_pixel_shader,
+                real_create_blend_state: dummy_create_blend_state,
+                real_create_rasterizer_state: dummy_create_rasterizer_state,
+                real_create_depth_stencil_state: dummy_create_depth_stencil_state,
+                real_create_sampler_state: dummy_create_sampler_state,
             });
         };
 
@@ -1747,7 +2069,20 @@ pub mod tests {
                 assert_eq!(ds.rs.device_input_layouts_by_ptr.len(), 0);
                 assert_eq!(ds.rs.context_input_layouts_by_ptr.len(), 1);
                 assert!(ds.rs.context_input_layouts_by_ptr.contains_key(&(pLayout as usize)));
+            });
 
+            // instanced and non-indexed draws go through the same hook path
+            let vtbl = (*context).lpVtbl;
+            assert_eq!((*vtbl).DrawIndexedInstanced as usize, hook_draw_indexed_instanced as *const () as usize);
+            assert_eq!((*vtbl).Draw as usize, hook_draw as *const () as usize);
+            (*context).DrawIndexedInstanced(12, 4, 0, 0, 0);
+            assert_eq!(GLOBAL_STATE.metrics.dip_calls, 2 * HOOK_DRAW_PERIODIC_CALLS + 1);
+            (*context).Draw(12, 0);
+            assert_eq!(GLOBAL_STATE.metrics.dip_calls, 2 * HOOK_DRAW_PERIODIC_CALLS + 2);
+            // rehooking doesn't hook them again
+            assert_eq!(apply_context_hooks(context, false).expect("rehook failed"), 0);
+
+            dev_state_d3d11_write().map(|(_lck, ds)| {
                 ds.rs = DX11RenderState::new();
             });
 
@@ -25,6 +25,7 @@ use crate::debug_spam;
 use crate::input_commands;
 use crate::mod_render;
 use mod_stats::mod_stats;
+use mm_core::draw_trace;
 use global_state::{GLOBAL_STATE, GLOBAL_STATE_LOCK, LOADED_MODS};
 use device_state::{dev_state_read, dev_state_write};
 use hook_snapshot;
@@ -38,6 +39,7 @@ use std::time::SystemTime;
 use shared_dx::util::*;
 use shared_dx::error::*;
 use shared_dx::types::*;
+use shared_dx::defs_dx9::DrawIndexedPrimitiveFn;
 
 pub (crate) const CLR_OK:u64 = 1;
 pub (crate) const CLR_FAIL:u64 = 666;
@@ -734,7 +736,8 @@ pub unsafe extern "system" fn hook_release(THIS: *mut IUnknown) -> ULONG {
 decl_profile_globals!(hdip);
 
 /// Render a mod using d3d9.  Returns true if the mod was rendered, false if not.
-unsafe fn render_mod_d3d9(THIS:*mut IDirect3DDevice9, d3dd:&ModD3DData9, nmod:&NativeModData,
+unsafe fn render_mod_d3d9(THIS:*mut IDirect3DDevice9, real_dip:DrawIndexedPrimitiveFn,
+    d3dd:&ModD3DData9, nmod:&NativeModData,
     override_texture: *mut IDirect3DBaseTexture9, override_stage:u32,
     primVerts:(u32,u32)) -> bool {
     if THIS == null_mut() {
@@ -773,9 +776,21 @@ unsafe fn render_mod_d3d9(THIS:*mut IDirect3DDevice9, d3dd:&ModD3DData9, nmod:&N
         return false;
     }
 
+    // save the index buffer if the mod has one, since we'll replace it
+    let mut pIndices: *mut IDirect3DIndexBuffer9 = null_mut();
+    let _ib_rod = if !d3dd.ib.is_null() {
+        (*THIS).GetIndices(&mut pIndices);
+        Some(ReleaseOnDrop::new(pIndices))
+    } else {
+        None
+    };
+
     // Note: C++ code did not change StreamSourceFreq...may need it for some games.
     (*THIS).SetVertexDeclaration(d3dd.decl);
     (*THIS).SetStreamSource(0, d3dd.vb, 0, nmod.mod_data.numbers.vert_size_bytes as u32);
+    if !d3dd.ib.is_null() {
+        (*THIS).SetIndices(d3dd.ib);
+    }
 
     // set mod textures
     let mut save_tex:[Option<*mut IDirect3DBaseTexture9>; 4] = [None; 4];
@@ -804,16 +819,31 @@ unsafe fn render_mod_d3d9(THIS:*mut IDirect3DDevice9, d3dd:&ModD3DData9, nmod:&N
         }
     };
 
-    // draw
-    (*THIS).DrawPrimitive(
-        nmod.mod_data.numbers.prim_type as u32,
-        0,
-        nmod.mod_data.numbers.prim_count as u32,
-    );
+    // draw.  we're inside the DIP hook, so the indexed draw has to use the real function.
+    if !d3dd.ib.is_null() {
+        (real_dip)(
+            THIS,
+            nmod.mod_data.numbers.prim_type as u32,
+            0,
+            0,
+            d3dd.vert_count,
+            0,
+            nmod.mod_data.numbers.prim_count as u32,
+        );
+    } else {
+        (*THIS).DrawPrimitive(
+            nmod.mod_data.numbers.prim_type as u32,
+            0,
+            nmod.mod_data.numbers.prim_count as u32,
+        );
+    }
 
     // restore state
     (*THIS).SetVertexDeclaration(pDecl);
     (*THIS).SetStreamSource(0, pStreamVB, offsetBytes, stride);
+    if !d3dd.ib.is_null() {
+        (*THIS).SetIndices(pIndices);
+    }
     // restore textures
     for (i,tex) in save_tex.iter().enumerate() {
         tex.map(|tex| {
@@ -941,6 +971,30 @@ where
     }
 }
 
+/// If a draw trace is being recorded (see `mm_core::draw_trace`), append a record for this
+/// draw.  The bound VB's checksum is only included if it has already been computed.
+#[inline]
+pub unsafe fn record_draw_trace(prim_count:u32, vert_count:u32, semantic_mask:u128) {
+    if !draw_trace::is_recording() {
+        return;
+    }
+    let vb = GLOBAL_STATE.bound_vertex_buffer;
+    let vb_checksum = if vb == 0 {
+        None
+    } else {
+        GLOBAL_STATE.vb_checksums.as_ref()
+            .and_then(|m| m.get(&vb))
+            .and_then(|s| s.checksum())
+    };
+    draw_trace::record_draw(&draw_trace::DrawTraceRecord {
+        frame: GLOBAL_STATE.metrics.total_frames,
+        prim_count,
+        vert_count,
+        vb_checksum,
+        semantic_mask,
+    });
+}
+
 pub unsafe extern "system" fn hook_draw_indexed_primitive(
     THIS: *mut IDirect3DDevice9,
     PrimitiveType: D3DPRIMITIVETYPE,
@@ -1011,10 +1065,11 @@ pub unsafe extern "system" fn hook_draw_indexed_primitive(
 
     // Compute a CRC for the currently bound VB if we haven't already, but
     // only when we actually need it: during a snapshot (so the CRC is
-    // available to the snapshot meta) or when a loaded mod has a
-    // VB-checksum constraint for this draw's (prim,vert) counts.
+    // available to the snapshot meta), when recording a draw trace, or when a
+    // loaded mod has a VB-checksum constraint for this draw's (prim,vert) counts.
     if GLOBAL_STATE.bound_vertex_buffer != 0
         && (GLOBAL_STATE.is_snapping
+            || draw_trace::is_recording()
             || global_state::vb_checksum_target_matches(primCount, NumVertices))
     {
         crate::hook_device::ensure_vb_checksum_dx9(
@@ -1022,6 +1077,9 @@ pub unsafe extern "system" fn hook_draw_indexed_primitive(
         );
     }
 
+    // dx9 doesn't track vertex declaration semantics, so the mask is always 0
+    record_draw_trace(primCount, NumVertices, 0);
+
     if GLOBAL_STATE.is_snapping {
         let mut sd = types::interop::SnapshotData {
             sd_size: std::mem::size_of::<types::interop::SnapshotData>() as u32,
@@ -1039,7 +1097,7 @@ pub unsafe extern "system" fn hook_draw_indexed_primitive(
             },
         };
         let mut dp = DevicePointer::D3D9(THIS);
-        hook_snapshot::take(&mut dp, &mut sd, this_is_selected);
+        hook_snapshot::take(&mut dp, &mut sd, this_is_selected, true);
     }
 
     profile_start!(hdip, main_combinator);
@@ -1055,7 +1113,7 @@ pub unsafe extern "system" fn hook_draw_indexed_primitive(
     let mod_status = check_and_render_mod(primCount, NumVertices,
         |d3dd,nmod| {
             if let ModD3DData::D3D9(d3dd) = d3dd {
-                render_mod_d3d9(THIS, d3dd, nmod,
+                render_mod_d3d9(THIS, real_dip, d3dd, nmod,
                     override_texture as *mut IDirect3DBaseTexture9, sel_stage,
                     (primCount,NumVertices))
             } else {
@@ -6,6 +6,7 @@ use std::time::{SystemTime, Duration};
 
 use global_state::{GLOBAL_STATE, LOADED_MODS, METRICS_TRACK_MOD_PRIMS, HWND};
 use mod_stats::mod_stats;
+use mm_core::draw_trace;
 use shared_dx::dx11rs::{DX11RenderState, VertexFormat};
 use shared_dx::types::{DevicePointer, DX11Metrics, D3D11Tex};
 use shared_dx::types_dx11::{HookDirect3D11Context};
@@ -17,22 +18,25 @@ use types::native_mod::{ModD3DData, ModD3DState, NativeModData};
 use winapi::ctypes::c_void;
 use winapi::shared::dxgiformat::{DXGI_FORMAT, DXGI_FORMAT_UNKNOWN, DXGI_FORMAT_R8G8B8A8_UNORM};
 use winapi::shared::dxgitype::DXGI_SAMPLE_DESC;
-use winapi::shared::winerror::{E_NOINTERFACE};
+use winapi::shared::winerror::{E_NOINTERFACE, E_FAIL};
 use winapi::um::d3d11::{ID3D11Buffer, ID3D11InputLayout, D3D11_PRIMITIVE_TOPOLOGY,
     ID3D11ShaderResourceView, D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_TEXTURE2D_DESC,
     D3D11_USAGE_DEFAULT, D3D11_BIND_SHADER_RESOURCE, D3D11_SUBRESOURCE_DATA,
-    ID3D11Texture2D, ID3D11Resource};
+    ID3D11Texture2D, ID3D11Resource, ID3D11VertexShader, ID3D11PixelShader, ID3D11ClassInstance,
+    D3D11_BOX, D3D11_MAP, D3D11_MAP_READ, D3D11_MAPPED_SUBRESOURCE, ID3D11BlendState,
+    ID3D11DepthStencilState, ID3D11RasterizerState, ID3D11SamplerState};
+use winapi::um::winnt::HRESULT;
 use winapi::shared::ntdef::ULONG;
 use winapi::um::d3dcommon::{D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST, D3D11_SRV_DIMENSION_TEXTURE2D};
 use winapi::um::processthreadsapi::GetCurrentProcessId;
 use winapi::um::unknwnbase::IUnknown;
 use winapi::um::winuser::{EnumWindows, GetWindowThreadProcessId, GetParent, GetDesktopWindow, GetForegroundWindow};
 use winapi::um::{d3d11::ID3D11DeviceContext, winnt::INT};
-use winapi::shared::minwindef::UINT;
+use winapi::shared::minwindef::{UINT, FLOAT};
 use device_state::{dev_state_d3d11_read, dev_state_d3d11_write};
 use shared_dx::error::{Result, HookError};
 use crate::hook_device_d3d11::apply_context_hooks;
-use crate::hook_render::{process_metrics, frame_init_clr, frame_load_mods, check_and_render_mod, CheckRenderModResult, track_set_texture, get_override_tex_if_selected};
+use crate::hook_render::{process_metrics, frame_init_clr, frame_load_mods, check_and_render_mod, CheckRenderModResult, track_set_texture, get_override_tex_if_selected, record_draw_trace};
 use crate::{input_commands, debugmode, mod_render};
 use winapi::um::d3d11::D3D11_BUFFER_DESC;
 use crate::debugmode::DebugModeCalledFns;
@@ -136,58 +140,271 @@ pub unsafe extern "system" fn hook_release(THIS: *mut IUnknown) -> ULONG {
     rc
 }
 
-// this was only needed when I was "rehooking" constantly (before I switched to copying
-// the vtable).  so its disabled now.  its a fairly hot function so better not to be doing
-// stuff here if I can avoid it.
-// pub unsafe extern "system" fn hook_VSSetConstantBuffers(
-//     THIS: *mut ID3D11DeviceContext,
-//     StartSlot: UINT,
-//     NumBuffers: UINT,
-//     ppConstantBuffers: *const *mut ID3D11Buffer,
-// ) {
-//     debugmode::note_called(DebugModeCalledFns::Hook_ContextVSSetConstantBuffers);
+/// Record objects (constant buffers, samplers) bound to a range of slots, growing the slot list
+/// as needed.  A null array clears the slots.
+unsafe fn set_bound_slots<T>(slots:&mut Vec<usize>, StartSlot: UINT, NumObjects: UINT,
+    ppObjects: *const *mut T) {
+    let end = (StartSlot + NumObjects) as usize;
+    if slots.len() < end {
+        slots.resize(end, 0);
+    }
+    for idx in 0..NumObjects as usize {
+        slots[StartSlot as usize + idx] = if ppObjects.is_null() {
+            0
+        } else {
+            *ppObjects.add(idx) as usize
+        };
+    }
+}
 
-//     let hook_context = match get_hook_context() {
-//         Ok(ctx) => ctx,
-//         Err(_) => return,
-//     };
+/// True if the objects already bound to the slots are the ones being set.
+unsafe fn bound_slots_match<T>(slots:&[usize], StartSlot: UINT, NumObjects: UINT,
+    ppObjects: *const *mut T) -> bool {
+    (0..NumObjects as usize).all(|idx| {
+        let new = if ppObjects.is_null() { 0 } else { *ppObjects.add(idx) as usize };
+        slots.get(StartSlot as usize + idx).copied().unwrap_or(0) == new
+    })
+}
 
-//     // TODO11: probably need to get more zealous about locking around this as DX11 and later
-//     // games are more likely to use multihreaded rendering, though hopefully i'll just never use
-//     // MM with one of those :|
-//     // But these metrics should just be thread local
-
-//     // let was_hooked = if debugmode::rehook_enabled(DebugModeCalledFns::Hook_ContextVSSetConstantBuffers) {
-//     //     let func_hooked = apply_context_hooks(THIS, false);
-//     //     match func_hooked {
-//     //         Ok(n) if n > 0 => true,
-//     //         Ok(_) => false,
-//     //         _ => {
-//     //             write_log_file("error late hooking");
-//     //             false
-//     //         }
-//     //     }
-//     // } else {
-//     //     false
-//     // };
-
-//     // match dev_state_d3d11_nolock() {
-//     //     Some(state) => {
-//     //         state.metrics.vs_set_const_buffers_calls += 1;
-//     //         if was_hooked {
-//     //             state.metrics.vs_set_const_buffers_hooks += 1;
-//     //         }
-//     //     },
-//     //     None => {}
-//     // };
-
-//     (hook_context.real_vs_setconstantbuffers)(
-//         THIS,
-//         StartSlot,
-//         NumBuffers,
-//         ppConstantBuffers
-//     )
-// }
+/// Apply `update` to the render state under the write lock, unless `unchanged` (checked under
+/// the read lock) says that it is already up to date.  Games mostly rebind what is already
+/// bound, so this keeps the setter hooks from serializing on the write lock.
+fn update_bound_state<U, F>(unchanged: U, update: F)
+where U: FnOnce(&DX11RenderState) -> bool, F: FnOnce(&mut DX11RenderState) {
+    if dev_state_d3d11_read().is_some_and(|(_lck, state)| unchanged(&state.rs)) {
+        return;
+    }
+    if let Some((_lck, state)) = dev_state_d3d11_write() {
+        update(&mut state.rs);
+    }
+}
+
+// The constant buffer hooks are only installed when precopying data, since they are only
+// used to capture constants for snapshots.  VSSetConstantBuffers is a fairly hot function
+// so they do as little as possible.
+pub unsafe extern "system" fn hook_VSSetConstantBuffers(
+    THIS: *mut ID3D11DeviceContext,
+    StartSlot: UINT,
+    NumBuffers: UINT,
+    ppConstantBuffers: *const *mut ID3D11Buffer,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextVSSetConstantBuffers, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    update_bound_state(
+        |rs| bound_slots_match(&rs.vs_constant_buffers, StartSlot, NumBuffers, ppConstantBuffers),
+        |rs| set_bound_slots(&mut rs.vs_constant_buffers, StartSlot, NumBuffers, ppConstantBuffers));
+
+    (hook_context.real_vs_setconstantbuffers)(THIS, StartSlot, NumBuffers, ppConstantBuffers)
+}
+
+pub unsafe extern "system" fn hook_PSSetConstantBuffers(
+    THIS: *mut ID3D11DeviceContext,
+    StartSlot: UINT,
+    NumBuffers: UINT,
+    ppConstantBuffers: *const *mut ID3D11Buffer,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextPSSetConstantBuffers, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    update_bound_state(
+        |rs| bound_slots_match(&rs.ps_constant_buffers, StartSlot, NumBuffers, ppConstantBuffers),
+        |rs| set_bound_slots(&mut rs.ps_constant_buffers, StartSlot, NumBuffers, ppConstantBuffers));
+
+    (hook_context.real_ps_setconstantbuffers)(THIS, StartSlot, NumBuffers, ppConstantBuffers)
+}
+
+/// Returns true if a copy of the resource is kept because it is a constant buffer.  Only takes
+/// the read lock, the Map/Unmap/UpdateSubresource hooks see every resource and shouldn't
+/// serialize on the write lock for ones they don't care about.
+fn is_tracked_constant_buffer(res: *mut ID3D11Resource) -> bool {
+    dev_state_d3d11_read()
+        .is_some_and(|(_lck, state)| state.rs.device_constant_buffer_data.contains_key(&(res as usize)))
+}
+
+pub unsafe extern "system" fn hook_UpdateSubresource(
+    THIS: *mut ID3D11DeviceContext,
+    pDstResource: *mut ID3D11Resource,
+    DstSubresource: UINT,
+    pDstBox: *const D3D11_BOX,
+    pSrcData: *const c_void,
+    SrcRowPitch: UINT,
+    SrcDepthPitch: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextUpdateSubresource, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    // most updates are for other resources, so check that it is a tracked constant buffer
+    // before taking the write lock
+    if !pSrcData.is_null() && is_tracked_constant_buffer(pDstResource) {
+        if let Some((_lck, state)) = dev_state_d3d11_write() {
+            if let Some(data) = state.rs.device_constant_buffer_data.get_mut(&(pDstResource as usize)) {
+                // for buffers the box is a byte range; without one the whole buffer is written
+                let (start, end) = if pDstBox.is_null() {
+                    (0, data.len())
+                } else {
+                    ((*pDstBox).left as usize, ((*pDstBox).right as usize).min(data.len()))
+                };
+                if start < end {
+                    std::ptr::copy_nonoverlapping(pSrcData as *const u8, data[start..end].as_mut_ptr(), end - start);
+                }
+            }
+        }
+    }
+
+    (hook_context.real_update_subresource)(THIS, pDstResource, DstSubresource, pDstBox, pSrcData,
+        SrcRowPitch, SrcDepthPitch)
+}
+
+pub unsafe extern "system" fn hook_Map(
+    THIS: *mut ID3D11DeviceContext,
+    pResource: *mut ID3D11Resource,
+    Subresource: UINT,
+    MapType: D3D11_MAP,
+    MapFlags: UINT,
+    pMappedResource: *mut D3D11_MAPPED_SUBRESOURCE,
+) -> HRESULT {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextMap, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return E_FAIL,
+    };
+
+    let hr = (hook_context.real_map)(THIS, pResource, Subresource, MapType, MapFlags, pMappedResource);
+
+    if hr == 0 && MapType != D3D11_MAP_READ && !pMappedResource.is_null()
+        && !(*pMappedResource).pData.is_null() && is_tracked_constant_buffer(pResource) {
+        if let Some((_lck, state)) = dev_state_d3d11_write() {
+            if state.rs.device_constant_buffer_data.contains_key(&(pResource as usize)) {
+                state.rs.mapped_constant_buffers.insert(pResource as usize, (*pMappedResource).pData as usize);
+            }
+        }
+    }
+    hr
+}
+
+pub unsafe extern "system" fn hook_Unmap(
+    THIS: *mut ID3D11DeviceContext,
+    pResource: *mut ID3D11Resource,
+    Subresource: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextUnmap, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    // the mapped memory is only valid until the real unmap, so copy it first
+    let is_mapped = dev_state_d3d11_read()
+        .is_some_and(|(_lck, state)| state.rs.mapped_constant_buffers.contains_key(&(pResource as usize)));
+    if is_mapped {
+        if let Some((_lck, state)) = dev_state_d3d11_write() {
+            if let Some(mapped) = state.rs.mapped_constant_buffers.remove(&(pResource as usize)) {
+                if let Some(data) = state.rs.device_constant_buffer_data.get_mut(&(pResource as usize)) {
+                    std::ptr::copy_nonoverlapping(mapped as *const u8, data.as_mut_ptr(), data.len());
+                }
+            }
+        }
+    }
+
+    (hook_context.real_unmap)(THIS, pResource, Subresource)
+}
+
+// The state hooks just record what is bound so that snapshots can look up the descriptions
+// saved by the device Create*State hooks.
+pub unsafe extern "system" fn hook_OMSetBlendState(
+    THIS: *mut ID3D11DeviceContext,
+    pBlendState: *mut ID3D11BlendState,
+    BlendFactor: *const [FLOAT; 4],
+    SampleMask: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextOMSetBlendState, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    // the factor can be null, which means all ones
+    let blend_state = (pBlendState as usize, if BlendFactor.is_null() { [1.0; 4] } else { *BlendFactor }, SampleMask);
+    update_bound_state(
+        |rs| rs.current_blend_state == blend_state,
+        |rs| rs.current_blend_state = blend_state);
+
+    (hook_context.real_om_set_blend_state)(THIS, pBlendState, BlendFactor, SampleMask)
+}
+
+pub unsafe extern "system" fn hook_OMSetDepthStencilState(
+    THIS: *mut ID3D11DeviceContext,
+    pDepthStencilState: *mut ID3D11DepthStencilState,
+    StencilRef: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextOMSetDepthStencilState, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    let ds_state = (pDepthStencilState as usize, StencilRef);
+    update_bound_state(
+        |rs| rs.current_depth_stencil_state == ds_state,
+        |rs| rs.current_depth_stencil_state = ds_state);
+
+    (hook_context.real_om_set_depth_stencil_state)(THIS, pDepthStencilState, StencilRef)
+}
+
+pub unsafe extern "system" fn hook_RSSetState(
+    THIS: *mut ID3D11DeviceContext,
+    pRasterizerState: *mut ID3D11RasterizerState,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextRSSetState, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    update_bound_state(
+        |rs| rs.current_rasterizer_state == pRasterizerState as usize,
+        |rs| rs.current_rasterizer_state = pRasterizerState as usize);
+
+    (hook_context.real_rs_set_state)(THIS, pRasterizerState)
+}
+
+pub unsafe extern "system" fn hook_PSSetSamplers(
+    THIS: *mut ID3D11DeviceContext,
+    StartSlot: UINT,
+    NumSamplers: UINT,
+    ppSamplers: *const *mut ID3D11SamplerState,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextPSSetSamplers, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    update_bound_state(
+        |rs| bound_slots_match(&rs.ps_samplers, StartSlot, NumSamplers, ppSamplers),
+        |rs| set_bound_slots(&mut rs.ps_samplers, StartSlot, NumSamplers, ppSamplers));
+
+    (hook_context.real_ps_set_samplers)(THIS, StartSlot, NumSamplers, ppSamplers)
+}
 
 pub unsafe extern "system" fn hook_IASetPrimitiveTopology (
     THIS: *mut ID3D11DeviceContext,
@@ -241,9 +458,13 @@ pub unsafe extern "system" fn hook_IASetVertexBuffers(
                     if pbuf != null_mut() {
                         // clear on first add of a valid buffer, the game appears to be calling this
                         // with 1 null buffer sometimes (and then calling draw) and I don't know why its
-                        // doing that.
-                        if idx == 0 {
+                        // doing that.  if the game is only setting later slots (e.g. instance
+                        // data), just replace those.
+                        let slot = StartSlot + idx;
+                        if idx == 0 && StartSlot == 0 {
                             state.rs.vb_state.clear();
+                        } else {
+                            state.rs.vb_state.retain(|(s,_,_)| *s != slot);
                         }
                         // Track slot-0 pointer for the VB-checksum mesh identifier.
                         // `StartSlot` is the absolute slot of the first buffer in
@@ -256,7 +477,7 @@ pub unsafe extern "system" fn hook_IASetVertexBuffers(
                         (*pbuf).GetDesc(&mut desc);
                         let bw = desc.ByteWidth;
                         let stride = desc.StructureByteStride;
-                        let vbinfo = (idx,bw,stride);
+                        let vbinfo = (slot,bw,stride);
                         state.rs.vb_state.push(vbinfo);
                     } else if StartSlot + idx == 0 {
                         // null rebind of slot 0 clears our tracked pointer
@@ -323,6 +544,46 @@ pub unsafe extern "system" fn hook_IASetInputLayout(
     )
 }
 
+pub unsafe extern "system" fn hook_VSSetShader(
+    THIS: *mut ID3D11DeviceContext,
+    pVertexShader: *mut ID3D11VertexShader,
+    ppClassInstances: *const *mut ID3D11ClassInstance,
+    NumClassInstances: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextVSSetShader, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    update_bound_state(
+        |rs| rs.current_vs == pVertexShader as usize,
+        |rs| rs.current_vs = pVertexShader as usize);
+
+    (hook_context.real_vs_set_shader)(THIS, pVertexShader, ppClassInstances, NumClassInstances)
+}
+
+pub unsafe extern "system" fn hook_PSSetShader(
+    THIS: *mut ID3D11DeviceContext,
+    pPixelShader: *mut ID3D11PixelShader,
+    ppClassInstances: *const *mut ID3D11ClassInstance,
+    NumClassInstances: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextPSSetShader, THIS as usize);
+
+    let hook_context = match get_hook_context() {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+
+    update_bound_state(
+        |rs| rs.current_ps == pPixelShader as usize,
+        |rs| rs.current_ps = pPixelShader as usize);
+
+    (hook_context.real_ps_set_shader)(THIS, pPixelShader, ppClassInstances, NumClassInstances)
+}
+
 /// Compute the primitives and vertex counts being used by the current active draw call.
 /// Current bug: this is derived from the full size of currently bound vertex buffers; it does not consider the 
 /// min or base vertex index arguments passed to drawindexed.  So if the game uses those 
@@ -330,14 +591,18 @@ pub unsafe extern "system" fn hook_IASetInputLayout(
 /// If you suspect that is a problem, there is some (disabled) logging code in DrawIndexed which can be used to log misses.
 /// (Search for CheckRenderModResult::NotRendered; In practice I have observed at least one game using vertex offsets 
 /// but only for things I don't mod like particle emitters, which also tend to use small primitive counts)
-fn compute_prim_vert_count(index_count: UINT, rs:&DX11RenderState) -> Option<(u32,u32)> {
-    if index_count <= 6 { // = 2 triangles generally, mods can't be this small or even close to this small
+///
+/// `elem_count` is the index count of an indexed draw, or the vertex count of a non-indexed one.
+/// For instanced draws, the per-vertex data is taken from the buffer in slot 0; other slots
+/// are assumed to hold instance data.
+fn compute_prim_vert_count(elem_count: UINT, instanced: bool, rs:&DX11RenderState) -> Option<(u32,u32)> {
+    if elem_count <= 6 { // = 2 triangles generally, mods can't be this small or even close to this small
         // don't bother
         return None;
     }
     // assumes triangle list, actual topology is in render state but we shouldn't even be in
     // here if its not triangle list.
-    let prim_count = index_count / 3;
+    let prim_count = elem_count / 3;
 
     // vert count has to be computed from the current vertex buffer
     // stream and the current input layout (vertex size)
@@ -355,6 +620,12 @@ fn compute_prim_vert_count(index_count: UINT, rs:&DX11RenderState) -> Option<(u3
             write_log_file("compute_prim_vert_count: no current vertex buffer set");
             return None;
         },
+        _n if instanced => {
+            match vb_state.iter().find(|(slot,_,_)| *slot == 0) {
+                Some((_slot,byteWidth,_stride)) if *byteWidth > 0 => *byteWidth,
+                _ => return None,
+            }
+        },
         _n => {
             // not sure how to figure out which one to use, maybe log warning
             return None;
@@ -411,6 +682,48 @@ unsafe fn needs_refill_for_layout(
     VertexFormat::has_extra_semantics(old_mask, new_mask)
 }
 
+/// (vertex shader checksum, semantics provided by the input layout)
+type ShaderLayoutKey = ([u8;16], Vec<(String,u32)>);
+
+thread_local! {
+    /// Vertex shader inputs missing from an input layout, by shader checksum and the semantics
+    /// the layout provides, so that the shader is only parsed (and the problem logged) once for
+    /// each combination.  This is keyed on content rather than the shader and layout pointers
+    /// because those are reused once the objects are released.
+    static LAYOUT_MISSING_VS_INPUTS: RefCell<FnvHashMap<ShaderLayoutKey, Vec<String>>> = RefCell::new(FnvHashMap::default());
+}
+
+/// True if the current input layout doesn't provide all the inputs of the current vertex shader.
+/// A mod filled using that layout would be missing data for the shader, and since the game
+/// can't draw with that combination either it probably means the tracked state is wrong.
+/// Returns false if it can't be checked, see `shader_capture::layout_missing_vs_inputs`.
+fn current_layout_missing_vs_inputs(rs:&DX11RenderState) -> bool {
+    let layout = rs.current_input_layout as usize;
+    if layout == 0 || rs.current_vs == 0 {
+        return false;
+    }
+    let sum = match rs.device_shader_bytecode.get(&rs.current_vs).and_then(|code| dxbc::checksum(code)) {
+        Some(sum) => sum,
+        None => return false,
+    };
+    let provided = match rs.context_input_layouts_by_ptr.get(&layout) {
+        Some(vf) => vf.provided_semantics(),
+        None => return false,
+    };
+    LAYOUT_MISSING_VS_INPUTS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let missing = cache.entry((sum, provided)).or_insert_with(|| {
+            let missing = shader_capture::layout_missing_vs_inputs(rs, layout).unwrap_or_default();
+            if !missing.is_empty() {
+                write_log_file(&format!("input layout {:x} is missing inputs of vertex shader {:x}: {:?}; mods will not be loaded with it",
+                    layout, rs.current_vs, missing));
+            }
+            missing
+        });
+        !missing.is_empty()
+    })
+}
+
 fn update_drawn_recently(metrics:&mut DX11Metrics, prim_count:u32, vert_count: u32, checkres:&CheckRenderModResult) {
     if METRICS_TRACK_MOD_PRIMS {
         use shared_dx::types::MetricsDrawStatus::*;
@@ -485,28 +798,123 @@ pub const HOOK_DRAW_PERIODIC_CALLS:u32 = 1000;
 const MM_DISABLE:bool = true;
 #[cfg(not(feature = "mmdisable"))]
 /// When enabled (at compile time) this causes a hardcoded pass through and then exit from
-/// `hook_draw_indexed`.  Since that function triggers all MM code in this rendering API,
+/// the draw hooks.  Since those functions trigger all MM code in this rendering API,
 /// that means MM essentially does nothing, not even initting the CLR or processing input.  This is useful
 /// for benchmarking the best case possible performance for MM without considering any draw
 /// overhead.  Note that other MM hook functions aren't affected by this and will still run
 /// their logic.
 const MM_DISABLE:bool = false;
 
+/// A hooked context draw call and its arguments.  All of the hooked draw functions share the
+/// same mod check and render logic, this is used to pass the call through to the right real
+/// function afterwards.
+#[derive(Copy, Clone, Debug)]
+enum DrawCall {
+    Indexed { index_count: UINT, start_index: UINT, base_vertex: INT },
+    IndexedInstanced { index_count: UINT, instance_count: UINT, start_index: UINT, base_vertex: INT,
+        start_instance: UINT },
+    NonIndexed { vertex_count: UINT, start_vertex: UINT },
+}
+
+impl DrawCall {
+    /// Number of indices (or vertices for a non-indexed draw) used by each instance.
+    fn elem_count(&self) -> UINT {
+        match *self {
+            DrawCall::Indexed { index_count, .. } => index_count,
+            DrawCall::IndexedInstanced { index_count, .. } => index_count,
+            DrawCall::NonIndexed { vertex_count, .. } => vertex_count,
+        }
+    }
+
+    fn indexed(&self) -> bool {
+        !matches!(self, DrawCall::NonIndexed { .. })
+    }
+
+    /// Instance count and start instance, if this is an instanced draw.  A mod drawn in place
+    /// of an instanced draw must be drawn with the same instances.
+    fn instances(&self) -> Option<(UINT,UINT)> {
+        match *self {
+            DrawCall::IndexedInstanced { instance_count, start_instance, .. } =>
+                Some((instance_count, start_instance)),
+            _ => None,
+        }
+    }
+
+    /// Base vertex and start index, as recorded in snapshots.  A non-indexed draw starts at
+    /// `start_vertex` and uses the generated indices from zero.
+    fn base_vertex_and_start_index(&self) -> (INT,UINT) {
+        match *self {
+            DrawCall::Indexed { start_index, base_vertex, .. } => (base_vertex, start_index),
+            DrawCall::IndexedInstanced { start_index, base_vertex, .. } => (base_vertex, start_index),
+            DrawCall::NonIndexed { start_vertex, .. } => (start_vertex as INT, 0),
+        }
+    }
+
+    /// Call the real (unhooked) draw function.
+    unsafe fn draw_real(&self, context: *mut ID3D11DeviceContext, hook_context: &HookDirect3D11Context) {
+        match *self {
+            DrawCall::Indexed { index_count, start_index, base_vertex } =>
+                (hook_context.real_draw_indexed)(context, index_count, start_index, base_vertex),
+            DrawCall::IndexedInstanced { index_count, instance_count, start_index, base_vertex, start_instance } =>
+                (hook_context.real_draw_indexed_instanced)(context, index_count, instance_count,
+                    start_index, base_vertex, start_instance),
+            DrawCall::NonIndexed { vertex_count, start_vertex } =>
+                (hook_context.real_draw)(context, vertex_count, start_vertex),
+        }
+    }
+}
+
 pub unsafe extern "system" fn hook_draw_indexed(
     THIS: *mut ID3D11DeviceContext,
     IndexCount: UINT,
     StartIndexLocation: UINT,
     BaseVertexLocation: INT,
 ) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextDrawIndexed, THIS as usize);
+    draw_hooked(THIS, DrawCall::Indexed {
+        index_count: IndexCount,
+        start_index: StartIndexLocation,
+        base_vertex: BaseVertexLocation,
+    });
+}
+
+pub unsafe extern "system" fn hook_draw_indexed_instanced(
+    THIS: *mut ID3D11DeviceContext,
+    IndexCountPerInstance: UINT,
+    InstanceCount: UINT,
+    StartIndexLocation: UINT,
+    BaseVertexLocation: INT,
+    StartInstanceLocation: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextDrawIndexedInstanced, THIS as usize);
+    draw_hooked(THIS, DrawCall::IndexedInstanced {
+        index_count: IndexCountPerInstance,
+        instance_count: InstanceCount,
+        start_index: StartIndexLocation,
+        base_vertex: BaseVertexLocation,
+        start_instance: StartInstanceLocation,
+    });
+}
+
+pub unsafe extern "system" fn hook_draw(
+    THIS: *mut ID3D11DeviceContext,
+    VertexCount: UINT,
+    StartVertexLocation: UINT,
+) {
+    debugmode::note_called(DebugModeCalledFns::Hook_ContextDraw, THIS as usize);
+    draw_hooked(THIS, DrawCall::NonIndexed {
+        vertex_count: VertexCount,
+        start_vertex: StartVertexLocation,
+    });
+}
+
+/// Common implementation of the draw hooks: checks for a mod to render in place of the call,
+/// takes snapshots and does the periodic processing.
+unsafe fn draw_hooked(THIS: *mut ID3D11DeviceContext, call: DrawCall) {
     if MM_DISABLE {
         match get_hook_context() {
             Ok(ctx) => {
-                (ctx.real_draw_indexed)(
-                    THIS,
-                    IndexCount,
-                    StartIndexLocation,
-                    BaseVertexLocation,
-                );
+                call.draw_real(THIS, &ctx);
                 return
             },
             Err(_) => return,
@@ -549,7 +957,6 @@ pub unsafe extern "system" fn hook_draw_indexed(
     }
 
     profile_start!(hdi, start);
-    debugmode::note_called(DebugModeCalledFns::Hook_ContextDrawIndexed, THIS as usize);
 
     let hook_context = match get_hook_context() {
         Ok(ctx) => ctx,
@@ -563,12 +970,7 @@ pub unsafe extern "system" fn hook_draw_indexed(
         periodic(); // need to do this so that input processes
 
         profile_start!(hdi, draw_input);
-        (hook_context.real_draw_indexed)(
-            THIS,
-            IndexCount,
-            StartIndexLocation,
-            BaseVertexLocation,
-        );
+        call.draw_real(THIS, &hook_context);
         profile_end!(hdi, draw_input);
 
         profile_end!(hdi, total);
@@ -606,29 +1008,30 @@ pub unsafe extern "system" fn hook_draw_indexed(
         // internally re-acquires the device-state lock.
         let snap_inputs = match dev_state_d3d11_read() {
             Some((_lck, state)) => {
-                let checkres = compute_prim_vert_count(IndexCount, &state.rs);
+                let checkres = compute_prim_vert_count(call.elem_count(), call.instances().is_some(), &state.rs);
                 let (prim_count, vert_count) = checkres.unwrap_or_else(|| (0,0));
                 Some((state.rs.prim_topology, prim_count, vert_count, state.devptr))
             },
             None => None,
         };
         if let Some((prim_topology, prim_count, vert_count, mut devptr)) = snap_inputs {
+            let (base_vertex, start_index) = call.base_vertex_and_start_index();
             let mut sd = types::interop::SnapshotData {
                 sd_size: std::mem::size_of::<types::interop::SnapshotData>() as u32,
                 was_reset: false,
                 clear_sd_on_reset: false,
                 prim_type: prim_topology as i32,
-                base_vertex_index: BaseVertexLocation,
+                base_vertex_index: base_vertex,
                 min_vertex_index: 0,
                 num_vertices: vert_count,
-                start_index: StartIndexLocation,
+                start_index: start_index,
                 prim_count: prim_count,
                 rend_data: SnapshotRendData {
                     // this value is overwritten by hook_snapshot::take()
                     d3d11: D3D11SnapshotRendData::new(),
                 },
             };
-            hook_snapshot::take(&mut devptr, &mut sd, this_is_selected);
+            hook_snapshot::take(&mut devptr, &mut sd, this_is_selected, call.indexed());
         }
 
     }
@@ -646,7 +1049,8 @@ pub unsafe extern "system" fn hook_draw_indexed(
                 profile_end!(hdi, geom_check);
                 None
             } else {
-                Some((compute_prim_vert_count(IndexCount, &state.rs), state.rs.vb_state.clone()))
+                Some((compute_prim_vert_count(call.elem_count(), call.instances().is_some(), &state.rs),
+                    state.rs.vb_state.clone()))
             }
         },
         None => {
@@ -683,16 +1087,27 @@ pub unsafe extern "system" fn hook_draw_indexed(
                         }
                     }
 
-                    // Compute the VB Crc if snapping or if the bound vb matches this prim/vert count.
+                    // Compute the VB Crc if snapping, recording a draw trace, or if the bound vb
+                    // matches this prim/vert count.
                     // No dev_state guard is held here so this is safe to call.
+                    let tracing = draw_trace::is_recording();
                     if !GLOBAL_STATE.is_snapping
                         && GLOBAL_STATE.bound_vertex_buffer != 0
-                        && global_state::vb_checksum_target_matches(prim_count, vert_count)
+                        && (tracing || global_state::vb_checksum_target_matches(prim_count, vert_count))
                     {
                         crate::hook_device_d3d11::ensure_vb_checksum_dx11(
                             GLOBAL_STATE.bound_vertex_buffer,
                         );
                     }
+                    if tracing {
+                        let semantic_mask = dev_state_d3d11_read().and_then(|(_lck, state)| {
+                            let il = state.rs.current_input_layout;
+                            state.rs.context_input_layouts_by_ptr
+                                .get(&(il as usize))
+                                .map(|vf| vf.semantic_mask())
+                        }).unwrap_or(0);
+                        record_draw_trace(prim_count, vert_count, semantic_mask);
+                    }
 
                     // if there is a matching mod, render it
                     profile_start!(hdi, mod_precheck);
@@ -747,7 +1162,8 @@ pub unsafe extern "system" fn hook_draw_indexed(
                                                 nmod.name.clone()));
                                         false
                                     } else {
-                                        render_mod_d3d11(THIS, &hook_context, d3d11d, nmod, override_texture, sel_stage, (prim_count,vert_count))
+                                        render_mod_d3d11(THIS, &hook_context, d3d11d, nmod, override_texture, sel_stage,
+                                            call.instances(), (prim_count,vert_count))
                                     }
                                 } else {
                                     false
@@ -771,14 +1187,21 @@ pub unsafe extern "system" fn hook_draw_indexed(
                             // current input layout pointer briefly under the
                             // dev_state lock; LOADED_MODS is a separate
                             // mutex so it is safe to take it inside the
-                            // dev_state guard.
-                            let il = match dev_state_d3d11_read() {
-                                Some((_lck, state)) => state.rs.current_input_layout,
-                                None => null_mut(),
+                            // dev_state guard.  Don't load with a layout
+                            // that can't feed the vertex shader, a later
+                            // draw will request the load again.
+                            let (il, missing_inputs) = match dev_state_d3d11_read() {
+                                Some((_lck, state)) => (state.rs.current_input_layout,
+                                    current_layout_missing_vs_inputs(&state.rs)),
+                                None => (null_mut(), false),
                             };
                             match LOADED_MODS.lock() {
                                 Ok(mut loaded_mods_guard) => {
-                                    let nmod = mod_load::get_mod_by_name(name, &mut *loaded_mods_guard);
+                                    let nmod = if missing_inputs {
+                                        None
+                                    } else {
+                                        mod_load::get_mod_by_name(name, &mut *loaded_mods_guard)
+                                    };
                                     if let Some(nmod) = nmod {
                                         // If the mod is already loaded but we
                                         // need a refill (semantics-mismatch
@@ -841,12 +1264,7 @@ pub unsafe extern "system" fn hook_draw_indexed(
         };
         profile_end!(hdi, draw_ovtex_check);
         profile_start!(hdi, draw_input);
-        (hook_context.real_draw_indexed)(
-            THIS,
-            IndexCount,
-            StartIndexLocation,
-            BaseVertexLocation,
-        );
+        call.draw_real(THIS, &hook_context);
         profile_end!(hdi, draw_input);
         profile_start!(hdi, draw_ovtex_reset);
         save_srv.as_mut().map(|srv| {
@@ -1195,7 +1613,7 @@ fn draw_periodic(context:*mut ID3D11DeviceContext) {
 unsafe fn render_mod_d3d11(context:*mut ID3D11DeviceContext, hook_context: &HookDirect3D11Context,
      d3dd:&ModD3DData11, _nmod:&NativeModData,
     override_texture: *mut ID3D11ShaderResourceView, override_stage:u32,
-    _primVerts:(u32,u32)) -> bool {
+    instances:Option<(UINT,UINT)>, _primVerts:(u32,u32)) -> bool {
     if context.is_null() {
         return false;
     }
@@ -1235,6 +1653,11 @@ unsafe fn render_mod_d3d11(context:*mut ID3D11DeviceContext, hook_context: &Hook
         vbuffer_stride.as_ptr(),
         vbuffer_offset.as_ptr());
 
+    // set the mod index buffer, if it has one; the current one is restored below
+    if !d3dd.ib.is_null() {
+        (*context).IASetIndexBuffer(d3dd.ib, d3dd.ib_format, 0);
+    }
+
     // if the mod has textures, need to set the pixel shader resources for them
     let mut orig_srvs: [*mut ID3D11ShaderResourceView; 16] = [null_mut(); 16];
     // keep this outside of if block so it doesn't get dropped while the context (maybe)
@@ -1293,8 +1716,21 @@ unsafe fn render_mod_d3d11(context:*mut ID3D11DeviceContext, hook_context: &Hook
         None
     };
 
-    // draw
-    (*context).Draw(d3dd.vert_count as UINT, 0);
+    // draw.  call the real functions to avoid entering our hooks.  if the game drew instances,
+    // draw the mod with the same instances; the instance data in the other vertex buffer slots
+    // is still bound.
+    match (d3dd.ib.is_null(), instances) {
+        (false, None) =>
+            (hook_context.real_draw_indexed)(context, d3dd.index_count as UINT, 0, 0),
+        (false, Some((instance_count, start_instance))) =>
+            (hook_context.real_draw_indexed_instanced)(context, d3dd.index_count as UINT,
+                instance_count, 0, 0, start_instance),
+        (true, None) =>
+            (hook_context.real_draw)(context, d3dd.vert_count as UINT, 0),
+        (true, Some((instance_count, start_instance))) =>
+            (hook_context.real_draw_instanced)(context, d3dd.vert_count as UINT,
+                instance_count, 0, start_instance),
+    }
 
     // restore overridden tex
     override_save_srv.as_mut().map(|srv| {
@@ -1422,25 +1858,6 @@ fn create_selection_texture_dx11() -> Result<()> {
 //     );
 // }
 
-// pub unsafe extern "system" fn hook_draw(
-//     THIS: *mut ID3D11DeviceContext,
-//     VertexCount: UINT,
-//     StartVertexLocation: UINT,
-// ) -> () {
-//     let hook_context = match get_hook_context() {
-//         Ok(ctx) => ctx,
-//         Err(_) => return,
-//     };
-
-//     // write_log_file("hook_draw called");
-
-//     return (hook_context.real_draw)(
-//         THIS,
-//         VertexCount,
-//         StartVertexLocation,
-//     );
-// }
-
 // pub unsafe extern "system" fn hook_draw_auto (
 //     THIS: *mut ID3D11DeviceContext,
 // ) -> () {
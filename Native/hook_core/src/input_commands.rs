@@ -32,6 +32,7 @@ use snaplib::anim_snap_state::AnimConstants;
 use hook_snapshot::SNAP_CONFIG;
 
 use snaplib::snap_config::SnapConfig;
+use key_bindings::{Command, KeyBindings};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use shared_dx::types::DevicePointer::{D3D9, D3D11};
@@ -121,6 +122,7 @@ pub fn init_snapshot_mode() {
                 vert_count: 0,
                 sequence: 0,
                 constants: constant_tracking::ConstantGroup::new(),
+                cbuffer: Vec::new(),
                 capture_count: 0,
                 frame: 0,
                 player_transform: Err(HookError::SnapshotFailed("".to_owned())),
@@ -360,59 +362,174 @@ fn select_prev_variant() {
     }
 }
 
-fn setup_fkey_input(device: DevicePointer, inp: &mut input::Input) {
-    write_log_file("using fkey input layout");
-    // If you change these, be sure to change LocStrings/ProfileText in MMLaunch!
-
-    // Allow the handlers to take a copy of the device pointer in the closure.
-    // This means that these handlers must be cleared when the device is destroyed,
-    // (see purge_device_resources)
-    // but lets us avoid passing a context argument through the input layer.
-    inp.add_press_fn(input::DIK_F1, Box::new(move || cmd_reload_mods(device)));
-    inp.add_press_fn(input::DIK_F2, Box::new(cmd_toggle_show_mods));
-    inp.add_press_fn(
-        input::DIK_F3,
-        Box::new(move || cmd_select_next_texture(device)),
-    );
-    inp.add_press_fn(
-        input::DIK_F4,
-        Box::new(move || cmd_select_prev_texture(device)),
-    );
-    inp.add_press_fn(input::DIK_F6, Box::new(move || cmd_clear_texture_lists(device)));
-    inp.add_press_fn(input::DIK_F7, Box::new(cmd_take_snapshot));
-    inp.add_press_fn(input::DIK_NUMPAD8, Box::new(select_next_variant));
-    inp.add_press_fn(input::DIK_NUMPAD9, Box::new(select_prev_variant));
-
-    // Hot-reload: Ctrl+F10 reloads the managed DLL.  MMManaged.dll is now a thin shell
-    // that dynamically loads MMManaged.Engine.dll (the engine implementation).  On reload,
-    // the shell reads a fresh copy of the engine DLL from disk and swaps in its callbacks.
-    inp.add_press_fn(input::DIK_F10, Box::new(move || cmd_reload_managed_dll(device)));
+fn cmd_save_variant_preset() {
+    match LOADED_MODS.lock() {
+        Ok(g) => {
+            g.as_ref().map(|mstate| {
+                mod_prefs::save_variant_preset(&mstate.mods, &mstate.selected_variant, None)
+            });
+        }
+        Err(e) => {
+            write_log_file(&format!("cmd_save_variant_preset: LOADED_MODS lock poisoned: {}", e));
+        }
+    }
+}
+fn cmd_next_variant_preset() {
+    match LOADED_MODS.lock() {
+        Ok(mut g) => {
+            g.as_mut().map(|mstate| {
+                if mod_prefs::apply_next_variant_preset(&mstate.mods, &mut mstate.selected_variant).is_none() {
+                    write_log_file("no variant presets saved");
+                }
+            });
+        }
+        Err(e) => {
+            write_log_file(&format!("cmd_next_variant_preset: LOADED_MODS lock poisoned: {}", e));
+        }
+    }
+}
+
+/// Disable the mod that was rendered most recently, and remember that in the prefs.
+fn cmd_disable_recent_mod() {
+    let hookstate = unsafe { &mut GLOBAL_STATE };
+    let lastframe = hookstate.metrics.total_frames;
+
+    match LOADED_MODS.lock() {
+        Ok(mut g) => {
+            let disabled = g.as_mut().and_then(|mstate| mod_render::disable_recent_mod(mstate, lastframe));
+            match disabled {
+                Some(name) => {
+                    // free its d3d data now, it won't be loaded again while the mod is disabled
+                    if let Some(nmod) = mod_load::get_mod_by_name(&name, &mut *g) {
+                        unsafe { mod_load::reset_for_reload(nmod) };
+                    }
+                    g.as_ref().map(|mstate| mod_prefs::save_disabled_mods(&mstate.disabled_mods));
+                }
+                None => write_log_file("no recently rendered mod to disable"),
+            }
+        }
+        Err(e) => {
+            write_log_file(&format!("cmd_disable_recent_mod: LOADED_MODS lock poisoned: {}", e));
+        }
+    }
+}
+fn cmd_enable_last_disabled_mod() {
+    match LOADED_MODS.lock() {
+        Ok(mut g) => {
+            g.as_mut().map(|mstate| {
+                match mod_render::enable_last_disabled_mod(mstate) {
+                    Some(_) => mod_prefs::save_disabled_mods(&mstate.disabled_mods),
+                    None => write_log_file("no disabled mods to enable"),
+                }
+            });
+        }
+        Err(e) => {
+            write_log_file(&format!("cmd_enable_last_disabled_mod: LOADED_MODS lock poisoned: {}", e));
+        }
+    }
 }
 
-fn setup_punct_input(device: DevicePointer, inp: &mut input::Input) {
-    write_log_file("using punct key input layout");
-    // If you change these, be sure to change LocStrings/ProfileText in MMLaunch!
-    inp.add_press_fn(input::DIK_BACKSLASH, Box::new(move || cmd_reload_mods(device)));
-    inp.add_press_fn(input::DIK_RBRACKET, Box::new(cmd_toggle_show_mods));
-    inp.add_press_fn(input::DIK_SEMICOLON, Box::new(move || cmd_clear_texture_lists(device)));
-    inp.add_press_fn(
-        input::DIK_COMMA,
-        Box::new(move || cmd_select_next_texture(device)),
-    );
-    inp.add_press_fn(
-        input::DIK_PERIOD,
-        Box::new(move || cmd_select_prev_texture(device)),
-    );
-    inp.add_press_fn(input::DIK_SLASH, Box::new(cmd_take_snapshot));
-
-    // Running out of punct!  oh well use these
-    inp.add_press_fn(input::DIK_NUMPAD8, Box::new(select_next_variant));
-    inp.add_press_fn(input::DIK_NUMPAD9, Box::new(select_prev_variant));
-
-    // Hot-reload managed DLL (Ctrl+F10) - available in all input profiles
-    inp.add_press_fn(input::DIK_F10, Box::new(move || cmd_reload_managed_dll(device)));
-
-    // _punctKeyMap[DIK_MINUS] = [&]() { this->loadEverything(); };
+/// Log how many mods are waiting for, or being loaded by, the load threads.
+fn cmd_print_load_status() {
+    match mod_load::load_progress() {
+        Ok(p) => write_log_file(&format!("mod load status: {}", p)),
+        Err(e) => write_log_file(&format!("cmd_print_load_status: {}", e)),
+    }
+}
+
+/// Draw traces stop by themselves after this many frames so that a forgotten trace doesn't
+/// fill up the disk.
+const DRAW_TRACE_MAX_FRAMES:u64 = 60 * 60;
+
+/// Start or stop recording a draw trace.  The trace is written next to the log file and can be
+/// replayed against a mod DB with the `trace_replay` tool.
+fn cmd_toggle_draw_trace() {
+    use mm_core::draw_trace;
+
+    if draw_trace::is_recording() {
+        draw_trace::stop_recording();
+        return;
+    }
+    let log_path = get_log_file_path();
+    let dir = std::path::Path::new(&log_path).parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(std::env::temp_dir);
+    let stamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs()).unwrap_or(0);
+    let path = dir.join(format!("drawtrace_{}.mmdt", stamp));
+    let path = path.to_string_lossy();
+    if let Err(e) = draw_trace::start_recording(&path, DRAW_TRACE_MAX_FRAMES) {
+        write_log_file(&format!("failed to start draw trace {}: {}", path, e));
+    }
+}
+
+/// Return the handler for the command.
+///
+/// Allow the handlers to take a copy of the device pointer in the closure.
+/// This means that these handlers must be cleared when the device is destroyed,
+/// (see purge_device_resources)
+/// but lets us avoid passing a context argument through the input layer.
+fn command_fn(device: DevicePointer, cmd: Command) -> Box<dyn FnMut()> {
+    match cmd {
+        Command::ReloadMods => Box::new(move || cmd_reload_mods(device)),
+        Command::ToggleMods => Box::new(cmd_toggle_show_mods),
+        Command::ClearTextures => Box::new(move || cmd_clear_texture_lists(device)),
+        Command::NextTexture => Box::new(move || cmd_select_next_texture(device)),
+        Command::PrevTexture => Box::new(move || cmd_select_prev_texture(device)),
+        Command::Snapshot => Box::new(cmd_take_snapshot),
+        Command::NextVariant => Box::new(select_next_variant),
+        Command::PrevVariant => Box::new(select_prev_variant),
+        // Hot-reload: Ctrl+F10 reloads the managed DLL.  MMManaged.dll is now a thin shell
+        // that dynamically loads MMManaged.Engine.dll (the engine implementation).  On reload,
+        // the shell reads a fresh copy of the engine DLL from disk and swaps in its callbacks.
+        Command::ReloadManagedDll => Box::new(move || cmd_reload_managed_dll(device)),
+        Command::ToggleDrawTrace => Box::new(cmd_toggle_draw_trace),
+        Command::DisableRecentMod => Box::new(cmd_disable_recent_mod),
+        Command::EnableLastDisabledMod => Box::new(cmd_enable_last_disabled_mod),
+        Command::SaveVariantPreset => Box::new(cmd_save_variant_preset),
+        Command::NextVariantPreset => Box::new(cmd_next_variant_preset),
+        Command::PrintLoadStatus => Box::new(cmd_print_load_status),
+    }
+}
+
+/// Get the key bindings.  If there is a bindings file in the MM root dir (the per-game
+/// `<game>.keybindings.yaml` or `keybindings.yaml`) it is applied on top of the built-in
+/// layout for the input profile, otherwise the built-in layout is used as is.
+fn load_key_bindings(inp_profile: &str) -> KeyBindings {
+    let base = KeyBindings::for_profile(inp_profile).unwrap_or_else(|| {
+        write_log_file(&format!(
+            "input scheme unrecognized: {}, using FKeys",
+            inp_profile
+        ));
+        KeyBindings::fkeys()
+    });
+
+    let root = match get_mm_conf_info() {
+        Ok((_, Some(dir))) => dir,
+        _ => return base,
+    };
+    let game = get_module_name_base().ok();
+    let path = match key_bindings::find_bindings_file(&root, game.as_deref()) {
+        Some(p) => p,
+        None => return base,
+    };
+    let res = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| KeyBindings::from_yaml(&text, &base));
+    match res {
+        Ok((bindings, warnings)) => {
+            write_log_file(&format!("loaded key bindings from {:?}", path));
+            for w in warnings {
+                write_log_file(&format!("key bindings: {}", w));
+            }
+            bindings
+        },
+        Err(e) => {
+            write_log_file(&format!("error: failed to load key bindings from {:?}: {}; using {} layout",
+                path, e, inp_profile));
+            base
+        }
+    }
 }
 
 pub fn setup_input(device: DevicePointer, inp: &mut input::Input) -> Result<()> {
@@ -432,8 +549,6 @@ pub fn setup_input(device: DevicePointer, inp: &mut input::Input) -> Result<()>
         // )));
     }
 
-    // Set key bindings.  Input also assumes that CONTROL modifier is required for these as well.
-    // TODO: should push this out to conf file eventually so that they can be customized without rebuild
     let interop_state = unsafe { &GLOBAL_STATE.interop_state };
     interop_state
         .as_ref()
@@ -447,19 +562,11 @@ pub fn setup_input(device: DevicePointer, inp: &mut input::Input) -> Result<()>
                 .map_err(HookError::CStrConvertFailed)
         })
         .map(|inp_profile| {
-            let lwr = inp_profile.to_owned().to_lowercase();
-            if lwr.starts_with("fk") {
-                setup_fkey_input(device, inp);
-            } else if lwr.starts_with("punct") {
-                setup_punct_input(device, inp);
-            } else {
-                write_log_file(&format!(
-                    "input scheme unrecognized: {}, using FKeys",
-                    inp_profile
-                ));
-                setup_fkey_input(device, inp);
+            let bindings = load_key_bindings(inp_profile);
+            write_log_file(&format!("key bindings: {}", bindings));
+            for (cmd, kb) in bindings.bindings.iter() {
+                inp.add_binding(kb, command_fn(device, *cmd));
             }
-
         })
 }
 
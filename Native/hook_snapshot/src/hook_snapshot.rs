@@ -11,14 +11,17 @@ use winapi::shared::d3d9::*;
 use winapi::shared::d3d9types::*;
 use winapi::shared::dxgiformat::DXGI_FORMAT;
 use winapi::shared::dxgiformat::DXGI_FORMAT_R16_UINT;
+use winapi::shared::dxgiformat::DXGI_FORMAT_R16G16_SINT;
+use winapi::shared::dxgiformat::DXGI_FORMAT_R16G16B16A16_SINT;
 use winapi::shared::dxgiformat::DXGI_FORMAT_R32_UINT;
 use winapi::shared::dxgiformat::DXGI_FORMAT_UNKNOWN;
 use winapi::shared::minwindef::{DWORD, UINT, BOOL};
 
 use constant_tracking;
+use constant_tracking::{BoundConstantBuffer, ConstantBufferGroup};
 use d3dx;
 use global_state::GLOBAL_STATE;
-use winapi::um::{d3d11::{D3D11_BIND_RENDER_TARGET, D3D11_BUFFER_DESC, D3D11_INPUT_ELEMENT_DESC,
+use winapi::um::{d3d11::{D3D11_BIND_RENDER_TARGET, D3D11_BUFFER_DESC, D3D11_INPUT_ELEMENT_DESC, D3D11_INPUT_PER_VERTEX_DATA,
     D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_TEXTURE2D_DESC, ID3D11Buffer, ID3D11Device,
     ID3D11DeviceContext, ID3D11Resource, ID3D11ShaderResourceView,
     ID3D11Texture2D, ID3D11View}, d3dcommon::D3D11_SRV_DIMENSION_TEXTURE2D};
@@ -35,16 +38,20 @@ use std::time::SystemTime;
 use shared_dx::util::*;
 use shared_dx::error::*;
 
-use snaplib::snap_config::{SnapConfig};
+use snaplib::snap_config::{SnapConfig, SnapFilters};
 use snaplib::anim_frame::{AnimFrame, AnimFrameFile};
 use snaplib::anim_frame::RenderStateMap;
+use snaplib::render_state_d3d11::{D3D11RenderStateMap, BlendState, RasterizerState, DepthStencilState, SamplerState};
 use snaplib::anim_frame::write_obj_to_file;
 use snaplib::anim_snap_state::AnimSnapState;
+use snaplib::snap_manifest::{most_common_vec_encoding, MeshEntry, SnapManifest, MANIFEST_VERSION};
+use mod_load::{score_vec_encodings, EncodingScore};
 
 use std::collections::HashMap;
 
 use std::sync::RwLock;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 lazy_static! {
     // Snapshotting currently stops after a certain amount of real time has passed from the start of
@@ -70,9 +77,35 @@ lazy_static! {
         plugins: None,
         clear_sd_on_reset: false,
         extdll_path: String::new(),
+        anim_cbuffer_slot: None,
+        player_transform: None,
+        filters: SnapFilters::default(),
     }));
 
     pub static ref WAS_RESET: AtomicBool = AtomicBool::new(false);
+    // set once the missing bone palette has been logged, so that it isn't logged on every draw
+    static ref ANIM_CBUFFER_WARNED: AtomicBool = AtomicBool::new(false);
+
+    // meshes snapped in the current session, written to the manifest when it ends
+    static ref SNAP_SESSION: Mutex<SnapSession> = Mutex::new(SnapSession { device: "", meshes: Vec::new() });
+}
+
+struct SnapSession {
+    device: &'static str,
+    meshes: Vec<MeshEntry>,
+}
+
+/// Texture files written for a snapshot, by the managed code (d3d9) or `save_textures` (d3d11).
+fn snapped_textures(snap_dir:&str, snap_prefix:&str) -> Vec<String> {
+    let prefix = format!("{}_texture", snap_prefix);
+    let mut textures = std::fs::read_dir(snap_dir).map(|rd| {
+        rd.filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(&prefix) && name.ends_with(".dds"))
+            .collect::<Vec<_>>()
+    }).unwrap_or_default();
+    textures.sort();
+    textures
 }
 
 fn snapshot_extra() -> bool {
@@ -111,7 +144,10 @@ fn set_pconsts(device: *mut IDirect3DDevice9, num_to_read:usize, pconsts: &mut c
     pconsts.bools.set(0, dest.as_ptr(), num_to_read as u32);
 }
 
-pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotData, this_is_selected:bool) {
+/// Take a snapshot of the current draw call if it is selected (or an anim snapshot is active).
+/// `indexed` is false for a non-indexed draw (DX11 `Draw`), in which case there is no index
+/// buffer to snapshot; sequential indices are written instead.
+pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotData, this_is_selected:bool, indexed:bool) {
     if devptr.is_null() {
         return;
     }
@@ -143,6 +179,8 @@ pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDa
     }
 
     let pre_rc;
+    let mut entry = MeshEntry::new(sd.prim_count, sd.num_vertices, (*gs).metrics.total_frames);
+    let mut filtered = false;
     // snap in a block so that drops within activate and we can check ref count after
     unsafe {
         write_log_file(&format!("==> New snap started: prims: {}, verts: {}, basevert: {}, startindex: {}", sd.prim_count, sd.num_vertices, sd.base_vertex_index, sd.start_index));
@@ -163,14 +201,28 @@ pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDa
                 .ok();
         }
 
-        save_constants(devptr, gs, &snap_conf);
+        let (vcbufs, pcbufs) = save_constants(devptr, gs, &snap_conf);
 
         let save_rs = save_render_state(devptr);
 
-        match set_buffers(devptr, sd) {
+        match set_buffers(devptr, sd, indexed) {
             Ok(bufs) => {
+                entry.vb_checksum = bufs.vb_checksum();
+                // filter here rather than earlier so that the checksum is available
+                if let Err(reason) = snap_conf.filters.check(sd.prim_count, sd.num_vertices, entry.vb_checksum) {
+                    write_log_file(&format!("snapshot filtered out: {}", reason));
+                    filtered = true;
+                }
+                if !filtered {
+                    let scores = bufs.vec_encoding_scores();
+                    if let Some(best) = scores.first() {
+                        write_log_file(&format!("vec encoding guess: {} (scores: {})", best.name,
+                            scores.iter().map(|s| format!("{} {:.3}", s.name, s.score)).collect::<Vec<_>>().join(", ")));
+                        entry.vec_encoding = Some(best.name.to_owned());
+                    }
+                }
                 write_log_file(&format!("snapshot data size is: {}", sd.sd_size));
-                GLOBAL_STATE.interop_state.as_mut().map(|is| {
+                GLOBAL_STATE.interop_state.as_mut().filter(|_| !filtered).map(|is| {
                     // If the snapshot state was reset set that flag in sd and clear WAS_RESET
                     sd.clear_sd_on_reset = snap_conf.clear_sd_on_reset;
                     sd.was_reset = WAS_RESET.load(Ordering::Relaxed);
@@ -186,6 +238,7 @@ pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDa
                     let sresult = if res == 0 { 
                         Some(*(cb.GetSnapshotResult)())
                     } else {
+                        entry.errors.push(format!("managed snapshot failed with code {}", res));
                         None
                     };
 
@@ -210,6 +263,9 @@ pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDa
                         (*gs).last_snapshot_dir = Some(dir.clone())
                     }                    
 
+                    entry.file_prefix = sprefix.clone();
+                    snap_plugins::capture(devptr, sd.prim_count, sd.num_vertices, (*gs).metrics.total_frames, &dir, &sprefix);
+
                     if res == 0 && snapshot_extra() {
                         ANIM_SNAP_STATE.get_mut().as_mut().map(|ass| {
                             if ass.snap_dir == "" {
@@ -222,12 +278,32 @@ pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDa
 
                         let _ = save_textures(devptr, &bufs, &dir, &sprefix).map_err(|e| {
                             write_log_file(&format!("failed to save textures: {:?}", e));
+                            entry.errors.push(format!("failed to save textures: {:?}", e));
                         });
+                        entry.textures = snapped_textures(&dir, &sprefix);
 
                         let (gotpix,gotvert) = shader_capture::take_snapshot(devptr, &dir, &sprefix);
-                        let vc = if gotvert { &(*gs).vertex_constants } else { &None };
-                        let pc = if gotpix { &(*gs).pixel_constants } else { &None };
-                        constant_tracking::take_snapshot(&dir, &sprefix, vc, pc);
+                        entry.vertex_shader = gotvert;
+                        entry.pixel_shader = gotpix;
+                        match devptr {
+                            DevicePointer::D3D9(_) => {
+                                let vc = if gotvert { &(*gs).vertex_constants } else { &None };
+                                let pc = if gotpix { &(*gs).pixel_constants } else { &None };
+                                entry.vertex_constants = vc.is_some();
+                                entry.pixel_constants = pc.is_some();
+                                constant_tracking::take_snapshot(&dir, &sprefix, vc, pc);
+                            },
+                            DevicePointer::D3D11(_) => {
+                                // the shader resource definitions name the buffers and their variables
+                                let vinfo = shader_capture::snapped_shader_info(&dir, &sprefix, "_vshader");
+                                let pinfo = shader_capture::snapped_shader_info(&dir, &sprefix, "_pshader");
+                                entry.vertex_constants = vcbufs.is_some();
+                                entry.pixel_constants = pcbufs.is_some();
+                                constant_tracking::take_snapshot_d3d11(&dir, &sprefix,
+                                    &vcbufs, vinfo.as_ref().and_then(|i| i.resources.as_ref()),
+                                    &pcbufs, pinfo.as_ref().and_then(|i| i.resources.as_ref()));
+                            },
+                        }
 
                         if save_rs.has_state() {
                             let file = format!("{}/{}_rstate.yaml", &dir, &sprefix);
@@ -248,11 +324,20 @@ pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDa
                                 }).map(|contents| {
                                     if contents.contains("m4x4 oPos, v0, c0") {
                                         write_log_file("=======> error: shader contains simple position multiply, likely not gpu animated, aborting snap.  you must not snap an animation or set require_cpu to false in the conf to snap this mesh");
+                                        entry.errors.push("shader is likely not gpu animated, snapshot aborted".to_owned());
                                         write_log_file(&format!("file: {}", &file));
                                         (*gs).is_snapping = false;
                                         *ANIM_SNAP_STATE.get_mut() = None; 
                                 }
                                 }).unwrap_or_default();
+                            } else if shader_capture::snapped_vshader_has_skinning(&dir, &sprefix) == Some(false) {
+                                // d3d11 shaders aren't disassembled, but no blend inputs means
+                                // the same thing
+                                write_log_file("=======> error: vertex shader has no blend index or weight inputs, likely not gpu animated, aborting snap.  you must not snap an animation or set require_cpu to false in the conf to snap this mesh");
+                                entry.errors.push("vertex shader has no blend inputs, likely not gpu animated, snapshot aborted".to_owned());
+                                write_log_file(&format!("file: {}/{}_vshader.dxbc", &dir, &sprefix));
+                                (*gs).is_snapping = false;
+                                *ANIM_SNAP_STATE.get_mut() = None;
                             }
                         }
                     }
@@ -260,10 +345,23 @@ pub unsafe fn take(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDa
             },
             Err(e) => {
                 write_log_file(&format!("snapshot::take: failed to set buffers: {:?}", e));
+                entry.errors.push(format!("failed to set buffers: {:?}", e));
             }
         }
         (*gs).device = None;
     }
+    // filtered meshes are left out of the manifest too
+    match SNAP_SESSION.lock() {
+        Ok(_) if filtered => (),
+        Ok(mut session) => {
+            session.device = match devptr {
+                DevicePointer::D3D9(_) => "d3d9",
+                DevicePointer::D3D11(_) => "d3d11",
+            };
+            session.meshes.push(entry);
+        },
+        Err(e) => write_log_file(&format!("failed to lock snap session: {}", e)),
+    }
     // check for resource leak, we do this in another block so that all the release on
     // drops activated.
     {
@@ -394,13 +492,41 @@ unsafe fn save_textures(devtr:&mut DevicePointer, buffers:&Box<dyn SnapDeviceBuf
     }
 }
 
-fn auto_snap_anim(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotData, gs:*mut HookState, snap_conf:&SnapConfig) -> bool {
-    let device = match devptr {
-        DevicePointer::D3D9(d) => *d as *mut _,
-        | _ => {
-            write_log_file("auto_snap_anim: not a d3d9 device");
-            return false;
+/// Copy the bone palette for a D3D11 anim snapshot into `dest`.  This is the vertex shader
+/// constant buffer in `slot`, or the largest bound buffer if no slot is configured.  Like
+/// `save_constants_d3d11`, this requires data precopy.
+fn copy_anim_cbuffer_d3d11(slot:Option<u32>, dest:&mut Vec<u8>) -> bool {
+    let (_lck, state) = match dev_state_d3d11_read() {
+        Some(s) => s,
+        None => return false,
+    };
+    let bound = state.rs.vs_constant_buffers.iter().enumerate()
+        .filter(|(_slot, ptr)| **ptr != 0)
+        .filter_map(|(slot, ptr)| state.rs.device_constant_buffer_data.get(ptr).map(|data| (slot as u32, data)));
+    let found = match slot {
+        Some(want) => bound.filter(|(slot, _data)| *slot == want).next(),
+        None => bound.max_by_key(|(_slot, data)| data.len()),
+    };
+    match found {
+        Some((_slot, data)) => {
+            dest.clear();
+            dest.extend_from_slice(data);
+            true
+        },
+        None => {
+            if !ANIM_CBUFFER_WARNED.swap(true, Ordering::Relaxed) {
+                write_log_file(&format!("auto_snap_anim: no bone palette constant buffer (slot {:?}) bound to vertex shader, data precopy may be disabled", slot));
+            }
+            false
         }
+    }
+}
+
+fn auto_snap_anim(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotData, gs:*mut HookState, snap_conf:&SnapConfig) -> bool {
+    // None for D3D11, where the bone palette is copied from the precopied constant buffers instead
+    let d3d9_device = match devptr {
+        DevicePointer::D3D9(d) => Some(*d as *mut _),
+        DevicePointer::D3D11(_) => None,
     };
     let mut autosnap = false;
 
@@ -442,7 +568,14 @@ fn auto_snap_anim(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDat
                     write_log_file("too many constant captures!");
                 } else {
                     let next = &mut ass.sequence_vconstants[ass.next_vconst_idx];
-                    set_vconsts(device, snap_conf.vconsts_to_capture, &mut next.constants, false);
+                    match d3d9_device {
+                        Some(device) => set_vconsts(device, snap_conf.vconsts_to_capture, &mut next.constants, false),
+                        None => {
+                            if !copy_anim_cbuffer_d3d11(snap_conf.anim_cbuffer_slot, &mut next.cbuffer) {
+                                return false;
+                            }
+                        }
+                    }
                     // (*THIS).GetTransform(D3DTS_WORLD, std::mem::transmute(next.worldmat.m.as_mut_ptr()));
                     // (*THIS).GetTransform(D3DTS_VIEW, std::mem::transmute(next.viewmat.m.as_mut_ptr()));
                     // (*THIS).GetTransform(D3DTS_PROJECTION, std::mem::transmute(next.projmat.m.as_mut_ptr()));
@@ -453,14 +586,16 @@ fn auto_snap_anim(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDat
                     next.frame = ass.curr_frame;
                     next.capture_count = *cap_count;
                     ass.next_vconst_idx += 1;
-                    unsafe {
-                        // the reference game I used for testing requires an external dll to obtain the player transform
-                        // because the animation bones fed to the shader are in world space; this transform lets me 
-                        // map them back into object space.
-                        XDLLSTATE.as_mut().map(|tbstate| {
-                            next.player_transform = tbstate.get_player_transform();
-                        });
-                    }
+                    // the reference game I used for testing requires an external dll to obtain the player transform
+                    // because the animation bones fed to the shader are in world space; this transform lets me 
+                    // map them back into object space.  Other games may not need it, or may
+                    // have it in the constants, see player_transform.
+                    let consts = match d3d9_device {
+                        Some(_) => CapturedConstants::Registers(&next.constants.floats),
+                        None => CapturedConstants::Buffer(&next.cbuffer),
+                    };
+                    let xform = unsafe { player_transform::player_transform(&consts) };
+                    next.player_transform = xform;
                 }
             }
             else if !ass.seen_primverts.contains(primvert) {
@@ -478,11 +613,41 @@ fn auto_snap_anim(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotDat
 
 }
 
-unsafe fn save_constants(devptr:&mut DevicePointer, gs:*mut HookState, snap_conf:&SnapConfig) {
+/// Copy the constant buffers bound to the vertex and pixel shaders.  These are only available
+/// if the data is being precopied, since the buffers can't be read back.
+fn save_constants_d3d11() -> (Option<ConstantBufferGroup>, Option<ConstantBufferGroup>) {
+    let (_lck, state) = match dev_state_d3d11_read() {
+        Some(s) => s,
+        None => return (None, None),
+    };
+    let copy_bound = |slots:&Vec<usize>| {
+        let buffers = slots.iter().enumerate()
+            .filter(|(_slot, ptr)| **ptr != 0)
+            .filter_map(|(slot, ptr)| state.rs.device_constant_buffer_data.get(ptr)
+                .map(|data| BoundConstantBuffer { slot: slot as u32, data: data.clone() }))
+            .collect::<Vec<_>>();
+        if buffers.is_empty() {
+            None
+        } else {
+            Some(ConstantBufferGroup { buffers })
+        }
+    };
+    let vcbufs = copy_bound(&state.rs.vs_constant_buffers);
+    let pcbufs = copy_bound(&state.rs.ps_constant_buffers);
+    if vcbufs.is_none() && pcbufs.is_none() {
+        write_log_file("no constant buffer data for current shaders, data precopy may be disabled");
+    }
+    (vcbufs, pcbufs)
+}
+
+/// Capture shader constants.  For D3D9 these are read into the constant groups in the hook
+/// state, for D3D11 the bound constant buffers are returned.
+unsafe fn save_constants(devptr:&mut DevicePointer, gs:*mut HookState, snap_conf:&SnapConfig)
+    -> (Option<ConstantBufferGroup>, Option<ConstantBufferGroup>) {
     let device = match devptr {
         &mut DevicePointer::D3D9(device) => device,
         &mut DevicePointer::D3D11(_) => {
-            return;
+            return save_constants_d3d11();
         },
     };
     // constant tracking workaround: read back all the constants
@@ -494,6 +659,7 @@ unsafe fn save_constants(devptr:&mut DevicePointer, gs:*mut HookState, snap_conf
             set_pconsts(device, snap_conf.pconsts_to_capture, pconsts);
         });
     }
+    (None, None)
 }
 
 trait SnapRendState {
@@ -583,18 +749,48 @@ unsafe fn save_render_state_d3d9(device:*mut IDirect3DDevice9) -> Box<dyn SnapRe
     })
 }
 
-struct D3D11SnapRenderState {}
+struct D3D11SnapRenderState {
+    states: D3D11RenderStateMap,
+}
 impl SnapRendState for D3D11SnapRenderState {
     fn has_state(&self) -> bool {
-        false
+        self.states.has_state()
     }
-    fn save(self: Box<Self>, _file:&str) -> Result<()> {
-        Ok(())
+    fn save(self: Box<Self>, file:&str) -> Result<()> {
+        if self.has_state() {
+            write_obj_to_file(&file, false, &self.states)
+        } else {
+            Ok(())
+        }
     }
 }
 
+/// Look up the descriptions of the currently bound states.  These are only available if data
+/// is being precopied, since that is when the device state creation functions are hooked.
 fn save_render_state_d3d11() -> Box<dyn SnapRendState> {
-    Box::new(D3D11SnapRenderState{})
+    let states = match dev_state_d3d11_read() {
+        Some((_lck, state)) => {
+            let rs = &state.rs;
+            let (blend_ptr, blend_factor, sample_mask) = rs.current_blend_state;
+            let blend = rs.device_blend_states.get(&blend_ptr)
+                .map(|desc| BlendState::from_desc(desc, blend_factor, sample_mask));
+            let rasterizer = rs.device_rasterizer_states.get(&rs.current_rasterizer_state)
+                .map(RasterizerState::from_desc);
+            let (ds_ptr, stencil_ref) = rs.current_depth_stencil_state;
+            let depth_stencil = rs.device_depth_stencil_states.get(&ds_ptr)
+                .map(|desc| DepthStencilState::from_desc(desc, stencil_ref));
+            let samplers = rs.ps_samplers.iter().enumerate()
+                .filter_map(|(slot, ptr)| rs.device_sampler_states.get(ptr)
+                    .map(|desc| (slot as u32, SamplerState::from_desc(desc))))
+                .collect();
+            D3D11RenderStateMap::new(blend, rasterizer, depth_stencil, samplers)
+        },
+        None => D3D11RenderStateMap::new(None, None, None, BTreeMap::new()),
+    };
+    if !states.has_state() {
+        write_log_file("no render state descriptions for current draw, data precopy may be disabled");
+    }
+    Box::new(D3D11SnapRenderState { states })
 }
 fn save_render_state(devptr:&mut DevicePointer) -> Box<dyn SnapRendState> {
     match devptr {
@@ -606,10 +802,17 @@ fn save_render_state(devptr:&mut DevicePointer) -> Box<dyn SnapRendState> {
 use std::any::Any;
 
 use crate::snap_extdll::init_xdll;
-use crate::snap_extdll::XDLLSTATE;
+use crate::player_transform;
+use crate::snap_plugins;
+use crate::player_transform::CapturedConstants;
 
 trait SnapDeviceBuffers {
     fn as_any(&self) -> &dyn Any;
+    /// Checksum of the vertex buffer data, if it was copied natively
+    fn vb_checksum(&self) -> Option<u32>;
+    /// Scores for the vector encodings the normals might use, best first.  Empty if the vertex
+    /// data isn't available natively or the normals aren't stored as pairs of 16 bit ints.
+    fn vec_encoding_scores(&self) -> Vec<EncodingScore>;
 }
 
 struct D3D9SnapDeviceBuffers {
@@ -623,6 +826,13 @@ impl SnapDeviceBuffers for D3D9SnapDeviceBuffers{
     fn as_any(&self) -> &dyn Any {
         self
     }
+    fn vb_checksum(&self) -> Option<u32> {
+        // the managed code reads the vertex buffer
+        None
+    }
+    fn vec_encoding_scores(&self) -> Vec<EncodingScore> {
+        vec![]
+    }
 }
 
 unsafe fn set_buffers_d3d9(device:*mut IDirect3DDevice9, sd:&mut types::interop::SnapshotData) -> Result<Box<dyn SnapDeviceBuffers>> {
@@ -666,10 +876,12 @@ unsafe fn set_buffers_d3d9(device:*mut IDirect3DDevice9, sd:&mut types::interop:
 const MAX_SRV: u32 = 32;
 
 struct D3D11SnapDeviceBuffers {
-    pub _ld:Vec<D3D11_INPUT_ELEMENT_DESC>,
+    pub ld:Vec<D3D11_INPUT_ELEMENT_DESC>,
     pub _context_rod:ReleaseOnDrop<*mut ID3D11DeviceContext>,
-    pub _ib_data:Vec<u8>,
-    pub _vb_data:Vec<u8>,
+    pub ib_data:Vec<u8>,
+    pub index_size:u32,
+    pub vb_data:Vec<u8>,
+    pub vert_size:usize,
     pub srvs:[*mut ID3D11ShaderResourceView; MAX_SRV as usize],
     pub _srv_rods:Vec<ReleaseOnDrop<*mut ID3D11ShaderResourceView>>,
     pub srv_2d_tex:Vec<u32>,
@@ -679,9 +891,49 @@ impl SnapDeviceBuffers for D3D11SnapDeviceBuffers{
     fn as_any(&self) -> &dyn Any {
         self
     }
+    fn vb_checksum(&self) -> Option<u32> {
+        Some(util::vb_checksum::compute(&self.vb_data))
+    }
+    fn vec_encoding_scores(&self) -> Vec<EncodingScore> {
+        let semantic = |el:&D3D11_INPUT_ELEMENT_DESC| unsafe { CStr::from_ptr(el.SemanticName) }
+            .to_string_lossy().to_ascii_lowercase();
+        let in_vert = |el:&&D3D11_INPUT_ELEMENT_DESC, size:usize| el.AlignedByteOffset as usize + size <= self.vert_size;
+        // the normal is in the first two s16s of either format
+        let norm = match self.ld.iter().find(|el| semantic(el).starts_with("normal")
+            && (el.Format == DXGI_FORMAT_R16G16B16A16_SINT || el.Format == DXGI_FORMAT_R16G16_SINT)
+            && in_vert(el, 4)) {
+            Some(norm) => norm.AlignedByteOffset as usize,
+            None => return vec![],
+        };
+        let verts = self.vb_data.chunks_exact(self.vert_size);
+        let pairs:Vec<(i16,i16)> = verts.clone()
+            .map(|v| (i16::from_le_bytes([v[norm], v[norm + 1]]), i16::from_le_bytes([v[norm + 2], v[norm + 3]])))
+            .collect();
+        let positions:Option<Vec<[f32;3]>> = self.ld.iter()
+            .find(|el| semantic(el).starts_with("position"))
+            .and_then(|el| vertex_codec::codec(el.Format).ok()
+                .filter(|c| c.components() >= 3 && in_vert(&el, c.size()))
+                .map(|c| (el.AlignedByteOffset as usize, c)))
+            .and_then(|(off, codec)| verts.map(|v| codec.decode(&v[off..]).ok().map(|p| [p[0], p[1], p[2]])).collect());
+        let indices:Vec<u32> = match self.index_size {
+            2 => self.ib_data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]) as u32).collect(),
+            _ => self.ib_data.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect(),
+        };
+        score_vec_encodings(&pairs, Some(&indices), positions.as_deref())
+    }
+}
+
+/// Index data for a non-indexed draw of `count` vertices: 0..count, using 16 bit indices if
+/// possible.  Returns the data and the index size in bytes.
+fn sequential_index_data(count:u32) -> (Vec<u8>, u32) {
+    if count <= u16::MAX as u32 + 1 {
+        ((0..count).flat_map(|i| (i as u16).to_le_bytes()).collect(), 2)
+    } else {
+        ((0..count).flat_map(|i| i.to_le_bytes()).collect(), 4)
+    }
 }
 
-unsafe fn set_buffers_d3d11(device:*mut ID3D11Device, sd:&mut types::interop::SnapshotData) -> Result<Box<dyn SnapDeviceBuffers>> {
+unsafe fn set_buffers_d3d11(device:*mut ID3D11Device, sd:&mut types::interop::SnapshotData, indexed:bool) -> Result<Box<dyn SnapDeviceBuffers>> {
     // Hold a single read guard for the duration so that we don't acquire the
     // device-state lock multiple times (which can deadlock on RwLock if a
     // writer queues up between two reads on the same thread).
@@ -716,43 +968,51 @@ unsafe fn set_buffers_d3d11(device:*mut ID3D11Device, sd:&mut types::interop::Sn
         let context_rod = ReleaseOnDrop::new(context);
 
         // and we'll need the index buffer
-        let mut curr_ibuffer: *mut ID3D11Buffer = null_mut();
-        let mut curr_ibuffer_offset: UINT = 0;
-        let mut curr_ibuffer_format: DXGI_FORMAT = DXGI_FORMAT_UNKNOWN;
-        (*context).IAGetIndexBuffer(&mut curr_ibuffer, &
-            mut curr_ibuffer_format, &mut curr_ibuffer_offset);
-        if curr_ibuffer.is_null() {
-            return Err(HookError::SnapshotFailed("failed to get index buffer".to_string()));
-        }
-        // create the ib_rod but actually we don't need to save it outside of this func
-        let _ib_rod = ReleaseOnDrop::new(curr_ibuffer);
-
-        // determine if we have the data for the buffer since at this time we can't read it
-        // directly via Map
-        let ib_copy = state.rs.device_index_buffer_data
-            .get(&(curr_ibuffer as usize))
-            .map(|v| v.clone())
-            .ok_or_else(|| {
-                HookError::SnapshotFailed("failed to get index buffer data, was not previously saved".to_string())
-            })?;
+        let (ib_copy, index_size) = if !indexed {
+            // nothing to copy for a non-indexed draw, the verts are used in order
+            let (ib_copy, index_size) = sequential_index_data(sd.prim_count * 3);
+            write_log_file(&format!("non-indexed draw, generated {} indices", sd.prim_count * 3));
+            (ib_copy, index_size)
+        } else {
+            let mut curr_ibuffer: *mut ID3D11Buffer = null_mut();
+            let mut curr_ibuffer_offset: UINT = 0;
+            let mut curr_ibuffer_format: DXGI_FORMAT = DXGI_FORMAT_UNKNOWN;
+            (*context).IAGetIndexBuffer(&mut curr_ibuffer, &
+                mut curr_ibuffer_format, &mut curr_ibuffer_offset);
+            if curr_ibuffer.is_null() {
+                return Err(HookError::SnapshotFailed("failed to get index buffer".to_string()));
+            }
+            // create the ib_rod but actually we don't need to save it outside of this func
+            let _ib_rod = ReleaseOnDrop::new(curr_ibuffer);
+
+            // determine if we have the data for the buffer since at this time we can't read it
+            // directly via Map
+            let ib_copy = state.rs.device_index_buffer_data
+                .get(&(curr_ibuffer as usize))
+                .map(|v| v.clone())
+                .ok_or_else(|| {
+                    HookError::SnapshotFailed("failed to get index buffer data, was not previously saved".to_string())
+                })?;
+
+            // determine if 16 or 32 bit indices
+            let mut ib_desc:D3D11_BUFFER_DESC = std::mem::zeroed();
+            (*curr_ibuffer).GetDesc(&mut ib_desc);
+            let index_size = match curr_ibuffer_format {
+                DXGI_FORMAT_R16_UINT => 2,
+                DXGI_FORMAT_R32_UINT => 4,
+                _ => return Err(HookError::SnapshotFailed(format!("unknown index buffer format: {:x}", curr_ibuffer_format))),
+            };
+
+            // should match expected size
+            let ex_size = (sd.prim_count * 3 * index_size) as usize;
+            if ib_copy.len() != ex_size {
+                return Err(HookError::SnapshotFailed(format!("index buffer data size mismatch, expected: {}, got: {}", ex_size, ib_copy.len())));
+            }
 
-        // determine if 16 or 32 bit indices
-        let mut ib_desc:D3D11_BUFFER_DESC = std::mem::zeroed();
-        (*curr_ibuffer).GetDesc(&mut ib_desc);
-        let index_size = match curr_ibuffer_format {
-            DXGI_FORMAT_R16_UINT => 2,
-            DXGI_FORMAT_R32_UINT => 4,
-            _ => return Err(HookError::SnapshotFailed(format!("unknown index buffer format: {:x}", curr_ibuffer_format))),
+            write_log_file(&format!("index buffer size: {}, format: {}", ib_copy.len(), curr_ibuffer_format));
+            (ib_copy, index_size)
         };
 
-        // should match expected size
-        let ex_size = (sd.prim_count * 3 * index_size) as usize;
-        if ib_copy.len() != ex_size {
-            return Err(HookError::SnapshotFailed(format!("index buffer data size mismatch, expected: {}, got: {}", ex_size, ib_copy.len())));
-        }
-
-        write_log_file(&format!("index buffer size: {}, format: {}", ib_copy.len(), curr_ibuffer_format));
-
         // now same for vertex buffers
         const MAX_VBUFFERS: usize = 16;
         let mut curr_vbuffers: [*mut ID3D11Buffer; MAX_VBUFFERS] = [null_mut(); MAX_VBUFFERS];
@@ -765,8 +1025,15 @@ unsafe fn set_buffers_d3d11(device:*mut ID3D11Device, sd:&mut types::interop::Sn
         let _vb_rods =
             curr_vbuffers.iter().filter(|vb| !vb.is_null())
              .map(|vb| ReleaseOnDrop::new(*vb)).collect::<Vec<_>>();
+        // buffers in slots that only have per-instance data (in an instanced draw) don't
+        // contain verts, so ignore them
+        let vertex_slot = |slot:usize| vf.layout.iter()
+            .any(|el| el.InputSlot as usize == slot && el.InputSlotClass == D3D11_INPUT_PER_VERTEX_DATA);
         // filter active
-        let curr_vbuffers = curr_vbuffers.iter().filter(|vb| !vb.is_null()).collect::<Vec<_>>();
+        let curr_vbuffers = curr_vbuffers.iter().enumerate()
+            .filter(|(slot,vb)| !vb.is_null() && vertex_slot(*slot))
+            .map(|(_slot,vb)| vb)
+            .collect::<Vec<_>>();
         if curr_vbuffers.is_empty() {
             return Err(HookError::SnapshotFailed("no vertex buffers".to_string()));
         }
@@ -823,9 +1090,11 @@ unsafe fn set_buffers_d3d11(device:*mut ID3D11Device, sd:&mut types::interop::Sn
 
         return Ok(Box::new(D3D11SnapDeviceBuffers{
             _context_rod: context_rod,
-            _ld: ld,
-            _ib_data: ib_copy,
-            _vb_data: vb_copy,
+            ld,
+            ib_data: ib_copy,
+            index_size: index_size as u32,
+            vert_size,
+            vb_data: vb_copy,
             srvs: orig_srvs,
             srv_2d_tex: tex_indices,
             _srv_rods,
@@ -834,10 +1103,10 @@ unsafe fn set_buffers_d3d11(device:*mut ID3D11Device, sd:&mut types::interop::Sn
     Err(HookError::SnapshotFailed("set_buffers_d3d11: failed snapshot".to_string()))
 }
 
-fn set_buffers(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotData) -> Result<Box<dyn SnapDeviceBuffers>> {
+fn set_buffers(devptr:&mut DevicePointer, sd:&mut types::interop::SnapshotData, indexed:bool) -> Result<Box<dyn SnapDeviceBuffers>> {
     match devptr {
         &mut DevicePointer::D3D9(device) => unsafe { set_buffers_d3d9(device, sd) },
-        &mut DevicePointer::D3D11(device) => unsafe { set_buffers_d3d11(device, sd) },
+        &mut DevicePointer::D3D11(device) => unsafe { set_buffers_d3d11(device, sd, indexed) },
     }
 }
 
@@ -874,38 +1143,24 @@ fn write_anim_snap_state(ass:&AnimSnapState) -> Result<()> {
         let frame_file = frames_by_mesh.entry((aseq.prim_count, aseq.vert_count))
             .or_insert_with(AnimFrameFile::new);
 
-        // for testing when I don't have this
-        //let hack_pxform = "0.0 0.0 0.0 0.0".to_owned();
         let pxform = match aseq.player_transform.as_ref() {
             Err(e) => {
-                //&hack_pxform
                 return Err(HookError::SnapshotFailed(
                     format!("player transform not available at frame {}, aborting constant write (error: {:?})", frame, e)
                 ));
             },
-            Ok(xfrm) => {
-                xfrm
-            }
-        };
-        let origxf = pxform;
-        let mut pxform = pxform.split(" ");
-        // meh https://stackoverflow.com/questions/31046763/does-rust-have-anything-like-scanf
-        let parse_next = |split: &mut std::str::Split<&str>, comp:&str, orig:&str| -> Result<f32> {
-            let res = split.next().ok_or_else(|| HookError::SnapshotFailed("Failed transform parse".to_owned()))?;
-            Ok(res.parse().map_err(|_| HookError::SnapshotFailed(format!("failed to parse float, source str: '{}', comp: {}, orig: '{}'", res, comp, orig)))?)
+            Ok(xfrm) => xfrm
         };
-        let x = parse_next(&mut pxform, "x", &origxf)?;
-        let y = parse_next(&mut pxform, "y", &origxf)?;
-        let z = parse_next(&mut pxform, "z", &origxf)?;
-        let rot = parse_next(&mut pxform, "rot", &origxf)?;
-        let pxform = constant_tracking::vecToVec4(&vec![x,y,z,rot], 0);
         let framedata = AnimFrame {
             snapped_at: aseq.snapped_at,
-            floats: aseq.constants.floats.get_as_btree(),
-            transform1: Some(pxform),
-            // transform2: None,
-            // transform3: None,
-            // transform4: None,
+            floats: if aseq.cbuffer.is_empty() {
+                aseq.constants.floats.get_as_btree()
+            } else {
+                constant_tracking::buffer_registers(&aseq.cbuffer)
+            }.into_iter()
+                .map(|(reg, v)| (reg, v.to_array()))
+                .collect(),
+            transform1: Some(pxform.to_array()),
         };
         frame_file.frames.push(framedata);
     }
@@ -915,12 +1170,46 @@ fn write_anim_snap_state(ass:&AnimSnapState) -> Result<()> {
     // each mesh per frame
     for ((prims,verts), frame_file) in frames_by_mesh {
         let out_file = format!("{}/animframes_{}p_{}v.dat", anim_dir, prims, verts);
-        frame_file.write_to_file(&out_file)?;
+        frame_file.write_to_file(&out_file).map_err(HookError::SerdeError)?;
     }
     write_log_file("wrote anim sequences");
     Ok(())
 }
 
+/// Write the manifest for the session that just ended and start a new one.
+fn write_manifest(dir:Option<&str>, started_at:SystemTime, elapsed:std::time::Duration) {
+    let (device, meshes) = match SNAP_SESSION.lock() {
+        Ok(mut session) => (session.device, std::mem::take(&mut session.meshes)),
+        Err(e) => {
+            write_log_file(&format!("failed to lock snap session: {}", e));
+            return;
+        }
+    };
+    let dir = match dir {
+        Some(dir) if !dir.is_empty() => dir,
+        _ => {
+            if !meshes.is_empty() {
+                write_log_file("no snapshot directory, not writing manifest");
+            }
+            return;
+        }
+    };
+    let manifest = SnapManifest {
+        version: MANIFEST_VERSION,
+        device: device.to_owned(),
+        directory: dir.to_owned(),
+        started_at,
+        duration_ms: elapsed.as_millis() as u64,
+        vb_checksum_algorithm: util::vb_checksum::ALGORITHM_NAME.to_owned(),
+        vec_encoding: most_common_vec_encoding(&meshes),
+        meshes,
+    };
+    match manifest.write() {
+        Ok(file) => write_log_file(&format!("wrote snapshot manifest with {} meshes: {}", manifest.meshes.len(), file)),
+        Err(e) => write_log_file(&format!("failed to write snapshot manifest: {:?}", e)),
+    }
+}
+
 pub unsafe fn present_process() {
     let snap_ms = match SNAP_CONFIG.read() {
         Err(e) => {
@@ -939,6 +1228,7 @@ pub unsafe fn present_process() {
         let elapsed = now
             .duration_since((*gs).snap_start)
             .unwrap_or(max_dur);
+        snap_plugins::process_frame((*gs).metrics.total_frames, elapsed.as_millis() as u64);
         if elapsed >= max_dur {
             write_log_file("ending snapshot");
             if let Some(dir) = &(*gs).last_snapshot_dir {
@@ -952,6 +1242,7 @@ pub unsafe fn present_process() {
                 write_ss_complete().unwrap_or_else(|e| 
                     write_log_file(&format!("failed to write {}: {:?}", out_file, e)));
             }
+            write_manifest((*gs).last_snapshot_dir.as_deref(), (*gs).snap_start, elapsed);
             (*gs).is_snapping = false;
             
             let o = ANIM_SNAP_STATE.get();
@@ -969,10 +1260,14 @@ pub unsafe fn present_process() {
 /// Called when the clear texture key is pressed, and when a new snapshot is started.
 pub fn reset() {
     WAS_RESET.store(true, std::sync::atomic::Ordering::Relaxed);
+    ANIM_CBUFFER_WARNED.store(false, std::sync::atomic::Ordering::Relaxed);
 
     unsafe {
         init_xdll().map_err(|e| {
             write_log_file(&format!("failed to load snap ext dll, snapshot transforms will be incorrect: {:?}", e))
         }).unwrap_or_default();
+        player_transform::init_provider();
+        // no-op unless the plugin list in the snap config changed
+        snap_plugins::load_plugins();
      };
 }
\ No newline at end of file
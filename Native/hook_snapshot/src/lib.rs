@@ -7,6 +7,8 @@
 #![allow(non_snake_case)]
 mod hook_snapshot;
 mod snap_extdll;
+mod player_transform;
+mod snap_plugins;
 pub use crate::hook_snapshot::*;
 
 #[macro_use]
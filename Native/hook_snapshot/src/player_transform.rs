@@ -0,0 +1,214 @@
+//! Player transform providers for anim snapshots.  The provider is chosen from the snap config
+//! when snapshotting is reset, and is queried for each anim capture right after the vertex
+//! constants are captured.
+
+use constant_tracking::FloatConstList;
+use shared_dx::error::*;
+use shared_dx::util::write_log_file;
+use snaplib::anim_snap_state::PlayerTransform;
+use snaplib::snap_config::{PlayerTransformSource, SnapConfig};
+
+use crate::snap_extdll::{GetActivePlayerTransformFn, XDLLSTATE};
+use crate::SNAP_CONFIG;
+
+/// Vertex shader constants captured for the current draw.
+pub enum CapturedConstants<'a> {
+    /// D3D9 float registers
+    Registers(&'a FloatConstList),
+    /// D3D11 bone palette constant buffer
+    Buffer(&'a [u8]),
+}
+
+impl<'a> CapturedConstants<'a> {
+    /// Read the float at byte `offset`, which must be 4 byte aligned.  Registers are 16 bytes.
+    fn read_float(&self, offset:u32) -> Option<f32> {
+        match self {
+            CapturedConstants::Registers(regs) => regs.get(offset / 16)
+                .map(|v| v.to_array()[(offset % 16 / 4) as usize]),
+            CapturedConstants::Buffer(data) => data.get(offset as usize..offset as usize + 4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        }
+    }
+}
+
+pub trait PlayerTransformProvider {
+    fn name(&self) -> &'static str;
+    fn get(&self, consts:&CapturedConstants) -> Result<PlayerTransform>;
+}
+
+/// Always returns the zero transform, so bones are written as captured.
+pub struct IdentityTransform;
+
+impl PlayerTransformProvider for IdentityTransform {
+    fn name(&self) -> &'static str {
+        "identity"
+    }
+    fn get(&self, _consts:&CapturedConstants) -> Result<PlayerTransform> {
+        Ok(PlayerTransform::default())
+    }
+}
+
+/// Reads x, y, z and rotation from consecutive floats in the captured constants.
+pub struct ConstantOffsetTransform {
+    offset: u32,
+}
+
+impl ConstantOffsetTransform {
+    pub fn new(offset:u32) -> Result<Self> {
+        if offset % 4 != 0 {
+            return Err(HookError::SnapshotFailed(
+                format!("player transform constant offset {} is not a multiple of 4", offset)));
+        }
+        Ok(Self { offset })
+    }
+}
+
+impl PlayerTransformProvider for ConstantOffsetTransform {
+    fn name(&self) -> &'static str {
+        "constant offset"
+    }
+    fn get(&self, consts:&CapturedConstants) -> Result<PlayerTransform> {
+        let read = |i:u32| consts.read_float(self.offset + i * 4).ok_or_else(|| {
+            HookError::SnapshotFailed(format!("player transform offset {} is outside the captured constants", self.offset + i * 4))
+        });
+        Ok(PlayerTransform { x: read(0)?, y: read(1)?, z: read(2)?, rot: read(3)? })
+    }
+}
+
+/// Calls `GetActivePlayerTransform` in the snap ext dll, which returns "x y z rot".
+pub struct ExtDllTransform {
+    get_transform: GetActivePlayerTransformFn,
+}
+
+impl PlayerTransformProvider for ExtDllTransform {
+    fn name(&self) -> &'static str {
+        "ext dll"
+    }
+    fn get(&self, _consts:&CapturedConstants) -> Result<PlayerTransform> {
+        let xfrm = unsafe {
+            let xfrm = (self.get_transform)();
+            if xfrm.is_null() {
+                return Err(HookError::SnapshotFailed("ext dll returned null player transform".to_owned()));
+            }
+            std::ffi::CStr::from_ptr(xfrm).to_string_lossy().into_owned()
+        };
+        if xfrm.starts_with("error") {
+            return Err(HookError::SnapshotFailed(format!("failed to get player transform: {:?}", xfrm)));
+        }
+        parse_transform(&xfrm)
+    }
+}
+
+/// Parse "x y z rot".
+pub fn parse_transform(s:&str) -> Result<PlayerTransform> {
+    let mut split = s.split_whitespace();
+    let mut parse_next = |comp:&str| -> Result<f32> {
+        let res = split.next().ok_or_else(|| HookError::SnapshotFailed(format!("Failed transform parse, missing {}: '{}'", comp, s)))?;
+        res.parse().map_err(|_| HookError::SnapshotFailed(format!("failed to parse float, source str: '{}', comp: {}, orig: '{}'", res, comp, s)))
+    };
+    Ok(PlayerTransform {
+        x: parse_next("x")?,
+        y: parse_next("y")?,
+        z: parse_next("z")?,
+        rot: parse_next("rot")?,
+    })
+}
+
+/// Create the provider for `source`.  The ext dll must already be loaded.
+pub fn create_provider(source:&PlayerTransformSource) -> Result<Box<dyn PlayerTransformProvider>> {
+    Ok(match source {
+        PlayerTransformSource::Identity => Box::new(IdentityTransform),
+        PlayerTransformSource::ConstantOffset { offset } => Box::new(ConstantOffsetTransform::new(*offset)?),
+        PlayerTransformSource::ExtDll => {
+            let get_transform = unsafe { XDLLSTATE.as_ref() }
+                .map(|xdll| xdll.GetActivePlayerTransform)
+                .ok_or_else(|| HookError::SnapshotFailed("snap ext dll is not loaded".to_owned()))?;
+            Box::new(ExtDllTransform { get_transform })
+        },
+    })
+}
+
+static mut PROVIDER: Option<Box<dyn PlayerTransformProvider>> = None;
+
+/// Select the provider from the snap config.  On error there is no provider and anim captures
+/// will fail to write.
+pub unsafe fn init_provider() {
+    let snap_conf =
+        match SNAP_CONFIG.read() {
+            Err(e) => {
+                write_log_file(&format!("failed to lock snap config: {}", e));
+                SnapConfig::new()
+            },
+            Ok(c) => c.clone()
+        };
+    let source = snap_conf.player_transform_source();
+    PROVIDER = match create_provider(&source) {
+        Ok(p) => {
+            write_log_file(&format!("using {} player transform provider", p.name()));
+            Some(p)
+        },
+        Err(e) => {
+            write_log_file(&format!("failed to create player transform provider {:?}: {:?}", source, e));
+            None
+        }
+    };
+}
+
+pub unsafe fn player_transform(consts:&CapturedConstants) -> Result<PlayerTransform> {
+    match PROVIDER.as_ref() {
+        Some(p) => p.get(consts),
+        None => Err(HookError::SnapshotFailed("no player transform provider".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winapi::um::winnt::LPCSTR;
+
+    #[test]
+    fn test_identity() {
+        let p = create_provider(&PlayerTransformSource::Identity).unwrap();
+        assert_eq!(p.get(&CapturedConstants::Buffer(&[])).unwrap(), PlayerTransform::default());
+    }
+
+    #[test]
+    fn test_constant_offset() {
+        let data:Vec<u8> = (0..12).flat_map(|f| (f as f32).to_le_bytes()).collect();
+        let p = create_provider(&PlayerTransformSource::ConstantOffset { offset: 8 }).unwrap();
+        let expected = PlayerTransform { x: 2.0, y: 3.0, z: 4.0, rot: 5.0 };
+        assert_eq!(p.get(&CapturedConstants::Buffer(&data)).unwrap(), expected);
+
+        let floats:Vec<f32> = (0..12).map(|f| f as f32).collect();
+        let mut regs = FloatConstList::new(4);
+        regs.set(0, floats.as_ptr(), 3);
+        assert_eq!(p.get(&CapturedConstants::Registers(&regs)).unwrap(), expected);
+
+        let p = create_provider(&PlayerTransformSource::ConstantOffset { offset: 40 }).unwrap();
+        assert!(p.get(&CapturedConstants::Buffer(&data)).is_err());
+        assert!(p.get(&CapturedConstants::Registers(&regs)).is_err());
+        assert!(create_provider(&PlayerTransformSource::ConstantOffset { offset: 6 }).is_err());
+    }
+
+    unsafe extern "system" fn dummy_transform() -> LPCSTR {
+        b"1.5 -2 3 0.25\0".as_ptr() as LPCSTR
+    }
+    unsafe extern "system" fn dummy_transform_error() -> LPCSTR {
+        b"error: no active player\0".as_ptr() as LPCSTR
+    }
+
+    #[test]
+    fn test_ext_dll() {
+        // not loaded
+        assert!(create_provider(&PlayerTransformSource::ExtDll).is_err());
+
+        let p = ExtDllTransform { get_transform: dummy_transform };
+        assert_eq!(p.get(&CapturedConstants::Buffer(&[])).unwrap(),
+            PlayerTransform { x: 1.5, y: -2.0, z: 3.0, rot: 0.25 });
+        let p = ExtDllTransform { get_transform: dummy_transform_error };
+        assert!(p.get(&CapturedConstants::Buffer(&[])).is_err());
+
+        assert!(parse_transform("1 2 3").is_err());
+        assert!(parse_transform("1 2 x 4").is_err());
+    }
+}
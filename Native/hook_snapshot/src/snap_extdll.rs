@@ -7,27 +7,13 @@ use shared_dx::{error::*, util::write_log_file};
 use winapi::um::winnt::LPCSTR;
 
 use crate::SNAP_CONFIG;
-type GetActivePlayerTransformFn = unsafe extern "system" fn() -> LPCSTR;
+pub type GetActivePlayerTransformFn = unsafe extern "system" fn() -> LPCSTR;
 
 pub struct XDLLState {
     pub _handle: HINSTANCE,
     pub GetActivePlayerTransform: GetActivePlayerTransformFn,
 }
 
-impl XDLLState {
-    pub fn get_player_transform(&self) -> Result<String> {
-        unsafe {
-            let xfrm = (self.GetActivePlayerTransform)();
-            let ret = std::ffi::CStr::from_ptr(xfrm);
-            let ret = ret.to_string_lossy();
-            if ret.starts_with("error") {
-                return Err(HookError::SnapshotFailed(format!("failed to get player transform: {:?}", ret)));
-            }
-            Ok(ret.into_owned())
-        }
-    }
-}
-
 pub static mut XDLLSTATE : Option<XDLLState> = None;
 
 pub unsafe fn init_xdll() -> Result<()> {
@@ -0,0 +1,133 @@
+//! Loads the snapshot plugins listed in the snap config and calls them during snapshots.  See
+//! the snap_plugin crate for the ABI.
+//!
+//! Plugins stay loaded between snapshots; they are only reloaded when the plugin list in the
+//! snap config changes, and are shut down when the process exits.
+
+use std::ffi::{c_void, CString};
+
+use shared_dx::error::*;
+use shared_dx::types::DevicePointer;
+use shared_dx::util::write_log_file;
+use snap_plugin::{DeviceType, DrawInfo, FrameInfo, SnapPlugin};
+use snaplib::snap_config::SnapConfig;
+use util;
+use winapi::shared::minwindef::HMODULE;
+
+use crate::SNAP_CONFIG;
+
+struct LoadedPlugin {
+    plugin: SnapPlugin,
+    handle: HMODULE,
+}
+
+static mut PLUGINS: Vec<LoadedPlugin> = Vec::new();
+/// The plugin list that `PLUGINS` was loaded from, or None if nothing has been loaded yet.
+static mut LOADED_LIST: Option<Vec<String>> = None;
+static REGISTER_EXIT: std::sync::Once = std::sync::Once::new();
+
+extern "C" {
+    fn atexit(cb: extern "C" fn()) -> std::os::raw::c_int;
+}
+
+/// Shuts the plugins down at process exit.  The libraries are left for the OS to release,
+/// since freeing them from an exit handler isn't safe; plugins should therefore not wait on
+/// other threads in shutdown.
+extern "C" fn shutdown_at_exit() {
+    unsafe {
+        for lp in PLUGINS.drain(..) {
+            lp.plugin.shutdown();
+        }
+    }
+}
+
+unsafe fn load_plugin(path:&str) -> Result<LoadedPlugin> {
+    let handle = util::load_lib(path)?;
+    let plugin = SnapPlugin::from_symbols(path, |sym| {
+        util::get_proc_address(handle, sym).ok().map(|p| p as *const c_void)
+    }).and_then(|plugin| plugin.init().map(|_| plugin));
+    match plugin {
+        Ok(plugin) => Ok(LoadedPlugin { plugin, handle }),
+        Err(e) => {
+            util::unload_lib(handle)?;
+            Err(HookError::SnapshotPluginError(e))
+        }
+    }
+}
+
+unsafe fn unload_plugins() {
+    for lp in PLUGINS.drain(..) {
+        write_log_file(&format!("unloading snap plugin: {}", lp.plugin.name));
+        lp.plugin.shutdown();
+        util::unload_lib(lp.handle).unwrap_or_else(|e| {
+            write_log_file(&format!("failed to unload snap plugin {}: {:?}", lp.plugin.name, e));
+        });
+    }
+}
+
+/// Load the plugins in the snap config, if its plugin list differs from the one that was last
+/// loaded; the current plugins are unloaded first.  Plugins that fail to load are logged and
+/// skipped, and are not retried until the list changes.
+pub unsafe fn load_plugins() {
+    let list =
+        match SNAP_CONFIG.read() {
+            Err(e) => {
+                write_log_file(&format!("failed to lock snap config: {}", e));
+                SnapConfig::new().plugins
+            },
+            Ok(c) => c.plugins.clone()
+        }.unwrap_or_default();
+    if LOADED_LIST.as_ref() == Some(&list) {
+        return;
+    }
+    unload_plugins();
+    for path in list.iter() {
+        write_log_file(&format!("loading snap plugin: {}", path));
+        match load_plugin(path) {
+            Ok(lp) => PLUGINS.push(lp),
+            Err(e) => write_log_file(&format!("failed to load snap plugin {}: {:?}", path, e)),
+        }
+    }
+    LOADED_LIST = Some(list);
+    if !PLUGINS.is_empty() {
+        REGISTER_EXIT.call_once(|| {
+            if atexit(shutdown_at_exit) != 0 {
+                write_log_file("failed to register snap plugin shutdown, plugins will not be shut down at exit");
+            }
+        });
+    }
+}
+
+/// Pass a snapshotted draw to the plugins.  `snap_dir` and `snap_prefix` are empty if the
+/// snapshot failed.
+pub unsafe fn capture(devptr:&mut DevicePointer, prim_count:u32, vert_count:u32, frame:u64, snap_dir:&str, snap_prefix:&str) {
+    if PLUGINS.is_empty() {
+        return;
+    }
+    let device_type = match devptr {
+        DevicePointer::D3D9(_) => DeviceType::D3D9,
+        DevicePointer::D3D11(_) => DeviceType::D3D11,
+    };
+    let snap_dir = CString::new(snap_dir).unwrap_or_default();
+    let snap_prefix = CString::new(snap_prefix).unwrap_or_default();
+    let draw = DrawInfo {
+        device_type,
+        device: devptr.as_c_void(),
+        prim_count,
+        vert_count,
+        frame,
+        snap_dir: snap_dir.as_ptr(),
+        snap_prefix: snap_prefix.as_ptr(),
+    };
+    for lp in PLUGINS.iter() {
+        lp.plugin.capture(&draw).unwrap_or_else(|e| write_log_file(&e));
+    }
+}
+
+/// Called once per frame while a snapshot is active.
+pub unsafe fn process_frame(frame:u64, snap_elapsed_ms:u64) {
+    let info = FrameInfo { frame, snap_elapsed_ms };
+    for lp in PLUGINS.iter() {
+        lp.plugin.process(&info).unwrap_or_else(|e| write_log_file(&e));
+    }
+}
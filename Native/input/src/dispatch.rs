@@ -0,0 +1,416 @@
+//! Turns keyboard state snapshots into key events and calls the bound handlers.  This is kept
+//! apart from the DirectInput code in `input` so that it can be driven with synthetic key
+//! states.
+
+use fnv::FnvHashMap;
+use key_bindings::{is_modifier_key, KeyBinding, KeyStroke, Modifiers};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type PressFn = Box<dyn FnMut()>;
+
+const INITIAL_REPEAT_DELAY: u16 = 500;
+const CONTINUED_REPEAT_DELAY: u16 = 75;
+/// How long after the first stroke of a chord the second one will be accepted.
+pub const CHORD_TIMEOUT_MS: u64 = 1500;
+
+pub const DIK_LALT: u8 = 0x38;
+pub const DIK_RALT: u8 = 0xB8;
+pub const DIK_LSHIFT: u8 = 0x2A;
+pub const DIK_RSHIFT: u8 = 0x36;
+pub const DIK_LCONTROL: u8 = 0x1D;
+pub const DIK_RCONTROL: u8 = 0x9D;
+pub const DIK_MENU: u8 = 0xDD;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+    /// True if this is an automatic repeat of a key that is held down.
+    pub repeat: bool,
+    /// Modifiers that were held when the event was generated.
+    pub mods: Modifiers,
+}
+
+/// Returns the modifiers held in the key state.  The menu key counts as control.
+pub fn held_modifiers(keyboard_state: &[u8]) -> Modifiers {
+    let down = |k: u8| keyboard_state[k as usize] & 0x80 > 0;
+    Modifiers {
+        ctrl: down(DIK_LCONTROL) || down(DIK_RCONTROL) || down(DIK_MENU),
+        alt: down(DIK_LALT) || down(DIK_RALT),
+        shift: down(DIK_LSHIFT) || down(DIK_RSHIFT),
+    }
+}
+
+pub struct KeyDispatcher {
+    events: Vec<KeyEvent>,
+    last_keyboard_state: Vec<u8>,
+    last_press_event: Vec<SystemTime>,
+    repeat_delay: Vec<u16>,
+    /// Handlers for each key, along with the modifiers they require.
+    press_event_fns: FnvHashMap<u8, Vec<(Modifiers, PressFn)>>,
+    /// Handlers for two stroke chords: (first, second, handler).
+    chord_fns: Vec<(KeyStroke, KeyStroke, PressFn)>,
+    /// First stroke of a chord that is waiting for its second stroke, and when it was pressed.
+    pending_chord: Option<(KeyStroke, SystemTime)>,
+}
+
+impl KeyDispatcher {
+    pub fn new() -> Self {
+        KeyDispatcher {
+            events: Vec::new(),
+            last_keyboard_state: vec![0; 256],
+            last_press_event: vec![UNIX_EPOCH; 256],
+            repeat_delay: vec![0; 256],
+            press_event_fns: FnvHashMap::with_capacity_and_hasher(
+                1024_usize,
+                Default::default(),
+            ),
+            chord_fns: Vec::new(),
+            pending_chord: None,
+        }
+    }
+
+    pub fn clear_handlers(&mut self) {
+        self.press_event_fns.clear();
+        self.chord_fns.clear();
+        self.pending_chord = None;
+    }
+
+    /// Add a handler for the key that requires the specified modifiers (at least one is
+    /// needed, handlers without any are never called).  If a key has several handlers, only
+    /// the one with the most modifiers that are all currently held is called, so Ctrl+Shift+X
+    /// doesn't also trigger Ctrl+X.  Replaces any existing handler with the same key and
+    /// modifiers.
+    pub fn add_press_fn_mods(&mut self, key: u8, mods: Modifiers, fun: PressFn) {
+        let handlers = self.press_event_fns.entry(key).or_default();
+        handlers.retain(|(m, _)| *m != mods);
+        handlers.push((mods, fun));
+    }
+
+    /// Add a handler for a chord: `first` (which needs at least one modifier) followed by
+    /// `second` within `CHORD_TIMEOUT_MS`.  If `first` would also trigger a single key
+    /// handler, the chord wins unless the single handler requires more modifiers.  Replaces
+    /// any existing handler for the same chord.
+    pub fn add_chord_fn(&mut self, first: KeyStroke, second: KeyStroke, fun: PressFn) {
+        self.chord_fns.retain(|(f, s, _)| *f != first || *s != second);
+        self.chord_fns.push((first, second, fun));
+    }
+
+    pub fn add_binding(&mut self, kb: &KeyBinding, fun: PressFn) {
+        match kb.chord {
+            Some(second) => self.add_chord_fn(kb.stroke, second, fun),
+            None => self.add_press_fn_mods(kb.stroke.key, kb.stroke.mods, fun),
+        }
+    }
+
+    pub fn get_press_fn_count(&self) -> usize {
+        self.press_event_fns.values().map(|v| v.len()).sum::<usize>() + self.chord_fns.len()
+    }
+
+    pub fn events(&self) -> &Vec<KeyEvent> {
+        &self.events
+    }
+
+    /// The first stroke of a chord, if one was pressed and is waiting for the second.
+    pub fn pending_chord(&self) -> Option<KeyStroke> {
+        self.pending_chord.map(|(stroke, _)| stroke)
+    }
+
+    /// Generate events from the new key state (256 DIK entries, high bit set if the key is
+    /// down) and call any handlers they trigger.
+    pub fn update(&mut self, keyboard_state: &[u8], now: SystemTime) {
+        self.events.clear();
+
+        let mods = held_modifiers(keyboard_state);
+        let zero_ms = Duration::from_millis(0);
+
+        for (i, state) in keyboard_state.iter().enumerate().take(256) {
+            let pressed = state & 0x80 > 0;
+            let was_pressed = self.last_keyboard_state[i] & 0x80 > 0;
+            let new_press = pressed && !was_pressed;
+            let new_release = !pressed && was_pressed;
+
+            if new_press {
+                // per-key repeat delay is probably overkill, but who cares.
+                self.repeat_delay[i] = INITIAL_REPEAT_DELAY;
+            }
+
+            let repeat = !new_press && pressed && self.last_press_event[i] != UNIX_EPOCH
+                && now.duration_since(self.last_press_event[i])
+                    .unwrap_or(zero_ms)
+                    .as_millis() >= self.repeat_delay[i].into();
+            if repeat {
+                // switch to lower delay now
+                self.repeat_delay[i] = CONTINUED_REPEAT_DELAY;
+            }
+
+            if new_press || repeat {
+                self.events.push(KeyEvent { key: i as u8, pressed: true, repeat, mods });
+                self.last_press_event[i] = now;
+            } else if new_release {
+                self.events.push(KeyEvent { key: i as u8, pressed: false, repeat: false, mods });
+            }
+
+            self.last_keyboard_state[i] = *state;
+        }
+
+        for i in 0..self.events.len() {
+            let evt = self.events[i];
+            if evt.pressed && !is_modifier_key(evt.key) {
+                self.dispatch(&evt, now);
+            }
+        }
+    }
+
+    fn dispatch(&mut self, evt: &KeyEvent, now: SystemTime) {
+        if let Some((first, started)) = self.pending_chord {
+            let expired = now.duration_since(started)
+                .map(|d| d > Duration::from_millis(CHORD_TIMEOUT_MS))
+                .unwrap_or(true);
+            if expired {
+                self.pending_chord = None;
+            } else if evt.repeat {
+                // first key still held down, keep waiting
+                return;
+            } else {
+                self.pending_chord = None;
+                let best = self.chord_fns.iter_mut()
+                    .filter(|(f, s, _)| *f == first && s.key == evt.key && evt.mods.contains(s.mods))
+                    .max_by_key(|(_, s, _)| s.mods.count());
+                if let Some((_, _, fun)) = best {
+                    fun();
+                    return;
+                }
+                // not part of the chord, so handle it like any other press
+            }
+        }
+
+        if evt.mods.is_empty() {
+            return;
+        }
+        let single = self.press_event_fns.get(&evt.key).and_then(|handlers| {
+            handlers.iter()
+                .map(|(m, _)| *m)
+                .filter(|m| !m.is_empty() && evt.mods.contains(*m))
+                .max_by_key(|m| m.count())
+        });
+        // repeats can't start a chord, otherwise holding the key would just keep restarting it
+        let chord_start = if evt.repeat {
+            None
+        } else {
+            self.chord_fns.iter()
+                .map(|(f, _, _)| *f)
+                .filter(|f| f.key == evt.key && !f.mods.is_empty() && evt.mods.contains(f.mods))
+                .max_by_key(|f| f.mods.count())
+        };
+
+        match (single, chord_start) {
+            (Some(m), Some(f)) if m.count() > f.mods.count() => self.call_press_fn(evt.key, m),
+            (_, Some(f)) => self.pending_chord = Some((f, now)),
+            (Some(m), None) => self.call_press_fn(evt.key, m),
+            (None, None) => (),
+        }
+    }
+
+    fn call_press_fn(&mut self, key: u8, mods: Modifiers) {
+        if let Some(handlers) = self.press_event_fns.get_mut(&key) {
+            if let Some((_, fun)) = handlers.iter_mut().find(|(m, _)| *m == mods) {
+                fun();
+            }
+        }
+    }
+}
+
+impl Default for KeyDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    const DIK_K: u8 = 0x25;
+    const DIK_S: u8 = 0x1F;
+    const DIK_N: u8 = 0x31;
+    const DIK_F7: u8 = 0x41;
+
+    /// Feeds key states to a dispatcher with a controlled clock.
+    struct Harness {
+        disp: KeyDispatcher,
+        now: SystemTime,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Harness { disp: KeyDispatcher::new(), now: UNIX_EPOCH + Duration::from_secs(1000) }
+        }
+
+        /// Set exactly these keys down and advance the clock by `ms`.
+        fn keys(&mut self, down: &[u8], ms: u64) {
+            self.now += Duration::from_millis(ms);
+            let mut state = [0_u8; 256];
+            for k in down {
+                state[*k as usize] = 0x80;
+            }
+            self.disp.update(&state, self.now);
+        }
+
+        fn bind(&mut self, binding: &str) -> Rc<Cell<u32>> {
+            let count = Rc::new(Cell::new(0));
+            let c = count.clone();
+            let kb = KeyBinding::parse(binding).unwrap();
+            self.disp.add_binding(&kb, Box::new(move || c.set(c.get() + 1)));
+            count
+        }
+    }
+
+    #[test]
+    fn test_events_and_repeat() {
+        let mut h = Harness::new();
+        h.keys(&[DIK_LSHIFT, DIK_N], 16);
+        let shift = Modifiers { ctrl: false, alt: false, shift: true };
+        assert_eq!(h.disp.events(), &vec![
+            KeyEvent { key: DIK_LSHIFT, pressed: true, repeat: false, mods: shift },
+            KeyEvent { key: DIK_N, pressed: true, repeat: false, mods: shift },
+        ]);
+        // held but not long enough for a repeat
+        h.keys(&[DIK_LSHIFT, DIK_N], 400);
+        assert!(h.disp.events().is_empty());
+        // initial delay is up, then the shorter delay applies
+        h.keys(&[DIK_LSHIFT, DIK_N], 100);
+        assert_eq!(h.disp.events().iter().filter(|e| e.repeat).count(), 2);
+        h.keys(&[DIK_LSHIFT, DIK_N], 75);
+        assert_eq!(h.disp.events().len(), 2);
+        h.keys(&[DIK_N], 16);
+        assert_eq!(h.disp.events(), &vec![
+            KeyEvent { key: DIK_LSHIFT, pressed: false, repeat: false, mods: Modifiers::NONE },
+        ]);
+    }
+
+    #[test]
+    fn test_modifier_sets() {
+        let mut h = Harness::new();
+        let ctrl_n = h.bind("Ctrl+N");
+        let ctrl_shift_n = h.bind("Ctrl+Shift+N");
+        let alt_f7 = h.bind("Alt+F7");
+        let plain_f7 = Rc::new(Cell::new(0));
+        let c = plain_f7.clone();
+        h.disp.add_press_fn_mods(DIK_F7, Modifiers::NONE, Box::new(move || c.set(c.get() + 1)));
+        assert_eq!(h.disp.get_press_fn_count(), 4);
+
+        // the most specific handler wins
+        h.keys(&[DIK_LCONTROL, DIK_LSHIFT], 16);
+        h.keys(&[DIK_LCONTROL, DIK_LSHIFT, DIK_N], 16);
+        assert_eq!((ctrl_n.get(), ctrl_shift_n.get()), (0, 1));
+        h.keys(&[DIK_RCONTROL], 16);
+        h.keys(&[DIK_RCONTROL, DIK_N], 16);
+        assert_eq!((ctrl_n.get(), ctrl_shift_n.get()), (1, 1));
+        // the menu key counts as control
+        h.keys(&[DIK_MENU], 16);
+        h.keys(&[DIK_MENU, DIK_N], 16);
+        assert_eq!(ctrl_n.get(), 2);
+
+        // unmodified presses never reach handlers
+        h.keys(&[DIK_F7], 16);
+        assert_eq!((alt_f7.get(), plain_f7.get()), (0, 0));
+        h.keys(&[DIK_RALT, DIK_F7], 16);
+        assert_eq!(alt_f7.get(), 0);
+        h.keys(&[DIK_RALT], 16);
+        h.keys(&[DIK_RALT, DIK_F7], 16);
+        assert_eq!((alt_f7.get(), plain_f7.get()), (1, 0));
+        // holding it repeats
+        h.keys(&[DIK_RALT, DIK_F7], 500);
+        assert_eq!(alt_f7.get(), 2);
+    }
+
+    #[test]
+    fn test_chords() {
+        let mut h = Harness::new();
+        let ks = h.bind("Ctrl+K S");
+        let k_shift_s = h.bind("Ctrl+K Shift+S");
+        let kn = h.bind("Ctrl+K Ctrl+N");
+        let ctrl_n = h.bind("Ctrl+N");
+        let ctrl_s = h.bind("Ctrl+S");
+
+        // release ctrl before the second key
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        assert_eq!(h.disp.pending_chord(), Some(KeyStroke::ctrl(DIK_K)));
+        h.keys(&[], 16);
+        h.keys(&[DIK_S], 16);
+        assert_eq!((ks.get(), k_shift_s.get(), ctrl_s.get()), (1, 0, 0));
+        assert_eq!(h.disp.pending_chord(), None);
+
+        // keep ctrl held; Ctrl+S completes the chord instead of triggering its own handler
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_S], 16);
+        assert_eq!((ks.get(), ctrl_s.get()), (2, 0));
+
+        // most specific second stroke wins
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        h.keys(&[], 16);
+        h.keys(&[DIK_LSHIFT, DIK_S], 16);
+        assert_eq!((ks.get(), k_shift_s.get()), (2, 1));
+
+        // second stroke that requires ctrl doesn't match without it
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        h.keys(&[], 16);
+        h.keys(&[DIK_N], 16);
+        assert_eq!((kn.get(), ctrl_n.get()), (0, 0));
+        assert_eq!(h.disp.pending_chord(), None);
+
+        // holding the first key doesn't cancel the chord
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        h.keys(&[DIK_LCONTROL, DIK_K], 600);
+        h.keys(&[DIK_LCONTROL, DIK_K], 100);
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_N], 16);
+        assert_eq!((kn.get(), ctrl_n.get()), (1, 0));
+
+        // a key that isn't part of the chord cancels it and is handled normally
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_F7], 16);
+        assert_eq!(h.disp.pending_chord(), None);
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_N], 16);
+        assert_eq!((kn.get(), ctrl_n.get()), (1, 1));
+
+        // too slow
+        h.keys(&[DIK_LCONTROL], 16);
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        h.keys(&[], 16);
+        h.keys(&[DIK_S], CHORD_TIMEOUT_MS + 1);
+        assert_eq!(ks.get(), 2);
+        assert_eq!(h.disp.pending_chord(), None);
+
+        h.disp.clear_handlers();
+        assert_eq!(h.disp.get_press_fn_count(), 0);
+    }
+
+    #[test]
+    fn test_chord_and_single_on_same_key() {
+        let mut h = Harness::new();
+        let chord = h.bind("Ctrl+K S");
+        let ctrl_shift_k = h.bind("Ctrl+Shift+K");
+
+        // the single handler needs more modifiers, so it wins when they are all held
+        h.keys(&[DIK_LCONTROL, DIK_LSHIFT, DIK_K], 16);
+        assert_eq!(ctrl_shift_k.get(), 1);
+        assert_eq!(h.disp.pending_chord(), None);
+
+        h.keys(&[], 16);
+        h.keys(&[DIK_LCONTROL, DIK_K], 16);
+        assert_eq!(h.disp.pending_chord(), Some(KeyStroke::ctrl(DIK_K)));
+        h.keys(&[], 16);
+        h.keys(&[DIK_S], 16);
+        assert_eq!((chord.get(), ctrl_shift_k.get()), (1, 1));
+    }
+}
@@ -15,8 +15,10 @@ use winapi::shared::guiddef::{GUID, REFGUID, REFIID};
 // use winapi::ctypes::c_void;
 // use winapi::um::wingdi::RGNDATA;
 
-use fnv::FnvHashMap;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use key_bindings::{KeyBinding, Modifiers};
+use std::time::SystemTime;
+
+use crate::dispatch::*;
 
 //extern HRESULT WINAPI DirectInput8Create(HINSTANCE hinst, DWORD dwVersion, REFIID riidltf, LPVOID *ppvOut, LPUNKNOWN punkOuter);
 
@@ -99,16 +101,6 @@ type DirectInput8CreateFn = unsafe extern "system" fn(
     punkOuter: LPUNKNOWN,
 ) -> HRESULT;
 
-const INITIAL_REPEAT_DELAY: u16 = 500;
-const CONTINUED_REPEAT_DELAY: u16 = 75;
-
-pub const DIK_LALT: u8 = 0x38;
-pub const DIK_RALT: u8 = 0xB8;
-pub const DIK_LSHIFT: u8 = 0x2A;
-pub const DIK_RSHIFT: u8 = 0x36;
-pub const DIK_LCONTROL: u8 = 0x1D;
-pub const DIK_RCONTROL: u8 = 0x9D;
-pub const DIK_MENU: u8 = 0xDD;
 pub const DIK_F1: u8 = 0x3B;
 pub const DIK_F2: u8 = 0x3C;
 pub const DIK_F3: u8 = 0x3D;
@@ -129,20 +121,10 @@ pub const DIK_COMMA: u8 = 0x33;
 pub const DIK_PERIOD: u8 = 0x34;
 pub const DIK_SLASH: u8 = 0x35;
 
-#[derive(Debug)]
-pub struct KeyEvent {
-    pub key: u8,
-    pub pressed: bool,
-}
-
 pub struct Input {
-    events: Vec<KeyEvent>,
     keyboard_state: Vec<u8>,
-    last_keyboard_state: Vec<u8>,
-    last_press_event: Vec<SystemTime>,
     last_update: SystemTime,
-    press_event_fns: FnvHashMap<u8, Box<dyn FnMut()>>,
-    repeat_delay: Vec<u16>,
+    keys: KeyDispatcher,
     pub alt_pressed: bool,
     pub ctrl_pressed: bool,
     pub shift_pressed: bool,
@@ -155,28 +137,15 @@ decl_profile_globals!(inp);
 impl Input {
     pub fn new() -> Result<Self> {
         let mut inp = Input {
-            events: Vec::new(),
-            keyboard_state: Vec::with_capacity(256),
-            last_keyboard_state: Vec::with_capacity(256),
-            repeat_delay: Vec::new(),
-            last_press_event: Vec::new(),
+            keyboard_state: vec![0; 256],
             last_update: SystemTime::now(),
-            press_event_fns: FnvHashMap::with_capacity_and_hasher(
-                1024_usize,
-                Default::default(),
-            ),
+            keys: KeyDispatcher::new(),
             alt_pressed: false,
             shift_pressed: false,
             ctrl_pressed: false,
             keyboard: null_mut(),
             setup_attempts: 0
         };
-        for _i in 0..256 {
-            inp.keyboard_state.push(0);
-            inp.last_keyboard_state.push(0);
-            inp.repeat_delay.push(0);
-            inp.last_press_event.push(UNIX_EPOCH);
-        }
         unsafe {
             inp.keyboard = inp.init()?;
         };
@@ -247,18 +216,28 @@ impl Input {
     }
 
     pub fn clear_handlers(&mut self) {
-        self.press_event_fns.clear();
+        self.keys.clear_handlers();
     }
 
+    /// Add a handler for the key that requires CONTROL.
     pub fn add_press_fn(&mut self, key: u8, fun: Box<dyn FnMut()>) {
-        self.press_event_fns.insert(key, fun);
+        self.add_press_fn_mods(key, Modifiers::CTRL, fun);
+    }
+    /// Add a handler for the key that requires the specified modifiers.  See
+    /// `KeyDispatcher::add_press_fn_mods`.
+    pub fn add_press_fn_mods(&mut self, key: u8, mods: Modifiers, fun: Box<dyn FnMut()>) {
+        self.keys.add_press_fn_mods(key, mods, fun);
+    }
+    /// Add a handler for a single key or chord binding.
+    pub fn add_binding(&mut self, kb: &KeyBinding, fun: Box<dyn FnMut()>) {
+        self.keys.add_binding(kb, fun);
     }
     pub fn get_press_fn_count(&self) -> usize {
-        self.press_event_fns.len()
+        self.keys.get_press_fn_count()
     }
 
     pub fn events(&self) -> &Vec<KeyEvent> {
-        &self.events
+        self.keys.events()
     }
 
     pub fn process(&mut self) -> Result<()> {
@@ -274,11 +253,9 @@ impl Input {
         profile_end!(inp, check);
         profile_start!(inp, process);
 
-        self.events.clear();
         self.last_update = now;
 
         // TODO: need to clear before GetDeviceState?
-        let menu_pressed;
         unsafe {
             {
                 let mut gds = |acquire| {
@@ -315,62 +292,10 @@ impl Input {
                 || (self.keyboard_state[DIK_RSHIFT as usize] & 0x80) > 0;
             self.ctrl_pressed = (self.keyboard_state[DIK_LCONTROL as usize] & 0x80) > 0
                 || (self.keyboard_state[DIK_RCONTROL as usize] & 0x80) > 0;
-            menu_pressed = (self.keyboard_state[DIK_MENU as usize] & 0x80) > 0
-                || (self.keyboard_state[DIK_MENU as usize] & 0x80) > 0;
-
-            let zero_ms = Duration::from_millis(0);
-
-            for i in 0..256 {
-                let i = i as usize;
-                let pressed = self.keyboard_state[i] & 0x80 > 0;
-                let was_pressed = self.last_keyboard_state[i] & 0x80 > 0;
-                let new_press = pressed && !was_pressed;
-                let new_release = !pressed && was_pressed;
-
-                if new_press {
-                    // per-key repeat delay is probably overkill, but who cares.
-                    self.repeat_delay[i] = INITIAL_REPEAT_DELAY;
-                }
-
-                let repeat = !new_press && pressed && self.last_press_event[i] != UNIX_EPOCH
-                    && (now.duration_since(self.last_press_event[i])
-                        .unwrap_or(zero_ms)
-                        .subsec_nanos() as f64 * 1e-6)
-                        >= self.repeat_delay[i].into();
-                if repeat {
-                    // switch to lower delay now
-                    self.repeat_delay[i] = CONTINUED_REPEAT_DELAY;
-                }
-
-                if new_press || repeat {
-                    self.events.push(KeyEvent {
-                        key: i as u8,
-                        pressed: true,
-                    });
-                    self.last_press_event[i] = now;
-                } else if new_release {
-                    self.events.push(KeyEvent {
-                        key: i as u8,
-                        pressed: false,
-                    });
-                }
-
-                self.last_keyboard_state[i] = self.keyboard_state[i];
-            }
         };
 
-        
-        let process_key_events = self.ctrl_pressed || menu_pressed;
+        self.keys.update(&self.keyboard_state, now);
 
-        if process_key_events {
-            for evt in self.events.iter() {
-                //write_log_file(&format!("event: {:x} pressed: {}", ke.key, ke.pressed));
-                if evt.pressed {
-                    if let Some(fun) = self.press_event_fns.get_mut(&evt.key) { fun(); }
-                }
-            }
-        }
-        
         // if self.events.len() > 0 {
         //     write_log_file("");
         // }
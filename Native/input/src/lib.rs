@@ -7,5 +7,7 @@ extern crate util;
 //#[macro_use]
 extern crate profiler;
 
+mod dispatch;
 mod input;
+pub use crate::dispatch::*;
 pub use crate::input::*;
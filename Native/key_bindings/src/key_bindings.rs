@@ -0,0 +1,576 @@
+use std::path::PathBuf;
+
+use serde_yaml::Value;
+
+use crate::keys::{is_modifier_key, key_code, key_name};
+
+const BINDINGS_VERSION: u64 = 1;
+const BINDINGS_FILE: &str = "keybindings.yaml";
+
+/// Commands that can be bound to keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Command {
+    ReloadMods,
+    ToggleMods,
+    ClearTextures,
+    NextTexture,
+    PrevTexture,
+    Snapshot,
+    NextVariant,
+    PrevVariant,
+    ReloadManagedDll,
+    ToggleDrawTrace,
+    DisableRecentMod,
+    EnableLastDisabledMod,
+    SaveVariantPreset,
+    NextVariantPreset,
+    PrintLoadStatus,
+}
+
+impl Command {
+    pub const ALL: &'static [Command] = &[
+        Command::ReloadMods,
+        Command::ToggleMods,
+        Command::ClearTextures,
+        Command::NextTexture,
+        Command::PrevTexture,
+        Command::Snapshot,
+        Command::NextVariant,
+        Command::PrevVariant,
+        Command::ReloadManagedDll,
+        Command::ToggleDrawTrace,
+        Command::DisableRecentMod,
+        Command::EnableLastDisabledMod,
+        Command::SaveVariantPreset,
+        Command::NextVariantPreset,
+        Command::PrintLoadStatus,
+    ];
+
+    /// Name used in the bindings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::ReloadMods => "reload_mods",
+            Command::ToggleMods => "toggle_mods",
+            Command::ClearTextures => "clear_textures",
+            Command::NextTexture => "next_texture",
+            Command::PrevTexture => "prev_texture",
+            Command::Snapshot => "snapshot",
+            Command::NextVariant => "next_variant",
+            Command::PrevVariant => "prev_variant",
+            Command::ReloadManagedDll => "reload_managed_dll",
+            Command::ToggleDrawTrace => "toggle_draw_trace",
+            Command::DisableRecentMod => "disable_recent_mod",
+            Command::EnableLastDisabledMod => "enable_last_disabled_mod",
+            Command::SaveVariantPreset => "save_variant_preset",
+            Command::NextVariantPreset => "next_variant_preset",
+            Command::PrintLoadStatus => "print_load_status",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Command> {
+        let name = name.trim();
+        Command::ALL.iter().copied().find(|c| c.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Modifier keys that must be held for a binding.  Ctrl is also satisfied by the Apps
+/// (menu) key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers { ctrl: false, alt: false, shift: false };
+    pub const CTRL: Modifiers = Modifiers { ctrl: true, alt: false, shift: false };
+
+    pub fn is_empty(&self) -> bool {
+        !(self.ctrl || self.alt || self.shift)
+    }
+
+    pub fn count(&self) -> usize {
+        self.ctrl as usize + self.alt as usize + self.shift as usize
+    }
+
+    /// True if every modifier in `other` is also in `self`.
+    pub fn contains(&self, other: Modifiers) -> bool {
+        (self.ctrl || !other.ctrl) && (self.alt || !other.alt) && (self.shift || !other.shift)
+    }
+}
+
+/// A key plus the modifiers that must be held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyStroke {
+    pub key: u8,
+    pub mods: Modifiers,
+}
+
+impl KeyStroke {
+    pub fn ctrl(key: u8) -> Self {
+        KeyStroke { key, mods: Modifiers::CTRL }
+    }
+
+    /// Parse a key stroke like `Ctrl+Shift+F7`.  Modifier names are `ctrl` (or `control`),
+    /// `alt` and `shift`; see `keys` for key names.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut mods = Modifiers::NONE;
+        let mut key = None;
+        for part in s.split('+').map(|p| p.trim()) {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => mods.ctrl = true,
+                "alt" => mods.alt = true,
+                "shift" => mods.shift = true,
+                "" => return Err(format!("empty key name in '{}'", s)),
+                _ => {
+                    if key.is_some() {
+                        return Err(format!("more than one key in '{}'", s));
+                    }
+                    let code = key_code(part).ok_or_else(|| format!("unknown key '{}' in '{}'", part, s))?;
+                    if is_modifier_key(code) {
+                        return Err(format!("modifier key '{}' can't be bound in '{}'", part, s));
+                    }
+                    key = Some(code);
+                }
+            }
+        }
+        let key = key.ok_or_else(|| format!("no key in '{}'", s))?;
+        Ok(KeyStroke { key, mods })
+    }
+}
+
+impl std::fmt::Display for KeyStroke {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.mods.ctrl { write!(f, "Ctrl+")?; }
+        if self.mods.alt { write!(f, "Alt+")?; }
+        if self.mods.shift { write!(f, "Shift+")?; }
+        write!(f, "{}", key_name(self.key))
+    }
+}
+
+/// A single key stroke, or a two stroke chord where `chord` must be pressed shortly after
+/// `stroke` (e.g. `Ctrl+K S`).  The second stroke of a chord doesn't need modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub stroke: KeyStroke,
+    pub chord: Option<KeyStroke>,
+}
+
+impl KeyBinding {
+    pub fn ctrl(key: u8) -> Self {
+        KeyBinding { stroke: KeyStroke::ctrl(key), chord: None }
+    }
+
+    /// Parse a binding: one key stroke (see `KeyStroke::parse`), or two separated by
+    /// whitespace for a chord.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        // allow spaces around the +, so that only the stroke separator remains
+        let mut norm = s.trim().to_owned();
+        while norm.contains(" +") || norm.contains("+ ") {
+            norm = norm.replace(" +", "+").replace("+ ", "+");
+        }
+        let strokes: Vec<&str> = norm.split_whitespace().collect();
+        match strokes.as_slice() {
+            [one] => Ok(KeyBinding { stroke: KeyStroke::parse(one)?, chord: None }),
+            [first, second] => Ok(KeyBinding {
+                stroke: KeyStroke::parse(first)?,
+                chord: Some(KeyStroke::parse(second)?),
+            }),
+            [] => Err("empty binding".to_owned()),
+            _ => Err(format!("chords can only have two key strokes: '{}'", s)),
+        }
+    }
+
+    /// True if both bindings can't be used at the same time: they are the same, or one is a
+    /// single stroke that starts the other's chord.
+    pub fn conflicts_with(&self, other: &KeyBinding) -> bool {
+        self == other || (self.stroke == other.stroke && (self.chord.is_none() || other.chord.is_none()))
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.stroke)?;
+        if let Some(chord) = self.chord {
+            write!(f, " {}", chord)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub bindings: Vec<(Command, KeyBinding)>,
+}
+
+impl KeyBindings {
+    /// The "FKeys" input profile.
+    // If you change these, be sure to change LocStrings/ProfileText in MMLaunch!
+    pub fn fkeys() -> Self {
+        use crate::keys::key_code as k;
+        let b = |name| KeyBinding::ctrl(k(name).expect("bad builtin key"));
+        KeyBindings { bindings: vec![
+            (Command::ReloadMods, b("F1")),
+            (Command::ToggleMods, b("F2")),
+            (Command::NextTexture, b("F3")),
+            (Command::PrevTexture, b("F4")),
+            (Command::ClearTextures, b("F6")),
+            (Command::Snapshot, b("F7")),
+            (Command::NextVariant, b("Numpad8")),
+            (Command::PrevVariant, b("Numpad9")),
+            (Command::ReloadManagedDll, b("F10")),
+            (Command::ToggleDrawTrace, b("F9")),
+            (Command::DisableRecentMod, b("F5")),
+            (Command::EnableLastDisabledMod, shifted(b("F5"))),
+            (Command::NextVariantPreset, b("F8")),
+            (Command::SaveVariantPreset, shifted(b("F8"))),
+            (Command::PrintLoadStatus, shifted(b("F9"))),
+        ]}
+    }
+
+    /// The "PunctuationKeys" input profile.
+    // If you change these, be sure to change LocStrings/ProfileText in MMLaunch!
+    pub fn punct() -> Self {
+        use crate::keys::key_code as k;
+        let b = |name| KeyBinding::ctrl(k(name).expect("bad builtin key"));
+        KeyBindings { bindings: vec![
+            (Command::ReloadMods, b("Backslash")),
+            (Command::ToggleMods, b("RBracket")),
+            (Command::ClearTextures, b("Semicolon")),
+            (Command::NextTexture, b("Comma")),
+            (Command::PrevTexture, b("Period")),
+            (Command::Snapshot, b("Slash")),
+            // Running out of punct!  oh well use these
+            (Command::NextVariant, b("Numpad8")),
+            (Command::PrevVariant, b("Numpad9")),
+            (Command::ReloadManagedDll, b("F10")),
+            (Command::ToggleDrawTrace, b("F9")),
+            (Command::DisableRecentMod, b("Apostrophe")),
+            (Command::EnableLastDisabledMod, shifted(b("Apostrophe"))),
+            (Command::NextVariantPreset, b("LBracket")),
+            (Command::SaveVariantPreset, shifted(b("LBracket"))),
+            (Command::PrintLoadStatus, shifted(b("F9"))),
+        ]}
+    }
+
+    /// Return the built-in layout for the launcher input profile name, if it is recognized.
+    pub fn for_profile(input_profile: &str) -> Option<Self> {
+        let lwr = input_profile.trim().to_lowercase();
+        if lwr.starts_with("fk") {
+            Some(Self::fkeys())
+        } else if lwr.starts_with("punct") {
+            Some(Self::punct())
+        } else {
+            None
+        }
+    }
+
+    /// Parse a bindings file.  The file looks like this:
+    ///
+    /// ```yaml
+    /// version: 1
+    /// # optional: built-in layout to start from (fkeys, punct or none).  If omitted, the
+    /// # layout selected by the launcher's input profile is used.
+    /// base: fkeys
+    /// bindings:
+    ///   snapshot: Ctrl+Shift+S
+    ///   next_variant: [Ctrl+Numpad8, Alt+Right]
+    ///   reload_managed_dll: Ctrl+K R
+    ///   toggle_draw_trace: none
+    /// ```
+    ///
+    /// A command listed under `bindings` replaces all of its bindings from the base layout;
+    /// `none` (or an empty list) unbinds it.  Every binding needs at least one modifier on its
+    /// first key stroke so that it can't collide with the game's own controls.  A single key
+    /// stroke can't also be used to start a chord.  Invalid bindings are skipped, as are
+    /// bindings that conflict with an earlier one; both are reported in the returned warnings.
+    /// Only errors that make the whole file unusable are returned as Err.
+    pub fn from_yaml(text: &str, base: &KeyBindings) -> Result<(KeyBindings, Vec<String>), String> {
+        let root = match serde_yaml::from_str::<Value>(text).map_err(|e| e.to_string())? {
+            Value::Mapping(m) => m,
+            Value::Null => serde_yaml::Mapping::new(),
+            _ => return Err("expected a yaml mapping".to_owned()),
+        };
+        let mut warnings = vec![];
+
+        if let Some(v) = root.get("version") {
+            match v.as_u64() {
+                Some(v) if v <= BINDINGS_VERSION => (),
+                _ => return Err(format!("unsupported bindings version: {:?}", v)),
+            }
+        }
+
+        let base = match root.get("base").map(|b| b.as_str()) {
+            None => base.clone(),
+            Some(Some(b)) if b.eq_ignore_ascii_case("none") => KeyBindings { bindings: vec![] },
+            Some(Some(b)) => Self::for_profile(b).ok_or_else(|| format!("unknown base layout: {}", b))?,
+            Some(None) => return Err("'base' must be a string".to_owned()),
+        };
+
+        // bindings from the file, in command order
+        let mut from_file: Vec<(Command, Vec<KeyBinding>)> = vec![];
+        match root.get("bindings") {
+            None | Some(Value::Null) => (),
+            Some(Value::Mapping(m)) => {
+                for (k, v) in m.iter() {
+                    let name = k.as_str().unwrap_or("");
+                    let cmd = match Command::from_name(name) {
+                        Some(c) => c,
+                        None => {
+                            warnings.push(format!("unknown command: {:?}", k));
+                            continue;
+                        }
+                    };
+                    if from_file.iter().any(|(c, _)| *c == cmd) {
+                        warnings.push(format!("command {} is listed more than once, using the first", cmd.name()));
+                        continue;
+                    }
+                    let strs: Vec<&str> = match v {
+                        Value::Null => vec![],
+                        Value::String(s) => vec![s.as_str()],
+                        Value::Sequence(seq) => seq.iter().filter_map(|s| {
+                            let r = s.as_str();
+                            if r.is_none() {
+                                warnings.push(format!("{}: binding is not a string: {:?}", cmd.name(), s));
+                            }
+                            r
+                        }).collect(),
+                        _ => {
+                            warnings.push(format!("{}: expected a binding or list of bindings", cmd.name()));
+                            continue;
+                        }
+                    };
+                    let mut kbs = vec![];
+                    for s in strs.iter().filter(|s| !s.trim().eq_ignore_ascii_case("none")) {
+                        match KeyBinding::parse(s) {
+                            Ok(kb) if kb.stroke.mods.is_empty() => warnings.push(format!(
+                                "{}: binding '{}' has no modifier (ctrl, alt or shift), ignoring it", cmd.name(), s)),
+                            Ok(kb) => kbs.push(kb),
+                            Err(e) => warnings.push(format!("{}: {}", cmd.name(), e)),
+                        }
+                    }
+                    from_file.push((cmd, kbs));
+                }
+            },
+            Some(_) => return Err("'bindings' must be a mapping of command name to binding".to_owned()),
+        }
+        from_file.sort_by_key(|(c, _)| *c);
+
+        // file bindings take precedence over the base layout in conflicts, so add them first
+        let mut bindings: Vec<(Command, KeyBinding)> = vec![];
+        let mut add = |cmd: Command, kb: KeyBinding, warnings: &mut Vec<String>| {
+            match bindings.iter().find(|(_, existing)| existing.conflicts_with(&kb)) {
+                Some((other, existing)) if *other == cmd && *existing == kb => (),
+                Some((other, existing)) => warnings.push(format!(
+                    "conflicting bindings: {} ({}) conflicts with {} ({}); using {}",
+                    kb, cmd.name(), existing, other.name(), other.name())),
+                None => bindings.push((cmd, kb)),
+            }
+        };
+        for (cmd, kbs) in from_file.iter() {
+            for kb in kbs {
+                add(*cmd, *kb, &mut warnings);
+            }
+        }
+        for (cmd, kb) in base.bindings.iter() {
+            if !from_file.iter().any(|(c, _)| c == cmd) {
+                add(*cmd, *kb, &mut warnings);
+            }
+        }
+        bindings.sort_by_key(|(c, _)| *c);
+
+        Ok((KeyBindings { bindings }, warnings))
+    }
+
+    /// Bindings for the command.
+    pub fn for_command(&self, cmd: Command) -> impl Iterator<Item = &KeyBinding> {
+        self.bindings.iter().filter(move |(c, _)| *c == cmd).map(|(_, kb)| kb)
+    }
+}
+
+impl std::fmt::Display for KeyBindings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let strs: Vec<String> = self.bindings.iter().map(|(c, kb)| format!("{}={}", c.name(), kb)).collect();
+        write!(f, "{}", strs.join(", "))
+    }
+}
+
+/// Builtin layouts use Ctrl+Shift for commands that go with another command's key.
+fn shifted(mut kb: KeyBinding) -> KeyBinding {
+    kb.stroke.mods.shift = true;
+    kb
+}
+
+/// Return the path of the bindings file in the ModelMod root directory, if there is one.
+/// A per-game `<game>.keybindings.yaml` is used in preference to `keybindings.yaml`.
+pub fn find_bindings_file(rootdir: &str, game_name: Option<&str>) -> Option<PathBuf> {
+    let mut names = vec![];
+    if let Some(game) = game_name.filter(|g| !g.is_empty()) {
+        names.push(format!("{}.{}", game, BINDINGS_FILE));
+    }
+    names.push(BINDINGS_FILE.to_owned());
+    for name in names {
+        // try two paths to account for rust's weird push semantics with absolute paths (same as
+        // snapconfig.yaml)
+        let mut pb = PathBuf::from(rootdir);
+        pb.push(&name);
+        if !pb.is_file() {
+            pb = PathBuf::from(rootdir);
+            pb.push(format!("\\{}", name));
+        }
+        if pb.is_file() {
+            return Some(pb);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kb(s: &str) -> KeyBinding {
+        KeyBinding::parse(s).unwrap()
+    }
+
+    fn single(key: u8, mods: Modifiers) -> KeyBinding {
+        KeyBinding { stroke: KeyStroke { key, mods }, chord: None }
+    }
+
+    #[test]
+    fn test_parse_binding() {
+        assert_eq!(kb("Ctrl+F1"), KeyBinding::ctrl(0x3B));
+        assert_eq!(kb("ctrl + shift + n"), single(0x31, Modifiers { ctrl: true, alt: false, shift: true }));
+        assert_eq!(kb("Alt+\\"), single(0x2B, Modifiers { ctrl: false, alt: true, shift: false }));
+        assert_eq!(kb("Control+0x44"), KeyBinding::ctrl(0x44));
+        assert_eq!(kb("F7").stroke.mods, Modifiers::NONE);
+        assert!(KeyBinding::parse("Ctrl+Bogus").is_err());
+        assert!(KeyBinding::parse("Ctrl+F1+F2").is_err());
+        assert!(KeyBinding::parse("Ctrl+Shift").is_err());
+        assert!(KeyBinding::parse("Ctrl+LShift").is_err());
+        assert!(KeyBinding::parse("Ctrl++").is_err());
+
+        assert_eq!(kb("shift+alt+ctrl+numpad8").to_string(), "Ctrl+Alt+Shift+Numpad8");
+        assert_eq!(KeyBinding::ctrl(0xFE).to_string(), "Ctrl+0xFE");
+
+        // chords
+        let chord = kb("Ctrl+K  Shift+S");
+        assert_eq!(chord.stroke, KeyStroke::ctrl(0x25));
+        assert_eq!(chord.chord, Some(KeyStroke { key: 0x1F, mods: Modifiers { ctrl: false, alt: false, shift: true } }));
+        assert_eq!(chord.to_string(), "Ctrl+K Shift+S");
+        assert_eq!(kb("Ctrl + K ,").chord, Some(KeyStroke { key: 0x33, mods: Modifiers::NONE }));
+        assert!(KeyBinding::parse("Ctrl+K S D").is_err());
+        assert!(KeyBinding::parse("Ctrl+K Bogus").is_err());
+        assert!(KeyBinding::parse("  ").is_err());
+
+        assert!(kb("Ctrl+K S").conflicts_with(&kb("Ctrl+K")));
+        assert!(kb("Ctrl+K").conflicts_with(&kb("Ctrl+K S")));
+        assert!(kb("Ctrl+K S").conflicts_with(&kb("Ctrl+K S")));
+        assert!(!kb("Ctrl+K S").conflicts_with(&kb("Ctrl+K D")));
+        assert!(!kb("Ctrl+K S").conflicts_with(&kb("Ctrl+Shift+K")));
+    }
+
+    #[test]
+    fn test_builtin_layouts() {
+        for layout in [KeyBindings::fkeys(), KeyBindings::punct()] {
+            // every command bound exactly once, no conflicts
+            for cmd in Command::ALL {
+                assert_eq!(layout.for_command(*cmd).count(), 1, "{:?}", cmd);
+            }
+            let (parsed, warnings) = KeyBindings::from_yaml("", &layout).unwrap();
+            assert!(warnings.is_empty(), "{:?}", warnings);
+            assert_eq!(parsed.bindings.len(), Command::ALL.len());
+        }
+        assert_eq!(KeyBindings::for_profile("FKeys"), Some(KeyBindings::fkeys()));
+        assert_eq!(KeyBindings::for_profile("PunctuationKeys"), Some(KeyBindings::punct()));
+        assert_eq!(KeyBindings::for_profile("whatever"), None);
+    }
+
+    #[test]
+    fn test_from_yaml() {
+        let yaml = r#"
+version: 1
+base: punct
+bindings:
+  snapshot: Ctrl+Shift+S
+  next_variant: [Ctrl+Numpad8, Alt+Right]
+  toggle_draw_trace: none
+"#;
+        let (b, warnings) = KeyBindings::from_yaml(yaml, &KeyBindings::fkeys()).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let snap: Vec<_> = b.for_command(Command::Snapshot).collect();
+        assert_eq!(snap, vec![&kb("Ctrl+Shift+S")]);
+        let nv: Vec<_> = b.for_command(Command::NextVariant).collect();
+        assert_eq!(nv, vec![&kb("Ctrl+Numpad8"), &kb("Alt+Right")]);
+        assert_eq!(b.for_command(Command::ToggleDrawTrace).count(), 0);
+        // rest comes from punct
+        assert_eq!(b.for_command(Command::ReloadMods).next(), Some(&kb("Ctrl+\\")));
+    }
+
+    #[test]
+    fn test_from_yaml_validation() {
+        let yaml = r#"
+bindings:
+  snapshot: Ctrl+F1
+  toggle_mods: F2
+  prev_texture: Ctrl+Nope
+  make_coffee: Ctrl+F11
+  next_texture: [Ctrl+F12, 5]
+"#;
+        let (b, warnings) = KeyBindings::from_yaml(yaml, &KeyBindings::fkeys()).unwrap();
+        // snapshot takes Ctrl+F1 from reload_mods since file bindings win
+        assert_eq!(b.for_command(Command::Snapshot).next(), Some(&kb("Ctrl+F1")));
+        assert_eq!(b.for_command(Command::ReloadMods).count(), 0);
+        assert_eq!(b.for_command(Command::ToggleMods).count(), 0);
+        assert_eq!(b.for_command(Command::PrevTexture).count(), 0);
+        assert_eq!(b.for_command(Command::NextTexture).next(), Some(&kb("Ctrl+F12")));
+        let expect = ["no modifier", "unknown key", "unknown command", "not a string", "conflicting bindings: Ctrl+F1"];
+        assert_eq!(warnings.len(), expect.len(), "{:?}", warnings);
+        for e in expect {
+            assert!(warnings.iter().any(|w| w.contains(e)), "missing warning {:?} in {:?}", e, warnings);
+        }
+
+        // two file bindings for the same key, first command wins
+        let yaml = "bindings:\n  reload_mods: Alt+R\n  toggle_mods: Alt+R\n";
+        let (b, warnings) = KeyBindings::from_yaml(yaml, &KeyBindings::fkeys()).unwrap();
+        assert_eq!(b.for_command(Command::ReloadMods).next(), Some(&kb("Alt+R")));
+        assert_eq!(b.for_command(Command::ToggleMods).count(), 0);
+        assert_eq!(warnings.len(), 1);
+
+        // a chord can't start with a key that is also bound on its own
+        let yaml = "bindings:\n  reload_mods: Ctrl+K R\n  toggle_mods: Ctrl+K\n  snapshot: Ctrl+K S\n";
+        let (b, warnings) = KeyBindings::from_yaml(yaml, &KeyBindings::fkeys()).unwrap();
+        assert_eq!(b.for_command(Command::ReloadMods).next(), Some(&kb("Ctrl+K R")));
+        assert_eq!(b.for_command(Command::ToggleMods).count(), 0);
+        assert_eq!(b.for_command(Command::Snapshot).next(), Some(&kb("Ctrl+K S")));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+
+        assert!(KeyBindings::from_yaml("version: 99", &KeyBindings::fkeys()).is_err());
+        assert!(KeyBindings::from_yaml("base: qwerty", &KeyBindings::fkeys()).is_err());
+        assert!(KeyBindings::from_yaml("bindings: [1,2]", &KeyBindings::fkeys()).is_err());
+        assert!(KeyBindings::from_yaml("- a", &KeyBindings::fkeys()).is_err());
+    }
+
+    #[test]
+    fn test_find_bindings_file() {
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("target");
+        dir.push("tmp");
+        dir.push("test_find_bindings_file");
+        std::fs::create_dir_all(&dir).unwrap();
+        let root = dir.to_str().unwrap();
+        let generic = dir.join("keybindings.yaml");
+        let game = dir.join("game.keybindings.yaml");
+        let _ = std::fs::remove_file(&generic);
+        let _ = std::fs::remove_file(&game);
+
+        assert_eq!(find_bindings_file(root, Some("game")), None);
+        std::fs::write(&generic, "").unwrap();
+        assert_eq!(find_bindings_file(root, Some("game")), Some(generic.clone()));
+        assert_eq!(find_bindings_file(root, None), Some(generic.clone()));
+        std::fs::write(&game, "").unwrap();
+        assert_eq!(find_bindings_file(root, Some("game")), Some(game.clone()));
+        assert_eq!(find_bindings_file(root, Some("othergame")), Some(generic));
+    }
+}
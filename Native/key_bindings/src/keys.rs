@@ -0,0 +1,89 @@
+//! DirectInput key codes and the names used for them in bindings files.
+
+/// (name, DIK code).  Names are matched case insensitively.  Where a key has more than one
+/// name, the first one is used when printing.
+const KEY_NAMES: &[(&str, u8)] = &[
+    ("Escape", 0x01), ("Esc", 0x01),
+    ("1", 0x02), ("2", 0x03), ("3", 0x04), ("4", 0x05), ("5", 0x06),
+    ("6", 0x07), ("7", 0x08), ("8", 0x09), ("9", 0x0A), ("0", 0x0B),
+    ("Minus", 0x0C), ("-", 0x0C),
+    ("Equals", 0x0D), ("=", 0x0D),
+    ("Backspace", 0x0E), ("Back", 0x0E),
+    ("Tab", 0x0F),
+    ("Q", 0x10), ("W", 0x11), ("E", 0x12), ("R", 0x13), ("T", 0x14),
+    ("Y", 0x15), ("U", 0x16), ("I", 0x17), ("O", 0x18), ("P", 0x19),
+    ("LBracket", 0x1A), ("[", 0x1A),
+    ("RBracket", 0x1B), ("]", 0x1B),
+    ("Enter", 0x1C), ("Return", 0x1C),
+    ("LControl", 0x1D),
+    ("A", 0x1E), ("S", 0x1F), ("D", 0x20), ("F", 0x21), ("G", 0x22),
+    ("H", 0x23), ("J", 0x24), ("K", 0x25), ("L", 0x26),
+    ("Semicolon", 0x27), (";", 0x27),
+    ("Apostrophe", 0x28), ("'", 0x28),
+    ("Grave", 0x29), ("`", 0x29),
+    ("LShift", 0x2A),
+    ("Backslash", 0x2B), ("\\", 0x2B),
+    ("Z", 0x2C), ("X", 0x2D), ("C", 0x2E), ("V", 0x2F), ("B", 0x30),
+    ("N", 0x31), ("M", 0x32),
+    ("Comma", 0x33), (",", 0x33),
+    ("Period", 0x34), (".", 0x34),
+    ("Slash", 0x35), ("/", 0x35),
+    ("RShift", 0x36),
+    ("Multiply", 0x37), ("NumpadStar", 0x37),
+    ("LAlt", 0x38),
+    ("Space", 0x39),
+    ("CapsLock", 0x3A),
+    ("F1", 0x3B), ("F2", 0x3C), ("F3", 0x3D), ("F4", 0x3E), ("F5", 0x3F),
+    ("F6", 0x40), ("F7", 0x41), ("F8", 0x42), ("F9", 0x43), ("F10", 0x44),
+    ("NumLock", 0x45),
+    ("ScrollLock", 0x46),
+    ("Numpad7", 0x47), ("Numpad8", 0x48), ("Numpad9", 0x49),
+    ("Subtract", 0x4A), ("NumpadMinus", 0x4A),
+    ("Numpad4", 0x4B), ("Numpad5", 0x4C), ("Numpad6", 0x4D),
+    ("Add", 0x4E), ("NumpadPlus", 0x4E),
+    ("Numpad1", 0x4F), ("Numpad2", 0x50), ("Numpad3", 0x51),
+    ("Numpad0", 0x52),
+    ("Decimal", 0x53), ("NumpadPeriod", 0x53),
+    ("F11", 0x57), ("F12", 0x58),
+    ("NumpadEnter", 0x9C),
+    ("RControl", 0x9D),
+    ("Divide", 0xB5), ("NumpadSlash", 0xB5),
+    ("RAlt", 0xB8),
+    ("Home", 0xC7),
+    ("Up", 0xC8),
+    ("PageUp", 0xC9), ("Prior", 0xC9),
+    ("Left", 0xCB),
+    ("Right", 0xCD),
+    ("End", 0xCF),
+    ("Down", 0xD0),
+    ("PageDown", 0xD1), ("Next", 0xD1),
+    ("Insert", 0xD2),
+    ("Delete", 0xD3),
+    ("Apps", 0xDD), ("Menu", 0xDD),
+];
+
+/// Keys that act as modifiers and so can't be bound to a command.
+const MODIFIER_KEYS: &[u8] = &[0x1D, 0x9D, 0x2A, 0x36, 0x38, 0xB8, 0xDD];
+
+/// Look up a key by name.  Hex DIK codes (e.g. `0x3B`) are also accepted.
+pub fn key_code(name: &str) -> Option<u8> {
+    let name = name.trim();
+    if let Some(hex) = name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+        return u8::from_str_radix(hex, 16).ok().filter(|c| *c != 0);
+    }
+    KEY_NAMES.iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, code)| *code)
+}
+
+/// Return the name of the key, or its hex code if it doesn't have one.
+pub fn key_name(code: u8) -> String {
+    KEY_NAMES.iter()
+        .find(|(_, c)| *c == code)
+        .map(|(n, _)| n.to_string())
+        .unwrap_or_else(|| format!("0x{:02X}", code))
+}
+
+pub fn is_modifier_key(code: u8) -> bool {
+    MODIFIER_KEYS.contains(&code)
+}
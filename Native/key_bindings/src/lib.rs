@@ -0,0 +1,10 @@
+//! Key bindings for the input commands.  The bindings are either one of the built-in layouts
+//! (selected by the launcher's input profile) or come from a yaml file in the ModelMod root
+//! directory.  This crate only deals with the binding data; `input` handles the keyboard and
+//! `hook_core::input_commands` maps the commands to their implementations.
+
+mod keys;
+mod key_bindings;
+
+pub use crate::keys::*;
+pub use crate::key_bindings::*;
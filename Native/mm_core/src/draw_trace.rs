@@ -0,0 +1,277 @@
+//! Draw call trace recording.  When recording is active the draw hooks append one compact
+//! record per (triangle list) draw to a trace file, containing the inputs that mod selection
+//! uses: the prim/vert counts, the checksum of the bound vertex buffer, the semantic mask of
+//! the current input layout and the frame number.  The `trace_replay` tool can feed a trace
+//! back through `preselect`/`select` against a mod DB to find out which mod would have rendered
+//! each draw, which makes "wrong variant"/"mod flickers" bugs reproducible without the game.
+//!
+//! File format (little endian): the 4 byte magic `MMDT`, a u32 version, then a sequence of
+//! fixed size records (see `DrawTraceRecord::write_to`).
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::log::write_log_file;
+
+pub const TRACE_MAGIC: &[u8; 4] = b"MMDT";
+pub const TRACE_VERSION: u32 = 1;
+/// Size in bytes of a single encoded record.
+pub const TRACE_RECORD_SIZE: usize = 8 + 4 + 4 + 1 + 4 + 16;
+
+/// One recorded draw call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DrawTraceRecord {
+    pub frame: u64,
+    pub prim_count: u32,
+    pub vert_count: u32,
+    /// CRC32 of the bound stream-0 VB, if it was known at draw time.
+    pub vb_checksum: Option<u32>,
+    /// `VertexFormat::semantic_mask` of the current input layout; always 0 on DX9, which
+    /// doesn't track layout semantics.
+    pub semantic_mask: u128,
+}
+
+impl DrawTraceRecord {
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut buf = [0u8; TRACE_RECORD_SIZE];
+        buf[0..8].copy_from_slice(&self.frame.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.prim_count.to_le_bytes());
+        buf[12..16].copy_from_slice(&self.vert_count.to_le_bytes());
+        buf[16] = self.vb_checksum.is_some() as u8;
+        buf[17..21].copy_from_slice(&self.vb_checksum.unwrap_or(0).to_le_bytes());
+        buf[21..37].copy_from_slice(&self.semantic_mask.to_le_bytes());
+        w.write_all(&buf)
+    }
+
+    /// Read a record.  Returns Ok(None) at a clean end of file.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Option<DrawTraceRecord>> {
+        let mut buf = [0u8; TRACE_RECORD_SIZE];
+        let mut got = 0;
+        while got < TRACE_RECORD_SIZE {
+            match r.read(&mut buf[got..]) {
+                Ok(0) if got == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                    format!("truncated trace record ({} of {} bytes)", got, TRACE_RECORD_SIZE))),
+                Ok(n) => got += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        let u32_at = |i: usize| u32::from_le_bytes([buf[i], buf[i+1], buf[i+2], buf[i+3]]);
+        let mut frame = [0u8; 8];
+        frame.copy_from_slice(&buf[0..8]);
+        let mut mask = [0u8; 16];
+        mask.copy_from_slice(&buf[21..37]);
+        Ok(Some(DrawTraceRecord {
+            frame: u64::from_le_bytes(frame),
+            prim_count: u32_at(8),
+            vert_count: u32_at(12),
+            vb_checksum: if buf[16] != 0 { Some(u32_at(17)) } else { None },
+            semantic_mask: u128::from_le_bytes(mask),
+        }))
+    }
+}
+
+/// Writes the trace header and then records to `W`.
+pub struct DrawTraceWriter<W: Write> {
+    w: W,
+    pub records: u64,
+}
+
+impl<W: Write> DrawTraceWriter<W> {
+    pub fn new(mut w: W) -> io::Result<Self> {
+        w.write_all(TRACE_MAGIC)?;
+        w.write_all(&TRACE_VERSION.to_le_bytes())?;
+        Ok(Self { w, records: 0 })
+    }
+
+    pub fn write(&mut self, rec: &DrawTraceRecord) -> io::Result<()> {
+        rec.write_to(&mut self.w)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Iterates the records in a trace.  The header is checked by `new`.
+pub struct DrawTraceReader<R: Read> {
+    r: R,
+}
+
+impl<R: Read> DrawTraceReader<R> {
+    pub fn new(mut r: R) -> io::Result<Self> {
+        let mut hdr = [0u8; 8];
+        r.read_exact(&mut hdr)?;
+        if &hdr[0..4] != TRACE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a draw trace file (bad magic)"));
+        }
+        let version = u32::from_le_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]);
+        if version != TRACE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("unsupported draw trace version {} (expected {})", version, TRACE_VERSION)));
+        }
+        Ok(Self { r })
+    }
+}
+
+impl<R: Read> Iterator for DrawTraceReader<R> {
+    type Item = io::Result<DrawTraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        DrawTraceRecord::read_from(&mut self.r).transpose()
+    }
+}
+
+pub fn open_trace_file<P: AsRef<Path>>(path: P) -> io::Result<DrawTraceReader<BufReader<File>>> {
+    DrawTraceReader::new(BufReader::new(File::open(path)?))
+}
+
+struct Recorder {
+    writer: DrawTraceWriter<BufWriter<File>>,
+    path: String,
+    first_frame: Option<u64>,
+    max_frames: u64,
+}
+
+/// Checked by the draw hooks on every draw, so that they don't need to take the lock unless
+/// recording.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+#[inline]
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Start recording to the specified file, replacing it if it exists.  Recording stops
+/// automatically once `max_frames` frames have been recorded (0 means no limit).  If a
+/// recording is already active it is stopped first.
+pub fn start_recording(path: &str, max_frames: u64) -> io::Result<()> {
+    stop_recording();
+    let writer = DrawTraceWriter::new(BufWriter::new(File::create(path)?))?;
+    let mut rec = RECORDER.lock().map_err(|e| io::Error::other(format!("lock error: {}", e)))?;
+    *rec = Some(Recorder { writer, path: path.to_owned(), first_frame: None, max_frames });
+    RECORDING.store(true, Ordering::SeqCst);
+    write_log_file(&format!("draw trace: recording to {}", path));
+    Ok(())
+}
+
+/// Stop recording and flush the file.  Returns the number of records written, or None if
+/// there was no active recording.
+pub fn stop_recording() -> Option<u64> {
+    RECORDING.store(false, Ordering::SeqCst);
+    let mut rec = match RECORDER.lock() {
+        Ok(r) => r,
+        Err(e) => {
+            write_log_file(&format!("draw trace: lock poisoned: {}", e));
+            return None;
+        }
+    };
+    rec.take().map(|mut r| {
+        if let Err(e) = r.writer.flush() {
+            write_log_file(&format!("draw trace: failed to flush {}: {}", r.path, e));
+        }
+        write_log_file(&format!("draw trace: stopped, wrote {} draws to {}", r.writer.records, r.path));
+        r.writer.records
+    })
+}
+
+/// Append a record if recording.  Errors stop the recording.
+pub fn record_draw(draw: &DrawTraceRecord) {
+    if !is_recording() {
+        return;
+    }
+    let done = {
+        let mut rec = match RECORDER.lock() {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+        let rec = match rec.as_mut() {
+            Some(r) => r,
+            None => return,
+        };
+        let first = *rec.first_frame.get_or_insert(draw.frame);
+        if rec.max_frames > 0 && draw.frame.saturating_sub(first) >= rec.max_frames {
+            true
+        } else if let Err(e) = rec.writer.write(draw) {
+            write_log_file(&format!("draw trace: write failed: {}", e));
+            true
+        } else {
+            false
+        }
+    };
+    if done {
+        stop_recording();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<DrawTraceRecord> {
+        vec![
+            DrawTraceRecord { frame: 1, prim_count: 12, vert_count: 8, vb_checksum: None, semantic_mask: 0 },
+            DrawTraceRecord { frame: 1, prim_count: 586, vert_count: 765,
+                vb_checksum: Some(0xDEADBEEF), semantic_mask: 1 << 100 | 0x7 },
+            DrawTraceRecord { frame: u64::MAX, prim_count: u32::MAX, vert_count: 3,
+                vb_checksum: Some(0), semantic_mask: u128::MAX },
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut w = DrawTraceWriter::new(Vec::new()).unwrap();
+        for r in sample() {
+            w.write(&r).unwrap();
+        }
+        assert_eq!(w.records, 3);
+        let bytes = w.w;
+        assert_eq!(bytes.len(), 8 + 3 * TRACE_RECORD_SIZE);
+
+        let read:Vec<DrawTraceRecord> = DrawTraceReader::new(bytes.as_slice()).unwrap()
+            .map(|r| r.unwrap()).collect();
+        assert_eq!(read, sample());
+    }
+
+    #[test]
+    fn test_bad_input() {
+        assert!(DrawTraceReader::new(&b"XXXX\x01\0\0\0"[..]).is_err());
+        assert!(DrawTraceReader::new(&b"MMDT\x63\0\0\0"[..]).is_err());
+        assert!(DrawTraceReader::new(&b"MM"[..]).is_err());
+
+        // truncated record is an error, not a silent end
+        let mut w = DrawTraceWriter::new(Vec::new()).unwrap();
+        w.write(&sample()[0]).unwrap();
+        let mut bytes = w.w;
+        bytes.truncate(bytes.len() - 1);
+        let res:Vec<_> = DrawTraceReader::new(bytes.as_slice()).unwrap().collect();
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_err());
+    }
+
+    #[test]
+    fn test_recorder_frame_limit() {
+        // the recorder logs, don't let that land in another test's log file
+        let _loglock = crate::log::LOG_EXCL_LOCK.lock().unwrap();
+        let path = std::env::temp_dir().join(format!("mm_draw_trace_test_{}.mmdt", std::process::id()));
+        let path = path.to_str().unwrap().to_owned();
+        start_recording(&path, 2).unwrap();
+        assert!(is_recording());
+        for frame in 10..20 {
+            record_draw(&DrawTraceRecord { frame, prim_count: 1, vert_count: 3, ..Default::default() });
+        }
+        // frames 10 and 11 recorded, recording stopped at 12
+        assert!(!is_recording());
+        assert_eq!(stop_recording(), None);
+        let frames:Vec<u64> = open_trace_file(&path).unwrap().map(|r| r.unwrap().frame).collect();
+        assert_eq!(frames, vec![10, 11]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
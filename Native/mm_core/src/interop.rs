@@ -0,0 +1,160 @@
+//! Mod data structures shared with the managed code.  These only contain plain data so they
+//! are kept here rather than in `types::interop` (which also has the D3D specific interop types)
+//! so that they can be used without pulling in winapi.
+#![allow(non_snake_case)]
+
+/// Same as the winapi `WCHAR`.
+pub type WCHAR = u16;
+
+pub enum ModType {
+    None = 0,
+    GPUAdditive,
+    CPUReplacement,
+    GPUReplacement,
+    GPUPertubation,
+    Deletion,
+}
+
+pub const MAX_TEX_PATH_LEN: usize = 8192;
+pub const MAX_MOD_NAME_LEN: usize = 1024;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ModNumbers {
+    pub mod_type: i32,
+    pub prim_type: i32,
+    pub vert_count: i32,
+    pub prim_count: i32,
+    pub index_count: i32,
+    pub ref_vert_count: i32,
+    pub ref_prim_count: i32,
+    pub decl_size_bytes: i32,
+    pub vert_size_bytes: i32,
+    pub index_elem_size_bytes: i32,
+}
+
+impl ModNumbers {
+    /// Returns the index count and index element size in bytes if the mod should be drawn
+    /// from an index buffer.  Element sizes other than 2 and 4 bytes aren't valid, so
+    /// mods with those are drawn without one.
+    pub fn index_info(&self) -> Option<(u32, u32)> {
+        match self.index_elem_size_bytes {
+            2 | 4 if self.index_count > 0 && self.vert_count > 0 =>
+                Some((self.index_count as u32, self.index_elem_size_bytes as u32)),
+            _ => None,
+        }
+    }
+
+    /// Number of vertices in the mod's vertex buffer.  Indexed mods have `vert_count`
+    /// vertices; otherwise each primitive has three vertices of its own.
+    pub fn vb_vert_count(&self) -> i32 {
+        match self.index_info() {
+            Some(_) => self.vert_count,
+            None => self.prim_count * 3,
+        }
+    }
+
+    /// Size of the mod's index buffer in bytes, zero if it doesn't have one.
+    pub fn ib_size_bytes(&self) -> i32 {
+        self.index_info().map(|(count, size)| (count * size) as i32).unwrap_or(0)
+    }
+}
+
+pub const MAX_SNAPPROFILE_STRING: usize = 256;
+pub const MAX_TRANSFORM_SIZE: usize = 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ModSnapProfile {
+    pub valid: bool,
+
+    pub name: [WCHAR; MAX_SNAPPROFILE_STRING],
+
+    pub pos_xfrm_length: i32,
+    pub pos_xfrm: [[WCHAR; MAX_SNAPPROFILE_STRING]; MAX_TRANSFORM_SIZE],
+
+    pub uv_xfrm_length: i32,
+    pub uv_xfrm: [[WCHAR; MAX_SNAPPROFILE_STRING]; MAX_TRANSFORM_SIZE],
+
+    pub flip_tangent: bool,
+
+    pub vec_encoding: [WCHAR; MAX_SNAPPROFILE_STRING],
+
+    pub blend_index_in_color1: bool,
+
+    pub blend_weight_in_color2: bool,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+/// Contains information and data associated with a mod, but _not_ any D3D resources.
+/// This structure is passed to/from managed code so must have a defined layout, and can
+/// only contain types that can be marshalled over the interop boundary.
+pub struct ModData {
+    pub numbers: ModNumbers,
+    pub update_tangent_space: i32,
+    pub texPath0: [WCHAR; MAX_TEX_PATH_LEN],
+    pub texPath1: [WCHAR; MAX_TEX_PATH_LEN],
+    pub texPath2: [WCHAR; MAX_TEX_PATH_LEN],
+    pub texPath3: [WCHAR; MAX_TEX_PATH_LEN],
+    pub modName: [WCHAR; MAX_MOD_NAME_LEN],
+    pub parentModName: [WCHAR; MAX_MOD_NAME_LEN],
+    pub _pixelShaderPath: [WCHAR; MAX_TEX_PATH_LEN], // not used
+    pub mod_snap_profile: ModSnapProfile,
+    pub data_available: bool,
+    /// Vertex-buffer CRC32 that this mod requires of the bound stream-0 VB
+    /// in order to render. Only consulted when `vb_checksum_set` is true.
+    pub vb_checksum: u32,
+    /// Whether `vb_checksum` is a real constraint; if false the mod matches
+    /// any VB (legacy behavior).
+    pub vb_checksum_set: bool,
+    /// Full path to the mod's mmobj file.  Only set by managed code when native code can fill
+    /// the mod's buffers from the file itself (see `mod_load::native_mesh`), otherwise empty.
+    pub meshPath: [WCHAR; MAX_TEX_PATH_LEN],
+    /// Whether blend indices and weights come from the mod mesh (the "mod" weight mode).  If
+    /// false and the layout needs blend data, the native fill can't be used.
+    pub mesh_blend_from_mod: bool,
+}
+
+impl ModData {
+    pub fn new() -> Self {
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+impl Default for ModData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_info() {
+        let mut n = ModData::new().numbers;
+        n.prim_count = 36;
+        n.vert_count = 57;
+        assert_eq!(n.index_info(), None);
+        assert_eq!(n.vb_vert_count(), 108);
+        assert_eq!(n.ib_size_bytes(), 0);
+
+        n.index_count = 108;
+        n.index_elem_size_bytes = 2;
+        assert_eq!(n.index_info(), Some((108, 2)));
+        assert_eq!(n.vb_vert_count(), 57);
+        assert_eq!(n.ib_size_bytes(), 216);
+        n.index_elem_size_bytes = 4;
+        assert_eq!(n.ib_size_bytes(), 432);
+
+        // bad element size or no verts, draw without indices
+        n.index_elem_size_bytes = 3;
+        assert_eq!(n.index_info(), None);
+        assert_eq!(n.vb_vert_count(), 108);
+        n.index_elem_size_bytes = 2;
+        n.vert_count = 0;
+        assert_eq!(n.index_info(), None);
+    }
+}
@@ -0,0 +1,18 @@
+//! Renderer independent parts of the native mod code: the mod data structures, the
+//! parent/variant/VB checksum selection logic, and logging.  Nothing here depends on winapi so
+//! it can be built and tested on any platform; the D3D resources are supplied by the hook as
+//! the `D` type parameter of `NativeModData` and `LoadedModState`.
+
+pub const ENABLE_DEBUG_SPAM:bool = false;
+pub const DEBUG_SPAM_TO_STDERR:bool = false;
+
+pub mod draw_trace;
+pub mod interop;
+pub mod log;
+pub mod native_mod;
+mod mod_state;
+mod mod_select;
+
+pub use crate::native_mod::{NativeModData, ModD3DState};
+pub use crate::mod_state::*;
+pub use crate::mod_select::*;
@@ -0,0 +1,49 @@
+use fnv::FnvHashMap;
+
+use crate::native_mod::NativeModData;
+
+/// Resolved state of a vertex buffer's CRC32 entry in `vb_checksums`.
+///
+/// A VB that is not present in the map at all is implicitly "pending" —
+/// the DX9 DIP hook will attempt to hash it the first time it sees it
+/// bound, and insert either `Checksum` or `NotPossible` depending on
+/// whether the Lock succeeded. DX11 hashes at create time using the
+/// supplied initial data and goes straight to `Checksum`.
+#[derive(Debug, Clone, Copy)]
+pub enum VBChecksumStatus {
+    /// Successfully computed CRC32 over the buffer's bytes.
+    Checksum(u32),
+    /// Buffer cannot be hashed (Lock failed). Don't retry.
+    NotPossible,
+}
+
+impl VBChecksumStatus {
+    /// Return the CRC if this entry has successfully been hashed.
+    /// `NotPossible` returns `None`.
+    pub fn checksum(&self) -> Option<u32> {
+        match self {
+            VBChecksumStatus::Checksum(c) => Some(*c),
+            VBChecksumStatus::NotPossible => None,
+        }
+    }
+}
+
+pub type LoadedModsMap<D> = FnvHashMap<u32, Vec<NativeModData<D>>>;
+pub type ModsByNameMap = FnvHashMap<String,u32>;
+pub type SelectedVariantMap = FnvHashMap<u32, usize>;
+pub fn new_fnv_map<A,B> (capacity:usize) -> FnvHashMap<A,B> {
+    FnvHashMap::with_capacity_and_hasher(capacity, Default::default())
+}
+
+/// All loaded mods, keyed by `mod_key`, plus lookup tables.  `D` is the renderer specific
+/// resource type stored in each `NativeModData`.
+pub struct LoadedModState<D> {
+    pub mods: LoadedModsMap<D>,
+    pub mods_by_name: ModsByNameMap,
+    pub selected_variant: SelectedVariantMap,
+    /// Names of mods that the user has disabled, in the order they were disabled.  `select`
+    /// never returns these.  Saved in the prefs file.
+    pub disabled_mods: Vec<String>,
+    /// (mod key, index) of the mod most recently returned by `select`.
+    pub last_selected: Option<(u32, usize)>,
+}
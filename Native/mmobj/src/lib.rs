@@ -0,0 +1,2 @@
+mod mmobj;
+pub use crate::mmobj::*;
@@ -0,0 +1,416 @@
+use std::fmt::Debug;
+use std::path::Path;
+use std::str::FromStr;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+
+#[repr(C)]
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Float3 {
+    pub x:f32,
+    pub y:f32,
+    pub z:f32
+}
+
+#[repr(C)]
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Float2 {
+    pub x:f32,
+    pub y:f32,
+}
+
+#[repr(C)]
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct BlendPair {
+    pub idx:u32,
+    pub weight:f32,
+}
+
+/// Zero-based indices into the position, normal and texcoord lists of an `MMObj`.
+#[repr(C)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct FaceVert {
+    pub pos:usize,
+    pub nrm:usize,
+    pub tex:usize,
+}
+
+/// Raw contents of an mmobj file.  No post processing (transforms, blend weight adjustment,
+/// vertex expansion, etc) is done here, that is up to the consumer.
+#[derive(Debug,Clone,Default)]
+pub struct MMObj {
+    pub filename: String,
+    pub positions: Vec<Float3>,
+    pub texcoord: Vec<Float2>,
+    pub normals: Vec<Float3>,
+    pub vgroup_names:Vec<String>,
+    pub vgroup_lists:Vec<Vec<i32>>,
+    pub vblend:Vec<Vec<BlendPair>>,
+    pub posx:Vec<Vec<String>>,
+    pub uvx:Vec<Vec<String>>,
+    pub faces:Vec<[FaceVert;3]>,
+    pub mtllib:Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum MMObjError {
+    IOError(String, std::io::Error),
+    /// File, line number (1-based), message
+    ParseError(String, usize, String),
+    /// File, face index, message
+    BadIndex(String, usize, String),
+}
+
+impl std::fmt::Display for MMObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MMObjError::IOError(file, e) => write!(f, "{}: io error: {}", file, e),
+            MMObjError::ParseError(file, line, msg) => write!(f, "{}:{}: {}", file, line, msg),
+            MMObjError::BadIndex(file, face, msg) => write!(f, "{}: face {}: {}", file, face, msg),
+        }
+    }
+}
+
+impl std::error::Error for MMObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MMObjError::IOError(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, MMObjError>;
+
+/// Parse helpers return a plain message, `parse_x` attaches the file and line.
+type LineResult<T> = std::result::Result<T, String>;
+
+// Each line type in an mmobj begins with a unique prefix, so aho-corasick can sort all the
+// lines into buckets in one pass.  Order here must match the bucket indices used in `parse_with`.
+const PATTERNS: [&str; 10] = [
+    "vt ",
+    "v ",
+    "vn ",
+    "#vgn ",
+    "#vg ",
+    "#vbld ",
+    "#pos_xforms ",
+    "#uv_xforms ",
+    "f ",
+    "mtllib ",
+];
+
+fn pf32<'a, I: Iterator<Item=&'a str>>(i: &mut I) -> LineResult<f32> {
+    let sval = i.next().ok_or("pf32: missing value")?;
+    sval.parse::<f32>().map_err(|e| format!("failed to parse float from str '{}': {}", sval, e))
+}
+fn parse_2_f32(line:&str) -> LineResult<Float2> {
+    let mut i = line.split_whitespace();
+    let f1 = pf32(&mut i)?;
+    let f2 = pf32(&mut i)?;
+    Ok(Float2 { x:f1, y:f2 })
+}
+fn parse_3_f32(line:&str) -> LineResult<Float3> {
+    let mut i = line.split_whitespace();
+    let f1 = pf32(&mut i)?;
+    let f2 = pf32(&mut i)?;
+    let f3 = pf32(&mut i)?;
+    Ok(Float3 { x:f1, y:f2, z:f3 })
+}
+fn parse_1_str(line:&str) -> LineResult<String> {
+    let s = line.split_whitespace().next().ok_or("parse_1_str: empty name")?;
+    Ok(s.to_owned())
+}
+fn parse_n_int<T: FromStr>(line:&str) -> LineResult<Vec<T>>
+    where <T as FromStr>::Err: Debug {
+    line.split_whitespace().map(|s| {
+        s.parse::<T>().map_err(|e| format!("parse_n_int: failed to parse int from str '{}': {:?}", s, e))
+    }).collect()
+}
+fn parse_blendpairs(line:&str) -> LineResult<Vec<BlendPair>> {
+    line.split_whitespace().map(|s| {
+        let mut i = s.split('/');
+        let bidx = i.next().ok_or("parse_blendpairs: bad split")?
+            .parse::<u32>().map_err(|e| format!("parse_blendpairs: failed to parse bp int from str '{}': {}", s, e))?;
+        let bw = i.next().ok_or_else(|| format!("parse_blendpairs: missing weight in '{}'", s))?
+            .parse::<f32>().map_err(|e| format!("parse_blendpairs: failed to parse bp f32 from str '{}': {}", s, e))?;
+        Ok(BlendPair { idx:bidx, weight:bw })
+    }).collect()
+}
+fn parse_n_strs(line:&str) -> LineResult<Vec<String>> {
+    let v:Vec<String> = line.split_whitespace().map(|s| s.to_owned()).collect();
+    if v.is_empty() {
+        return Err("parse_n_strs: no values".to_owned());
+    }
+    Ok(v)
+}
+fn parse_face_idx(s:Option<&str>, what:&str, vert:&str) -> LineResult<usize> {
+    let s = s.ok_or_else(|| format!("parse_face_pos_tex_nrm: missing {} index in '{}'", what, vert))?;
+    // obj indices are 1-based, sub1 to make them zero based
+    let idx = s.parse::<u32>()
+        .map_err(|e| format!("failed to parse f {} int from str '{}': {}", what, vert, e))?
+        .checked_sub(1).ok_or_else(|| format!("parse_face_pos_tex_nrm: zero {} index in '{}'", what, vert))?;
+    Ok(idx as usize)
+}
+fn parse_face_pos_tex_nrm(line:&str) -> LineResult<[FaceVert;3]> {
+    let mut verts = line.split_whitespace();
+    let mut res = [FaceVert { pos: 0, nrm: 0, tex: 0} ;3];
+    for fv in res.iter_mut() { // 3 points because triangles
+        let vert = verts.next().ok_or("parse_face_pos_tex_nrm: face must have 3 verts")?;
+        let mut i = vert.split('/');
+        let pos = parse_face_idx(i.next(), "pos", vert)?;
+        let tex = parse_face_idx(i.next(), "tex", vert)?;
+        let nrm = parse_face_idx(i.next(), "nrm", vert)?;
+        *fv = FaceVert { pos, nrm, tex };
+    }
+    if verts.next().is_some() {
+        return Err("parse_face_pos_tex_nrm: only triangles are supported".to_owned());
+    }
+
+    Ok(res)
+}
+
+fn line_number(text:&str, off:usize) -> usize {
+    text.as_bytes()[0..off].iter().filter(|b| **b == b'\n').count() + 1
+}
+
+fn parse_x<F,D>(filename:&str, offsets:&[usize], text:&str, pfn:F) -> Result<Vec<D>>
+    where F: Fn(&str) -> LineResult<D> {
+    offsets.iter().map(|&off| {
+        let rest = &text[off..];
+        let line = &rest[0..rest.find('\n').unwrap_or(rest.len())];
+        pfn(line).map_err(|msg| MMObjError::ParseError(filename.to_owned(), line_number(text, off), msg))
+    }).collect()
+}
+
+fn dedup<T: Ord>(v:Vec<T>) -> Vec<T> {
+    let set = std::collections::BTreeSet::from_iter(v);
+    set.into_iter().collect()
+}
+
+fn make_matcher() -> AhoCorasick {
+    AhoCorasickBuilder::new()
+        .match_kind(MatchKind::LeftmostLongest)
+        .build(PATTERNS)
+}
+
+fn parse_with(ac:&AhoCorasick, filename:&str, text:&str) -> Result<MMObj> {
+    let mut outputs = PATTERNS.map(|_| Vec::with_capacity(16));
+    let bytes = text.as_bytes();
+    ac.find_iter(text)
+        // prefixes only count at the start of a line, ignore them in names, comments, etc
+        .filter(|mat| mat.start() == 0 || bytes[mat.start() - 1] == b'\n')
+        .for_each(|mat| outputs[mat.pattern()].push(mat.end()));
+
+    let texcoord = parse_x(filename, &outputs[0], text, parse_2_f32)?;
+    let positions = parse_x(filename, &outputs[1], text, parse_3_f32)?;
+    let normals = parse_x(filename, &outputs[2], text, parse_3_f32)?;
+    let vgroup_names = parse_x(filename, &outputs[3], text, parse_1_str)?;
+    let vgroup_lists = parse_x(filename, &outputs[4], text, parse_n_int::<i32>)?;
+    let vblend = parse_x(filename, &outputs[5], text, parse_blendpairs)?;
+    let posx = dedup(parse_x(filename, &outputs[6], text, parse_n_strs)?);
+    let uvx = dedup(parse_x(filename, &outputs[7], text, parse_n_strs)?);
+    let faces = parse_x(filename, &outputs[8], text, parse_face_pos_tex_nrm)?;
+    let mtllib = parse_x(filename, &outputs[9], text, parse_1_str)?;
+
+    Ok(MMObj {
+        filename: filename.to_owned(),
+        positions,
+        texcoord,
+        normals,
+        vgroup_names,
+        vgroup_lists,
+        vblend,
+        posx,
+        uvx,
+        faces,
+        mtllib,
+    })
+}
+
+/// Parse mmobj text.  `filename` is only used for the result and error messages.
+/// Instead of using regex/captures like the managed code does, this uses aho-corasick to sort
+/// the various line types into buckets, then each bucket is parsed without regex since the
+/// format for each is very specific.  The result is validated before it is returned.
+pub fn parse_mmobj(filename:&str, text:&str) -> Result<MMObj> {
+    let mmobj = parse_with(&make_matcher(), filename, text)?;
+    mmobj.validate()?;
+    Ok(mmobj)
+}
+
+/// Read and parse a single mmobj file.
+pub fn load_mmobj<P: AsRef<Path>>(path:P) -> Result<MMObj> {
+    let path = path.as_ref();
+    let filename = path.to_string_lossy();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| MMObjError::IOError(filename.to_string(), e))?;
+    parse_mmobj(&filename, &text)
+}
+
+/// Load a set of mmobj files using up to `max_threads` threads (0 means use the available
+/// parallelism).  Results are returned in the same order as `paths`; a failure in one file does
+/// not prevent the others from loading.
+pub fn load_mmobj_files<P: AsRef<Path> + Sync>(paths:&[P], max_threads:usize) -> Vec<Result<MMObj>> {
+    if paths.is_empty() {
+        return vec![];
+    }
+    let max_threads = if max_threads == 0 {
+        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    } else {
+        max_threads
+    };
+    let chunk_size = paths.len().div_ceil(max_threads);
+    std::thread::scope(|s| {
+        let handles:Vec<_> = paths.chunks(chunk_size).map(|chunk| {
+            s.spawn(move || chunk.iter().map(load_mmobj).collect::<Vec<_>>())
+        }).collect();
+        handles.into_iter().flat_map(|h| h.join().expect("mmobj load thread panicked")).collect()
+    })
+}
+
+impl MMObj {
+    pub fn prim_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Check that every face index refers to an existing position, texcoord and normal, and
+    /// that per-vertex group and blend lists (if present) match the position count.
+    pub fn validate(&self) -> Result<()> {
+        let bad = |face:usize, msg:String| Err(MMObjError::BadIndex(self.filename.clone(), face, msg));
+        for (fidx, face) in self.faces.iter().enumerate() {
+            for fv in face.iter() {
+                if fv.pos >= self.positions.len() {
+                    return bad(fidx, format!("position index {} out of range ({})", fv.pos + 1, self.positions.len()));
+                }
+                if fv.tex >= self.texcoord.len() {
+                    return bad(fidx, format!("texcoord index {} out of range ({})", fv.tex + 1, self.texcoord.len()));
+                }
+                if fv.nrm >= self.normals.len() {
+                    return bad(fidx, format!("normal index {} out of range ({})", fv.nrm + 1, self.normals.len()));
+                }
+            }
+        }
+        if !self.vgroup_lists.is_empty() && self.vgroup_lists.len() != self.positions.len() {
+            return bad(0, format!("vertex group list count {} does not match position count {}",
+                self.vgroup_lists.len(), self.positions.len()));
+        }
+        if !self.vblend.is_empty() && self.vblend.len() != self.positions.len() {
+            return bad(0, format!("blend pair count {} does not match position count {}",
+                self.vblend.len(), self.positions.len()));
+        }
+        for (vidx, groups) in self.vgroup_lists.iter().enumerate() {
+            if let Some(g) = groups.iter().find(|g| **g < 0 || **g as usize >= self.vgroup_names.len()) {
+                return bad(0, format!("vertex {} references unknown vertex group {}", vidx, g));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_data_path(name:&str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../TestData").join(name)
+    }
+
+    #[test]
+    fn test_load_monolith_ref() {
+        let mmobj = load_mmobj(test_data_path("MonolithRef.mmobj")).expect("load failed");
+        assert_eq!(mmobj.positions.len(), 8);
+        assert_eq!(mmobj.texcoord.len(), 3);
+        assert_eq!(mmobj.normals.len(), 8);
+        // matches ExpectedPrimCount in MonolithRef.yaml
+        assert_eq!(mmobj.prim_count(), 12);
+        assert!(mmobj.vgroup_names.is_empty());
+        assert!(mmobj.vblend.is_empty());
+        assert!(mmobj.mtllib.is_empty());
+
+        assert_eq!(mmobj.positions[0], Float3 { x: 4.0, y: 0.0, z: 0.0 });
+        assert_eq!(mmobj.texcoord[2], Float2 { x: 1.0, y: 1.0 });
+        assert_eq!(mmobj.normals[0], Float3 { x: -0.5773, y: 0.5773, z: -0.5773 });
+        // "f 4/1/4 5/2/5 6/3/6"
+        assert_eq!(mmobj.faces[1][0], FaceVert { pos: 3, tex: 0, nrm: 3 });
+        assert_eq!(mmobj.faces[1][2], FaceVert { pos: 5, tex: 2, nrm: 5 });
+    }
+
+    #[test]
+    fn test_load_monolith_mod() {
+        let mmobj = load_mmobj(test_data_path("MonolithMod.mmobj")).expect("load failed");
+        assert_eq!(mmobj.positions.len(), 24);
+        assert_eq!(mmobj.texcoord.len(), 6);
+        assert_eq!(mmobj.normals.len(), 16);
+        assert_eq!(mmobj.prim_count(), 36);
+    }
+
+    #[test]
+    fn test_load_multiple() {
+        let paths = [
+            test_data_path("MonolithRef.mmobj"),
+            test_data_path("MonolithMod.mmobj"),
+            test_data_path("Mods/FT_Crate/FT_CrateMod.mmobj"),
+            test_data_path("DoesNotExist.mmobj"),
+        ];
+        let res = load_mmobj_files(&paths, 2);
+        assert_eq!(res.len(), 4);
+        assert_eq!(res[0].as_ref().expect("ref").prim_count(), 12);
+        assert_eq!(res[1].as_ref().expect("mod").prim_count(), 36);
+        let crate_mod = res[2].as_ref().expect("crate");
+        assert_eq!(crate_mod.positions.len(), 765);
+        assert_eq!(crate_mod.prim_count(), 586);
+        assert_eq!(crate_mod.mtllib, vec!["FT_CrateRef.mtl".to_owned()]);
+        assert_eq!(crate_mod.posx, vec![vec!["rot_x_90".to_owned(), "rot_z_180".to_owned(), "scale_0.1".to_owned()]]);
+        assert_eq!(crate_mod.uvx, vec![vec!["flip_y".to_owned()]]);
+        match &res[3] {
+            Err(MMObjError::IOError(_, _)) => {},
+            r => panic!("expected io error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_groups_and_blend() {
+        // no trailing newline on purpose; crlf line endings
+        let text = "#vgn Bone0\r\n#vgn Bone1\r\nv 0 0 0\r\nv 1 0 0\r\nv 0 1 0\r\n\
+            #vg 0\r\n#vg 0 1\r\n#vg 1\r\n\
+            #vbld 0/1.0 1/0.0\r\n#vbld 0/0.5 1/0.5\r\n#vbld 1/1.0 0/0.0\r\n\
+            vt 0 0\r\nvn 0 0 1\r\n# v 1 2 3 comment line, ignored\r\nf 1/1/1 2/1/1 3/1/1";
+        let mmobj = parse_mmobj("inline", text).expect("parse failed");
+        assert_eq!(mmobj.positions.len(), 3);
+        assert_eq!(mmobj.vgroup_names, vec!["Bone0".to_owned(), "Bone1".to_owned()]);
+        assert_eq!(mmobj.vgroup_lists, vec![vec![0], vec![0, 1], vec![1]]);
+        assert_eq!(mmobj.vblend[1], vec![BlendPair { idx: 0, weight: 0.5 }, BlendPair { idx: 1, weight: 0.5 }]);
+        assert_eq!(mmobj.faces.len(), 1);
+        assert_eq!(mmobj.faces[0][2], FaceVert { pos: 2, tex: 0, nrm: 0 });
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        match parse_mmobj("bad", "v 0 0 0\nv 1 0\n") {
+            Err(MMObjError::ParseError(f, line, _)) => {
+                assert_eq!(f, "bad");
+                assert_eq!(line, 2);
+            },
+            r => panic!("expected parse error, got {:?}", r),
+        }
+        match parse_mmobj("bad", "v 0 0 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 1/1/1 0/1/1\n") {
+            Err(MMObjError::ParseError(_, line, _)) => assert_eq!(line, 4),
+            r => panic!("expected parse error, got {:?}", r),
+        }
+        match parse_mmobj("bad", "v 0 0 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 1/1/1 1/1/1 1/1/1\n") {
+            Err(MMObjError::ParseError(_, line, _)) => assert_eq!(line, 4),
+            r => panic!("expected parse error, got {:?}", r),
+        }
+        match parse_mmobj("bad", "v 0 0 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 1/2/1 1/1/1\n") {
+            Err(MMObjError::BadIndex(_, face, msg)) => {
+                assert_eq!(face, 0);
+                assert!(msg.contains("texcoord"), "{}", msg);
+            },
+            r => panic!("expected index error, got {:?}", r),
+        }
+        match parse_mmobj("bad", "#vgn Bone0\nv 0 0 0\n#vg 3\n") {
+            Err(MMObjError::BadIndex(_, _, msg)) => assert!(msg.contains("vertex group"), "{}", msg),
+            r => panic!("expected index error, got {:?}", r),
+        }
+    }
+}
@@ -53,6 +53,8 @@ pub fn decode_packed_vector(a: i16, b: i16) -> (f32, f32, f32) {
 /// more closely matches the sample shader I gave them, but when I try to use it
 /// the results are much worse (in particular it seems to flip the handedness
 /// of the coordinate system on a per-vert basis as shown by the debug )
+/// Update: this was storing the z sign bit inverted relative to `decode_octa_vector` (and
+/// `OctaV1` in the managed code), which would explain the flipping.
 pub fn encode_octa_vector(v: &Float3) -> (i16, i16) {
     //--- 1. normalise (defensive) ------------------------------------------
     let len = (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
@@ -75,7 +77,7 @@ pub fn encode_octa_vector(v: &Float3) -> (i16, i16) {
         (((c * 0.5 + 0.5) * FRAC_SCALE).round()) as i32
     };
 
-    let sign_bit = if v.z >= 0.0 { 0 } else { 1 };   // 0 or 1
+    let sign_bit = if v.z >= 0.0 { 1 } else { 0 };   // 1 => +z hemisphere
     let raw0 = sign_bit * SHIFT + to_frac15(x) - SHIFT;
     let raw1 =                /* sign bit not stored here */ to_frac15(y) - SHIFT;
 
@@ -110,3 +112,403 @@ pub fn decode_octa_vector(a: i16, b: i16) -> (f32, f32, f32) {
 
     (x, y, z)
 }
+
+fn snorm16(v: i16) -> f32 {
+    (v as f32 / 32767.0).max(-1.0)
+}
+
+fn to_snorm16(f: f32) -> i16 {
+    (f.clamp(-1.0, 1.0) * 32767.0).round() as i16
+}
+
+fn sign_not_zero(f: f32) -> f32 {
+    if f >= 0.0 { 1.0 } else { -1.0 }
+}
+
+/// Project onto the octahedron and fold the lower hemisphere into the corners, result is in
+/// -1..1.
+fn octa_fold(v: &Float3) -> (f32, f32) {
+    let l1 = v.x.abs() + v.y.abs() + v.z.abs();
+    let (x, y) = (v.x / l1, v.y / l1);
+    if v.z < 0.0 {
+        ((1.0 - y.abs()) * sign_not_zero(x), (1.0 - x.abs()) * sign_not_zero(y))
+    } else {
+        (x, y)
+    }
+}
+
+fn octa_unfold(mut x: f32, mut y: f32) -> (f32, f32, f32) {
+    let z = 1.0 - x.abs() - y.abs();
+    if z < 0.0 {
+        let (ox, oy) = (x, y);
+        x = (1.0 - oy.abs()) * sign_not_zero(ox);
+        y = (1.0 - ox.abs()) * sign_not_zero(oy);
+    }
+    let len = (x * x + y * y + z * z).sqrt();
+    (x / len, y / len, z / len)
+}
+
+/// Standard octahedral encoding with each coordinate stored as a snorm16 (as described in
+/// "A Survey of Efficient Representations for Independent Unit Vectors", Cigolle et al).  Unlike
+/// `decode_octa_vector` there is no sign bit, the lower hemisphere is folded into the corners
+/// of the square.
+pub fn encode_octa_snorm16_vector(v: &Float3) -> (i16, i16) {
+    let (x, y) = octa_fold(v);
+    (to_snorm16(x), to_snorm16(y))
+}
+
+pub fn decode_octa_snorm16_vector(a: i16, b: i16) -> (f32, f32, f32) {
+    octa_unfold(snorm16(a), snorm16(b))
+}
+
+/// Octahedral encoding in two unorm8s, for games that declare the normal as
+/// `R16G16B16A16_SINT` but only use the low 16 bits of the first component, unpacking the two
+/// bytes in the shader (e.g. `uint p = asuint(n.x); float2 e = float2(p & 0xff, (p >> 8) & 0xff) / 255.0`).
+/// x goes in the low byte, y in the high byte; the second i16 is unused and written as 0.  This
+/// is not for actual `R8G8_UNORM` normal elements, the managed code can't write those.
+pub fn encode_octa_unorm8_vector(v: &Float3) -> (i16, i16) {
+    let (x, y) = octa_fold(v);
+    let to_unorm8 = |c: f32| ((c * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0).round() as u16;
+    ((to_unorm8(x) | to_unorm8(y) << 8) as i16, 0)
+}
+
+pub fn decode_octa_unorm8_vector(a: i16, _b: i16) -> (f32, f32, f32) {
+    let a = a as u16;
+    let from_unorm8 = |c: u16| (c & 0xff) as f32 / 255.0 * 2.0 - 1.0;
+    octa_unfold(from_unorm8(a), from_unorm8(a >> 8))
+}
+
+/// x and y stored as snorm16s, z reconstructed as positive.  Only usable for vectors known to
+/// point towards +z, such as tangent space normals; others come back mirrored to +z.
+pub fn encode_xy_snorm16_vector(v: &Float3) -> (i16, i16) {
+    let len = (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+    (to_snorm16(v.x / len), to_snorm16(v.y / len))
+}
+
+pub fn decode_xy_snorm16_vector(a: i16, b: i16) -> (f32, f32, f32) {
+    let (x, y) = (snorm16(a), snorm16(b));
+    let z = (1.0 - (x * x + y * y)).max(0.0).sqrt();
+    (x, y, z)
+}
+
+/// Spheremap (Lambert azimuthal equal-area) encoding, as used by some deferred renderers, with
+/// the two 0..1 values stored as snorm16s of -1..1.  Precision is worst around -z, which maps to
+/// the edge of the circle.
+pub fn encode_spheremap_vector(v: &Float3) -> (i16, i16) {
+    let len = (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+    let (x, y, z) = (v.x / len, v.y / len, v.z / len);
+    let f = (8.0 * z + 8.0).sqrt();
+    // -z itself is a point on the edge, any will do
+    let (ex, ey) = if f > 1e-6 { (x / f + 0.5, y / f + 0.5) } else { (1.0, 0.5) };
+    (to_snorm16(ex * 2.0 - 1.0), to_snorm16(ey * 2.0 - 1.0))
+}
+
+pub fn decode_spheremap_vector(a: i16, b: i16) -> (f32, f32, f32) {
+    // snorm back to 0..1, then to -2..2
+    let fx = (snorm16(a) * 0.5 + 0.5) * 4.0 - 2.0;
+    let fy = (snorm16(b) * 0.5 + 0.5) * 4.0 - 2.0;
+    let f = (fx * fx + fy * fy).min(4.0);
+    let g = (1.0 - f / 4.0).sqrt();
+    (fx * g, fy * g, 1.0 - f / 2.0)
+}
+
+/// Smallest |w| written by `encode_qtangent`, so that the sign of w (which holds the handedness)
+/// survives quantization.
+const QTANGENT_BIAS: f32 = 1.0 / 32767.0;
+
+/// QTangent encoding ("Spherical Skinning with Dual-Quaternions and QTangents", Frey &
+/// Herzeg): the whole tangent frame as a quaternion in 4 snorm16s (x, y, z, w), with the
+/// handedness of the bitangent in the sign of w.  The rotation maps x to the tangent and z to the
+/// normal.  The tangent is orthogonalized against the normal, only the sign of the bitangent is
+/// kept.
+pub fn encode_qtangent(n: &Float3, t: &Float3, b: &Float3) -> [i16; 4] {
+    // degenerate input (e.g. the zero normal of an unused vertex) still gets a valid frame
+    let n = n.normalized().unwrap_or(Float3::new(0.0, 0.0, 1.0));
+    let t = t.sub(&n.scale(n.dot(t))).normalized()
+        .or_else(|| n.cross(&Float3::new(0.0, 1.0, 0.0)).normalized())
+        .unwrap_or(Float3::new(1.0, 0.0, 0.0));
+    let bt = n.cross(&t);
+    // m[row][col], columns are t, bt, n
+    let m = [[t.x, bt.x, n.x], [t.y, bt.y, n.y], [t.z, bt.z, n.z]];
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let (mut x, mut y, mut z, mut w);
+    if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        w = s / 4.0;
+        x = (m[2][1] - m[1][2]) / s;
+        y = (m[0][2] - m[2][0]) / s;
+        z = (m[1][0] - m[0][1]) / s;
+    } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+        let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+        w = (m[2][1] - m[1][2]) / s;
+        x = s / 4.0;
+        y = (m[0][1] + m[1][0]) / s;
+        z = (m[0][2] + m[2][0]) / s;
+    } else if m[1][1] > m[2][2] {
+        let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+        w = (m[0][2] - m[2][0]) / s;
+        x = (m[0][1] + m[1][0]) / s;
+        y = s / 4.0;
+        z = (m[1][2] + m[2][1]) / s;
+    } else {
+        let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+        w = (m[1][0] - m[0][1]) / s;
+        x = (m[0][2] + m[2][0]) / s;
+        y = (m[1][2] + m[2][1]) / s;
+        z = s / 4.0;
+    }
+    // q and -q are the same rotation, so w can be made positive
+    if w < 0.0 {
+        (x, y, z, w) = (-x, -y, -z, -w);
+    }
+    if w < QTANGENT_BIAS {
+        let scale = (1.0 - QTANGENT_BIAS * QTANGENT_BIAS).sqrt();
+        (x, y, z, w) = (x * scale, y * scale, z * scale, QTANGENT_BIAS);
+    }
+    if bt.dot(b) < 0.0 {
+        (x, y, z, w) = (-x, -y, -z, -w);
+    }
+    [to_snorm16(x), to_snorm16(y), to_snorm16(z), to_snorm16(w)]
+}
+
+/// Returns (normal, tangent, bitangent).  Only the tests need this for now.
+#[cfg(test)]
+pub fn decode_qtangent(q: [i16; 4]) -> (Float3, Float3, Float3) {
+    let [x, y, z, w] = q.map(snorm16);
+    let len = (x * x + y * y + z * z + w * w).sqrt();
+    let (x, y, z, w) = (x / len, y / len, z / len, w / len);
+    let t = Float3::new(1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + w * z), 2.0 * (x * z - w * y));
+    let n = Float3::new(2.0 * (x * z + w * y), 2.0 * (y * z - w * x), 1.0 - 2.0 * (x * x + y * y));
+    let b = n.cross(&t);
+    let b = if w < 0.0 { b.neg() } else { b };
+    (n, t, b)
+}
+
+/// The encodings that can be named in `ModSnapProfile.vec_encoding`.  These only apply to the
+/// 16 bit int formats (`R16G16B16A16_SINT` holding normal and tangent, `R16G16_SINT` holding
+/// the bitangent); other formats are handled by `vertex_codec`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VecEncoding {
+    Packed,
+    Octa,
+    OctaSnorm16,
+    OctaUnorm8,
+    Spheremap,
+    /// Only for vectors that point towards +z, see `encode_xy_snorm16_vector`
+    XySnorm16,
+    /// Whole tangent frame in the 4 i16s of the normal element
+    QTangent,
+}
+
+impl VecEncoding {
+    pub const ALL: [VecEncoding; 7] = [VecEncoding::Packed, VecEncoding::Octa, VecEncoding::OctaSnorm16,
+        VecEncoding::OctaUnorm8, VecEncoding::Spheremap, VecEncoding::XySnorm16, VecEncoding::QTangent];
+
+    /// Parse a profile name, ignoring case and surrounding whitespace.
+    pub fn from_name(name: &str) -> Option<VecEncoding> {
+        let name = name.trim().to_lowercase();
+        VecEncoding::ALL.iter().copied().find(|e| e.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VecEncoding::Packed => "packed",
+            VecEncoding::Octa => "octa",
+            VecEncoding::OctaSnorm16 => "octa_snorm16",
+            VecEncoding::OctaUnorm8 => "octa_unorm8",
+            VecEncoding::Spheremap => "spheremap",
+            VecEncoding::XySnorm16 => "xy_snorm16",
+            VecEncoding::QTangent => "qtangent",
+        }
+    }
+
+    /// True if the encoding stores the whole tangent frame rather than one vector per pair of
+    /// i16s.
+    pub fn is_frame(&self) -> bool {
+        *self == VecEncoding::QTangent
+    }
+
+    /// The encoding the managed code writes vectors with when this one is selected; it only
+    /// knows packed and octa, anything else has to be re-encoded by native code.
+    pub fn managed(&self) -> VecEncoding {
+        match self {
+            VecEncoding::Octa => VecEncoding::Octa,
+            _ => VecEncoding::Packed,
+        }
+    }
+
+    /// Encode one vector into a pair of i16s.  For `QTangent` this is the packed encoding, use
+    /// `encode_qtangent` for the frame.
+    pub fn encode(&self, v: &Float3) -> (i16, i16) {
+        match self {
+            VecEncoding::Packed | VecEncoding::QTangent => encode_packed_vector(v),
+            VecEncoding::Octa => encode_octa_vector(v),
+            VecEncoding::OctaSnorm16 => encode_octa_snorm16_vector(v),
+            VecEncoding::OctaUnorm8 => encode_octa_unorm8_vector(v),
+            VecEncoding::Spheremap => encode_spheremap_vector(v),
+            VecEncoding::XySnorm16 => encode_xy_snorm16_vector(v),
+        }
+    }
+
+    /// Inverse of `encode`.
+    pub fn decode(&self, a: i16, b: i16) -> (f32, f32, f32) {
+        match self {
+            VecEncoding::Packed | VecEncoding::QTangent => decode_packed_vector(a, b),
+            VecEncoding::Octa => decode_octa_vector(a, b),
+            VecEncoding::OctaSnorm16 => decode_octa_snorm16_vector(a, b),
+            VecEncoding::OctaUnorm8 => decode_octa_unorm8_vector(a, b),
+            VecEncoding::Spheremap => decode_spheremap_vector(a, b),
+            VecEncoding::XySnorm16 => decode_xy_snorm16_vector(a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_vectors() -> Vec<Float3> {
+        // fibonacci sphere plus the axes and some diagonals, which are edge cases for the folds
+        let count = 500;
+        let golden = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+        let mut out: Vec<Float3> = (0..count).map(|i| {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let r = (1.0 - z * z).sqrt();
+            let phi = golden * i as f32;
+            Float3::new(r * phi.cos(), r * phi.sin(), z)
+        }).collect();
+        for s in [1.0, -1.0] {
+            out.extend([Float3::new(s, 0.0, 0.0), Float3::new(0.0, s, 0.0), Float3::new(0.0, 0.0, s)]);
+            out.push(Float3::new(s, s, s).normalized().unwrap());
+            out.push(Float3::new(s, -s, 0.0).normalized().unwrap());
+            out.push(Float3::new(0.0, s, -s).normalized().unwrap());
+        }
+        out
+    }
+
+    fn pair_round_trip(e: VecEncoding, v: &Float3) -> Float3 {
+        let (a, b) = e.encode(v);
+        let (x, y, z) = e.decode(a, b);
+        Float3::new(x, y, z)
+    }
+
+    /// Right handed frame built from a normal, or left handed if `flip`
+    fn frame(n: &Float3, flip: bool) -> (Float3, Float3, Float3) {
+        let up = if n.z.abs() < 0.9 { Float3::new(0.0, 0.0, 1.0) } else { Float3::new(1.0, 0.0, 0.0) };
+        let t = up.cross(n).normalized().unwrap();
+        let b = n.cross(&t);
+        let b = if flip { b.neg() } else { b };
+        (*n, t, b)
+    }
+
+    fn handedness(n: &Float3, t: &Float3, b: &Float3) -> f32 {
+        n.cross(t).dot(b).signum()
+    }
+
+    #[test]
+    fn test_names() {
+        for e in VecEncoding::ALL {
+            assert_eq!(VecEncoding::from_name(e.name()), Some(e));
+        }
+        assert_eq!(VecEncoding::from_name(" QTangent "), Some(VecEncoding::QTangent));
+        assert_eq!(VecEncoding::from_name("octa_snorm8"), None);
+        assert_eq!(VecEncoding::from_name(""), None);
+        assert_eq!(VecEncoding::Octa.managed(), VecEncoding::Octa);
+        assert_eq!(VecEncoding::Spheremap.managed(), VecEncoding::Packed);
+        assert_eq!(VecEncoding::from_name("xy_snorm16"), Some(VecEncoding::XySnorm16));
+    }
+
+    #[test]
+    fn test_pair_round_trips() {
+        for (e, min_dot) in [(VecEncoding::Packed, 0.9999), (VecEncoding::Octa, 0.9999),
+            (VecEncoding::OctaSnorm16, 0.99999), (VecEncoding::OctaUnorm8, 0.9995),
+            (VecEncoding::Spheremap, 0.9999), (VecEncoding::XySnorm16, 0.9999)] {
+            // xy_snorm16 only keeps +z vectors
+            for v in unit_vectors().into_iter().filter(|v| e != VecEncoding::XySnorm16 || v.z >= 0.0) {
+                let d = pair_round_trip(e, &v);
+                assert!((d.length() - 1.0).abs() < 1e-3, "{:?} {:?} -> {:?}", e, v, d);
+                // spheremap loses precision approaching -z, where the circle's edge is
+                let min_dot = if e == VecEncoding::Spheremap && v.z < -0.99 { 0.99 } else { min_dot };
+                assert!(v.dot(&d) > min_dot, "{:?} {:?} -> {:?}", e, v, d);
+            }
+        }
+        // -z is a singularity for spheremap, but still decodes
+        let d = pair_round_trip(VecEncoding::Spheremap, &Float3::new(0.0, 0.0, -1.0));
+        assert!(d.z < -0.9999, "{:?}", d);
+        // and -z vectors come back mirrored from xy_snorm16
+        let d = pair_round_trip(VecEncoding::XySnorm16, &Float3::new(0.6, 0.0, -0.8));
+        assert!((d.x - 0.6).abs() < 1e-4 && (d.z - 0.8).abs() < 1e-4, "{:?}", d);
+    }
+
+    #[test]
+    fn test_octa_sign() {
+        // the z sign bit must agree with the decoder (and the managed OctaV1)
+        let (a, _) = encode_octa_vector(&Float3::new(0.0, 0.0, 1.0));
+        assert!(a >= 0);
+        let (a, _) = encode_octa_vector(&Float3::new(0.0, 0.0, -1.0));
+        assert!(a < 0);
+    }
+
+    #[test]
+    fn test_octa_unorm8_bytes() {
+        // +z is the center of the square
+        let (a, b) = encode_octa_unorm8_vector(&Float3::new(0.0, 0.0, 1.0));
+        assert_eq!((a as u16, b), (0x8080, 0));
+        // +x is the right edge, the second i16 is ignored
+        let (a, _) = encode_octa_unorm8_vector(&Float3::new(1.0, 0.0, 0.0));
+        assert_eq!(a as u16 & 0xff, 0xff);
+        let (x, _, _) = decode_octa_unorm8_vector(a, 1234);
+        assert!((x - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_pair_handedness() {
+        // a frame of +z vectors only exists along +z, so xy_snorm16 can't keep handedness
+        for e in VecEncoding::ALL.iter().filter(|e| !e.is_frame() && **e != VecEncoding::XySnorm16) {
+            for v in unit_vectors() {
+                for flip in [false, true] {
+                    let (n, t, b) = frame(&v, flip);
+                    let (dn, dt, db) = (pair_round_trip(*e, &n), pair_round_trip(*e, &t), pair_round_trip(*e, &b));
+                    assert_eq!(handedness(&dn, &dt, &db), handedness(&n, &t, &b), "{:?} {:?}", e, v);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_qtangent_round_trip() {
+        for v in unit_vectors() {
+            for flip in [false, true] {
+                let (n, t, b) = frame(&v, flip);
+                let (dn, dt, db) = decode_qtangent(encode_qtangent(&n, &t, &b));
+                assert!(n.dot(&dn) > 0.9999, "{:?} -> {:?}", n, dn);
+                assert!(t.dot(&dt) > 0.9999, "{:?} -> {:?}", t, dt);
+                assert!(b.dot(&db) > 0.9999, "{:?} -> {:?}", b, db);
+                assert_eq!(handedness(&dn, &dt, &db), if flip { -1.0 } else { 1.0 });
+            }
+        }
+    }
+
+    #[test]
+    fn test_qtangent_handedness_at_zero_w() {
+        // 180 degree rotation about x: w is 0, so without the bias the sign would be lost
+        let (n, t) = (Float3::new(0.0, 0.0, -1.0), Float3::new(1.0, 0.0, 0.0));
+        for b in [Float3::new(0.0, -1.0, 0.0), Float3::new(0.0, 1.0, 0.0)] {
+            let q = encode_qtangent(&n, &t, &b);
+            assert_ne!(q[3], 0);
+            let (dn, dt, db) = decode_qtangent(q);
+            assert!(n.dot(&dn) > 0.9999 && t.dot(&dt) > 0.9999, "{:?} {:?}", dn, dt);
+            assert!(b.dot(&db) > 0.9999, "{:?} -> {:?}", b, db);
+        }
+    }
+
+    #[test]
+    fn test_qtangent_orthogonalizes() {
+        // tangent not perpendicular to the normal, and an unnormalized bitangent
+        let n = Float3::new(0.0, 0.0, 1.0);
+        let (dn, dt, db) = decode_qtangent(encode_qtangent(&n, &Float3::new(1.0, 0.0, 0.5), &Float3::new(0.0, -3.0, 0.0)));
+        assert!(dn.dot(&n) > 0.9999);
+        assert!(dt.dot(&Float3::new(1.0, 0.0, 0.0)) > 0.9999, "{:?}", dt);
+        assert!(db.dot(&Float3::new(0.0, -1.0, 0.0)) > 0.9999, "{:?}", db);
+    }
+}
@@ -0,0 +1,244 @@
+//! Guesses which vector encoding a game uses for normals stored as pairs of i16s (the first two
+//! components of an `R16G16B16A16_SINT` normal, or an `R16G16_SINT` element), so that
+//! `ModSnapProfile.vec_encoding` doesn't have to be found by trial and error.
+//!
+//! Each candidate decoding is scored on snapshotted vertex data by:
+//!  * unit length error: some encodings reconstruct z from x and y, and if the pair wasn't
+//!    written by that encoding x and y often don't fit in a unit vector.
+//!  * neighbour coherence: vertices that share an edge of the mesh have similar normals in real
+//!    models; the wrong decoding scatters them.
+//!  * face agreement (if positions are available): normals point the same way as the faces
+//!    around them.  Since the winding isn't known, only the size of the average is used.
+//!
+//! Lower scores are better.
+
+use std::collections::HashSet;
+
+use crate::data_encoding::VecEncoding;
+use crate::mod_vector::Float3;
+
+/// Candidate encodings: everything that can be selected in a profile and stores one vector per
+/// pair.  The frame encodings need all four components of the normal, so they can't be told
+/// apart from pairs.
+fn candidates() -> impl Iterator<Item = VecEncoding> {
+    VecEncoding::ALL.into_iter().filter(|e| !e.is_frame())
+}
+
+/// Limit on how many edges are checked, so that huge meshes don't stall the snapshot
+const MAX_EDGES: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodingScore {
+    pub name: &'static str,
+    /// Mean of `| |v| - 1 |` over the decoded vectors
+    pub unit_error: f32,
+    /// Mean dot product of the normals at either end of each edge, -1..1
+    pub coherence: f32,
+    /// Absolute value of the mean dot product of each normal with its faces' normals, 0..1; None
+    /// if positions weren't given.
+    pub face_agreement: Option<f32>,
+    pub score: f32,
+}
+
+/// Triangles of the mesh: from `indices` if given, otherwise consecutive vertices.  Triangles
+/// that reference missing vertices are dropped.
+fn triangles(vert_count: usize, indices: Option<&[u32]>) -> Vec<[usize; 3]> {
+    let seq: Vec<u32>;
+    let indices = match indices {
+        Some(indices) => indices,
+        None => {
+            seq = (0..vert_count as u32).collect();
+            &seq
+        }
+    };
+    indices.chunks_exact(3)
+        .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+        .filter(|t| t.iter().all(|&i| i < vert_count))
+        .collect()
+}
+
+/// Unique edges of the triangles, up to `MAX_EDGES`.
+fn edges(tris: &[[usize; 3]]) -> Vec<(usize, usize)> {
+    let mut seen = HashSet::new();
+    let mut out = vec![];
+    for t in tris {
+        for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+            let e = (a.min(b), a.max(b));
+            if e.0 != e.1 && seen.insert(e) {
+                out.push(e);
+                if out.len() >= MAX_EDGES {
+                    return out;
+                }
+            }
+        }
+    }
+    out
+}
+
+fn face_normal(positions: &[[f32; 3]], t: &[usize; 3]) -> Option<Float3> {
+    let p = |i: usize| Float3::from(positions[t[i]]);
+    let (p0, p1, p2) = (p(0), p(1), p(2));
+    p1.sub(&p0).cross(&p2.sub(&p0)).normalized()
+}
+
+/// Score every candidate encoding against the normal `pairs` of a mesh, best first.
+/// `positions` must have the same length as `pairs` if given.  Returns an empty list if there
+/// are no pairs.
+pub fn score_vec_encodings(pairs: &[(i16, i16)], indices: Option<&[u32]>, positions: Option<&[[f32; 3]]>)
+    -> Vec<EncodingScore> {
+    if pairs.is_empty() {
+        return vec![];
+    }
+    let positions = positions.filter(|p| p.len() == pairs.len());
+    let tris = triangles(pairs.len(), indices);
+    let edges = edges(&tris);
+    let faces: Vec<([usize; 3], Float3)> = match positions {
+        Some(positions) => tris.iter()
+            .filter_map(|t| face_normal(positions, t).map(|n| (*t, n)))
+            .take(MAX_EDGES)
+            .collect(),
+        None => vec![],
+    };
+
+    let mut scores: Vec<EncodingScore> = candidates().map(|e| {
+        let name = e.name();
+        let decoded: Vec<Float3> = pairs.iter().map(|&(a, b)| Float3::from(e.decode(a, b))).collect();
+        let unit_error = decoded.iter()
+            .map(|v| (v.length() - 1.0).abs())
+            .sum::<f32>() / decoded.len() as f32;
+        let unit: Vec<Option<Float3>> = decoded.iter().map(|v| v.normalized()).collect();
+
+        let mut sum = 0.0;
+        let mut count = 0;
+        for &(a, b) in edges.iter() {
+            if let (Some(na), Some(nb)) = (unit[a], unit[b]) {
+                sum += na.dot(&nb);
+                count += 1;
+            }
+        }
+        // with no edges there's no information, treat it as neutral
+        let coherence = if count > 0 { sum / count as f32 } else { 0.0 };
+
+        let face_agreement = positions.map(|_| {
+            let mut sum = 0.0;
+            let mut count = 0;
+            for (t, fnorm) in faces.iter() {
+                for &v in t {
+                    if let Some(n) = unit[v] {
+                        sum += n.dot(fnorm);
+                        count += 1;
+                    }
+                }
+            }
+            if count > 0 { (sum / count as f32).abs() } else { 0.0 }
+        });
+
+        let mut score = unit_error + (1.0 - coherence) * 0.5;
+        if let Some(fa) = face_agreement {
+            score += 1.0 - fa;
+        }
+        EncodingScore { name, unit_error, coherence, face_agreement, score }
+    }).collect();
+    scores.sort_by(|a, b| a.score.total_cmp(&b.score));
+    scores
+}
+
+/// The best scoring encoding for the mesh, if there is any data.
+pub fn detect_vec_encoding(pairs: &[(i16, i16)], indices: Option<&[u32]>, positions: Option<&[[f32; 3]]>)
+    -> Option<EncodingScore> {
+    score_vec_encodings(pairs, indices, positions).into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_encoding::{encode_octa_snorm16_vector, encode_octa_unorm8_vector, encode_octa_vector,
+        encode_packed_vector, encode_spheremap_vector, encode_xy_snorm16_vector};
+    use crate::mod_vector::Float3;
+
+    /// Latitude/longitude sphere (or just the +z half) with shared vertices; returns
+    /// (positions, normals, indices)
+    fn sphere(rings: usize, segments: usize, hemisphere: bool) -> (Vec<[f32; 3]>, Vec<Float3>, Vec<u32>) {
+        let mut pos = vec![];
+        let mut nrm = vec![];
+        let max_theta = if hemisphere { std::f32::consts::FRAC_PI_2 } else { std::f32::consts::PI };
+        for r in 0..=rings {
+            let theta = max_theta * r as f32 / rings as f32;
+            for s in 0..=segments {
+                let phi = 2.0 * std::f32::consts::PI * s as f32 / segments as f32;
+                let n = Float3 { x: theta.sin() * phi.cos(), y: theta.sin() * phi.sin(), z: theta.cos() };
+                pos.push([n.x * 2.0, n.y * 2.0, n.z * 2.0]);
+                nrm.push(n);
+            }
+        }
+        let mut idx = vec![];
+        let row = segments as u32 + 1;
+        for r in 0..rings as u32 {
+            for s in 0..segments as u32 {
+                let a = r * row + s;
+                idx.extend_from_slice(&[a, a + row, a + 1, a + 1, a + row, a + row + 1]);
+            }
+        }
+        (pos, nrm, idx)
+    }
+
+    fn check_detects(name: &str, encode: fn(&Float3) -> (i16, i16), hemisphere: bool) {
+        let (pos, nrm, idx) = sphere(16, 24, hemisphere);
+        let pairs: Vec<(i16, i16)> = nrm.iter().map(encode).collect();
+        // with and without the positions
+        for positions in [Some(&pos[..]), None] {
+            let scores = score_vec_encodings(&pairs, Some(&idx), positions);
+            assert_eq!(scores[0].name, name, "{:#?}", scores);
+            assert!(scores[0].coherence > 0.9, "{:#?}", scores);
+            assert!(scores[0].unit_error < 1e-3, "{:#?}", scores);
+            // whatever is reported can be used in a profile
+            assert!(scores.iter().all(|s| VecEncoding::from_name(s.name).is_some()));
+        }
+    }
+
+    #[test]
+    fn test_detect_packed() {
+        check_detects("packed", encode_packed_vector, false);
+    }
+
+    #[test]
+    fn test_detect_octa() {
+        check_detects("octa", encode_octa_vector, false);
+    }
+
+    #[test]
+    fn test_detect_octa_snorm16() {
+        check_detects("octa_snorm16", encode_octa_snorm16_vector, false);
+    }
+
+    #[test]
+    fn test_detect_octa_unorm8() {
+        check_detects("octa_unorm8", encode_octa_unorm8_vector, false);
+    }
+
+    #[test]
+    fn test_detect_spheremap() {
+        check_detects("spheremap", encode_spheremap_vector, false);
+    }
+
+    #[test]
+    fn test_detect_xy_snorm16() {
+        check_detects("xy_snorm16", encode_xy_snorm16_vector, true);
+    }
+
+    #[test]
+    fn test_unindexed_and_empty() {
+        assert!(detect_vec_encoding(&[], None, None).is_none());
+        // triangle list without indices; a flat patch facing +z, so every decoding that
+        // puts it on the z axis agrees, but the packed scheme must still be among the best
+        let n = Float3 { x: 0.0, y: 0.0, z: 1.0 };
+        let pairs = vec![encode_packed_vector(&n); 6];
+        let scores = score_vec_encodings(&pairs, None, None);
+        assert_eq!(scores.len(), candidates().count());
+        let packed = scores.iter().find(|s| s.name == "packed").unwrap();
+        assert!(packed.unit_error < 1e-3 && packed.coherence > 0.999, "{:#?}", scores);
+        // out of range indices are ignored
+        let scores = score_vec_encodings(&pairs, Some(&[0, 1, 99]), None);
+        assert_eq!(scores[0].coherence, 0.0);
+    }
+}
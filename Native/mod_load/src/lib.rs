@@ -6,5 +6,10 @@
 mod mod_load;
 mod mod_vector;
 mod data_encoding;
+mod encoding_detect;
+pub use crate::encoding_detect::{detect_vec_encoding, score_vec_encodings, EncodingScore};
+mod tangent_space;
+mod native_mesh;
 pub use crate::mod_load::*;
-mod load_thread;
\ No newline at end of file
+mod load_thread;
+pub use crate::load_thread::{load_progress, LoadProgress};
\ No newline at end of file
@@ -1,27 +1,27 @@
-//! This module provides a load thread that mod_load can use to load resources on the device if it supports multithreading;
+//! This module provides load threads that mod_load can use to load resources on the device if it supports multithreading;
 //! currently only d3d11 devices (though I think d3d9 could support it, it wasn't the default unlike d3d11).
 //! This avoids blocking the main render thread.  Without this there is a pause when resources are finally loaded onto the
 //! device, especially if tangent space also needs updating, which can be quite long if the system is slow or running in 
 //! low power mode (such as my laptop, which I underclock so that
 //! I only get nearly 1st degree burns on my legs).
 //! 
-//! The load thread only loads one resource at a time.  It could do more, but there is some bad mojo around the device 
-//! reference count that I made an attempt at preserving (and that probably isn't even correct on a multithreaded device).
-//! Making it able to load more than one at a time also probaby involves looking at the managed code to make sure it can handle 
-//! more than one thread in there.
+//! Loads run on a small pool of threads so that a crowded area with many mods doesn't wait on each of them in turn.
+//! Pending loads are kept in a queue ordered by `NativeModData.last_frame_render`, so the mods that were drawn most
+//! recently get loaded first; if a queued mod is drawn again its priority is raised.  The managed fill callback isn't
+//! thread safe so calls to it are serialized (see `FILL_LOCK` in mod_load), the rest of the load (tangent space,
+//! creating the device resources) runs concurrently.  The device reference count bookkeeping is done over a whole batch
+//! of overlapping loads since the per-load before/after counts would pick up the other loads' resources.
 //! 
 //! Most of this code was initially generated by LLM (openai o3, though I used 4o for some helpers).  Which explains some of
 //! the inane comments.  Other inane comments are ones I wrote.  
 //! I did however come up with the scheme of cloning the native mod data and sending 
-//! it over a channel to the thread, then overwriting the same entry for it in the mod database after load, 
+//! it to the thread, then overwriting the same entry for it in the mod database after load, 
 //! which avoids the need for the load thread to lock the entire mod database when load completes.
 
 use std::{
     collections::{hash_map::Entry, HashMap},
-    sync::{
-        mpsc::{self, Sender},
-        Arc, LazyLock, Mutex, RwLock,
-    },
+    fmt,
+    sync::{Arc, Condvar, LazyLock, Mutex, Once, RwLock},
     thread,
 };
 
@@ -35,11 +35,50 @@ use crate::load_d3d_data11;
 /// ─────────────────────────────── core types ──────────────────────────────
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ResourceState {
+    /// Waiting in the queue for a load thread
+    Queued,
     Loading,
     Loaded,
     Error(String),
 }
 
+/// Counts of the resources in each state, for reporting load progress.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub queued: usize,
+    pub loading: usize,
+    pub loaded: usize,
+    pub errors: usize,
+}
+
+impl ResourceState {
+    pub fn progress<'a>(states: impl Iterator<Item = &'a ResourceState>) -> LoadProgress {
+        let mut p = LoadProgress::default();
+        for s in states {
+            match s {
+                ResourceState::Queued => p.queued += 1,
+                ResourceState::Loading => p.loading += 1,
+                ResourceState::Loaded => p.loaded += 1,
+                ResourceState::Error(_) => p.errors += 1,
+            }
+        }
+        p
+    }
+}
+
+impl LoadProgress {
+    pub fn total(&self) -> usize {
+        self.queued + self.loading + self.loaded + self.errors
+    }
+}
+
+impl fmt::Display for LoadProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} loaded, {} loading, {} queued, {} errors", self.loaded, self.total(), self.loading,
+            self.queued, self.errors)
+    }
+}
+
 type TableInner = Mutex<HashMap<i32, ResourceState>>;
 type TableHandle = Arc<TableInner>;
 
@@ -74,46 +113,158 @@ struct LoadMsg {
 }
 unsafe impl Send for LoadMsg {}
 
-/// ───────────────── loader thread & queue initialisation ──────────────────
-static LOAD_SENDER: LazyLock<Sender<LoadMsg>> = LazyLock::new(|| {
-    let (tx, rx) = mpsc::channel::<LoadMsg>();
-
-    // Dedicated loader thread.
-    thread::spawn(move || {
-        while let Ok(msg) = rx.recv() {
-            let id = msg.nmod.midx;
-            // Always use the table that is current *now*.
-            let table = current_table();
-            match load_resource(msg) {
-                Ok(()) => match update_state(&table, id, ResourceState::Loaded) {
+/// Upper limit on load threads; each one holds a copy of the mod's data while loading it, and past a few the
+/// serialized managed fill is the bottleneck anyway.
+const MAX_LOAD_THREADS: usize = 4;
+
+struct QueueEntry<T> {
+    id: i32,
+    priority: u64,
+    seq: u64,
+    item: T,
+}
+
+/// Pending loads.  The highest priority is taken first, ties go to the oldest request.  The queue is short (one
+/// entry per mod at most) so a linear search is fine and makes changing priorities easy.
+struct LoadQueue<T> {
+    entries: Vec<QueueEntry<T>>,
+    next_seq: u64,
+}
+
+impl<T> LoadQueue<T> {
+    const fn new() -> Self {
+        LoadQueue { entries: Vec::new(), next_seq: 0 }
+    }
+
+    fn push(&mut self, id: i32, priority: u64, item: T) {
+        self.entries.push(QueueEntry { id, priority, seq: self.next_seq, item });
+        self.next_seq += 1;
+    }
+
+    /// Raise the priority of `id` if it is still queued.  Returns false if it isn't.
+    fn bump(&mut self, id: i32, priority: u64) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(e) => {
+                e.priority = e.priority.max(priority);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn pop(&mut self) -> Option<(i32, T)> {
+        let best = self.entries.iter().enumerate()
+            .max_by(|(_, a), (_, b)| a.priority.cmp(&b.priority).then(b.seq.cmp(&a.seq)))
+            .map(|(i, _)| i)?;
+        let e = self.entries.swap_remove(best);
+        Some((e.id, e.item))
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// ───────────────── loader threads & queue initialisation ──────────────────
+static LOAD_QUEUE: Mutex<LoadQueue<LoadMsg>> = Mutex::new(LoadQueue::new());
+static LOAD_READY: Condvar = Condvar::new();
+static START_WORKERS: Once = Once::new();
+
+fn start_workers() {
+    let count = thread::available_parallelism()
+        .map(|n| n.get().saturating_sub(1))
+        .unwrap_or(1)
+        .clamp(1, MAX_LOAD_THREADS);
+    write_log_file(&format!("load thread: starting {count} load threads"));
+    for n in 0..count {
+        let res = thread::Builder::new()
+            .name(format!("mm_load_{n}"))
+            .spawn(worker);
+        if let Err(e) = res {
+            write_log_file(&format!("load thread error: failed to start load thread {n}: {e}"));
+        }
+    }
+}
+
+fn next_load() -> Option<(i32, LoadMsg)> {
+    let mut queue = LOAD_QUEUE.lock().ok()?;
+    loop {
+        if let Some(next) = queue.pop() {
+            return Some(next);
+        }
+        queue = LOAD_READY.wait(queue).ok()?;
+    }
+}
+
+fn worker() {
+    while let Some((id, msg)) = next_load() {
+        // Always use the table that is current *now*.
+        let table = current_table();
+        if let Err(x) = update_state(&table, id, ResourceState::Loading) {
+            write_log_file(&format!("load thread error: failed to update load state for {id}: {x}"));
+        }
+        match load_resource(msg) {
+            Ok(()) => match update_state(&table, id, ResourceState::Loaded) {
+                Ok(_) => (),
+                Err(x) => write_log_file(&format!("load thread error: failed to update load state for {id}: {x}")),
+            },
+            Err(x) => {
+                write_log_file(&format!("load thread error: resource {id}: {x}"));
+                match update_state(&table, id, ResourceState::Error(x)) {
                     Ok(_) => (),
                     Err(x) => write_log_file(&format!("load thread error: failed to update load state for {id}: {x}")),
-                },
-                Err(x) => {
-                    write_log_file(&format!("load thread error: resource {id}: {x}"));
-                    match update_state(&table, id, ResourceState::Error(x)) {
-                        Ok(_) => (),
-                        Err(x) => write_log_file(&format!("load thread error: failed to update load state for {id}: {x}")),
-                    }
                 }
             }
         }
-    });
+    }
+    write_log_file("load thread: queue gone, exiting");
+}
 
-    tx
-});
+/// Device ref count bookkeeping for loads that overlap: the count before the first of them started and how many
+/// are still running.
+struct RefCountBatch {
+    active: usize,
+    pre_rc: u32,
+}
+
+static REF_COUNT_BATCH: Mutex<RefCountBatch> = Mutex::new(RefCountBatch { active: 0, pre_rc: 0 });
+
+unsafe fn begin_ref_count(device: DevicePointer) {
+    let mut batch = REF_COUNT_BATCH.lock().unwrap_or_else(|e| e.into_inner());
+    if batch.active == 0 {
+        batch.pre_rc = get_dev_ref_count(device);
+    }
+    batch.active += 1;
+}
+
+/// Returns the (added, new) ref counts if this was the last load of the batch.
+unsafe fn end_ref_count(device: DevicePointer) -> Option<(u32, u32)> {
+    let mut batch = REF_COUNT_BATCH.lock().unwrap_or_else(|e| e.into_inner());
+    batch.active = batch.active.saturating_sub(1);
+    if batch.active == 0 {
+        Some(update_ref_count(device, batch.pre_rc))
+    } else {
+        None
+    }
+}
 
 /// Try to begin loading `id`.  
-/// Returns `true` iff the ID was queued for the loader thread.
+/// Returns `true` iff the ID was queued for the loader threads.
 ///
 /// Spawn/queue rules:
-/// * Absent   → queued, marked `Loading`.
+/// * Absent   → queued, marked `Queued`.
+/// * `Queued` → priority raised to the mod's `last_frame_render` if that is more recent.
 /// * Anything else (`Loading`, `Loaded`, `Error`) → ignored.
 pub fn maybe_start_load(device:DevicePointer, callbacks:ManagedCallbacks, nmod:&mut NativeModData) -> Result<bool, String> {
     use ResourceState::*;
 
     let table = current_table();
     let mut should_queue = false;
+    let mut queued = false;
 
     let id = nmod.midx;
     // Decide under the per-table mutex.
@@ -122,71 +273,98 @@ pub fn maybe_start_load(device:DevicePointer, callbacks:ManagedCallbacks, nmod:&
         let mut map = table.lock().map_err(|_| "maybe_start_load error: table mutex poisoned".to_string())?;
         match map.entry(id) {
             Entry::Vacant(v) => {
-                v.insert(Loading);
+                v.insert(Queued);
                 should_queue = true;
             }
+            Entry::Occupied(o) if matches!(o.get(), Queued) => {
+                queued = true;
+            }
             Entry::Occupied(mut o) 
                 // do not reload if its already in the table for these states - in general the only way to get a reload
                 // is to reload the whole mod database
                 if ! matches!(o.get(), 
                     ResourceState::Loading | ResourceState::Error(_) | ResourceState::Loaded) => {
-                o.insert(Loading);
+                o.insert(Queued);
                 should_queue = true;
             }
             _ => {}
         }
     }
 
+    if queued {
+        // it was drawn again while waiting, so move it up
+        LOAD_QUEUE.lock().map_err(|_| "maybe_start_load error: queue mutex poisoned".to_string())?
+            .bump(id, nmod.last_frame_render);
+    }
+
     // Push onto the queue (non-blocking) if required.
     if should_queue {
+        START_WORKERS.call_once(start_workers);
         let nmod = nmod.clone();
+        let priority = nmod.last_frame_render;
         let msg = LoadMsg {
             device,
             callbacks,
             nmod
         };
-        // Handle the case when the loader thread has exited gracefully.
-        LOAD_SENDER.send(msg).map_err(|_| "maybe_start_load error: loader thread gone".to_string())?;
+        LOAD_QUEUE.lock().map_err(|_| "maybe_start_load error: queue mutex poisoned".to_string())?
+            .push(id, priority, msg);
+        LOAD_READY.notify_one();
     }
 
     Ok(should_queue)
 }
 
+/// Counts of resources in each state in the current table.
+pub fn load_progress() -> Result<LoadProgress, String> {
+    let table = current_table();
+    let map = table.lock().map_err(|_| "load_progress error: table mutex poisoned".to_string())?;
+    Ok(ResourceState::progress(map.values()))
+}
+
 /// This is called when the mod database is reloaded.  It just swaps out the whole table with a new one.  If the 
 /// thread is loading a resource it will observe that the table id changed after it finished the load, and drop the 
 /// resource.  Eventually a new load should be triggered for the resource if it is still relevant to something in the scene.
+/// Loads that are still queued are for the old table, so they are dropped.
 pub fn reinit_load_thread_table() -> Result<(), String>
 {
     increment_resource_table_id();
+    match LOAD_QUEUE.lock() {
+        Ok(mut queue) => {
+            if queue.len() > 0 {
+                write_log_file(&format!("load thread: dropping {} queued loads", queue.len()));
+            }
+            queue.clear();
+        }
+        Err(_) => write_log_file("error: load thread queue mutex poisoned"),
+    }
     let new_map = HashMap::new();
     let new_handle = Arc::new(Mutex::new(new_map));
     *RESOURCE_TABLE.write().map_err(|_| "error: failed to acquire load thread table write lock".to_string())? = new_handle;
     Ok(())
 }
 
+/// Load the mod in `msg`.  Returns an error if the device type isn't supported or the load failed, so that the
+/// resource state records it.  A load that is dropped because the table changed isn't an error.
 fn load_resource(mut msg: LoadMsg) -> Result<(), String> {
     let id = msg.nmod.midx;
     let table_id = get_resource_table_id();
+    let dev = match msg.device {
+        DevicePointer::D3D11(dev) => dev,
+        _ => return Err("load_resource can only handle d3d11 devices at this time; load request dropped".to_string()),
+    };
     unsafe {
-        write_log_file(&format!("load thread: start loading #{id}"));
-        let pre_rc = get_dev_ref_count(msg.device);
+        write_log_file(&format!("load thread: start loading #{id} (last drawn on frame {})", msg.nmod.last_frame_render));
+        begin_ref_count(msg.device);
 
-        let loaded = match msg.device {
-            DevicePointer::D3D11(dev) => {
-                load_d3d_data11(dev, msg.callbacks, msg.nmod.midx, &mut msg.nmod)
-            },
-            _ => {
-                write_log_file("load thread error: load_resource can only handle d3d11 devices at this time; load request dropped");
-                false
-            }
-        };
+        let loaded = load_d3d_data11(dev, msg.callbacks, msg.nmod.midx, &mut msg.nmod);
 
         let curr_table_id = get_resource_table_id();
-        if curr_table_id != table_id {
+        let res = if curr_table_id != table_id {
             write_log_file(&format!("load thread: table changed while loading, dropping resource: {}", id));
+            Ok(())
         }
         else if loaded {
-            let (diff,new_rc) = update_ref_count(msg.device, pre_rc);
             // The Mutex around LOADED_MODS now also covers the render thread's reads,
             // so the prior "torn write" concern (render thread observing a half-copied
             // NativeModData) is resolved as long as everyone goes through the lock.
@@ -200,13 +378,19 @@ fn load_resource(mut msg: LoadMsg) -> Result<(), String> {
                     write_log_file(&format!("load thread: LOADED_MODS lock poisoned, skipping writeback for {}: {}", id, e));
                 }
             }
-            write_log_file(&format!("load thread: load_resource added {} to device rc, new rc: {}", diff, new_rc));
+            Ok(())
+        } else {
+            Err(format!("failed to load d3d data for {}", msg.nmod.name))
+        };
+
+        // resources from a dropped load stay on the device, so they are counted either way
+        if let Some((diff, new_rc)) = end_ref_count(msg.device) {
+            write_log_file(&format!("load thread: loads added {} to device rc, new rc: {}", diff, new_rc));
         }
 
         write_log_file(&format!("load thread: end loading #{id}"));
+        res
     }
-
-    Ok(())
 }
 
 fn update_state(table: &TableHandle, id: i32, new_state: ResourceState) -> Result<(), String> {
@@ -214,3 +398,84 @@ fn update_state(table: &TableHandle, id: i32, new_state: ResourceState) -> Resul
     map.insert(id, new_state);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::interop::{ConfData, ModData, SnapshotData, SnapshotResult};
+    use std::ffi::c_void;
+    use winapi::um::winnt::WCHAR;
+
+    // none of these should be reached by the tests, the loads fail before the managed code is called
+    unsafe extern "system" fn set_paths(_: *mut WCHAR, _: *mut WCHAR) -> *mut ConfData { unreachable!() }
+    unsafe extern "system" fn no_args() -> i32 { unreachable!() }
+    unsafe extern "system" fn get_mod_data(_: i32) -> *mut ModData { unreachable!() }
+    unsafe extern "system" fn load_mod_data(_: i32) -> i32 { unreachable!() }
+    unsafe extern "system" fn fill_mod_data(_: i32, _: *mut u8, _: i32, _: *mut u8, _: i32, _: *mut u8, _: i32) -> i32 {
+        unreachable!()
+    }
+    unsafe extern "system" fn take_snapshot(_: *mut c_void, _: *mut SnapshotData) -> i32 { unreachable!() }
+    unsafe extern "system" fn get_snapshot_result() -> *mut SnapshotResult { unreachable!() }
+
+    fn load_msg(device: DevicePointer) -> LoadMsg {
+        let callbacks = ManagedCallbacks {
+            SetPaths: set_paths,
+            LoadModDB: no_args,
+            GetModCount: no_args,
+            GetModData: get_mod_data,
+            FillModData: fill_mod_data,
+            LoadModData: load_mod_data,
+            TakeSnapshot: take_snapshot,
+            GetLoadingState: no_args,
+            GetSnapshotResult: get_snapshot_result,
+        };
+        let mut nmod = NativeModData::new();
+        nmod.name = "test_mod".to_owned();
+        LoadMsg { device, callbacks, nmod }
+    }
+
+    #[test]
+    fn test_load_resource_errors() {
+        let err = load_resource(load_msg(DevicePointer::D3D9(std::ptr::null_mut()))).unwrap_err();
+        assert!(err.contains("can only handle d3d11 devices"), "{}", err);
+        // the d3d11 load fails on the null device
+        let err = load_resource(load_msg(DevicePointer::D3D11(std::ptr::null_mut()))).unwrap_err();
+        assert_eq!(err, "failed to load d3d data for test_mod");
+    }
+
+    #[test]
+    fn test_queue_priority() {
+        let mut q = LoadQueue::new();
+        q.push(1, 10, "a");
+        q.push(2, 30, "b");
+        q.push(3, 20, "c");
+        q.push(4, 30, "d");
+        // most recently drawn first, oldest request first on a tie
+        assert_eq!(q.pop(), Some((2, "b")));
+        assert_eq!(q.pop(), Some((4, "d")));
+        // drawn again while queued
+        assert!(q.bump(1, 40));
+        // priorities don't go down
+        assert!(q.bump(3, 5));
+        assert_eq!(q.pop(), Some((1, "a")));
+        assert_eq!(q.pop(), Some((3, "c")));
+        assert_eq!(q.pop(), None);
+        assert!(!q.bump(1, 50));
+
+        q.push(5, 0, "e");
+        assert_eq!(q.len(), 1);
+        q.clear();
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_progress() {
+        use ResourceState::*;
+        let states = [Queued, Loading, Loaded, Loaded, Error("x".to_owned()), Queued];
+        let p = ResourceState::progress(states.iter());
+        assert_eq!(p, LoadProgress { queued: 2, loading: 1, loaded: 2, errors: 1 });
+        assert_eq!(p.total(), 6);
+        assert_eq!(p.to_string(), "2/6 loaded, 1 loading, 2 queued, 1 errors");
+        assert_eq!(ResourceState::progress([].iter()), LoadProgress::default());
+    }
+}
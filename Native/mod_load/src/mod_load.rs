@@ -11,6 +11,8 @@ pub use winapi::shared::d3d9types::*;
 pub use winapi::shared::minwindef::*;
 pub use winapi::shared::windef::{HWND, RECT};
 pub use winapi::shared::winerror::{E_FAIL, S_OK};
+use winapi::shared::dxgiformat::{DXGI_FORMAT_R16_UINT, DXGI_FORMAT_R32_UINT};
+use winapi::um::d3d11::D3D11_BIND_INDEX_BUFFER;
 use winapi::um::d3d11::D3D11_BIND_VERTEX_BUFFER;
 use winapi::um::d3d11::D3D11_BUFFER_DESC;
 use winapi::um::d3d11::D3D11_INPUT_ELEMENT_DESC;
@@ -34,6 +36,7 @@ use types::native_mod;
 use crate::load_thread::maybe_start_load;
 use crate::load_thread::reinit_load_thread_table;
 use crate::mod_vector;
+use crate::native_mesh;
 
 pub enum AsyncLoadState {
     NotStarted = 51,
@@ -143,6 +146,10 @@ unsafe fn load_tex(dp:DevicePointer, texpath:&[u16]) -> Option<TexPtr> {
 }
 
 const MAX_FILL_ATTEMPTS:u32 = 1;
+
+/// The managed fill code isn't thread safe (it keeps the vector encoder in a mutable global), so
+/// the load threads take turns calling it.  The rest of the load can run concurrently.
+static FILL_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
 /// Create D3D resources for a mod using the data loaded by managed code. This usually consists of a
 /// vertex buffer, declaration and optionally one or more textures.  `midx` is the mod index
 /// into the current mod DB (and should be less than GetModCount()).
@@ -169,12 +176,13 @@ pub unsafe fn load_d3d_data9(device: *mut IDirect3DDevice9, callbacks: interop::
         (null_mut(), None)
     };
 
-    let vb_size = (*mdat).numbers.prim_count * 3 * (*mdat).numbers.vert_size_bytes;
+    let vb_vert_count = (*mdat).numbers.vb_vert_count();
+    let vb_size = vb_vert_count * (*mdat).numbers.vert_size_bytes;
     let mut vb_data: *mut u8 = null_mut();
 
-    // index buffers not currently supported
-    let ib_size = 0; //mdat->indexCount * mdat->indexElemSizeBytes;
-    let ib_data: *mut u8 = null_mut();
+    let index_info = (*mdat).numbers.index_info();
+    let ib_size = (*mdat).numbers.ib_size_bytes();
+    let mut ib_data: *mut u8 = null_mut();
 
     // create vb
     let mut out_vb: *mut IDirect3DVertexBuffer9 = null_mut();
@@ -197,12 +205,50 @@ pub unsafe fn load_d3d_data9(device: *mut IDirect3DDevice9, callbacks: interop::
 
     let vb = *out_vb;
 
-    // lock vb to obtain write buffer
+    // create ib, if the mod has indices
+    let mut ib: *mut IDirect3DIndexBuffer9 = null_mut();
+    if let Some((_, elem_size)) = index_info {
+        let format = if elem_size == 4 { D3DFMT_INDEX32 } else { D3DFMT_INDEX16 };
+        let hr = (*device).CreateIndexBuffer(
+            ib_size as UINT,
+            D3DUSAGE_WRITEONLY,
+            format,
+            D3DPOOL_MANAGED,
+            &mut ib,
+            null_mut(),
+        );
+        if hr != 0 {
+            write_log_file(&format!(
+                "failed to create index buffer for mod {}: HR {:x}",
+                nmd.name, hr
+            ));
+            (*vb).Release();
+            return;
+        }
+    }
+    let release_buffers = || {
+        (*vb).Release();
+        if !ib.is_null() {
+            (*ib).Release();
+        }
+    };
+
+    // lock vb (and ib) to obtain write buffers
     let hr = (*vb).Lock(0, 0, std::mem::transmute(&mut vb_data), 0);
     if hr != 0 {
         write_log_file(&format!("failed to lock vertex buffer: {:x}", hr));
+        release_buffers();
         return;
     }
+    if !ib.is_null() {
+        let hr = (*ib).Lock(0, 0, std::mem::transmute(&mut ib_data), 0);
+        if hr != 0 {
+            write_log_file(&format!("failed to lock index buffer: {:x}", hr));
+            (*vb).Unlock();
+            release_buffers();
+            return;
+        }
+    }
 
     // fill all data buckets with managed code
     let ret = (callbacks.FillModData)(
@@ -210,21 +256,24 @@ pub unsafe fn load_d3d_data9(device: *mut IDirect3DDevice9, callbacks: interop::
     );
 
     let hr = (*vb).Unlock();
-    if hr != 0 {
-        write_log_file(&format!("failed to unlock vertex buffer: {:x}", hr));
-        (*vb).Release();
+    let ib_hr = if !ib.is_null() { (*ib).Unlock() } else { 0 };
+    if hr != 0 || ib_hr != 0 {
+        write_log_file(&format!("failed to unlock vertex/index buffer: {:x}/{:x}", hr, ib_hr));
+        release_buffers();
         return;
     }
 
     if ret != 0 {
         write_log_file(&format!("failed to fill mod data: fill ret {} for mod {} ", ret, nmd.name));
-        (*vb).Release();
+        release_buffers();
         return;
     }
 
     let mut d3dd = d3ddata::ModD3DData9::new();
 
     d3dd.vb = vb;
+    d3dd.ib = ib;
+    d3dd.vert_count = vb_vert_count as u32;
 
     // create vertex declaration
     let mut out_decl: *mut IDirect3DVertexDeclaration9 = null_mut();
@@ -233,12 +282,10 @@ pub unsafe fn load_d3d_data9(device: *mut IDirect3DDevice9, callbacks: interop::
         (*device).CreateVertexDeclaration(decl_data as *const D3DVERTEXELEMENT9, pp_out_decl);
     if hr != 0 {
         write_log_file(&format!("failed to create vertex declaration: {}", hr));
-        (*vb).Release();
         return;
     }
     if out_decl == null_mut() {
         write_log_file("vertex declaration is null");
-        (*vb).Release();
         return;
     }
     d3dd.decl = out_decl;
@@ -260,7 +307,8 @@ pub unsafe fn load_d3d_data9(device: *mut IDirect3DDevice9, callbacks: interop::
     d3dd.textures[3] = load_tex_d3d9(&(*mdat).texPath3);
 
     write_log_file(&format!(
-        "allocated vb/decl for mod {}, idx {}: {:?}", nmd.name,
+        "allocated vb/{}decl for mod {}, idx {}: {:?}",
+        if d3dd.ib.is_null() { "" } else { "ib/" }, nmd.name,
         midx,
         mdat.numbers
     ));
@@ -348,7 +396,7 @@ pub unsafe fn load_d3d_data11(device: *mut ID3D11Device, callbacks: interop::Man
         write_log_file(&format!("Error, vertex size is invalid for mod {}: {}", nmd.name, vert_size));
         return false;
     }
-    let vert_count = (*mdat).numbers.prim_count * 3;
+    let vert_count = (*mdat).numbers.vb_vert_count();
     let vert_count =
         if vert_count <= 0 {
             write_log_file(&format!("Error, vertex count is invalid for mod {}: {}", nmd.name, vert_count));
@@ -359,26 +407,46 @@ pub unsafe fn load_d3d_data11(device: *mut ID3D11Device, callbacks: interop::Man
     let vb_size = vert_count * vert_size;
     let mut vb_data = vec![0u8; vb_size as usize];
 
-    // index buffers not currently supported
-    let ib_size = 0; //mdat->indexCount * mdat->indexElemSizeBytes;
-    let ib_data: *mut u8 = null_mut();
+    let index_info = (*mdat).numbers.index_info();
+    let ib_size = (*mdat).numbers.ib_size_bytes();
+    let mut ib_data = vec![0u8; ib_size as usize];
+
+    // if the profile enables it, fill the buffers from the mod's mesh file here.  managed code
+    // only provides the path for mods this can handle, and if it fails anyway managed code
+    // fills them as usual.
+    let native_filled = GLOBAL_STATE.run_conf.profile.native_mmobj_load && {
+        let reverse_normals = GLOBAL_STATE.run_conf.profile.reverse_normals;
+        match native_mesh::fill_mod_buffers(mdat, &vlayout, reverse_normals, &mut vb_data, &mut ib_data) {
+            Ok(filled) => filled,
+            Err(e) => {
+                write_log_file(&format!("native fill failed for mod {}, using managed fill: {}", nmd.name, e));
+                false
+            }
+        }
+    };
 
-    // fill all data buckets with managed code.
+    // otherwise fill all data buckets with managed code.
     // not sure why I used signed ints in this interface, but if you are creating a >2GB mod vertex buffer
     // you've got bigger problems.
     let i32_vb_size = vb_size as i32;
-    let ret = (callbacks.FillModData)(
-        midx, decl_data as *mut u8, decl_size as i32, vb_data.as_mut_ptr(), i32_vb_size, ib_data, ib_size,
-    );
+    let ret = if native_filled { 0 } else {
+        let _fill = FILL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let ib_data_ptr = if ib_data.is_empty() { null_mut() } else { ib_data.as_mut_ptr() };
+        (callbacks.FillModData)(
+            midx, decl_data as *mut u8, decl_size as i32, vb_data.as_mut_ptr(), i32_vb_size, ib_data_ptr, ib_size,
+        )
+    };
 
     if ret != 0 {
         write_log_file(&format!("failed to fill mod data: {}", ret));
         return false;
     }
 
+    let indices = index_info.map(|(_, elem_size)| mod_vector::index_data_to_u32(&ib_data, elem_size));
     let mod_ts_update = (*mdat).update_tangent_space;
     let profile = nmd.mod_data.mod_snap_profile; // copy this out, since nmd mutably borrowed, can't pass it as readonly
-    let _ = mod_vector::update_normals(vb_data.as_mut_ptr(), &nmd.name, profile, mod_ts_update, vert_count, &vlayout)
+    let _ = mod_vector::update_normals(vb_data.as_mut_ptr(), &nmd.name, profile, mod_ts_update, vert_count,
+        indices.as_deref(), &vlayout)
         .map_err(|e| {
             write_log_file(&format!("Warning: failed to update normals: {:?}", e));
         });
@@ -426,7 +494,38 @@ pub unsafe fn load_d3d_data11(device: *mut ID3D11Device, callbacks: interop::Man
         return false;
     }
 
+    // create ib
+    let mut index_buffer = std::ptr::null_mut();
+    if let Some((index_count, elem_size)) = index_info {
+        let mut ib_desc = D3D11_BUFFER_DESC {
+            ByteWidth: ib_size as UINT,
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_INDEX_BUFFER,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+            StructureByteStride: 0,
+        };
+        let mut ib_init_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: ib_data.as_ptr() as *const c_void,
+            SysMemPitch: 0,
+            SysMemSlicePitch: 0,
+        };
+        let hr = (*device).CreateBuffer(
+            &mut ib_desc, &mut ib_init_data, &mut index_buffer);
+        if hr != 0 {
+            write_log_file(&format!(
+                "failed to create index buffer for mod {}: HR {:x}",
+                nmd.name, hr
+            ));
+            (*vertex_buffer).Release();
+            return false;
+        }
+        d3d_data.ib_format = if elem_size == 4 { DXGI_FORMAT_R32_UINT } else { DXGI_FORMAT_R16_UINT };
+        d3d_data.index_count = index_count;
+    }
+
     d3d_data.vb = vertex_buffer;
+    d3d_data.ib = index_buffer;
     d3d_data.vert_size = vert_size as u32;
     d3d_data.vert_count = vert_count as u32;
     // Remember which semantics the fill layout exposed, so we can detect later
@@ -474,7 +573,8 @@ pub unsafe fn load_d3d_data11(device: *mut ID3D11Device, callbacks: interop::Man
     load_tex_d3d11(&(*mdat).texPath3, 3);
 
     write_log_file(&format!(
-        "allocated vb for mod {}, idx {}: {:?}", nmd.name,
+        "allocated vb{} for mod {}, idx {}: {:?}",
+        if d3d_data.ib.is_null() { "" } else { "/ib" }, nmd.name,
         midx,
         mdat.numbers
     ));
@@ -483,29 +583,7 @@ pub unsafe fn load_d3d_data11(device: *mut ID3D11Device, callbacks: interop::Man
     true
 }
 
-/// Sort mod lists so that variants (mods with no parents) are up front.  This makes cycling 
-/// code simpler, since while iterating, once we see a parented mod or a deletion mod, 
-/// we know we've processed all the 
-/// variants and can wrap around. sort_by_key is stable so this should not break the ordering
-/// in the mod index.  This should be called whenever one or more mods are added.
-pub fn sort_mods(loaded_mods:&mut FnvHashMap<u32, Vec<native_mod::NativeModData>>) {
-    for nmodv in loaded_mods.values_mut() {
-        nmodv.sort_by_key(|nmod| {
-            // Key is a tuple:
-            // (0: mods with no parents, 1: mods with parents, 2: deletion mods)
-            // This will order: [variants], [parented], [deletions], preserving stable order.
-            let is_deletion = nmod.mod_data.numbers.mod_type == (interop::ModType::Deletion as i32);
-            let has_no_parents = nmod.parent_mod_names.is_empty();
-            if is_deletion {
-                2
-            } else if has_no_parents {
-                0
-            } else {
-                1
-            }
-        });
-    }
-}
+pub use mm_core::sort_mods;
 
 /// Set up mod data structures.  Should be called after the managed code is done loading
 /// on its side.  Note that this will also clear any previously loaded mods (and their DX
@@ -544,6 +622,9 @@ pub unsafe fn setup_mod_data(device: DevicePointer, callbacks: interop::ManagedC
     // `VBChecksum` constraint.
     let mut vb_checksum_targets: FnvHashSet<(u32, u32)> =
         FnvHashSet::with_capacity_and_hasher(16, Default::default());
+    // disabled mods are still registered so that they can be re-enabled at runtime, but their
+    // d3d data is never loaded while they are disabled (see `load_deferred_mods`)
+    let disabled_mods = mod_prefs::load_disabled_mods();
     write_log_file(&format!("setting up {} mods", mod_count));
     for midx in 0..mod_count {
         let mdat: *mut interop::ModData = (callbacks.GetModData)(midx);
@@ -662,6 +743,10 @@ pub unsafe fn setup_mod_data(device: DevicePointer, callbacks: interop::ManagedC
             continue;
         }
 
+        if disabled_mods.contains(&native_mod_data.name) {
+            write_log_file(&format!("mod is disabled in prefs, its data will not be loaded: {}", native_mod_data.name));
+        }
+
         // used to load the d3d resources here for all mods, but now that is delayed until the
         // mod is actually referenced so that we don't clog d3d with a bunch of possibly unused
         // stuff. (see `load_deferred_mods`)
@@ -752,6 +837,8 @@ pub unsafe fn setup_mod_data(device: DevicePointer, callbacks: interop::ManagedC
                 mods: loaded_mods,
                 mods_by_name: mods_by_name,
                 selected_variant,
+                disabled_mods,
+                last_selected: None,
             });
         }
         Err(e) => {
@@ -875,6 +962,11 @@ pub unsafe fn load_deferred_mods(device: DevicePointer, callbacks: interop::Mana
             }
         };
         for nmd in to_load.iter() {
+            // select() never returns disabled mods, but one may have been queued just before it
+            // was disabled
+            if loaded_mods_guard.as_ref().map_or(false, |ms| ms.disabled_mods.contains(nmd)) {
+                continue;
+            }
             let mut nmod =
                 get_mod_by_name(&nmd, &mut *loaded_mods_guard);
             if let Some(ref mut nmod) = nmod {
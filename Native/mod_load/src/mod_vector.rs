@@ -3,12 +3,6 @@ use shared_dx::dx11rs::VertexFormat;
 use shared_dx::error;
 use shared_dx::error::HookError;
 use types::interop::ModSnapProfile;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R16G16_FLOAT;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R32G32B32A32_FLOAT;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R32G32B32_FLOAT;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R32G32_FLOAT;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R16G16B16A16_SNORM;
 use winapi::shared::dxgiformat::DXGI_FORMAT_R16G16B16A16_SINT;
 use winapi::shared::dxgiformat::DXGI_FORMAT_R16G16_SINT;
 pub use winapi::shared::winerror::S_OK;
@@ -24,22 +18,80 @@ use shared_dx::util::*;
 
 use global_state::GLOBAL_STATE;
 
-use crate::data_encoding::decode_packed_vector;
-use crate::data_encoding::decode_octa_vector;
-use crate::data_encoding::encode_packed_vector;
-use crate::data_encoding::encode_octa_vector;
+use crate::data_encoding::{encode_qtangent, VecEncoding};
+use crate::tangent_space::{compute_normals, compute_tangent_frame, NormalWeighting};
+use vertex_codec::Codec;
 
 
 #[repr(C)]
-#[derive(Debug,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct Float3 {
     pub x:f32,
     pub y:f32,
     pub z:f32
 }
 
+impl Float3 {
+    pub const fn new(x:f32, y:f32, z:f32) -> Float3 {
+        Float3 { x, y, z }
+    }
+
+    pub fn add(&self, o:&Float3) -> Float3 {
+        Float3::new(self.x + o.x, self.y + o.y, self.z + o.z)
+    }
+
+    pub fn sub(&self, o:&Float3) -> Float3 {
+        Float3::new(self.x - o.x, self.y - o.y, self.z - o.z)
+    }
+
+    pub fn scale(&self, s:f32) -> Float3 {
+        Float3::new(self.x * s, self.y * s, self.z * s)
+    }
+
+    pub fn neg(&self) -> Float3 {
+        self.scale(-1.0)
+    }
+
+    pub fn dot(&self, o:&Float3) -> f32 {
+        self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    pub fn cross(&self, o:&Float3) -> Float3 {
+        Float3::new(
+            self.y * o.z - self.z * o.y,
+            self.z * o.x - self.x * o.z,
+            self.x * o.y - self.y * o.x)
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Unit vector in the direction of `self`, None if it is (nearly) zero length or not finite.
+    pub fn normalized(&self) -> Option<Float3> {
+        let len = self.length();
+        if len > 1e-12 && len.is_finite() {
+            Some(self.scale(1.0 / len))
+        } else {
+            None
+        }
+    }
+}
+
+impl From<(f32, f32, f32)> for Float3 {
+    fn from(v:(f32, f32, f32)) -> Float3 {
+        Float3::new(v.0, v.1, v.2)
+    }
+}
+
+impl From<[f32; 3]> for Float3 {
+    fn from(v:[f32; 3]) -> Float3 {
+        Float3::new(v[0], v[1], v[2])
+    }
+}
+
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Float2 {
     pub x:f32,
     pub y:f32,
@@ -56,12 +108,35 @@ enum CNormFlags {
     WindCW = 4,
 }
 
+/// The weighting and winding for the native normal generator from a combination of `CNormFlags`.
+fn native_normal_settings(flags:u32) -> (NormalWeighting, bool) {
+    let weighting = if flags & CNormFlags::WeightByArea as u32 != 0 {
+        NormalWeighting::Area
+    } else if flags & CNormFlags::WeightEqual as u32 != 0 {
+        NormalWeighting::Equal
+    } else {
+        NormalWeighting::Angle
+    };
+    (weighting, flags & CNormFlags::WindCW as u32 != 0)
+}
+
+/// Which implementation computes normals and tangents, selected per game profile by the
+/// `GameProfileTangentGenerator` registry value.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum TangentGenerator {
+    /// `tangent_space` (0, the default)
+    Native,
+    /// The DirectXMesh dll (1), for mods that were tuned against its output.  Falls back to
+    /// native if the dll can't be loaded.
+    DirectXMesh,
+}
+
 #[allow(non_camel_case_types)]
 /// DirectXMesh doesn't normally build as a dll.  Had to change it to do that and then manually
 /// export this function.
 /// Function exported by https://github.com/jmquigs/DirectXMesh/tree/changes-for-mm
 type DirectX_ComputeNormals_32Fn = unsafe extern "system" fn(indices:*const u32,
-    nFaces: usize, positions: *const Float3, nVerts:usize, flags:CNormFlags, normals:*mut Float3) -> HRESULT;
+    nFaces: usize, positions: *const Float3, nVerts:usize, flags:u32, normals:*mut Float3) -> HRESULT;
     #[allow(non_camel_case_types)]
 /// DirectXMesh doesn't normally build as a dll.  Had to change it to do that and then manually
 /// export this function.
@@ -76,37 +151,102 @@ const DXMESH_DLL:&'static str = r#"TPLib\DirectXMesh_x86.dll"#;
 #[cfg(target_pointer_width = "64")]
 const DXMESH_DLL:&'static str = r#"TPLib\DirectXMesh_x64.dll"#;
 
-/// Update normals and tangents/bitangents using DirectXMesh.
+/// Load the DirectXMesh dll and look up the functions that are needed.
+fn load_dxmesh(update_normals:bool, update_tangents:bool)
+    -> error::Result<(Option<DirectX_ComputeNormals_32Fn>, Option<DirectX_ComputeTangentFrame_32TBFn>)> {
+    let mut dllpath = unsafe { &GLOBAL_STATE.mm_root.as_ref() }.ok_or_else (||
+        HookError::MeshUpdateFailed(String::from("no mmroot")))?.to_owned();
+    dllpath.push('\\');
+    dllpath.push_str(DXMESH_DLL);
+    let lib = util::load_lib(&dllpath)?;
+
+    let compute_normals_32:Option<DirectX_ComputeNormals_32Fn> = if update_normals {
+        let addr = util::get_proc_address(lib, "DirectX_ComputeNormals_32")?;
+        unsafe { Some(std::mem::transmute(addr)) }
+    } else {
+        None
+    };
+    let compute_tangentframe_32tb:Option<DirectX_ComputeTangentFrame_32TBFn> = if update_tangents {
+        let addr = util::get_proc_address(lib, "DirectX_ComputeTangentFrame_32TB")?;
+        unsafe { Some(std::mem::transmute(addr)) }
+    } else {
+        None
+    };
+    Ok((compute_normals_32, compute_tangentframe_32tb))
+}
+
+/// Look up the codec for a layout element, which must have at least `min_components`.
+fn element_codec(elem:&D3D11_INPUT_ELEMENT_DESC, min_components:usize, what:&str) -> error::Result<&'static Codec> {
+    vertex_codec::codec(elem.Format)
+        .and_then(|c| if c.components() >= min_components { Ok(c) } else {
+            Err(format!("{} has {} components, need {}", c.name, c.components(), min_components))
+        })
+        .map_err(|e| HookError::MeshUpdateFailed(format!("unsupported {} format: {}", what, e)))
+}
+
+unsafe fn read_element(vertpos:*const u8, codec:&Codec) -> error::Result<[f32;4]> {
+    codec.decode(std::slice::from_raw_parts(vertpos, codec.size()))
+        .map_err(HookError::MeshUpdateFailed)
+}
+
+unsafe fn write_element(vertpos:*mut u8, codec:&Codec, v:&[f32;4]) -> error::Result<()> {
+    codec.encode(v, std::slice::from_raw_parts_mut(vertpos, codec.size()))
+        .map_err(HookError::MeshUpdateFailed)
+}
+
+/// Vectors in unorm formats are biased into 0..1.  If `reverse` the vector is stored in zyx order
+/// (MM may have reversed it when the mod was created); w is always at the end.
+fn element_to_vector(codec:&Codec, v:&[f32;4], reverse:bool) -> Float3 {
+    let unbias = |f:f32| if codec.is_unorm() { f * 2.0 - 1.0 } else { f };
+    let (x, y, z) = (unbias(v[0]), unbias(v[1]), unbias(v[2]));
+    if reverse { Float3 { x:z, y, z:x } } else { Float3 { x, y, z } }
+}
+
+fn vector_to_element(codec:&Codec, vec:&Float3, reverse:bool, w:f32) -> [f32;4] {
+    let bias = |f:f32| if codec.is_unorm() { (f + 1.0) * 0.5 } else { f };
+    let (x, y, z) = if reverse { (vec.z, vec.y, vec.x) } else { (vec.x, vec.y, vec.z) };
+    [bias(x), bias(y), bias(z), w]
+}
+
+/// Convert index buffer data with 2 or 4 byte elements to u32 indices.
+pub fn index_data_to_u32(data:&[u8], elem_size:u32) -> Vec<u32> {
+    match elem_size {
+        2 => data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]) as u32).collect(),
+        _ => data.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect(),
+    }
+}
+
+/// Update normals and tangents/bitangents using `tangent_space`, or DirectXMesh if the game
+/// profile selects it.
 ///
-/// Normal update generally disabled by default since it doesn't do smooth normals like blender,
-/// and the faceting looks bad most of the time.  Useful for debugging normals though, since the
-/// normals are accurate.
+/// Normal update generally disabled by default since the normals in the mod are usually what
+/// the author intended; the DirectXMesh generator in particular doesn't do smooth normals like
+/// blender, and the faceting looks bad most of the time.
 ///
 /// Tangent/bitangent update is enabled by default since its generates vectors that are much more
 /// accurate for most models than what the managed code generates (which is basically just wrong).
 ///
-pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_update:i32, vert_count:u32, layout:&VertexFormat) -> error::Result<()> {
+/// `indices` is the mod's index data, if it has any; otherwise each group of three vertices is
+/// a triangle.
+pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_update:i32, vert_count:u32,
+    indices:Option<&[u32]>, layout:&VertexFormat) -> error::Result<()> {
 
     let mut update_normals = false;
     let mut update_tangents = true;
-    let mut flags = CNormFlags::Default;
+    let mut flags = CNormFlags::Default as u32;
+    let mut generator = TangentGenerator::Native;
     let mut reverse = false;
 
-    let mut enc_vec_octa = false;
+    let mut vec_encoding = VecEncoding::Packed;
     let mut update_tangent_flip = false;
     if profile.valid {
         update_tangent_flip = profile.flip_tangent;
         // Note this only applies to some formats, see below for usage
         let encoding = util::from_wide_str(&profile.vec_encoding).unwrap_or_else(|_e| "".to_owned());
-        let encoding = encoding.to_lowercase();
-        enc_vec_octa = match encoding.trim() {
-            "packed" => false,
-            "octa" => true,
-            _ => {
-                write_log_file(&format!("error: unknown vector encoding from profile: {}", encoding));
-                false
-            }
-        }
+        vec_encoding = VecEncoding::from_name(&encoding).unwrap_or_else(|| {
+            write_log_file(&format!("error: unknown vector encoding from profile: {}", encoding));
+            VecEncoding::Packed
+        });
     }    
 
     let mut reg_profile_root = String::new();
@@ -144,6 +284,15 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
                 }).unwrap_or(1);
                 update_tangents = do_update_tan > 0;
 
+                generator = match util::reg_query_dword(profile_root, "GameProfileTangentGenerator") {
+                    Ok(1) => TangentGenerator::DirectXMesh,
+                    Ok(0) | Err(_) => TangentGenerator::Native,
+                    Ok(g) => {
+                        write_log_file(&format!("unknown tangent generator {}, using native", g));
+                        TangentGenerator::Native
+                    }
+                };
+
                 reverse = util::reg_query_dword(profile_root,"GameProfileReverseNormals",)
                 .map(|f| f > 0)
                 .map_err(|e| {
@@ -155,7 +304,6 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
                 }
                 if update_normals {
                     flags = util::reg_query_dword(profile_root, "GameProfileUpdateNormalFlags",)
-                    .map(|f| std::mem::transmute(f))
                     .map_err(|e| {
                         write_log_file(&format!("using default {:?} for update normal flags: {:?}", flags, e));
                     }).unwrap_or(flags);
@@ -183,40 +331,48 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
         }
     }
 
-    if !update_normals && !update_tangents {
+    // the managed code only writes packed or octa vectors, anything else has to be re-encoded here
+    // even if nothing is being recomputed (only applies to the packed formats, checked below)
+    let reencode = vec_encoding != vec_encoding.managed();
+    if vec_encoding.is_frame() && !update_tangents {
+        // the frame can't be encoded without the tangent
+        write_log_file(&format!("mod '{}': {} encoding requires tangents, updating them", name, vec_encoding.name()));
+        update_tangents = true;
+    }
+
+    if !update_normals && !update_tangents && !reencode {
         return Ok(());
     }
     let what = if update_normals && update_tangents {
         format!("normals, tangents, bitangents; normal flags: {:?}", flags)
     } else if update_normals {
         format!("normals; normal flags: {:?}", flags)
-    } else {
+    } else if update_tangents {
         format!("tangents and bitangents")
-    };
-    write_log_file(&format!("mod '{}': updating {}; reverse: {}", name, what, reverse));
-    write_log_file(&format!("enc_vec_octa: {}", enc_vec_octa));
-
-    let mut dllpath = unsafe { &GLOBAL_STATE.mm_root.as_ref() }.ok_or_else (||
-        HookError::MeshUpdateFailed(String::from("no mmroot")))?.to_owned();
-    dllpath.push('\\');
-    dllpath.push_str(DXMESH_DLL);
-    let lib = util::load_lib(&dllpath)?;
-
-    let compute_normals_32:Option<DirectX_ComputeNormals_32Fn> = if update_normals {
-        let addr = util::get_proc_address(lib, "DirectX_ComputeNormals_32")?;
-        unsafe { Some(std::mem::transmute(addr)) }
     } else {
-        None
+        format!("normal encoding")
     };
-    let compute_tangentframe_32tb:Option<DirectX_ComputeTangentFrame_32TBFn> = if update_tangents {
-        let addr = util::get_proc_address(lib, "DirectX_ComputeTangentFrame_32TB")?;
-        unsafe { Some(std::mem::transmute(addr)) }
-    } else {
-        None
+    write_log_file(&format!("mod '{}': updating {}; reverse: {}; generator: {:?}", name, what, reverse, generator));
+    write_log_file(&format!("vec encoding: {}", vec_encoding.name()));
+
+    let (compute_normals_32, compute_tangentframe_32tb) = match generator {
+        TangentGenerator::Native => (None, None),
+        TangentGenerator::DirectXMesh => load_dxmesh(update_normals, update_tangents)
+            .unwrap_or_else(|e| {
+                write_log_file(&format!("failed to load DirectXMesh, using native generator: {:?}", e));
+                (None, None)
+            }),
     };
 
-    // don't have an index buffer, so will need to generate an index array, using a 1:1 mapping between verts and indices
-    let indices:Vec<u32> = (0..vert_count).collect();
+    // if there is no index buffer, generate an index array, using a 1:1 mapping between verts and indices
+    let generated_indices:Vec<u32>;
+    let indices = match indices {
+        Some(indices) => indices,
+        None => {
+            generated_indices = (0..vert_count).collect();
+            &generated_indices
+        }
+    };
 
     // helper function to convert semantic name ptrs to a lowercase string
     let ptr_to_str = |ptr:*const i8| -> String {
@@ -229,15 +385,24 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
     let pos_elem = layout.layout.iter()
         .find(|l| ptr_to_str(l.SemanticName).starts_with("position"))
         .ok_or(HookError::MeshUpdateFailed("missing position in input layout".to_owned()))?;
-    // we can do this if the pos is 3 or 4 F32s but not anything else
-    if pos_elem.Format != DXGI_FORMAT_R32G32B32_FLOAT && pos_elem.Format != DXGI_FORMAT_R32G32B32A32_FLOAT {
-        return Err(HookError::MeshUpdateFailed("unsupported position format".to_owned()).into());
-    }
+    let pos_codec = element_codec(pos_elem, 3, "position")?;
     // also need normal offset
     let norm_elem = layout.layout.iter()
         .find(|l| ptr_to_str(l.SemanticName).starts_with("normal"))
         .ok_or(HookError::MeshUpdateFailed("missing normal in input layout".to_owned()))?;
 
+    // the named encodings only apply to the packed formats
+    let packed_normal = norm_elem.Format == DXGI_FORMAT_R16G16B16A16_SINT;
+    let reencode = reencode && packed_normal;
+    if !update_normals && !update_tangents && !reencode {
+        return Ok(());
+    }
+    // qtangent replaces the normal and the tangent packed after it with the whole frame
+    let qtangent = vec_encoding.is_frame() && packed_normal;
+    if vec_encoding.is_frame() && !qtangent {
+        write_log_file(&format!("mod '{}': {} encoding ignored for normal format {}", name, vec_encoding.name(), norm_elem.Format));
+    }
+
     // to compute tangents need the texcoord offset
     let tex_elem = if update_tangents {
         Some(layout.layout.iter()
@@ -249,6 +414,13 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
         None
     };
 
+    // the packed encodings are handled specially, anything else goes through the codec
+    let norm_codec = element_codec(norm_elem, 3, "normal")?;
+    let tex_codec = match tex_elem {
+        Some(tex_elem) => Some(element_codec(tex_elem, 2, "texcoord")?),
+        None => None,
+    };
+
     let pos_offset = pos_elem.AlignedByteOffset as usize;
     let norm_offset = norm_elem.AlignedByteOffset as usize;
 
@@ -259,27 +431,13 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
     let mut tangents:Vec<Float3> = Vec::with_capacity(vert_count as usize);
     let mut bitangents:Vec<Float3> = Vec::with_capacity(vert_count as usize);
 
-    let decode_normal = if enc_vec_octa {
-        decode_octa_vector 
-    } else {
-        decode_packed_vector
-    };
-    let encode_normal = if enc_vec_octa {
-        encode_octa_vector
-    } else {
-        encode_packed_vector
-    };
-
     for i in 0..vert_count {
         // compute the offset to the vert and then the offset to the position in the vert using
         // pos_offset
         unsafe {
             let vertpos = data.offset((i * layout.size) as isize + pos_offset as isize);
-            // there are at least 3 f32s starting at vertpos so copy them into a Float3 position
-            positions.push(Float3 {
-                x:*(vertpos as *const f32),
-                y:*(vertpos.offset(4) as *const f32),
-                z:*(vertpos.offset(8) as *const f32) });
+            let [x, y, z, _] = read_element(vertpos, pos_codec)?;
+            positions.push(Float3 { x, y, z });
         }
 
         // if we are computing the normals just push a zero normal.  otherwise, fill in the normal from the data
@@ -290,48 +448,16 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
                 let vertpos = data.offset((i * layout.size) as isize + norm_offset as isize);
                 match norm_elem.Format {
                     DXGI_FORMAT_R16G16B16A16_SINT => {
-                        // packed format of 2 16bit ints per normal
+                        // packed format of 2 16bit ints per normal, as written by the managed code
                         let vertpos = vertpos as *const i16;
                         let a = ptr::read_unaligned(vertpos);
                         let b = ptr::read_unaligned(vertpos.offset(1));
-                        let (x,y,z) = decode_normal(a, b);
+                        let (x,y,z) = vec_encoding.managed().decode(a, b);
                         normals.push(Float3 { x, y, z });
                     }
-                    DXGI_FORMAT_R32G32B32_FLOAT => {
-                        // skip until I have test data for this
-                        return Err(HookError::MeshUpdateFailed("unsupported normal format".to_owned()).into());
-                        // normals.push(Float3 {
-                        //     x:*(vertpos as *const f32),
-                        //     y:*(vertpos.offset(4) as *const f32),
-                        //     z:*(vertpos.offset(8) as *const f32) });
-                    },
-                    DXGI_FORMAT_R32G32B32A32_FLOAT => {
-                        return Err(HookError::MeshUpdateFailed("unsupported normal format".to_owned()).into());
-                    },
-                    DXGI_FORMAT_R8G8B8A8_UNORM => {
-                        // the normal is stored as a 4 byte RGBA value where each
-                        // component is a byte.  the value is in the range 0-255.  we need to convert it
-                        // to a float in the range -1.0 to 1.0
-                        // furthermore, MM might have reversed it, so we need to check the reverse flag
-                        // the reverse only applies to the first three components, w is always at end.
-                        // so basically x and z are swapped if reverse is true.
-                        // (don't ask me why games use this format, I just "work here", though it could be a bug in mm)
-                        let fval = |f| f / 255.0 * 2.0 - 1.0;
-                        if reverse {
-                            // these offsets are correct becase vertpos is a byte pointer and the format is bytes
-                            let x = fval(*(vertpos.offset(2)) as f32); 
-                            let y = fval(*(vertpos.offset(1)) as f32);
-                            let z = fval(*(vertpos.offset(0)) as f32);
-                            normals.push(Float3 { x, y, z });
-                        } else {
-                            let x = fval(*(vertpos) as f32);
-                            let y = fval(*(vertpos.offset(1)) as f32);
-                            let z = fval(*(vertpos.offset(2)) as f32);
-                            normals.push(Float3 { x, y, z });
-                        }
-                    },
-                    x => {
-                        return Err(HookError::MeshUpdateFailed(format!("unsupported normal format: {}", x)).into());
+                    _ => {
+                        let v = read_element(vertpos, norm_codec)?;
+                        normals.push(element_to_vector(norm_codec, &v, reverse));
                     }
                 }
             }
@@ -346,32 +472,9 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
             let tex_elem = tex_elem.ok_or(HookError::MeshUpdateFailed("missing texcoord in input layout".to_owned()))?;
             unsafe {
                 let vertpos = data.offset((i * layout.size) as isize + tex_elem.AlignedByteOffset as isize);
-                // support these formats
-                match tex_elem.Format {
-                    DXGI_FORMAT_R16G16B16A16_SNORM => {
-                        let vertpos = vertpos as *const i16;
-                        let u = ptr::read_unaligned(vertpos);
-                        let v = ptr::read_unaligned(vertpos.offset(1));
-                        let x = f32::max(-1.0, u as f32 * 32767_f32);
-                        let y = f32::max(-1.0, v as f32 * 32767_f32);
-                        texcoords.push(Float2 { x, y });
-                    }
-                    DXGI_FORMAT_R32G32_FLOAT => {
-                        texcoords.push(Float2 {
-                            x:*(vertpos as *const f32), // TODO should use read_unaligned??
-                            y:*(vertpos.offset(4) as *const f32) });
-                    },
-                    DXGI_FORMAT_R16G16_FLOAT => {
-                        // convert to f32
-                        // TODO: not sure this is right
-                        let x = *(vertpos as *const u16) as f32; // TODO should use read_unaligned??
-                        let y = *(vertpos.offset(2) as *const u16) as f32;
-                        texcoords.push(Float2 { x, y });
-                    },
-                    _ => {
-                        return Err(HookError::MeshUpdateFailed("unsupported texcoord format".to_owned()).into());
-                    }
-                }
+                let tex_codec = tex_codec.ok_or(HookError::MeshUpdateFailed("missing texcoord codec".to_owned()))?;
+                let [x, y, _, _] = read_element(vertpos, tex_codec)?;
+                texcoords.push(Float2 { x, y });
             }
         }
     }
@@ -389,13 +492,17 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
     let nfaces = indices.len() / 3;
 
     if update_normals {
-        let ret = unsafe {
-            compute_normals_32.ok_or_else(|| HookError::MeshUpdateFailed("failed to find DirectX_ComputeNormals_32".to_owned()))?
-                (indices.as_ptr(), nfaces, positions.as_ptr(), positions.len(), flags, normals.as_mut_ptr())
-        };
-
-        if ret != S_OK {
-            return Err(HookError::MeshUpdateFailed(format!("failed to compute normals: {}", ret)));
+        if let Some(compute_normals_32) = compute_normals_32 {
+            let ret = unsafe {
+                compute_normals_32(indices.as_ptr(), nfaces, positions.as_ptr(), positions.len(), flags, normals.as_mut_ptr())
+            };
+            if ret != S_OK {
+                return Err(HookError::MeshUpdateFailed(format!("failed to compute normals: {}", ret)));
+            }
+        } else {
+            let (weighting, clockwise) = native_normal_settings(flags);
+            compute_normals(indices, &positions, weighting, clockwise, &mut normals)
+                .map_err(|e| HookError::MeshUpdateFailed(format!("failed to compute normals: {}", e)))?;
         }
     }
 
@@ -418,29 +525,30 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
             .find(|l| ptr_to_str(l.SemanticName).starts_with("bitangent"));
         // check for other name on bitan
         if bitan_elem.is_none() {
-            bitan_elem = Some(layout.layout.iter()
-                .find(|l| ptr_to_str(l.SemanticName).starts_with("binormal"))
-                .ok_or(HookError::MeshUpdateFailed("missing bitangent in input layout".to_owned()))?);
+            bitan_elem = layout.layout.iter()
+                .find(|l| ptr_to_str(l.SemanticName).starts_with("binormal"));
+        }
+        // with qtangent the handedness is in the frame, so there may not be one
+        if bitan_elem.is_none() && !qtangent {
+            return Err(HookError::MeshUpdateFailed("missing bitangent in input layout".to_owned()));
         }
-        let ret = unsafe {
-            compute_tangentframe_32tb.ok_or_else(|| HookError::MeshUpdateFailed("failed to find DirectX_ComputeTangentFrame_32TB".to_owned()))?
-                (indices.as_ptr(), nfaces, positions.as_ptr(), normals.as_ptr(), texcoords.as_ptr(), positions.len(), tangents.as_mut_ptr(), bitangents.as_mut_ptr())
-        };
-        if ret != S_OK {
-            return Err(HookError::MeshUpdateFailed(format!("failed to compute tangents: {}", ret)));
+        if let Some(compute_tangentframe_32tb) = compute_tangentframe_32tb {
+            let ret = unsafe {
+                compute_tangentframe_32tb(indices.as_ptr(), nfaces, positions.as_ptr(), normals.as_ptr(), texcoords.as_ptr(),
+                    positions.len(), tangents.as_mut_ptr(), bitangents.as_mut_ptr())
+            };
+            if ret != S_OK {
+                return Err(HookError::MeshUpdateFailed(format!("failed to compute tangents: {}", ret)));
+            }
+        } else {
+            compute_tangent_frame(indices, &positions, &normals, &texcoords, &mut tangents, &mut bitangents)
+                .map_err(|e| HookError::MeshUpdateFailed(format!("failed to compute tangents: {}", e)))?;
         }
         (tan_elem, bitan_elem)
     } else {
         (None, None)
     };
 
-    // float to byte conversion function
-    let f_to_u8 = |f:f32| -> u8 {
-        //((f + 1.0) * 127.5 + 0.5) as u8
-        ((f + 1.0) * 127.5).round() as u8
-        //round((floating_point_value + 1) * 127.5)
-    };
-
     // Helper fn to write the vectors back to the original data using the various offsets and formats
     let write_vector = |i:u32,what:(&str, &D3D11_INPUT_ELEMENT_DESC), vec:&Float3| unsafe {
         let (name,elem) = what;
@@ -449,14 +557,14 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
         match elem.Format {
             DXGI_FORMAT_R16G16B16A16_SINT if name == "norm" => {
                 // norm packed into first two s16s
-                let (a,b) = encode_normal(vec);
+                let (a,b) = vec_encoding.encode(vec);
                 let vertpos = vertpos as *mut i16;
                 ptr::write_unaligned(vertpos, a);
                 ptr::write_unaligned(vertpos.offset(1), b);
             }
             DXGI_FORMAT_R16G16B16A16_SINT if name == "tan" => {
                 // tang packed into last two s16s
-                let (a,b) = encode_normal(vec);
+                let (a,b) = vec_encoding.encode(vec);
                 let vertpos = vertpos as *mut i16;
                 ptr::write_unaligned(vertpos.offset(2), a);
                 ptr::write_unaligned(vertpos.offset(3), b);
@@ -467,33 +575,20 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
                 b.x = -b.x;  b.y = -b.y;  b.z = -b.z;   // <── flip handedness
                 let vec = b;
 
-                let (a,b) = encode_normal(&vec);
+                let (a,b) = vec_encoding.encode(&vec);
                 let vertpos = vertpos as *mut i16;
                 ptr::write_unaligned(vertpos, a);
                 ptr::write_unaligned(vertpos.offset(1), b);                
             }
-            DXGI_FORMAT_R8G8B8A8_UNORM => {
-                if reverse {
-                    *(vertpos) = f_to_u8(vec.z);
-                    *(vertpos.offset(1)) = f_to_u8(vec.y);
-                    *(vertpos.offset(2)) = f_to_u8(vec.x);
-                    *(vertpos.offset(3)) = 0;
-                } else {
-                    *(vertpos) = f_to_u8(vec.x);
-                    *(vertpos.offset(1)) = f_to_u8(vec.y);
-                    *(vertpos.offset(2)) = f_to_u8(vec.z);
-                    *(vertpos.offset(3)) = 0;
-                }
-            },
-            DXGI_FORMAT_R32G32B32A32_FLOAT => {
-                return Err(HookError::MeshUpdateFailed(format!("unsupported vector format: {}", elem.Format)));
-            },
             _ => {
-                return Err(HookError::MeshUpdateFailed(format!("unsupported vector format: {}", elem.Format)));
+                // keep the existing w, some games store the handedness there
+                let codec = element_codec(elem, 3, name)?;
+                let old = read_element(vertpos, codec)?;
+                write_element(vertpos, codec, &vector_to_element(codec, vec, reverse, old[3]))?;
             }
         }
 
-        Ok(())
+        Ok::<(), HookError>(())
     };
 
     // some cases require the tangent to get flipped to match the game's coordinate system
@@ -508,24 +603,39 @@ pub fn update_normals(data:*mut u8, name:&str, profile:ModSnapProfile, mod_ts_up
     }
 
     for i in 0..vert_count {
-        if update_normals {
+        if qtangent {
+            // normal, tangent and handedness all go in the four s16s of the normal
+            let q = encode_qtangent(&normals[i as usize], &tangents[i as usize], &bitangents[i as usize]);
+            unsafe {
+                let vertpos = data.offset((i * layout.size) as isize + norm_offset as isize) as *mut i16;
+                for (k, v) in q.iter().enumerate() {
+                    ptr::write_unaligned(vertpos.add(k), *v);
+                }
+            }
+        } else if update_normals || reencode {
             let writing = ("norm", norm_elem);
             write_vector(i, writing, &normals[i as usize])?;
         }
     
         if update_tangents {            
             let tan_elem = tan_elem.ok_or(HookError::MeshUpdateFailed("missing tangent in input layout".to_owned()))?;
-            let writing = ("tan", tan_elem);
-            write_vector(i, writing, &tangents[i as usize])?;
-            let bitan_elem = bitan_elem.ok_or(HookError::MeshUpdateFailed("missing bitangent in input layout".to_owned()))?;
-            let writing = ("bit", bitan_elem);
-            write_vector(i, writing, &bitangents[i as usize])?;
+            if !(qtangent && ptr::eq(tan_elem, norm_elem)) {
+                let writing = ("tan", tan_elem);
+                write_vector(i, writing, &tangents[i as usize])?;
+            }
+            match bitan_elem {
+                Some(bitan_elem) if !(qtangent && bitan_elem.Format == DXGI_FORMAT_R16G16_SINT) => {
+                    let writing = ("bit", bitan_elem);
+                    write_vector(i, writing, &bitangents[i as usize])?;
+                }
+                _ => (),
+            }
         }
     }
 
     if update_tangents {
         #[cfg(feature = "tangent_debug")]
-        print_debug_info(data, layout.size as usize, norm_offset, vert_count as usize, enc_vec_octa);
+        print_debug_info(data, layout.size as usize, norm_offset, vert_count as usize, vec_encoding == VecEncoding::Octa);
     }
 
 
@@ -0,0 +1,535 @@
+//! Fills a mod's vertex and index buffers straight from its mmobj file, so that the mod can be
+//! loaded without the managed `FillModData` callback.  Enabled by the `GameProfileNativeMMObjLoad`
+//! profile setting.
+//!
+//! This writes the same data as `ModDBInterop.fillModDataInternalHelper` does for mods that take
+//! their blend data from the mod mesh and use the default mesh read flags (snapshot transforms
+//! reversed, "addx" blend weight adjustment).  Managed code only sets `ModData.meshPath` for
+//! those mods.  Anything else that can't be written the same way (formats, semantics) is an
+//! error, and the caller falls back to the callback.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+use mmobj::{FaceVert, MMObj};
+use shared_dx::dx11rs::VertexFormat;
+use types::interop::ModData;
+use vertex_codec::*;
+use winapi::um::d3d11::{D3D11_APPEND_ALIGNED_ELEMENT, D3D11_INPUT_PER_VERTEX_DATA};
+
+use crate::data_encoding::VecEncoding;
+use crate::mod_vector::{Float2, Float3};
+
+/// A per-vertex element of the input layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FillElement {
+    /// Upper case semantic name
+    pub semantic: String,
+    pub index: u32,
+    pub format: DXGI_FORMAT,
+    pub offset: u32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FillOptions {
+    /// 4 byte normals, tangents and binormals are stored zyxw (`GameProfile.reverse_normals`)
+    pub reverse_normals: bool,
+    /// Encoding of vectors in the 16 bit int formats.  This is the encoding managed code would
+    /// write (`VecEncoding::managed`), `update_normals` re-encodes afterwards as usual.
+    pub vec_encoding: VecEncoding,
+    /// Whether the mod's own blend data may be used (the "mod" weight mode)
+    pub blend_from_mod: bool,
+}
+
+/// Mesh data from an mmobj with the snapshot transforms reversed.
+#[derive(Debug, Clone)]
+pub struct NativeMesh {
+    positions: Vec<Float3>,
+    normals: Vec<Float3>,
+    texcoords: Vec<Float2>,
+    /// Blend indices and weights for each position; empty if the mesh has no blend data.
+    blend: Vec<([u8; 4], [f32; 4])>,
+    faces: Vec<[FaceVert; 3]>,
+}
+
+impl NativeMesh {
+    pub fn load(path: &str) -> Result<NativeMesh, String> {
+        let obj = mmobj::load_mmobj(path).map_err(|e| e.to_string())?;
+        NativeMesh::from_mmobj(obj)
+    }
+
+    pub fn from_mmobj(obj: MMObj) -> Result<NativeMesh, String> {
+        let mut mesh = NativeMesh {
+            positions: obj.positions.iter().map(|p| Float3::new(p.x, p.y, p.z)).collect(),
+            normals: obj.normals.iter().map(|n| Float3::new(n.x, n.y, n.z)).collect(),
+            texcoords: obj.texcoord.iter().map(|t| Float2 { x: t.x, y: t.y }).collect(),
+            blend: vec![],
+            faces: obj.faces,
+        };
+        if !obj.vblend.is_empty() {
+            if obj.vblend.len() != mesh.positions.len() {
+                return Err(format!("{} blend entries for {} positions", obj.vblend.len(), mesh.positions.len()));
+            }
+            mesh.blend = obj.vblend.iter().map(|pairs| blend_vectors(pairs)).collect::<Result<_, _>>()?;
+        }
+        let posx: Vec<&String> = obj.posx.iter().flatten().collect();
+        let uvx: Vec<&String> = obj.uvx.iter().flatten().collect();
+        for xf in posx.iter().rev() {
+            mesh.reverse_position_transform(xf)?;
+        }
+        for xf in uvx.iter().rev() {
+            mesh.reverse_uv_transform(xf)?;
+        }
+        Ok(mesh)
+    }
+
+    /// Undo one `#pos_xforms` transform (e.g. `rot_x_90`, `scale_0.1`).  These are the
+    /// transforms `MeshTransform.reverseFunc` can reverse.
+    fn reverse_position_transform(&mut self, xf: &str) -> Result<(), String> {
+        let parts: Vec<&str> = xf.split('_').collect();
+        let amount = |s: &str| s.trim().parse::<f32>()
+            .map_err(|e| format!("bad amount in transform '{}': {}", xf, e));
+        match (parts[0].to_ascii_lowercase().as_str(), parts.len()) {
+            ("scale", 2) => {
+                let s = 1.0 / amount(parts[1])?;
+                for p in self.positions.iter_mut() {
+                    *p = p.scale(s);
+                }
+                for n in self.normals.iter_mut() {
+                    *n = n.scale(s).normalized().unwrap_or(*n);
+                }
+            },
+            ("rot", 3) => {
+                let (sin, cos) = (-amount(parts[2])?).to_radians().sin_cos();
+                let rot: fn(&Float3, f32, f32) -> Float3 = match parts[1].to_ascii_lowercase().as_str() {
+                    "x" => |v, sin, cos| Float3::new(v.x, v.y * cos - v.z * sin, v.y * sin + v.z * cos),
+                    "y" => |v, sin, cos| Float3::new(v.x * cos + v.z * sin, v.y, v.z * cos - v.x * sin),
+                    "z" => |v, sin, cos| Float3::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos, v.z),
+                    axis => return Err(format!("unknown rotation axis '{}' in transform '{}'", axis, xf)),
+                };
+                for v in self.positions.iter_mut().chain(self.normals.iter_mut()) {
+                    *v = rot(v, sin, cos);
+                }
+            },
+            _ => return Err(format!("can't reverse position transform '{}'", xf)),
+        }
+        Ok(())
+    }
+
+    /// Undo one `#uv_xforms` transform; flips are their own inverse.
+    fn reverse_uv_transform(&mut self, xf: &str) -> Result<(), String> {
+        let parts: Vec<&str> = xf.split('_').collect();
+        let flip_x = match (parts[0].to_ascii_lowercase().as_str(), parts.get(1).map(|a| a.to_ascii_lowercase())) {
+            ("flip", Some(axis)) if axis == "x" => true,
+            ("flip", Some(axis)) if axis == "y" => false,
+            _ => return Err(format!("can't reverse uv transform '{}'", xf)),
+        };
+        for t in self.texcoords.iter_mut() {
+            if flip_x { t.x = 1.0 - t.x } else { t.y = 1.0 - t.y }
+        }
+        Ok(())
+    }
+
+    /// Number of vertices the vertex buffer needs; indexed buffers only have each distinct
+    /// face vertex once.
+    #[cfg(test)]
+    pub fn vert_count(&self, indexed: bool) -> usize {
+        if indexed { unique_tri_verts(&self.faces).0.len() } else { self.faces.len() * 3 }
+    }
+}
+
+/// Blend indices and weights of one position, padded to 4.  If the weights sum to less than 1,
+/// the difference is added to the first (the managed code's "addx" adjustment).
+fn blend_vectors(pairs: &[mmobj::BlendPair]) -> Result<([u8; 4], [f32; 4]), String> {
+    if pairs.len() > 4 {
+        return Err(format!("{} blend pairs for a vertex, at most 4 are supported", pairs.len()));
+    }
+    let mut idx = [0u8; 4];
+    let mut wgt = [0f32; 4];
+    for (i, bp) in pairs.iter().enumerate() {
+        idx[i] = u8::try_from(bp.idx).map_err(|_| format!("blend index {} is too large", bp.idx))?;
+        wgt[i] = bp.weight;
+    }
+    let sum: f32 = wgt.iter().sum();
+    if 1.0 - sum > 0.0 {
+        wgt[0] += 1.0 - sum;
+    }
+    Ok((idx, wgt))
+}
+
+/// The distinct face vertices in order of first use, and the index of each face vertex in that
+/// list (same as `MeshUtil.uniqueTriVerts`).
+fn unique_tri_verts(faces: &[[FaceVert; 3]]) -> (Vec<FaceVert>, Vec<u32>) {
+    let mut seen: HashMap<(usize, usize, usize), u32> = HashMap::new();
+    let mut verts = vec![];
+    let indices = faces.iter().flatten().map(|v| {
+        *seen.entry((v.pos, v.nrm, v.tex)).or_insert_with(|| {
+            verts.push(*v);
+            (verts.len() - 1) as u32
+        })
+    }).collect();
+    (verts, indices)
+}
+
+/// Tangent and binormal from the normal, computed the same (inaccurate) way as
+/// `DataWriters.modmBinormalTangent`.  `update_normals` normally replaces these.
+fn crude_tangent_binormal(n: &Float3) -> (Float3, Float3) {
+    let v1 = n.cross(&Float3::new(0.0, 0.0, 1.0));
+    let v2 = n.cross(&Float3::new(0.0, 1.0, 0.0));
+    let t = if v1.length() > v2.length() { v1 } else { v2 };
+    let t = t.normalized().unwrap_or(t);
+    let b = n.cross(&t);
+    (t, b.normalized().unwrap_or(b))
+}
+
+fn put_f32s(dst: &mut [u8], vals: &[f32]) {
+    for (i, f) in vals.iter().enumerate() {
+        dst[i * 4..i * 4 + 4].copy_from_slice(&f.to_le_bytes());
+    }
+}
+
+fn put_i16s(dst: &mut [u8], vals: &[i16]) {
+    for (i, v) in vals.iter().enumerate() {
+        dst[i * 2..i * 2 + 2].copy_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Vector as 4 bytes biased into 0..255, w is 0.  Truncated like the managed `write4ByteVector`
+/// (including -1 wrapping to 255) so both fill paths produce the same buffer.
+fn put_byte_vector(dst: &mut [u8], v: &Float3, reverse: bool) {
+    let b = |f: f32| (f * 128.0 + 127.0) as i32 as u8;
+    let (x, y, z) = if reverse { (v.z, v.y, v.x) } else { (v.x, v.y, v.z) };
+    dst[0..4].copy_from_slice(&[b(x), b(y), b(z), 0]);
+}
+
+fn unsupported(el: &FillElement) -> String {
+    let fname = codec(el.format).map(|c| c.name.to_owned()).unwrap_or_else(|_| el.format.to_string());
+    format!("unsupported format {} for {}{}", fname, el.semantic, el.index)
+}
+
+/// Write element `el` of face vertex `v` into `dst`, which is the element's space in the vertex.
+fn write_element(mesh: &NativeMesh, v: &FaceVert, el: &FillElement, opts: &FillOptions, dst: &mut [u8])
+    -> Result<(), String> {
+    let normal = || mesh.normals[v.nrm];
+    let enc = |x: f32, y: f32, z: f32| opts.vec_encoding.encode(&Float3::new(x, y, z));
+    match el.semantic.as_str() {
+        "POSITION" => match el.format {
+            DXGI_FORMAT_R32G32B32_FLOAT => {
+                let p = mesh.positions[v.pos];
+                put_f32s(dst, &[p.x, p.y, p.z]);
+            },
+            _ => return Err(unsupported(el)),
+        },
+        // only the first texture coordinate is tracked, others are left zeroed
+        "TEXCOORD" if el.index > 0 => {},
+        "TEXCOORD" => {
+            let t = &mesh.texcoords[v.tex];
+            match el.format {
+                DXGI_FORMAT_R32G32_FLOAT => put_f32s(dst, &[t.x, t.y]),
+                DXGI_FORMAT_R16G16_FLOAT => {
+                    dst[0..2].copy_from_slice(&f32_to_f16(t.x).to_le_bytes());
+                    dst[2..4].copy_from_slice(&f32_to_f16(t.y).to_le_bytes());
+                },
+                // truncated, not rounded, to match the managed writer
+                DXGI_FORMAT_R16G16B16A16_SNORM => put_i16s(dst, &[(t.x * 32767.0) as i16, (t.y * 32767.0) as i16, 0, 0]),
+                _ => return Err(unsupported(el)),
+            }
+        },
+        "NORMAL" => {
+            let n = normal();
+            match el.format {
+                DXGI_FORMAT_R32G32B32_FLOAT => put_f32s(dst, &[n.x, n.y, n.z]),
+                DXGI_FORMAT_R8G8B8A8_UNORM => put_byte_vector(dst, &n, opts.reverse_normals),
+                DXGI_FORMAT_R16G16B16A16_SINT => {
+                    // the second pair is the tangent in this format; a placeholder that
+                    // update_normals overwrites
+                    let (nx, ny) = opts.vec_encoding.encode(&n);
+                    let (tx, ty) = enc(0.0, 0.0, 1.0);
+                    put_i16s(dst, &[nx, ny, tx, ty]);
+                },
+                _ => return Err(unsupported(el)),
+            }
+        },
+        "TANGENT" | "BINORMAL" | "BITANGENT" => {
+            let (t, b) = crude_tangent_binormal(&normal());
+            let vec = if el.semantic == "TANGENT" { t } else { b };
+            match el.format {
+                DXGI_FORMAT_R32G32B32_FLOAT => put_f32s(dst, &[vec.x, vec.y, vec.z]),
+                DXGI_FORMAT_R8G8B8A8_UNORM => put_byte_vector(dst, &vec, opts.reverse_normals),
+                DXGI_FORMAT_R16G16_SINT => {
+                    let (x, y) = enc(0.0, 1.0, 0.0);
+                    put_i16s(dst, &[x, y]);
+                },
+                _ => return Err(unsupported(el)),
+            }
+        },
+        "BLENDINDICES" => match el.format {
+            DXGI_FORMAT_R8G8B8A8_UNORM => dst[0..4].copy_from_slice(&mesh.blend[v.pos].0),
+            _ => return Err(unsupported(el)),
+        },
+        "BLENDWEIGHT" => {
+            let w = &mesh.blend[v.pos].1;
+            match el.format {
+                // .net rounds to even
+                DXGI_FORMAT_R8G8B8A8_UNORM => for (d, w) in dst.iter_mut().zip(w.iter()) {
+                    *d = (w * 255.0).round_ties_even() as u8;
+                },
+                DXGI_FORMAT_R32G32B32A32_FLOAT => put_f32s(dst, w),
+                _ => return Err(unsupported(el)),
+            }
+        },
+        "COLOR" => match el.format {
+            DXGI_FORMAT_R32G32B32A32_FLOAT => put_f32s(dst, &[1.0; 4]),
+            DXGI_FORMAT_B8G8R8A8_UNORM => dst[0..4].copy_from_slice(&[255; 4]),
+            _ => return Err(unsupported(el)),
+        },
+        _ => return Err(format!("unsupported semantic {}", el.semantic)),
+    }
+    Ok(())
+}
+
+/// Fill `vb` with vertices of `vert_size` bytes laid out as `elements`, and `ib` with indices of
+/// `index_elem_size` bytes if it is set (otherwise each face vertex gets its own vertex and `ib`
+/// must be empty).  The buffer sizes must match the mesh exactly.
+pub fn fill_buffers(mesh: &NativeMesh, elements: &[FillElement], vert_size: usize, opts: &FillOptions,
+    vb: &mut [u8], ib: &mut [u8], index_elem_size: Option<u32>) -> Result<(), String> {
+    let mut elements = elements.to_vec();
+    elements.sort_by_key(|el| el.offset);
+    let mut end = 0;
+    for el in elements.iter() {
+        if el.offset == D3D11_APPEND_ALIGNED_ELEMENT {
+            return Err(format!("{}{} has an append aligned offset", el.semantic, el.index));
+        }
+        let size = codec(el.format).map_err(|_| unsupported(el))?.size();
+        if (el.offset as usize) < end || el.offset as usize + size > vert_size {
+            return Err(format!("{}{} at offset {} (size {}) overlaps another element or the end of the {} byte vertex",
+                el.semantic, el.index, el.offset, size, vert_size));
+        }
+        end = el.offset as usize + size;
+    }
+    let needs_blend = elements.iter().any(|el| el.semantic == "BLENDINDICES" || el.semantic == "BLENDWEIGHT");
+    if needs_blend && (!opts.blend_from_mod || mesh.blend.is_empty()) {
+        return Err("layout needs blend data, which is only available from the mod mesh in the mod weight mode".to_owned());
+    }
+    for fv in mesh.faces.iter().flatten() {
+        if fv.pos >= mesh.positions.len() || fv.nrm >= mesh.normals.len() || fv.tex >= mesh.texcoords.len() {
+            return Err(format!("face vertex {:?} is out of range", fv));
+        }
+    }
+
+    let (verts, indices) = match index_elem_size {
+        Some(_) => unique_tri_verts(&mesh.faces),
+        None => (mesh.faces.iter().flatten().copied().collect(), vec![]),
+    };
+    if vb.len() != verts.len() * vert_size {
+        return Err(format!("vb size mismatch: buffer is {} bytes, mesh needs {} ({} verts of {} bytes)",
+            vb.len(), verts.len() * vert_size, verts.len(), vert_size));
+    }
+    let elem_size = index_elem_size.unwrap_or(0) as usize;
+    if ib.len() != indices.len() * elem_size {
+        return Err(format!("ib size mismatch: buffer is {} bytes, mesh needs {} ({} indices of {} bytes)",
+            ib.len(), indices.len() * elem_size, indices.len(), elem_size));
+    }
+
+    for (v, vdata) in verts.iter().zip(vb.chunks_exact_mut(vert_size)) {
+        for el in elements.iter() {
+            let size = codec(el.format).map_err(|_| unsupported(el))?.size();
+            let off = el.offset as usize;
+            write_element(mesh, v, el, opts, &mut vdata[off..off + size])?;
+        }
+    }
+    match elem_size {
+        0 => {},
+        2 => for (i, d) in indices.iter().zip(ib.chunks_exact_mut(2)) {
+            let i = u16::try_from(*i).map_err(|_| format!("index {} doesn't fit in 16 bits", i))?;
+            d.copy_from_slice(&i.to_le_bytes());
+        },
+        4 => for (i, d) in indices.iter().zip(ib.chunks_exact_mut(4)) {
+            d.copy_from_slice(&i.to_le_bytes());
+        },
+        n => return Err(format!("unsupported index size {}", n)),
+    }
+    Ok(())
+}
+
+/// Per-vertex elements of an input layout.
+pub fn layout_elements(layout: &VertexFormat) -> Vec<FillElement> {
+    layout.layout.iter()
+        .filter(|el| el.InputSlotClass == D3D11_INPUT_PER_VERTEX_DATA && !el.SemanticName.is_null())
+        .map(|el| FillElement {
+            semantic: unsafe { CStr::from_ptr(el.SemanticName) }.to_string_lossy().to_ascii_uppercase(),
+            index: el.SemanticIndex,
+            format: el.Format,
+            offset: el.AlignedByteOffset,
+        })
+        .collect()
+}
+
+/// Fill the buffers of the mod from its mesh file, if managed code provided one.  Returns
+/// Ok(false) if it didn't, in which case the buffers need to be filled with `FillModData`.
+pub fn fill_mod_buffers(mdat: &ModData, layout: &VertexFormat, reverse_normals: bool,
+    vb: &mut [u8], ib: &mut [u8]) -> Result<bool, String> {
+    let path = util::from_wide_str(&mdat.meshPath).map_err(|e| format!("bad mesh path: {:?}", e))?;
+    if path.trim().is_empty() {
+        return Ok(false);
+    }
+    let profile = &mdat.mod_snap_profile;
+    let vec_encoding = if profile.valid {
+        let name = util::from_wide_str(&profile.vec_encoding).unwrap_or_default();
+        VecEncoding::from_name(&name).unwrap_or(VecEncoding::Packed).managed()
+    } else {
+        VecEncoding::Packed
+    };
+    let opts = FillOptions { reverse_normals, vec_encoding, blend_from_mod: mdat.mesh_blend_from_mod };
+    let mesh = NativeMesh::load(&path)?;
+    let index_elem_size = mdat.numbers.index_info().map(|(_, size)| size);
+    fill_buffers(&mesh, &layout_elements(layout), layout.size as usize, &opts, vb, ib, index_elem_size)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn el(semantic: &str, index: u32, format: DXGI_FORMAT, offset: u32) -> FillElement {
+        FillElement { semantic: semantic.to_owned(), index, format, offset }
+    }
+
+    fn opts() -> FillOptions {
+        FillOptions { reverse_normals: false, vec_encoding: VecEncoding::Packed, blend_from_mod: true }
+    }
+
+    fn f32_at(data: &[u8], off: usize) -> f32 {
+        f32::from_le_bytes([data[off], data[off + 1], data[off + 2], data[off + 3]])
+    }
+
+    /// A quad as two triangles that share an edge
+    const QUAD: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+        vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+        #vbld 0/1.0 1/0.0 0/0.0 0/0.0\n#vbld 0/0.5 1/0.25 0/0.0 0/0.0\n\
+        #vbld 1/1.0 0/0.0 0/0.0 0/0.0\n#vbld 1/0.5 0/0.5 0/0.0 0/0.0\n\
+        f 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\n";
+
+    fn quad() -> NativeMesh {
+        NativeMesh::from_mmobj(mmobj::parse_mmobj("quad", QUAD).unwrap()).unwrap()
+    }
+
+    fn basic_layout() -> Vec<FillElement> {
+        vec![
+            el("TEXCOORD", 0, DXGI_FORMAT_R32G32_FLOAT, 16),
+            el("POSITION", 0, DXGI_FORMAT_R32G32B32_FLOAT, 0),
+            el("NORMAL", 0, DXGI_FORMAT_R8G8B8A8_UNORM, 12),
+        ]
+    }
+
+    #[test]
+    fn test_indexed_and_unindexed() {
+        let mesh = quad();
+        assert_eq!(mesh.vert_count(true), 4);
+        assert_eq!(mesh.vert_count(false), 6);
+
+        let mut vb = vec![0u8; 4 * 24];
+        let mut ib = vec![0u8; 6 * 2];
+        fill_buffers(&mesh, &basic_layout(), 24, &opts(), &mut vb, &mut ib, Some(2)).unwrap();
+        assert_eq!(mod_vector_indices(&ib, 2), vec![0, 1, 2, 0, 2, 3]);
+        // third unique vert is (1,1,0) with uv (1,1)
+        let v = &vb[2 * 24..3 * 24];
+        assert_eq!((f32_at(v, 0), f32_at(v, 4), f32_at(v, 8)), (1.0, 1.0, 0.0));
+        assert_eq!(&v[12..16], &[127, 127, 255, 0]);
+        assert_eq!((f32_at(v, 16), f32_at(v, 20)), (1.0, 1.0));
+
+        let mut vb = vec![0u8; 6 * 24];
+        fill_buffers(&mesh, &basic_layout(), 24, &opts(), &mut vb, &mut [], None).unwrap();
+        // fifth face vertex is the third vertex of the second triangle, position 3
+        assert_eq!((f32_at(&vb, 4 * 24), f32_at(&vb, 4 * 24 + 4)), (1.0, 1.0));
+        assert_eq!((f32_at(&vb, 5 * 24), f32_at(&vb, 5 * 24 + 4)), (0.0, 1.0));
+
+        // sizes must match
+        let mut vb = vec![0u8; 5 * 24];
+        assert!(fill_buffers(&mesh, &basic_layout(), 24, &opts(), &mut vb, &mut [], None).is_err());
+    }
+
+    fn mod_vector_indices(ib: &[u8], size: u32) -> Vec<u32> {
+        crate::mod_vector::index_data_to_u32(ib, size)
+    }
+
+    #[test]
+    fn test_blend_data() {
+        let mesh = quad();
+        let layout = vec![
+            el("POSITION", 0, DXGI_FORMAT_R32G32B32_FLOAT, 0),
+            el("BLENDINDICES", 0, DXGI_FORMAT_R8G8B8A8_UNORM, 12),
+            el("BLENDWEIGHT", 0, DXGI_FORMAT_R8G8B8A8_UNORM, 16),
+        ];
+        let mut vb = vec![0u8; 6 * 20];
+        fill_buffers(&mesh, &layout, 20, &opts(), &mut vb, &mut [], None).unwrap();
+        // second position: weights 0.5 + 0.25 are short of 1, the rest goes to the first
+        let v = &vb[20..40];
+        assert_eq!(&v[12..16], &[0, 1, 0, 0]);
+        assert_eq!(&v[16..20], &[191, 64, 0, 0]);
+
+        let no_blend = FillOptions { blend_from_mod: false, ..opts() };
+        assert!(fill_buffers(&mesh, &layout, 20, &no_blend, &mut vb, &mut [], None).is_err());
+    }
+
+    #[test]
+    fn test_reverse_transforms() {
+        let text = "v 0 10 0\nvt 0 0.25\nvn 0 1 0\n#pos_xforms rot_x_90 scale_10\n#uv_xforms flip_y\nf 1/1/1 1/1/1 1/1/1\n";
+        let mesh = NativeMesh::from_mmobj(mmobj::parse_mmobj("xf", text).unwrap()).unwrap();
+        // reversed in reverse order: scale by 1/10, then rotate -90 about x, which takes +y to -z
+        let p = mesh.positions[0];
+        assert!(p.sub(&Float3::new(0.0, 0.0, -1.0)).length() < 1e-5, "{:?}", p);
+        let n = mesh.normals[0];
+        assert!(n.sub(&Float3::new(0.0, 0.0, -1.0)).length() < 1e-5, "{:?}", n);
+        assert_eq!(mesh.texcoords[0].y, 0.75);
+
+        let text = "v 0 0 0\nvt 0 0\nvn 0 1 0\n#pos_xforms recenter\nf 1/1/1 1/1/1 1/1/1\n";
+        assert!(NativeMesh::from_mmobj(mmobj::parse_mmobj("xf", text).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_packed_formats() {
+        let mesh = quad();
+        let layout = vec![
+            el("POSITION", 0, DXGI_FORMAT_R32G32B32_FLOAT, 0),
+            el("NORMAL", 0, DXGI_FORMAT_R16G16B16A16_SINT, 12),
+            el("BINORMAL", 0, DXGI_FORMAT_R16G16_SINT, 20),
+            el("TEXCOORD", 0, DXGI_FORMAT_R16G16_FLOAT, 24),
+            el("TEXCOORD", 1, DXGI_FORMAT_R32G32_FLOAT, 28),
+        ];
+        let mut vb = vec![0u8; 6 * 36];
+        fill_buffers(&mesh, &layout, 36, &opts(), &mut vb, &mut [], None).unwrap();
+        let v = &vb[2 * 36..3 * 36];
+        let i16_at = |off: usize| i16::from_le_bytes([v[off], v[off + 1]]);
+        let (a, b) = VecEncoding::Packed.encode(&Float3::new(0.0, 0.0, 1.0));
+        assert_eq!((i16_at(12), i16_at(14)), (a, b));
+        assert_eq!(&v[24..28], &[0x00, 0x3c, 0x00, 0x3c]); // half 1.0, 1.0
+        assert!(v[28..36].iter().all(|&b| b == 0));
+
+        // unknown semantic and overlapping elements are errors
+        let bad = vec![el("POSITION", 0, DXGI_FORMAT_R32G32B32_FLOAT, 0), el("PSIZE", 0, DXGI_FORMAT_R32_FLOAT, 12)];
+        assert!(fill_buffers(&mesh, &bad, 16, &opts(), &mut vec![0u8; 6 * 16], &mut [], None).unwrap_err()
+            .contains("PSIZE"));
+        let bad = vec![el("POSITION", 0, DXGI_FORMAT_R32G32B32_FLOAT, 0), el("NORMAL", 0, DXGI_FORMAT_R8G8B8A8_UNORM, 8)];
+        assert!(fill_buffers(&mesh, &bad, 16, &opts(), &mut vec![0u8; 6 * 16], &mut [], None).is_err());
+    }
+
+    #[test]
+    fn test_monolith_mod() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../TestData/MonolithMod.mmobj");
+        let mesh = NativeMesh::load(path.to_str().unwrap()).unwrap();
+        let count = mesh.vert_count(true);
+        let prims = mesh.faces.len();
+        let mut vb = vec![0u8; count * 24];
+        let mut ib = vec![0u8; prims * 3 * 4];
+        fill_buffers(&mesh, &basic_layout(), 24, &opts(), &mut vb, &mut ib, Some(4)).unwrap();
+        let indices = mod_vector_indices(&ib, 4);
+        assert!(indices.iter().all(|&i| (i as usize) < count));
+        // every index refers to a vertex with the face vertex's position
+        for (fv, &i) in mesh.faces.iter().flatten().zip(indices.iter()) {
+            let p = mesh.positions[fv.pos];
+            let off = i as usize * 24;
+            assert_eq!((f32_at(&vb, off), f32_at(&vb, off + 4), f32_at(&vb, off + 8)), (p.x, p.y, p.z));
+        }
+    }
+}
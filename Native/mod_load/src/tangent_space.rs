@@ -0,0 +1,394 @@
+//! Normal and tangent frame generation, replacing the DirectXMesh dll that was previously used
+//! for this.
+//!
+//! Both generators weld vertices that are split only because of other attributes (normals are
+//! shared between vertices with the same position, tangents between vertices with the same
+//! position, normal and texcoord), so meshes without an index buffer, or with uv seams, still
+//! get smooth results.
+//!
+//! Tangents follow MikkTSpace: per-triangle tangents are projected onto the plane of each
+//! corner's normal and accumulated weighted by the corner angle, and the bitangent is
+//! `sign * cross(normal, tangent)` where the sign is the handedness of the uv mapping.  Unlike
+//! the reference implementation, vertices whose triangles have mixed handedness are not split,
+//! since we can't add vertices to the mod; the majority handedness wins.
+
+use std::collections::HashMap;
+
+use crate::mod_vector::{Float2, Float3};
+
+/// How face normals are weighted when they are accumulated into a vertex normal.  These map to
+/// the `CNORM_*` flags of DirectXMesh so that existing game profile settings still apply.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// By the angle of the triangle's corner at the vertex (DirectXMesh default)
+    Angle,
+    /// By the area of the triangle
+    Area,
+    /// All triangles contribute equally
+    Equal,
+}
+
+const EPSILON: f32 = 1e-12;
+
+fn add_scaled(acc: &mut Float3, v: &Float3, s: f32) {
+    *acc = acc.add(&v.scale(s));
+}
+
+/// Angle between two edges leaving a corner; zero if either is degenerate.
+fn corner_angle(e1: &Float3, e2: &Float3) -> f32 {
+    match (e1.normalized(), e2.normalized()) {
+        (Some(a), Some(b)) => a.dot(&b).clamp(-1.0, 1.0).acos(),
+        _ => 0.0,
+    }
+}
+
+/// Remove the component of `v` along the unit vector `n`.
+fn project_to_plane(v: &Float3, n: &Float3) -> Float3 {
+    v.sub(&n.scale(n.dot(v)))
+}
+
+/// Some unit vector perpendicular to the unit vector `n`.
+fn any_perpendicular(n: &Float3) -> Float3 {
+    // cross with whichever axis is least aligned with n
+    let axis = if n.x.abs() < 0.9 {
+        Float3::new(1.0, 0.0, 0.0)
+    } else {
+        Float3::new(0.0, 1.0, 0.0)
+    };
+    n.cross(&axis).normalized().unwrap_or(Float3::new(0.0, 0.0, 1.0))
+}
+
+/// Assign each vertex the index of the first vertex with the same key.  Keys are compared
+/// bitwise, so only exact duplicates are welded.
+fn weld<K, F>(count: usize, key: F) -> Vec<usize>
+where K: std::hash::Hash + Eq, F: Fn(usize) -> K {
+    let mut first: HashMap<K, usize> = HashMap::with_capacity(count);
+    (0..count).map(|i| *first.entry(key(i)).or_insert(i)).collect()
+}
+
+fn bits3(v: &Float3) -> [u32; 3] {
+    // +0.0 and -0.0 should weld
+    let b = |f: f32| if f == 0.0 { 0 } else { f.to_bits() };
+    [b(v.x), b(v.y), b(v.z)]
+}
+
+fn bits2(v: &Float2) -> [u32; 2] {
+    let b = |f: f32| if f == 0.0 { 0 } else { f.to_bits() };
+    [b(v.x), b(v.y)]
+}
+
+/// Check that every index is in range, and return the triangles.
+fn triangles(indices: &[u32], vert_count: usize) -> Result<Vec<[usize; 3]>, String> {
+    if !indices.len().is_multiple_of(3) {
+        return Err(format!("index count {} is not a multiple of 3", indices.len()));
+    }
+    indices.chunks_exact(3).map(|tri| {
+        let mut out = [0usize; 3];
+        for (o, &i) in out.iter_mut().zip(tri) {
+            if i as usize >= vert_count {
+                return Err(format!("index {} out of range for {} vertices", i, vert_count));
+            }
+            *o = i as usize;
+        }
+        Ok(out)
+    }).collect()
+}
+
+/// Compute smooth vertex normals for the triangle list `indices`.  Triangles are counter
+/// clockwise unless `clockwise` is set, which reverses the normals (as `CNORM_WIND_CW` does).
+/// Vertices that aren't used by any non-degenerate triangle get a zero normal.
+pub fn compute_normals(indices: &[u32], positions: &[Float3], weighting: NormalWeighting,
+    clockwise: bool, normals: &mut [Float3]) -> Result<(), String> {
+    let vert_count = positions.len();
+    if normals.len() != vert_count {
+        return Err(format!("normal count {} does not match vertex count {}", normals.len(), vert_count));
+    }
+    let tris = triangles(indices, vert_count)?;
+    let group = weld(vert_count, |i| bits3(&positions[i]));
+
+    let mut acc = vec![Float3::new(0.0, 0.0, 0.0); vert_count];
+    for tri in tris.iter() {
+        let p = [&positions[tri[0]], &positions[tri[1]], &positions[tri[2]]];
+        let face = p[1].sub(p[0]).cross(&p[2].sub(p[0]));
+        let face = if clockwise { face.neg() } else { face };
+        let unit = match face.normalized() {
+            Some(n) => n,
+            None => continue, // degenerate
+        };
+        for c in 0..3 {
+            let w = match weighting {
+                NormalWeighting::Angle => corner_angle(
+                    &p[(c + 1) % 3].sub(p[c]), &p[(c + 2) % 3].sub(p[c])),
+                // face is twice the area, the factor doesn't matter
+                NormalWeighting::Area => face.length(),
+                NormalWeighting::Equal => 1.0,
+            };
+            add_scaled(&mut acc[group[tri[c]]], &unit, w);
+        }
+    }
+    for (i, n) in normals.iter_mut().enumerate() {
+        *n = acc[group[i]].normalized().unwrap_or(Float3::new(0.0, 0.0, 0.0));
+    }
+    Ok(())
+}
+
+/// Compute MikkTSpace style tangents and bitangents for the triangle list `indices`.  `normals`
+/// are normalized before use, since normals decoded from packed formats are only approximately
+/// unit length.  Vertices with no usable uv mapping get an arbitrary tangent perpendicular to
+/// their normal.
+pub fn compute_tangent_frame(indices: &[u32], positions: &[Float3], normals: &[Float3],
+    texcoords: &[Float2], tangents: &mut [Float3], bitangents: &mut [Float3]) -> Result<(), String> {
+    let vert_count = positions.len();
+    for (what, len) in [("normal", normals.len()), ("texcoord", texcoords.len()),
+        ("tangent", tangents.len()), ("bitangent", bitangents.len())] {
+        if len != vert_count {
+            return Err(format!("{} count {} does not match vertex count {}", what, len, vert_count));
+        }
+    }
+    let tris = triangles(indices, vert_count)?;
+    let zero = Float3::new(0.0, 0.0, 0.0);
+    let normals: Vec<Float3> = normals.iter().map(|n| n.normalized().unwrap_or(zero)).collect();
+    let group = weld(vert_count, |i| {
+        (bits3(&positions[i]), bits3(&normals[i]), bits2(&texcoords[i]))
+    });
+
+    let mut tan_acc = vec![zero; vert_count];
+    // sum of the angle weights of triangles with preserved (positive) and flipped uv orientation
+    let mut orient_acc = vec![0.0f32; vert_count];
+    for tri in tris.iter() {
+        let p = [&positions[tri[0]], &positions[tri[1]], &positions[tri[2]]];
+        let t = [&texcoords[tri[0]], &texcoords[tri[1]], &texcoords[tri[2]]];
+        let d1 = p[1].sub(p[0]);
+        let d2 = p[2].sub(p[0]);
+        let (s1, t1) = (t[1].x - t[0].x, t[1].y - t[0].y);
+        let (s2, t2) = (t[2].x - t[0].x, t[2].y - t[0].y);
+        let signed_area = s1 * t2 - t1 * s2;
+        if signed_area.abs() <= EPSILON {
+            continue; // no uv mapping, can't derive a direction
+        }
+        let orient = signed_area.signum();
+        // direction of increasing u in object space (MikkTSpace eq. 18), with the area factor
+        // reduced to its sign
+        let os = d1.scale(t2).sub(&d2.scale(t1)).scale(orient);
+        let os = match os.normalized() {
+            Some(v) => v,
+            None => continue,
+        };
+        for c in 0..3 {
+            let v = tri[c];
+            let n = &normals[v];
+            let proj = match project_to_plane(&os, n).normalized() {
+                Some(v) => v,
+                None => continue,
+            };
+            // corner angle measured in the plane of the normal, as MikkTSpace does
+            let e1 = project_to_plane(&p[(c + 1) % 3].sub(p[c]), n);
+            let e2 = project_to_plane(&p[(c + 2) % 3].sub(p[c]), n);
+            let w = corner_angle(&e1, &e2);
+            add_scaled(&mut tan_acc[group[v]], &proj, w);
+            orient_acc[group[v]] += orient * w;
+        }
+    }
+
+    for i in 0..vert_count {
+        let n = &normals[i];
+        let g = group[i];
+        // the accumulation was over vertices in the group, which share this normal
+        let tan = project_to_plane(&tan_acc[g], n).normalized()
+            .unwrap_or_else(|| any_perpendicular(n));
+        let sign = if orient_acc[g] < 0.0 { -1.0 } else { 1.0 };
+        bitangents[i] = n.cross(&tan).scale(sign);
+        tangents[i] = tan;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f2(x: f32, y: f32) -> Float2 {
+        Float2 { x, y }
+    }
+
+    fn assert_near(a: &Float3, b: &Float3) {
+        assert!(a.sub(b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    /// Unit quad in the z = 0 plane, facing -z with counter clockwise winding.  u increases with
+    /// x and v with -y.
+    fn quad() -> (Vec<u32>, Vec<Float3>, Vec<Float2>) {
+        let pos = vec![Float3::new(0.0, 0.0, 0.0), Float3::new(0.0, 1.0, 0.0), Float3::new(1.0, 1.0, 0.0), Float3::new(1.0, 0.0, 0.0)];
+        let uv = vec![f2(0.0, 1.0), f2(0.0, 0.0), f2(1.0, 0.0), f2(1.0, 1.0)];
+        (vec![0, 1, 2, 0, 2, 3], pos, uv)
+    }
+
+    /// Axis aligned cube of 24 vertices (4 per face, so each face can have its own uvs), facing
+    /// outwards with counter clockwise winding.
+    fn cube() -> (Vec<u32>, Vec<Float3>) {
+        // each face: outward normal, and two axes spanning it such that cross(a, b) = -n
+        let faces = [
+            (Float3::new(0.0, 0.0, -1.0), Float3::new(1.0, 0.0, 0.0), Float3::new(0.0, 1.0, 0.0)),
+            (Float3::new(0.0, 0.0, 1.0), Float3::new(0.0, 1.0, 0.0), Float3::new(1.0, 0.0, 0.0)),
+            (Float3::new(-1.0, 0.0, 0.0), Float3::new(0.0, 1.0, 0.0), Float3::new(0.0, 0.0, 1.0)),
+            (Float3::new(1.0, 0.0, 0.0), Float3::new(0.0, 0.0, 1.0), Float3::new(0.0, 1.0, 0.0)),
+            (Float3::new(0.0, -1.0, 0.0), Float3::new(0.0, 0.0, 1.0), Float3::new(1.0, 0.0, 0.0)),
+            (Float3::new(0.0, 1.0, 0.0), Float3::new(1.0, 0.0, 0.0), Float3::new(0.0, 0.0, 1.0)),
+        ];
+        let mut pos = vec![];
+        let mut idx = vec![];
+        for (n, a, b) in faces.iter() {
+            let base = pos.len() as u32;
+            for (sa, sb) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)] {
+                let mut p = *n;
+                add_scaled(&mut p, a, sa);
+                add_scaled(&mut p, b, sb);
+                pos.push(p);
+            }
+            idx.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        (idx, pos)
+    }
+
+    #[test]
+    fn test_quad_normals() {
+        let (idx, pos, _) = quad();
+        let mut normals = vec![Float3::new(0.0, 0.0, 0.0); 4];
+        for w in [NormalWeighting::Angle, NormalWeighting::Area, NormalWeighting::Equal] {
+            compute_normals(&idx, &pos, w, false, &mut normals).unwrap();
+            for n in normals.iter() {
+                assert_near(n, &Float3::new(0.0, 0.0, -1.0));
+            }
+        }
+        compute_normals(&idx, &pos, NormalWeighting::Angle, true, &mut normals).unwrap();
+        assert_near(&normals[0], &Float3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_cube_normals() {
+        let (idx, pos) = cube();
+        let mut normals = vec![Float3::new(0.0, 0.0, 0.0); pos.len()];
+        let s = 1.0 / 3.0f32.sqrt();
+
+        // every corner is shared by three faces which each contribute a 90 degree corner, so
+        // with angle weighting the result is the diagonal
+        compute_normals(&idx, &pos, NormalWeighting::Angle, false, &mut normals).unwrap();
+        for (p, n) in pos.iter().zip(normals.iter()) {
+            assert_near(n, &p.scale(s));
+        }
+
+        // area weighting: corners are in one or two triangles of each face depending on where
+        // the diagonal is, so the result leans towards the faces where it is in two.
+        compute_normals(&idx, &pos, NormalWeighting::Area, false, &mut normals).unwrap();
+        // (-1,-1,-1) is on the diagonal of all three faces that touch it
+        assert_near(&normals[0], &Float3::new(-s, -s, -s));
+        // (1,1,-1) is on the -z face diagonal, but in one triangle of the +x and +y faces
+        let i = pos.iter().position(|p| bits3(p) == bits3(&Float3::new(1.0, 1.0, -1.0))).unwrap();
+        let expect = Float3::new(1.0, 1.0, -2.0).normalized().unwrap();
+        assert_near(&normals[i], &expect);
+        // welded vertices agree
+        for (j, p) in pos.iter().enumerate() {
+            if bits3(p) == bits3(&pos[i]) {
+                assert_near(&normals[j], &expect);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unused_and_bad_indices() {
+        let (_, pos, _) = quad();
+        let mut normals = vec![Float3::new(9.0, 9.0, 9.0); 4];
+        // one degenerate triangle, nothing else
+        compute_normals(&[0, 1, 1], &pos, NormalWeighting::Angle, true, &mut normals).unwrap();
+        assert_near(&normals[0], &Float3::new(0.0, 0.0, 0.0));
+        assert!(compute_normals(&[0, 1, 4], &pos, NormalWeighting::Angle, true, &mut normals)
+            .unwrap_err().contains("out of range"));
+        assert!(compute_normals(&[0, 1], &pos, NormalWeighting::Angle, true, &mut normals).is_err());
+        assert!(compute_normals(&[0, 1, 2], &pos, NormalWeighting::Angle, true, &mut normals[0..3]).is_err());
+    }
+
+    #[test]
+    fn test_quad_tangents() {
+        let (idx, pos, mut uv) = quad();
+        // not quite unit length, as if decoded from a packed format
+        let normals = vec![Float3::new(0.0, 0.0, -0.98); 4];
+        let mut tan = vec![Float3::new(0.0, 0.0, 0.0); 4];
+        let mut bit = tan.clone();
+        compute_tangent_frame(&idx, &pos, &normals, &uv, &mut tan, &mut bit).unwrap();
+        for i in 0..4 {
+            // u increases along +x, v along -y
+            assert_near(&tan[i], &Float3::new(1.0, 0.0, 0.0));
+            assert_near(&bit[i], &Float3::new(0.0, -1.0, 0.0));
+        }
+
+        // mirror the texture horizontally: the tangent flips, and so does the handedness,
+        // so the bitangent is unchanged
+        for t in uv.iter_mut() {
+            t.x = 1.0 - t.x;
+        }
+        compute_tangent_frame(&idx, &pos, &normals, &uv, &mut tan, &mut bit).unwrap();
+        for i in 0..4 {
+            assert_near(&tan[i], &Float3::new(-1.0, 0.0, 0.0));
+            assert_near(&bit[i], &Float3::new(0.0, -1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn test_unindexed_and_skewed_tangents() {
+        // the quad as an unindexed triangle list with a non uniform uv scale; the duplicate
+        // vertices weld, and the scale doesn't change the directions
+        let (idx, pos, uv) = quad();
+        let pos: Vec<Float3> = idx.iter().map(|&i| pos[i as usize]).collect();
+        let uv: Vec<Float2> = idx.iter().map(|&i| f2(uv[i as usize].x * 4.0, uv[i as usize].y * 0.5)).collect();
+        let seq: Vec<u32> = (0..6).collect();
+        let mut normals = vec![Float3::new(0.0, 0.0, 0.0); 6];
+        compute_normals(&seq, &pos, NormalWeighting::Angle, false, &mut normals).unwrap();
+        let mut tan = vec![Float3::new(0.0, 0.0, 0.0); 6];
+        let mut bit = tan.clone();
+        compute_tangent_frame(&seq, &pos, &normals, &uv, &mut tan, &mut bit).unwrap();
+        for i in 0..6 {
+            assert_near(&tan[i], &Float3::new(1.0, 0.0, 0.0));
+            assert_near(&bit[i], &Float3::new(0.0, -1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn test_cube_tangents() {
+        // lay the faces out side by side in the texture so that no vertices weld; each face's
+        // tangent must lie along its first spanning axis and be orthogonal to the (smooth) normal
+        let (idx, pos) = cube();
+        let face_uv = [f2(0.0, 1.0), f2(0.0, 0.0), f2(1.0, 0.0), f2(1.0, 1.0)];
+        let uv: Vec<Float2> = (0..pos.len()).map(|i| f2(face_uv[i % 4].x + (i / 4) as f32, face_uv[i % 4].y)).collect();
+        let mut normals = vec![Float3::new(0.0, 0.0, 0.0); pos.len()];
+        compute_normals(&idx, &pos, NormalWeighting::Angle, false, &mut normals).unwrap();
+        let mut tan = vec![Float3::new(0.0, 0.0, 0.0); pos.len()];
+        let mut bit = tan.clone();
+        compute_tangent_frame(&idx, &pos, &normals, &uv, &mut tan, &mut bit).unwrap();
+        for i in 0..pos.len() {
+            let n = &normals[i];
+            assert!((tan[i].length() - 1.0).abs() < 1e-5);
+            assert!((bit[i].length() - 1.0).abs() < 1e-5);
+            assert!(n.dot(&tan[i]).abs() < 1e-5);
+            assert!(n.dot(&bit[i]).abs() < 1e-5);
+            assert!(tan[i].dot(&bit[i]).abs() < 1e-5);
+        }
+        // -z face: the projection of +x onto the plane of the corner's diagonal normal
+        let n = &normals[0];
+        let expect = project_to_plane(&Float3::new(1.0, 0.0, 0.0), n).normalized().unwrap();
+        assert_near(&tan[0], &expect);
+    }
+
+    #[test]
+    fn test_degenerate_uvs() {
+        let (idx, pos, _) = quad();
+        let normals = vec![Float3::new(0.0, 0.0, -1.0); 4];
+        let uv = vec![f2(0.5, 0.5); 4];
+        let mut tan = vec![Float3::new(0.0, 0.0, 0.0); 4];
+        let mut bit = tan.clone();
+        compute_tangent_frame(&idx, &pos, &normals, &uv, &mut tan, &mut bit).unwrap();
+        for i in 0..4 {
+            assert!((tan[i].length() - 1.0).abs() < 1e-5);
+            assert!(normals[i].dot(&tan[i]).abs() < 1e-5);
+        }
+    }
+}
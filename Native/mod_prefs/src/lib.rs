@@ -1,7 +1,8 @@
 //! Per-game user preferences stored in `%LOCALAPPDATA%\ModelMod\<gamename>.prefs.yaml`.
 //!
-//! Currently tracks which variant index is selected for each (ref prim, ref vert)
-//! geometry that has multiple non-parented mods. The file format is intentionally
+//! Tracks which variant index is selected for each (ref prim, ref vert) geometry that
+//! has multiple non-parented mods, named presets of those selections, and which mods the
+//! user has disabled. The file format is intentionally
 //! extensible (a versioned YAML document with sections) so additional preferences
 //! can be added later without breaking older files.
 //!
@@ -13,11 +14,13 @@ use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
-use global_state::{LoadedModsMap, SelectedVariantMap};
-use shared_dx::util::write_log_file;
-use types::native_mod::NativeModData;
+use mm_core::{LoadedModsMap, SelectedVariantMap};
+use mm_core::log::write_log_file;
+use mm_core::native_mod::mod_key;
 
 const PREFS_VERSION: u32 = 1;
+const DISABLED_VERSION: u32 = 1;
+const PRESETS_VERSION: u32 = 1;
 const PREFS_SUBDIR: &str = "ModelMod";
 const PREFS_EXT: &str = "prefs.yaml";
 
@@ -28,19 +31,86 @@ pub struct VariantPref {
     pub index: usize,
 }
 
+/// Mods the user has disabled, by (lowercase) name, in the order they were disabled.  This
+/// section has its own version so that it can change independently of the rest of the file.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct DisabledMods {
+    #[serde(default = "default_disabled_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub mods: Vec<String>,
+}
+
+fn default_disabled_version() -> u32 { DISABLED_VERSION }
+
+impl DisabledMods {
+    pub fn is_empty(&self) -> bool {
+        self.mods.is_empty()
+    }
+}
+
+impl Default for DisabledMods {
+    fn default() -> Self {
+        Self { version: DISABLED_VERSION, mods: Vec::new() }
+    }
+}
+
+/// A named set of variant selections.  Geometries that aren't listed use the first variant.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct VariantPreset {
+    pub name: String,
+    #[serde(default)]
+    pub variants: Vec<VariantPref>,
+}
+
+/// Saved variant presets, and the name of the one that was last saved or applied (which is
+/// where cycling continues from).
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct VariantPresets {
+    #[serde(default = "default_presets_version")]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<String>,
+    #[serde(default)]
+    pub list: Vec<VariantPreset>,
+}
+
+fn default_presets_version() -> u32 { PRESETS_VERSION }
+
+impl VariantPresets {
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
+
+impl Default for VariantPresets {
+    fn default() -> Self {
+        Self { version: PRESETS_VERSION, active: None, list: Vec::new() }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 pub struct ModPrefs {
     #[serde(default = "default_version")]
     pub version: u32,
     #[serde(default)]
     pub variants: Vec<VariantPref>,
+    #[serde(default, skip_serializing_if = "DisabledMods::is_empty")]
+    pub disabled: DisabledMods,
+    #[serde(default, skip_serializing_if = "VariantPresets::is_empty")]
+    pub presets: VariantPresets,
 }
 
 fn default_version() -> u32 { PREFS_VERSION }
 
 impl ModPrefs {
     pub fn new() -> Self {
-        Self { version: PREFS_VERSION, variants: Vec::new() }
+        Self {
+            version: PREFS_VERSION,
+            variants: Vec::new(),
+            disabled: DisabledMods::default(),
+            presets: VariantPresets::default(),
+        }
     }
 }
 
@@ -90,14 +160,26 @@ fn prefs_dir() -> Option<PathBuf> {
     }
 }
 
-fn prefs_file_path() -> Option<PathBuf> {
-    let stem = match util::get_module_name_base() {
-        Ok(s) => s,
+#[cfg(windows)]
+fn game_name() -> Option<String> {
+    match util::get_module_name_base() {
+        Ok(s) => Some(s),
         Err(e) => {
             write_log_file(&format!("prefs: cannot determine game name: {:?}", e));
-            return None;
+            None
         }
-    };
+    }
+}
+
+// the hook only runs on windows, this is here so that the prefs logic can be tested elsewhere
+#[cfg(not(windows))]
+fn game_name() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.file_stem()?.to_string_lossy().into_owned())
+}
+
+fn prefs_file_path() -> Option<PathBuf> {
+    let stem = game_name()?;
     let mut dir = prefs_dir()?;
     dir.push(format!("{}.{}", stem, PREFS_EXT));
     Some(dir)
@@ -105,31 +187,36 @@ fn prefs_file_path() -> Option<PathBuf> {
 
 fn read_prefs() -> Option<ModPrefs> {
     let pb = prefs_file_path()?;
-    if !pb.is_file() {
-        return None;
-    }
+    read_prefs_file(&pb)
+}
 
-    let file = match std::fs::File::open(&pb) {
-        Ok(f) => f,
+fn read_prefs_file(pb: &std::path::Path) -> Option<ModPrefs> {
+    match load_prefs_file(pb) {
+        Ok(p) => p,
         Err(e) => {
-            write_log_file(&format!("prefs: failed to open {:?}: {:?}", pb, e));
-            return None;
-        }
-    };
-    let reader = std::io::BufReader::new(file);
-    match serde_yaml::from_reader::<_, ModPrefs>(reader) {
-        Ok(p) => {
-            write_log_file(&format!("prefs: loaded from {:?} (version {}, {} variants)",
-                pb, p.version, p.variants.len()));
-            Some(p)
-        },
-        Err(e) => {
-            write_log_file(&format!("prefs: failed to parse {:?}: {}", pb, e));
+            write_log_file(&format!("prefs: {}", e));
             None
         }
     }
 }
 
+/// Load the prefs file at `pb`.  A missing file is `Ok(None)`; a file that exists but can't be
+/// opened or parsed is an error.
+fn load_prefs_file(pb: &std::path::Path) -> Result<Option<ModPrefs>, String> {
+    if !pb.is_file() {
+        return Ok(None);
+    }
+
+    let file = std::fs::File::open(pb)
+        .map_err(|e| format!("failed to open {:?}: {:?}", pb, e))?;
+    let reader = std::io::BufReader::new(file);
+    let p = serde_yaml::from_reader::<_, ModPrefs>(reader)
+        .map_err(|e| format!("failed to parse {:?}: {}", pb, e))?;
+    write_log_file(&format!("prefs: loaded from {:?} (version {}, {} variants, {} disabled mods)",
+        pb, p.version, p.variants.len(), p.disabled.mods.len()));
+    Ok(Some(p))
+}
+
 fn write_prefs(prefs: &ModPrefs) {
     let pb = match prefs_file_path() {
         Some(p) => p,
@@ -153,15 +240,58 @@ fn write_prefs(prefs: &ModPrefs) {
     }
 }
 
+/// Read the current prefs (or start from the defaults if there aren't any), change them and
+/// write them back, so that sections not being changed are kept.  If the existing file can't
+/// be read, nothing is saved, so that the user's file isn't replaced.
+fn update_prefs<F: FnOnce(&mut ModPrefs)>(f: F) {
+    let pb = match prefs_file_path() {
+        Some(p) => p,
+        None => return,
+    };
+    let mut prefs = match load_prefs_file(&pb) {
+        Ok(p) => p.unwrap_or_default(),
+        Err(e) => {
+            write_log_file(&format!("prefs: {}; not saving so that it isn't overwritten, fix or remove the file", e));
+            return;
+        }
+    };
+    f(&mut prefs);
+    write_prefs(&prefs);
+}
+
+/// Return the disabled mod names from `prefs`, lowercased (the convention for mod names in
+/// the loaded mod state) and without duplicates.  If the section is from a newer version that
+/// this code doesn't understand, it is ignored.
+fn disabled_mods(prefs: &ModPrefs) -> Vec<String> {
+    if prefs.disabled.version > DISABLED_VERSION {
+        write_log_file(&format!("prefs: ignoring disabled mods section with unsupported version {}",
+            prefs.disabled.version));
+        return vec![];
+    }
+    let mut names: Vec<String> = vec![];
+    for name in prefs.disabled.mods.iter() {
+        let name = name.trim().to_lowercase();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
 /// Apply `prefs` entries to `selected_variant` for each entry whose index is in
 /// range for the currently loaded mods. Entries that refer to ref geometry with
 /// no loaded mods, or whose index is out of range, are silently dropped
 /// (effectively resetting to 0). Returns `(applied, total)` counts.
-fn apply_variants(prefs: &ModPrefs, mods: &LoadedModsMap,
+fn apply_variants<D>(prefs: &ModPrefs, mods: &LoadedModsMap<D>,
+                  selected_variant: &mut SelectedVariantMap) -> (usize, usize) {
+    apply_variant_entries(&prefs.variants, mods, selected_variant)
+}
+
+fn apply_variant_entries<D>(entries: &[VariantPref], mods: &LoadedModsMap<D>,
                   selected_variant: &mut SelectedVariantMap) -> (usize, usize) {
     let mut applied = 0;
-    for entry in &prefs.variants {
-        let mod_key = NativeModData::mod_key(entry.ref_vert_count, entry.ref_prim_count);
+    for entry in entries {
+        let mod_key = mod_key(entry.ref_vert_count, entry.ref_prim_count);
         match mods.get(&mod_key) {
             Some(nmdv) if entry.index < nmdv.len() => {
                 selected_variant.insert(mod_key, entry.index);
@@ -177,7 +307,51 @@ fn apply_variants(prefs: &ModPrefs, mods: &LoadedModsMap,
             }
         }
     }
-    (applied, prefs.variants.len())
+    (applied, entries.len())
+}
+
+/// Add a preset with the given selections and make it the active one.  If `name` is given, a
+/// preset with that name gets the new selections, or a new one with that name is added.
+/// Otherwise, if an existing preset has exactly the same selections, that one is made active
+/// instead of adding a duplicate, so a preset that was renamed in the prefs file keeps its name.
+/// New unnamed presets are named "Preset N" with the lowest unused N.  Returns the preset name.
+fn add_preset(presets: &mut VariantPresets, name: Option<&str>, variants: Vec<VariantPref>) -> String {
+    let name = match name.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(name) => {
+            match presets.list.iter_mut().find(|p| p.name == name) {
+                Some(existing) => existing.variants = variants,
+                None => presets.list.push(VariantPreset { name: name.to_owned(), variants }),
+            }
+            name.to_owned()
+        }
+        None => match presets.list.iter().find(|p| p.variants == variants) {
+            Some(existing) => existing.name.clone(),
+            None => {
+                let name = (1..)
+                    .map(|n| format!("Preset {}", n))
+                    .find(|name| !presets.list.iter().any(|p| p.name == *name))
+                    .expect("ran out of preset names");
+                presets.list.push(VariantPreset { name: name.clone(), variants });
+                name
+            }
+        },
+    };
+    presets.active = Some(name.clone());
+    name
+}
+
+/// Make the preset after the active one active (wrapping around, or starting with the first
+/// if there is no active one) and return it.
+fn next_preset(presets: &mut VariantPresets) -> Option<&VariantPreset> {
+    if presets.list.is_empty() {
+        return None;
+    }
+    let next = presets.active.as_ref()
+        .and_then(|name| presets.list.iter().position(|p| p.name == *name))
+        .map(|i| (i + 1) % presets.list.len())
+        .unwrap_or(0);
+    presets.active = Some(presets.list[next].name.clone());
+    presets.list.get(next)
 }
 
 /// Build a `ModPrefs` from the current non-zero `selected_variant` entries.
@@ -185,7 +359,7 @@ fn apply_variants(prefs: &ModPrefs, mods: &LoadedModsMap,
 /// Entries whose ref geometry has no loaded mods are skipped. The resulting
 /// variants list is sorted by `(ref_prim_count, ref_vert_count)` for stable
 /// on-disk output.
-fn build_prefs(mods: &LoadedModsMap, selected_variant: &SelectedVariantMap) -> ModPrefs {
+fn build_prefs<D>(mods: &LoadedModsMap<D>, selected_variant: &SelectedVariantMap) -> ModPrefs {
     let mut prefs = ModPrefs::new();
     for (mod_key, index) in selected_variant.iter() {
         if *index == 0 {
@@ -210,7 +384,7 @@ fn build_prefs(mods: &LoadedModsMap, selected_variant: &SelectedVariantMap) -> M
 /// whose index is in range for the currently loaded mods. Entries that refer to
 /// ref geometry with no loaded mods, or whose index is out of range, are silently
 /// dropped (effectively resetting to 0).
-pub fn load_and_apply_variants(mods: &LoadedModsMap, selected_variant: &mut SelectedVariantMap) {
+pub fn load_and_apply_variants<D>(mods: &LoadedModsMap<D>, selected_variant: &mut SelectedVariantMap) {
     let prefs = match read_prefs() {
         Some(p) => p,
         None => return,
@@ -219,16 +393,91 @@ pub fn load_and_apply_variants(mods: &LoadedModsMap, selected_variant: &mut Sele
     write_log_file(&format!("prefs: applied {} of {} saved variant selections", applied, total));
 }
 
+/// Same as `load_and_apply_variants`, but reads the specified prefs file instead of the one for
+/// the current game.  Used by tools that work with another game's prefs.  Returns the
+/// `(applied, total)` counts, or None if the file couldn't be read.
+pub fn load_and_apply_variants_from<D>(path: &std::path::Path, mods: &LoadedModsMap<D>,
+                  selected_variant: &mut SelectedVariantMap) -> Option<(usize, usize)> {
+    let prefs = read_prefs_file(path)?;
+    Some(apply_variants(&prefs, mods, selected_variant))
+}
+
 /// Serialize the current non-zero `selected_variant` entries to the prefs file.
-pub fn save_variant_selections(mods: &LoadedModsMap, selected_variant: &SelectedVariantMap) {
-    let prefs = build_prefs(mods, selected_variant);
+pub fn save_variant_selections<D>(mods: &LoadedModsMap<D>, selected_variant: &SelectedVariantMap) {
+    let variants = build_prefs(mods, selected_variant).variants;
+    update_prefs(|prefs| prefs.variants = variants);
+}
+
+/// Save the current variant selections as a preset, optionally with a name (see `add_preset`).
+/// Returns the preset name.
+pub fn save_variant_preset<D>(mods: &LoadedModsMap<D>, selected_variant: &SelectedVariantMap,
+                  name: Option<&str>) -> Option<String> {
+    let variants = build_prefs(mods, selected_variant).variants;
+    let mut saved = None;
+    update_prefs(|prefs| {
+        if prefs.presets.version > PRESETS_VERSION {
+            write_log_file(&format!("prefs: not saving preset, presets section has unsupported version {}",
+                prefs.presets.version));
+            return;
+        }
+        let pname = add_preset(&mut prefs.presets, name, variants);
+        write_log_file(&format!("prefs: saved variant preset '{}'", pname));
+        saved = Some(pname);
+    });
+    saved
+}
+
+/// Apply the next variant preset to `selected_variant`, replacing the current selections.
+/// Out of range entries are dropped as in `load_and_apply_variants`.  The new selections are
+/// also saved as the current variants.  Returns the preset name, or None if there aren't any
+/// presets.
+pub fn apply_next_variant_preset<D>(mods: &LoadedModsMap<D>, selected_variant: &mut SelectedVariantMap) -> Option<String> {
+    let mut prefs = read_prefs()?;
+    if prefs.presets.version > PRESETS_VERSION {
+        write_log_file(&format!("prefs: ignoring presets section with unsupported version {}",
+            prefs.presets.version));
+        return None;
+    }
+    let preset = next_preset(&mut prefs.presets)?.clone();
+    selected_variant.clear();
+    let (applied, total) = apply_variant_entries(&preset.variants, mods, selected_variant);
+    write_log_file(&format!("prefs: applied {} of {} variant selections from preset '{}'",
+        applied, total, preset.name));
+    prefs.variants = build_prefs(mods, selected_variant).variants;
     write_prefs(&prefs);
+    Some(preset.name)
+}
+
+/// Read the names of mods that the user has disabled from the prefs file.  Names of mods that
+/// aren't currently loaded are kept, so that they stay disabled if the mod is loaded again.
+pub fn load_disabled_mods() -> Vec<String> {
+    let names = read_prefs().map(|p| disabled_mods(&p)).unwrap_or_default();
+    if !names.is_empty() {
+        write_log_file(&format!("prefs: {} mods are disabled: {:?}", names.len(), names));
+    }
+    names
+}
+
+/// Same as `load_disabled_mods`, but reads the specified prefs file.  Returns None if the file
+/// couldn't be read.
+pub fn load_disabled_mods_from(path: &std::path::Path) -> Option<Vec<String>> {
+    read_prefs_file(path).map(|p| disabled_mods(&p))
+}
+
+/// Save the list of disabled mods to the prefs file.
+pub fn save_disabled_mods(disabled: &[String]) {
+    update_prefs(|prefs| {
+        prefs.disabled = DisabledMods { version: DISABLED_VERSION, mods: disabled.to_vec() };
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use global_state::new_fnv_map;
+    use mm_core::new_fnv_map;
+
+    type NativeModData = mm_core::NativeModData<()>;
+    type LoadedModsMap = mm_core::LoadedModsMap<()>;
 
     fn new_mod(ref_prims: i32, ref_verts: i32) -> NativeModData {
         let mut m = NativeModData::new();
@@ -239,7 +488,7 @@ mod tests {
 
     fn add_mods(mmap: &mut LoadedModsMap, ref_prims: i32, ref_verts: i32, count: usize) {
         let mk = NativeModData::mod_key(ref_verts as u32, ref_prims as u32);
-        let v = mmap.entry(mk).or_insert_with(|| vec![]);
+        let v = mmap.entry(mk).or_default();
         for _ in 0..count {
             v.push(new_mod(ref_prims, ref_verts));
         }
@@ -250,6 +499,7 @@ mod tests {
         let p = ModPrefs::new();
         assert_eq!(p.version, PREFS_VERSION);
         assert!(p.variants.is_empty());
+        assert!(p.disabled.is_empty());
 
         let d = ModPrefs::default();
         assert_eq!(d.version, PREFS_VERSION);
@@ -291,6 +541,157 @@ mod tests {
         assert!(p.variants.is_empty());
     }
 
+    #[test]
+    fn old_files_load_without_disabled_section() {
+        let p: ModPrefs = serde_yaml::from_str(
+            "version: 1\nvariants:\n- ref_prim_count: 1\n  ref_vert_count: 2\n  index: 1\n").unwrap();
+        assert_eq!(p.variants.len(), 1);
+        assert!(p.disabled.is_empty());
+        assert_eq!(p.disabled.version, DISABLED_VERSION);
+
+        // an empty section isn't written, so files stay readable by older versions
+        let yaml = serde_yaml::to_string(&p).unwrap();
+        assert!(!yaml.contains("disabled"), "{}", yaml);
+    }
+
+    #[test]
+    fn disabled_mods_normalized() {
+        let p: ModPrefs = serde_yaml::from_str(
+            "disabled:\n  mods: [Foo, bar, ' FOO ', '']\n").unwrap();
+        assert_eq!(p.disabled.version, DISABLED_VERSION);
+        assert_eq!(disabled_mods(&p), vec!["foo", "bar"]);
+
+        let p: ModPrefs = serde_yaml::from_str(
+            "disabled:\n  version: 99\n  mods: [foo]\n").unwrap();
+        assert!(disabled_mods(&p).is_empty());
+    }
+
+    #[test]
+    fn saves_keep_other_sections() {
+        let mut mods: LoadedModsMap = new_fnv_map(4);
+        add_mods(&mut mods, 5, 10, 3);
+        let mut sel: SelectedVariantMap = new_fnv_map(4);
+        sel.insert(NativeModData::mod_key(10, 5), 2);
+
+        save_disabled_mods(&["moda".to_owned(), "modb".to_owned()]);
+        save_variant_selections(&mods, &sel);
+        let prefs = read_prefs().expect("no prefs");
+        assert_eq!(prefs.variants.len(), 1);
+        assert_eq!(load_disabled_mods(), vec!["moda", "modb"]);
+
+        save_disabled_mods(&["modb".to_owned()]);
+        let prefs = read_prefs().expect("no prefs");
+        assert_eq!(prefs.variants.len(), 1);
+        assert_eq!(prefs.disabled.mods, vec!["modb"]);
+
+        let path = prefs_file_path().unwrap();
+        assert_eq!(load_disabled_mods_from(&path), Some(vec!["modb".to_owned()]));
+        save_disabled_mods(&[]);
+        assert!(load_disabled_mods().is_empty());
+    }
+
+    #[test]
+    fn saves_dont_overwrite_unreadable_file() {
+        let path = prefs_file_path().unwrap();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let garbage = "variants: [this is not: valid\n";
+        std::fs::write(&path, garbage).unwrap();
+
+        save_disabled_mods(&["moda".to_owned()]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), garbage);
+        assert!(load_disabled_mods().is_empty());
+
+        // a missing file is fine though
+        std::fs::remove_file(&path).unwrap();
+        save_disabled_mods(&["moda".to_owned()]);
+        assert_eq!(load_disabled_mods(), vec!["moda"]);
+    }
+
+    #[test]
+    fn presets_add_and_cycle() {
+        let v = |prims, index| vec![VariantPref { ref_prim_count: prims, ref_vert_count: 10, index }];
+        let mut presets = VariantPresets::default();
+        assert!(next_preset(&mut presets).is_none());
+
+        assert_eq!(add_preset(&mut presets, None, v(5, 1)), "Preset 1");
+        assert_eq!(add_preset(&mut presets, None, v(5, 2)), "Preset 2");
+        // same selections as an existing preset
+        assert_eq!(add_preset(&mut presets, None, v(5, 1)), "Preset 1");
+        assert_eq!(presets.list.len(), 2);
+        assert_eq!(presets.active.as_deref(), Some("Preset 1"));
+
+        assert_eq!(next_preset(&mut presets).map(|p| p.name.as_str()), Some("Preset 2"));
+        assert_eq!(next_preset(&mut presets).map(|p| p.name.as_str()), Some("Preset 1"));
+        // lowest unused number is reused, unknown active name starts over
+        presets.list.remove(0);
+        assert_eq!(add_preset(&mut presets, None, vec![]), "Preset 1");
+        presets.active = Some("gone".to_owned());
+        assert_eq!(next_preset(&mut presets).map(|p| p.name.as_str()), Some("Preset 2"));
+    }
+
+    #[test]
+    fn presets_named() {
+        let v = |index| vec![VariantPref { ref_prim_count: 5, ref_vert_count: 10, index }];
+        let mut presets = VariantPresets::default();
+
+        assert_eq!(add_preset(&mut presets, Some(" Armor "), v(1)), "Armor");
+        // blank names are auto named
+        assert_eq!(add_preset(&mut presets, Some(""), v(2)), "Preset 1");
+        // saving with an existing name replaces its selections
+        assert_eq!(add_preset(&mut presets, Some("Armor"), v(3)), "Armor");
+        assert_eq!(presets.list.len(), 2);
+        assert_eq!(presets.list[0].variants, v(3));
+        // a named preset can have the same selections as another one
+        assert_eq!(add_preset(&mut presets, Some("Also armor"), v(3)), "Also armor");
+        assert_eq!(presets.list.len(), 3);
+        assert_eq!(presets.active.as_deref(), Some("Also armor"));
+
+        // a preset renamed in the file keeps its name when the same selections are saved again
+        presets.list[1].name = "Casual".to_owned();
+        assert_eq!(add_preset(&mut presets, None, v(2)), "Casual");
+        assert_eq!(presets.list.len(), 3);
+    }
+
+    #[test]
+    fn presets_save_and_apply() {
+        // start without a prefs file from a previous run
+        let _ = std::fs::remove_file(prefs_file_path().unwrap());
+        let mut mods: LoadedModsMap = new_fnv_map(4);
+        add_mods(&mut mods, 5, 10, 3);
+        add_mods(&mut mods, 20, 40, 2);
+        let mk_a = NativeModData::mod_key(10, 5);
+        let mk_b = NativeModData::mod_key(40, 20);
+
+        let mut sel: SelectedVariantMap = new_fnv_map(4);
+        assert_eq!(apply_next_variant_preset(&mods, &mut sel), None);
+
+        sel.insert(mk_a, 2);
+        sel.insert(mk_b, 1);
+        assert_eq!(save_variant_preset(&mods, &sel, None).as_deref(), Some("Preset 1"));
+        sel.insert(mk_a, 1);
+        sel.remove(&mk_b);
+        assert_eq!(save_variant_preset(&mods, &sel, None).as_deref(), Some("Preset 2"));
+        save_disabled_mods(&["moda".to_owned()]);
+
+        // cycling wraps around and replaces the whole selection
+        assert_eq!(apply_next_variant_preset(&mods, &mut sel).as_deref(), Some("Preset 1"));
+        assert_eq!((sel.get(&mk_a), sel.get(&mk_b)), (Some(&2), Some(&1)));
+        assert_eq!(read_prefs().unwrap().variants.len(), 2);
+        assert_eq!(apply_next_variant_preset(&mods, &mut sel).as_deref(), Some("Preset 2"));
+        assert_eq!((sel.get(&mk_a), sel.get(&mk_b)), (Some(&1), None));
+        let prefs = read_prefs().unwrap();
+        assert_eq!(prefs.variants.len(), 1);
+        assert_eq!(prefs.presets.active.as_deref(), Some("Preset 2"));
+        assert_eq!(prefs.disabled.mods, vec!["moda"]);
+
+        // entries that are now out of range are dropped when applied
+        let mut fewer: LoadedModsMap = new_fnv_map(4);
+        add_mods(&mut fewer, 5, 10, 2);
+        add_mods(&mut fewer, 20, 40, 2);
+        assert_eq!(apply_next_variant_preset(&fewer, &mut sel).as_deref(), Some("Preset 1"));
+        assert_eq!((sel.get(&mk_a), sel.get(&mk_b)), (None, Some(&1)));
+    }
+
     #[test]
     fn apply_variants_sets_selection_when_in_range() {
         let mut mods: LoadedModsMap = new_fnv_map(4);
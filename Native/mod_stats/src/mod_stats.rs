@@ -12,6 +12,11 @@
 //!
 //! A mod that was active but stops rendering will create a new entry in the log if it doesn't
 //! render for 4 minutes but then comes back.
+//!
+//! If `modstats.yaml` in the modelmod root selects a structured format (`jsonl` or `csv`), the
+//! file is instead `modstats.$ExeBaseName.jsonl` (or `.csv`) and contains one
+//! `mod_usage::SessionRecord` per mod session.  These files can be aggregated with the
+//! `mod_usage` tool.
 use std::cell::{RefCell};
 use std::io::{Seek, Read, SeekFrom};
 use std::sync::mpsc::{channel, Sender, Receiver};
@@ -20,6 +25,7 @@ use std::time::{SystemTime, Duration};
 use std::collections::{HashMap, HashSet};
 
 use global_state::{GLOBAL_STATE, LOADED_MODS};
+use mod_usage::{SessionLog, SessionRecord, StatsFormat};
 use shared_dx::util::write_log_file;
 
 use util::mm_verify_load;
@@ -70,6 +76,9 @@ thread_local! {
     static IDLE_NEW: RefCell<Duration> = RefCell::new(Duration::from_secs(DEF_IDLE_SECS));
     static MOD_STAT_FILE: RefCell<String> = RefCell::new(DEF_FILE_NAME.to_string());
     static MIN_ACTIVE_TIME: RefCell<Duration> = RefCell::new(Duration::from_secs(DEF_MIN_ACTIVE_TIME_SECS));
+    // None is the text format
+    static STATS_FORMAT: RefCell<Option<StatsFormat>> = RefCell::new(None);
+    static GAME_NAME: RefCell<String> = RefCell::new(String::new());
 }
 
 #[allow(dead_code)]
@@ -94,6 +103,7 @@ fn reset() {
         *s = Duration::from_secs(DEF_IDLE_SECS);
     });
     set_filename(DEF_FILE_NAME);
+    set_format(None, "");
     MIN_ACTIVE_TIME.with(|s| {
         let mut s = s.borrow_mut();
         *s = Duration::from_secs(DEF_MIN_ACTIVE_TIME_SECS);
@@ -254,14 +264,45 @@ fn process_mod_msgs(msgs:&[ModMsg], filename:&str) -> Result<(), String> {
     Ok(())
 }
 
-fn start_log_thread(filename:&str) -> LogThread {
+fn set_format(format:Option<StatsFormat>, game:&str) {
+    STATS_FORMAT.with(|f| *f.borrow_mut() = format);
+    GAME_NAME.with(|g| *g.borrow_mut() = game.to_string());
+}
+
+/// Process mod messages for a structured stats file.  Each active mod updates the record for
+/// its session, which is opened if necessary; sessions idle for longer than `idle` are closed.
+fn process_session_msgs(msgs:&[ModMsg], log:&mut Option<SessionLog>, filename:&str,
+    format:StatsFormat, game:&str, now:SystemTime, idle:Duration) -> Result<(), String> {
+    if log.is_none() {
+        *log = Some(SessionLog::open(std::path::Path::new(filename), format)
+            .map_err(|e| format!("{}: {}", filename, e))?);
+    }
+    let log = log.as_mut().expect("session log should be open");
+    let now = mod_usage::unix_secs(now);
+    for msg in msgs {
+        if let ModMsg::ModActive(name, start_time, time) = msg {
+            log.update(SessionRecord {
+                game: game.to_string(),
+                mod_name: name.clone(),
+                first_seen: mod_usage::unix_secs(*start_time),
+                last_seen: now,
+                active_secs: time.as_secs(),
+            });
+        }
+    }
+    log.flush(now, idle.as_secs()).map_err(|e| format!("{}: {}", filename, e))
+}
+
+fn start_log_thread(filename:&str, format:Option<StatsFormat>, game:&str, idle:Duration) -> LogThread {
     let (main_sender, thrd_receiver) = channel::<ThreadCommand>();
     let (thrd_sender, main_receiver) = channel::<ThreadReply>();
     let t_filename = filename.to_string();
+    let t_game = game.to_string();
 
     let logger = std::thread::spawn(move || {
         let mut err: Result<(), String> = Ok(());
         let mut msgs = vec![];
+        let mut session_log = None;
         loop {
             let cmd = thrd_receiver.recv();
 
@@ -277,7 +318,11 @@ fn start_log_thread(filename:&str) -> LogThread {
                     },
                     ThreadCommand::UpdateDone => {
                         //eprintln!("processing {} msgs", msgs.len());
-                        let res = process_mod_msgs(&msgs, &t_filename);
+                        let res = match format {
+                            None => process_mod_msgs(&msgs, &t_filename),
+                            Some(format) => process_session_msgs(&msgs, &mut session_log,
+                                &t_filename, format, &t_game, SystemTime::now(), idle),
+                        };
                         msgs.clear();
                         if res.is_err() {
                             thrd_sender.send(ThreadReply::Error(res)).unwrap_or(());
@@ -346,9 +391,18 @@ pub fn update(now:&SystemTime) -> Option<(u32,u32)> {
             if basen.is_empty() {
                 return;
             }
+            let format = match mod_usage::read_stats_config(&mm_root) {
+                Ok(format) => format,
+                Err(e) => {
+                    write_log_file(&format!("mod_stats: failed to read stats config, using text format: {}", e));
+                    None
+                }
+            };
+            set_format(format, &basen);
             let mut ldir = mm_root.to_owned();
             ldir.push_str("\\Logs\\");
-            let file_name = format!("modstats.{}.log", basen);
+            let ext = format.map(|f| f.extension()).unwrap_or("log");
+            let file_name = format!("modstats.{}.{}", basen, ext);
             ldir.push_str(&file_name);
             let mut f = f.borrow_mut();
             (*f) = ldir;
@@ -356,7 +410,10 @@ pub fn update(now:&SystemTime) -> Option<(u32,u32)> {
 
         if ms.log_thread.is_none() {
             let filename = MOD_STAT_FILE.with(|f| f.borrow().to_owned());
-            ms.log_thread = Some(start_log_thread(&filename));
+            let format = STATS_FORMAT.with(|f| *f.borrow());
+            let game = GAME_NAME.with(|g| g.borrow().to_owned());
+            let idle = IDLE_NEW.with(|i| *i.borrow());
+            ms.log_thread = Some(start_log_thread(&filename, format, &game, idle));
         }
 
         let elapsed = elapsed.unwrap_or_else(|| Duration::from_secs(0));
@@ -684,6 +741,45 @@ mod tests {
         //super::reset();
     }
 
+    #[test]
+    fn test_process_session_msgs() {
+        let dir = std::env::temp_dir().join(format!("mm_test_process_session_msgs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("doh");
+        let testpath = dir.join("sessions.jsonl");
+        let testfile = testpath.to_str().expect("doh");
+        if testpath.exists() {
+            std::fs::remove_file(testfile).expect("doh");
+        }
+        let read_recs = || -> Vec<SessionRecord> {
+            std::fs::read_to_string(testfile).expect("doh").lines()
+                .filter_map(|l| SessionRecord::parse_line(l, StatsFormat::Jsonl).expect("doh"))
+                .collect()
+        };
+        let secs = |s:u64| SystemTime::UNIX_EPOCH + Duration::from_secs(s);
+        let idle = Duration::from_secs(60);
+        let mut log = None;
+
+        let msgs = vec![
+            ModMsg::ModActive("foo".to_owned(), secs(1000), Duration::from_secs(5)),
+            ModMsg::NewModActive("bar".to_owned(), secs(1000)),];
+        process_session_msgs(&msgs, &mut log, testfile, StatsFormat::Jsonl, "game", secs(1005), idle).expect("doh");
+        let msgs = vec![
+            ModMsg::ModActive("foo".to_owned(), secs(1000), Duration::from_secs(10)),
+            ModMsg::ModActive("bar".to_owned(), secs(1008), Duration::from_secs(2)),];
+        process_session_msgs(&msgs, &mut log, testfile, StatsFormat::Jsonl, "game", secs(1010), idle).expect("doh");
+        let rec = |name:&str, first_seen, last_seen, active_secs| SessionRecord {
+            game: "game".to_owned(), mod_name: name.to_owned(), first_seen, last_seen, active_secs };
+        assert_eq!(read_recs(), vec![rec("foo", 1000, 1010, 10), rec("bar", 1008, 1010, 2)]);
+
+        // foo goes idle and is closed, bar keeps going
+        let msgs = vec![
+            ModMsg::ModActive("bar".to_owned(), secs(1008), Duration::from_secs(90)),];
+        process_session_msgs(&msgs, &mut log, testfile, StatsFormat::Jsonl, "game", secs(1100), idle).expect("doh");
+        assert_eq!(read_recs(), vec![rec("foo", 1000, 1010, 10), rec("bar", 1008, 1100, 90)]);
+        assert_eq!(log.as_ref().map(|l| l.open_sessions().len()), Some(1));
+        std::fs::remove_dir_all(&dir).expect("doh");
+    }
+
     #[test]
     fn test_mod_stats_update() {
         let _loglock = LOG_EXCL_LOCK.lock().unwrap();
@@ -753,6 +849,8 @@ mod tests {
             mods: loaded_mods,
             mods_by_name: mods_by_name,
             selected_variant: global_state::new_fnv_map(16),
+            disabled_mods: vec![],
+            last_selected: None,
         };
         *LOADED_MODS.lock().unwrap() = Some(lms);
         set_update_interval_ms(0);
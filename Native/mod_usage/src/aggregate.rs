@@ -0,0 +1,160 @@
+//! Reads structured stats files and totals up mod usage.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::record::{SessionRecord, StatsFormat};
+
+/// Read all records from a stats file.  The format is determined from the extension.  Lines
+/// that can't be parsed are skipped and described in the returned warnings.
+pub fn read_records(path: &Path) -> Result<(Vec<SessionRecord>, Vec<String>), String> {
+    let format = StatsFormat::from_path(path)
+        .ok_or_else(|| format!("{:?}: unknown stats format (expected .jsonl or .csv)", path))?;
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{:?}: {}", path, e))?;
+    let mut recs = vec![];
+    let mut warnings = vec![];
+    for (i, line) in text.lines().enumerate() {
+        match SessionRecord::parse_line(line, format) {
+            Ok(Some(rec)) => recs.push(rec),
+            Ok(None) => (),
+            Err(e) => warnings.push(format!("{:?}:{}: {}", path, i + 1, e)),
+        }
+    }
+    Ok((recs, warnings))
+}
+
+/// Remove duplicate records for the same session, keeping the most recent one.  A session is
+/// identified by game, mod (case insensitive) and start time.
+pub fn dedup_sessions(recs: Vec<SessionRecord>) -> Vec<SessionRecord> {
+    let mut by_key: HashMap<(String, String, u64), SessionRecord> = HashMap::new();
+    for rec in recs {
+        let key = (rec.game.clone(), rec.mod_name.to_lowercase(), rec.first_seen);
+        match by_key.get_mut(&key) {
+            Some(existing) if existing.last_seen >= rec.last_seen => (),
+            Some(existing) => *existing = rec,
+            None => { by_key.insert(key, rec); },
+        }
+    }
+    let mut out: Vec<_> = by_key.into_values().collect();
+    out.sort_by(|a, b| (a.first_seen, &a.game, &a.mod_name).cmp(&(b.first_seen, &b.game, &b.mod_name)));
+    out
+}
+
+/// Usage totals for one mod in one game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModUsage {
+    pub game: String,
+    /// Lowercased, like mod names everywhere else.
+    pub mod_name: String,
+    pub sessions: u32,
+    pub active_secs: u64,
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+/// Total up the sessions that were still going at or after `since` (unix secs).  Sorted by
+/// active time, most used first.
+pub fn aggregate(recs: &[SessionRecord], since: u64) -> Vec<ModUsage> {
+    let mut by_mod: HashMap<(String, String), ModUsage> = HashMap::new();
+    for rec in recs.iter().filter(|r| r.last_seen >= since) {
+        let name = rec.mod_name.to_lowercase();
+        let usage = by_mod.entry((rec.game.clone(), name.clone())).or_insert_with(|| ModUsage {
+            game: rec.game.clone(),
+            mod_name: name,
+            sessions: 0,
+            active_secs: 0,
+            first_seen: rec.first_seen,
+            last_seen: rec.last_seen,
+        });
+        usage.sessions += 1;
+        usage.active_secs += rec.active_secs;
+        usage.first_seen = usage.first_seen.min(rec.first_seen);
+        usage.last_seen = usage.last_seen.max(rec.last_seen);
+    }
+    let mut out: Vec<_> = by_mod.into_values().collect();
+    out.sort_by(|a, b| b.active_secs.cmp(&a.active_secs)
+        .then_with(|| a.game.cmp(&b.game))
+        .then_with(|| a.mod_name.cmp(&b.mod_name)));
+    out
+}
+
+/// Return the names in `mod_names` that don't appear in `usage`, lowercased and sorted.
+pub fn unused_mods(mod_names: &[String], usage: &[ModUsage]) -> Vec<String> {
+    let mut unused: Vec<String> = mod_names.iter()
+        .map(|n| n.to_lowercase())
+        .filter(|n| !usage.iter().any(|u| u.mod_name == *n))
+        .collect();
+    unused.sort();
+    unused.dedup();
+    unused
+}
+
+#[derive(Deserialize)]
+struct ModIndexEntry {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ModIndex {
+    #[serde(default)]
+    mods: Vec<ModIndexEntry>,
+}
+
+/// Read the mod names from a ModIndex.yaml file, as written by the mod manager.
+pub fn read_mod_index(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{:?}: {}", path, e))?;
+    parse_mod_index(&text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+fn parse_mod_index(text: &str) -> Result<Vec<String>, String> {
+    let index: ModIndex = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    Ok(index.mods.into_iter().map(|m| m.name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(game: &str, name: &str, first_seen: u64, last_seen: u64, active_secs: u64) -> SessionRecord {
+        SessionRecord { game: game.to_owned(), mod_name: name.to_owned(), first_seen, last_seen, active_secs }
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let recs = dedup_sessions(vec![
+            rec("g", "A", 100, 200, 90),
+            // older copy of the same session, from a flush that was later rewritten
+            rec("g", "a", 100, 150, 40),
+            rec("g", "a", 1000, 1100, 100),
+            rec("g", "b", 100, 500, 400),
+            rec("g", "c", 10, 20, 10),
+            rec("h", "a", 1000, 1010, 10),
+        ]);
+        assert_eq!(recs.len(), 5);
+        assert_eq!(recs[1], rec("g", "A", 100, 200, 90));
+
+        let usage = aggregate(&recs, 0);
+        let summary: Vec<_> = usage.iter()
+            .map(|u| (u.game.as_str(), u.mod_name.as_str(), u.sessions, u.active_secs)).collect();
+        assert_eq!(summary, [("g", "b", 1, 400), ("g", "a", 2, 190), ("g", "c", 1, 10), ("h", "a", 1, 10)]);
+        assert_eq!((usage[1].first_seen, usage[1].last_seen), (100, 1100));
+
+        // sessions that ended before the cutoff don't count
+        let usage = aggregate(&recs, 300);
+        let summary: Vec<_> = usage.iter().map(|u| (u.mod_name.as_str(), u.active_secs)).collect();
+        assert_eq!(summary, [("b", 400), ("a", 100), ("a", 10)]);
+
+        let names = ["A".to_owned(), "c".to_owned(), "d".to_owned(), "C".to_owned()];
+        assert_eq!(unused_mods(&names, &usage), ["c", "d"]);
+    }
+
+    #[test]
+    fn test_parse_mod_index() {
+        let text = "mods:\n  - name: Mod1\n    path: a\\b.yaml\n  - name: mod2\n";
+        assert_eq!(parse_mod_index(text), Ok(vec!["Mod1".to_owned(), "mod2".to_owned()]));
+        assert_eq!(parse_mod_index("other: 1"), Ok(vec![]));
+        assert!(parse_mod_index("mods: 1").is_err());
+    }
+}
@@ -0,0 +1,11 @@
+//! Structured mod usage stats.  The hook's `mod_stats` writes per-session records using the
+//! types here when `modstats.yaml` selects a structured format, and the `mod_usage` tool
+//! aggregates the files to find out which mods are actually being used.
+
+mod aggregate;
+mod record;
+mod session_log;
+
+pub use crate::aggregate::*;
+pub use crate::record::*;
+pub use crate::session_log::*;
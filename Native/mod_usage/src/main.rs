@@ -0,0 +1,136 @@
+//! Reports which mods were used recently, from the structured stats files written by the hook
+//! (see `modstats.yaml` in the user guide).
+//!
+//! Usage: `mod_usage [--days N] [--game <name>] [--index <ModIndex.yaml>] <files or dirs...>`
+//!
+//! Directories are searched (not recursively) for `modstats.*.jsonl` and `modstats.*.csv`
+//! files.  Mods used in the last N days (default 30) are printed with their total active
+//! time.  If `--index` is given, mods in the index that weren't used in that period are also
+//! listed, which is useful for pruning.
+
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::SystemTime;
+
+use mod_usage::*;
+
+fn usage() -> ! {
+    eprintln!("usage: mod_usage [--days N] [--game <name>] [--index <ModIndex.yaml>] <files or dirs...>");
+    exit(1);
+}
+
+fn is_stats_file(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    name.starts_with("modstats.") && StatsFormat::from_path(path).is_some()
+}
+
+fn format_secs(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut inputs = vec![];
+    let mut days: u64 = 30;
+    let mut game: Option<String> = None;
+    let mut index: Option<PathBuf> = None;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--days" => {
+                i += 1;
+                days = args.get(i).and_then(|d| d.parse().ok()).unwrap_or_else(|| usage());
+            },
+            "--game" => {
+                i += 1;
+                game = Some(args.get(i).unwrap_or_else(|| usage()).clone());
+            },
+            "--index" => {
+                i += 1;
+                index = Some(args.get(i).unwrap_or_else(|| usage()).into());
+            },
+            "-h" | "--help" => usage(),
+            a if a.starts_with("--") => {
+                eprintln!("unknown option: {}", a);
+                usage();
+            },
+            a => inputs.push(PathBuf::from(a)),
+        }
+        i += 1;
+    }
+    if inputs.is_empty() {
+        usage();
+    }
+
+    let mut files = vec![];
+    for input in inputs {
+        if input.is_dir() {
+            let entries = match std::fs::read_dir(&input) {
+                Ok(e) => e,
+                Err(e) => {
+                    eprintln!("error: failed to read {}: {}", input.display(), e);
+                    exit(1);
+                }
+            };
+            let mut found: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.is_file() && is_stats_file(p))
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(input);
+        }
+    }
+    if files.is_empty() {
+        eprintln!("error: no stats files found");
+        exit(1);
+    }
+
+    let mut recs = vec![];
+    for file in files.iter() {
+        match read_records(file) {
+            Ok((r, warnings)) => {
+                for w in warnings {
+                    eprintln!("warning: {}", w);
+                }
+                recs.extend(r);
+            },
+            Err(e) => {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+        }
+    }
+    if let Some(game) = game.as_ref() {
+        recs.retain(|r| r.game.eq_ignore_ascii_case(game));
+    }
+    let recs = dedup_sessions(recs);
+
+    let since = unix_secs(SystemTime::now()).saturating_sub(days * 86400);
+    let usage = aggregate(&recs, since);
+    println!("{} sessions in {} files; {} mods used in the last {} days", recs.len(), files.len(), usage.len(), days);
+    if !usage.is_empty() {
+        println!();
+        println!("{:>10} {:>8}  {:<16}  {:<16}  mod", "active", "sessions", "last seen (UTC)", "game");
+        for u in usage.iter() {
+            println!("{:>10} {:>8}  {:<16}  {:<16}  {}",
+                format_secs(u.active_secs), u.sessions, format_utc(u.last_seen), u.game, u.mod_name);
+        }
+    }
+
+    if let Some(index) = index {
+        let names = match read_mod_index(&index) {
+            Ok(n) => n,
+            Err(e) => {
+                eprintln!("error: failed to read mod index: {}", e);
+                exit(1);
+            }
+        };
+        let unused = unused_mods(&names, &usage);
+        println!();
+        println!("{} of {} mods in {} were not used in the last {} days", unused.len(), names.len(), index.display(), days);
+        for name in unused {
+            println!("    {}", name);
+        }
+    }
+}
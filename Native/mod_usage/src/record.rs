@@ -0,0 +1,238 @@
+//! Per-session mod usage records.  When a structured format is configured, the hook's mod stats
+//! write one of these for each period that a mod was active, and the `mod_usage` tool reads
+//! them back.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+const STATS_CONFIG_FILE: &str = "modstats.yaml";
+
+/// Structured stats file formats.  The original free text format is used when none is
+/// configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// One JSON object per line.
+    Jsonl,
+    /// CSV with a header line (see `CSV_HEADER`).
+    Csv,
+}
+
+impl StatsFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            StatsFormat::Jsonl => "jsonl",
+            StatsFormat::Csv => "csv",
+        }
+    }
+
+    /// Determine the format from a file's extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "jsonl" => Some(StatsFormat::Jsonl),
+            "csv" => Some(StatsFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+pub const CSV_HEADER: &str = "game,mod,first_seen,last_seen,active_secs";
+
+/// A period during which a mod was being rendered.  Times are unix seconds (UTC).
+/// `active_secs` is the time the mod was actually seen during the session, which can be less
+/// than `last_seen - first_seen`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionRecord {
+    pub game: String,
+    #[serde(rename = "mod")]
+    pub mod_name: String,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub active_secs: u64,
+}
+
+impl SessionRecord {
+    pub fn to_line(&self, format: StatsFormat) -> String {
+        match format {
+            // serializing a struct of strings and ints can't fail
+            StatsFormat::Jsonl => serde_json::to_string(self).expect("failed to serialize session record"),
+            StatsFormat::Csv => format!("{},{},{},{},{}",
+                csv_field(&self.game), csv_field(&self.mod_name),
+                self.first_seen, self.last_seen, self.active_secs),
+        }
+    }
+
+    /// Parse a line of a stats file.  Returns None for blank lines and the CSV header.
+    pub fn parse_line(line: &str, format: StatsFormat) -> Result<Option<Self>, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        match format {
+            StatsFormat::Jsonl => serde_json::from_str(line).map(Some).map_err(|e| e.to_string()),
+            StatsFormat::Csv => {
+                if line == CSV_HEADER {
+                    return Ok(None);
+                }
+                let fields = split_csv(line)?;
+                if fields.len() != 5 {
+                    return Err(format!("expected 5 fields, got {}", fields.len()));
+                }
+                let num = |i: usize| fields[i].trim().parse::<u64>()
+                    .map_err(|e| format!("bad number '{}': {}", fields[i], e));
+                Ok(Some(SessionRecord {
+                    game: fields[0].clone(),
+                    mod_name: fields[1].clone(),
+                    first_seen: num(2)?,
+                    last_seen: num(3)?,
+                    active_secs: num(4)?,
+                }))
+            }
+        }
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn split_csv(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            },
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_owned());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+pub fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Format unix seconds as `YYYY-MM-DD HH:MM` UTC.
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, rem / 3600, (rem % 3600) / 60)
+}
+
+/// Parse the stats config text.  The only setting is `format`, which is `text` (the default),
+/// `jsonl` or `csv`.  Returns None for the text format.
+pub fn parse_stats_config(text: &str) -> Result<Option<StatsFormat>, String> {
+    let doc: serde_yaml::Value = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    let format = match &doc {
+        serde_yaml::Value::Null => return Ok(None),
+        serde_yaml::Value::Mapping(_) => doc.get("format"),
+        _ => return Err("expected a mapping".to_owned()),
+    };
+    match format.map(|f| f.as_str().ok_or_else(|| "format must be a string".to_owned())) {
+        None => Ok(None),
+        Some(f) => match f?.trim().to_lowercase().as_str() {
+            "text" => Ok(None),
+            "jsonl" | "json" => Ok(Some(StatsFormat::Jsonl)),
+            "csv" => Ok(Some(StatsFormat::Csv)),
+            other => Err(format!("unknown format '{}'", other)),
+        },
+    }
+}
+
+/// Read `modstats.yaml` in the ModelMod root dir, if it exists.  Returns None (the text
+/// format) if it doesn't.
+pub fn read_stats_config(rootdir: &str) -> Result<Option<StatsFormat>, String> {
+    // try both to account for push semantics with windows paths on other platforms (proton),
+    // see snapconfig.yaml
+    let mut pb = PathBuf::from(rootdir);
+    pb.push(STATS_CONFIG_FILE);
+    if !pb.is_file() {
+        pb = PathBuf::from(rootdir);
+        pb.push(format!("\\{}", STATS_CONFIG_FILE));
+    }
+    if !pb.is_file() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(&pb).map_err(|e| format!("{:?}: {}", pb, e))?;
+    parse_stats_config(&text).map_err(|e| format!("{:?}: {}", pb, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_round_trip() {
+        let recs = [
+            SessionRecord { game: "Game".to_owned(), mod_name: "plain".to_owned(),
+                first_seen: 1700000000, last_seen: 1700000600, active_secs: 540 },
+            SessionRecord { game: "Game, the".to_owned(), mod_name: "has \"quotes\", commas".to_owned(),
+                first_seen: 1, last_seen: 2, active_secs: 0 },
+        ];
+        for format in [StatsFormat::Jsonl, StatsFormat::Csv] {
+            for rec in recs.iter() {
+                let line = rec.to_line(format);
+                assert!(!line.contains('\n'));
+                assert_eq!(SessionRecord::parse_line(&line, format), Ok(Some(rec.clone())), "{}", line);
+            }
+        }
+        assert_eq!(recs[0].to_line(StatsFormat::Jsonl),
+            r#"{"game":"Game","mod":"plain","first_seen":1700000000,"last_seen":1700000600,"active_secs":540}"#);
+        assert_eq!(recs[1].to_line(StatsFormat::Csv), r#""Game, the","has ""quotes"", commas",1,2,0"#);
+
+        assert_eq!(SessionRecord::parse_line(CSV_HEADER, StatsFormat::Csv), Ok(None));
+        assert_eq!(SessionRecord::parse_line("  \r\n", StatsFormat::Jsonl), Ok(None));
+        assert!(SessionRecord::parse_line("g,m,1,2", StatsFormat::Csv).is_err());
+        assert!(SessionRecord::parse_line("g,m,1,2,x", StatsFormat::Csv).is_err());
+        assert!(SessionRecord::parse_line("\"g,m,1,2,3", StatsFormat::Csv).is_err());
+        assert!(SessionRecord::parse_line("{\"game\":\"g\"}", StatsFormat::Jsonl).is_err());
+    }
+
+    #[test]
+    fn test_config_and_formats() {
+        assert_eq!(parse_stats_config(""), Ok(None));
+        assert_eq!(parse_stats_config("format: text"), Ok(None));
+        assert_eq!(parse_stats_config("format: JSONL\n"), Ok(Some(StatsFormat::Jsonl)));
+        assert_eq!(parse_stats_config("# comment\nformat: csv"), Ok(Some(StatsFormat::Csv)));
+        assert_eq!(parse_stats_config("other: 1"), Ok(None));
+        assert!(parse_stats_config("format: xml").is_err());
+        assert!(parse_stats_config("format: [1]").is_err());
+        assert!(parse_stats_config("- a").is_err());
+
+        assert_eq!(StatsFormat::from_path(Path::new("x/modstats.game.JSONL")), Some(StatsFormat::Jsonl));
+        assert_eq!(StatsFormat::from_path(Path::new("modstats.game.csv")), Some(StatsFormat::Csv));
+        assert_eq!(StatsFormat::from_path(Path::new("modstats.game.log")), None);
+
+        assert_eq!(format_utc(0), "1970-01-01 00:00");
+        assert_eq!(format_utc(951782400 + 3723), "2000-02-29 01:02");
+        assert_eq!(format_utc(1700000000), "2023-11-14 22:13");
+    }
+}
@@ -0,0 +1,139 @@
+//! Writes session records to a structured stats file.
+//!
+//! Sessions that may still be updated ("open" sessions) are kept at the end of the file and
+//! rewritten on each flush, so the file always has the latest totals even if the game exits
+//! without warning.  Once a session has been idle long enough it is closed, and its record is
+//! left alone from then on.  Like the text format, this assumes that only one process writes
+//! the file.
+
+use std::fs::OpenOptions;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::record::{SessionRecord, StatsFormat, CSV_HEADER};
+
+pub struct SessionLog {
+    path: PathBuf,
+    format: StatsFormat,
+    /// File offset of the first open session record.
+    open_start: u64,
+    open: Vec<SessionRecord>,
+}
+
+impl SessionLog {
+    /// Open (or create) the file.  Records already in it are never changed.
+    pub fn open(path: &Path, format: StatsFormat) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let len = file.metadata()?.len();
+        if len == 0 {
+            if format == StatsFormat::Csv {
+                writeln!(file, "{}", CSV_HEADER)?;
+            }
+        } else {
+            // if the last write was cut off, finish that line so it doesn't merge with ours
+            let mut last = [0_u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                writeln!(file)?;
+            }
+        }
+        let open_start = file.metadata()?.len();
+        Ok(SessionLog { path: path.to_owned(), format, open_start, open: vec![] })
+    }
+
+    /// Add a session, or update it if there is already an open session for the same mod that
+    /// started at the same time.
+    pub fn update(&mut self, rec: SessionRecord) {
+        match self.open.iter_mut().find(|r| r.mod_name == rec.mod_name && r.first_seen == rec.first_seen) {
+            Some(existing) => *existing = rec,
+            None => self.open.push(rec),
+        }
+    }
+
+    pub fn open_sessions(&self) -> &[SessionRecord] {
+        &self.open
+    }
+
+    /// Write out the open sessions.  Sessions that haven't been seen for more than
+    /// `idle_secs` before `now` are closed first.
+    pub fn flush(&mut self, now: u64, idle_secs: u64) -> io::Result<()> {
+        let (closed, open): (Vec<_>, Vec<_>) = std::mem::take(&mut self.open).into_iter()
+            .partition(|r| r.last_seen.saturating_add(idle_secs) < now);
+        self.open = open;
+
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        file.seek(SeekFrom::Start(self.open_start))?;
+        let mut buf = String::new();
+        for rec in closed.iter() {
+            buf.push_str(&rec.to_line(self.format));
+            buf.push('\n');
+        }
+        file.write_all(buf.as_bytes())?;
+        self.open_start += buf.len() as u64;
+        buf.clear();
+        for rec in self.open.iter() {
+            buf.push_str(&rec.to_line(self.format));
+            buf.push('\n');
+        }
+        file.write_all(buf.as_bytes())?;
+        file.set_len(self.open_start + buf.len() as u64)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(name: &str, first_seen: u64, last_seen: u64) -> SessionRecord {
+        SessionRecord { game: "game".to_owned(), mod_name: name.to_owned(), first_seen, last_seen,
+            active_secs: last_seen - first_seen }
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path).unwrap().lines().map(|l| l.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_session_log() {
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("target");
+        dir.push("tmp");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test_session_log.csv");
+        let _ = std::fs::remove_file(&path);
+
+        let mut log = SessionLog::open(&path, StatsFormat::Csv).unwrap();
+        log.update(rec("a", 100, 160));
+        log.update(rec("b", 100, 200));
+        log.flush(200, 60).unwrap();
+        assert_eq!(lines(&path), vec![CSV_HEADER.to_owned(), "game,a,100,160,60".to_owned(),
+            "game,b,100,200,100".to_owned()]);
+
+        // a is updated in place; b is idle and gets closed, then a new session for it starts
+        // and a is closed in turn
+        log.update(rec("a", 100, 300));
+        log.flush(300, 60).unwrap();
+        log.update(rec("b", 400, 470));
+        log.flush(470, 60).unwrap();
+        assert_eq!(log.open_sessions(), [rec("b", 400, 470)]);
+        assert_eq!(lines(&path)[1..], ["game,b,100,200,100", "game,a,100,300,200", "game,b,400,470,70"]);
+
+        // reopening keeps the existing records, and fixes a cut off line
+        drop(log);
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(f, "game,c,1").unwrap();
+        drop(f);
+        let mut log = SessionLog::open(&path, StatsFormat::Csv).unwrap();
+        log.update(rec("a", 1000, 1100));
+        log.flush(1100, 60).unwrap();
+        let l = lines(&path);
+        assert_eq!(l.len(), 6);
+        assert_eq!(l[4..], ["game,c,1", "game,a,1000,1100,100"]);
+        for line in l[1..].iter() {
+            let parsed = SessionRecord::parse_line(line, StatsFormat::Csv);
+            assert_eq!(parsed.is_ok(), line != "game,c,1", "{}", line);
+        }
+    }
+}
@@ -4,6 +4,8 @@ use shared_dx::util;
 use global_state::GLOBAL_STATE;
 
 use shared_dx::util::ReleaseOnDrop;
+use device_state::dev_state_d3d11_read;
+use shared_dx::dx11rs::DX11RenderState;
 
 use shared_dx::defs_dx9::*;
 use std::ptr::null_mut;
@@ -83,6 +85,61 @@ macro_rules! impl_save_shader {
 impl_save_shader!(save_pixel_shader_d3d9, IDirect3DPixelShader9, GetPixelShader);
 impl_save_shader!(save_vertex_shader_d3d9, IDirect3DVertexShader9, GetVertexShader);
 
+/// D3D11 shaders can't be disassembled, so write the DXBC blob and a yaml summary of it
+/// (`dxbc::ShaderInfo`) instead.  For vertex shaders, `layout_missing` is the list of shader
+/// inputs that the current input layout does not provide, if that could be checked.
+fn save_shader_d3d11(code:&[u8], snap_dir:&str, snap_prefix:&str, suffix:&str,
+    layout_missing:Option<&[String]>) -> Result<bool> {
+    use std::io::Write;
+    let fout = snap_dir.to_owned()  + "/" + snap_prefix + suffix + ".dxbc";
+    let mut file = std::fs::File::create(&fout)?;
+    file.write_all(code)?;
+    util::write_log_file(&format!("wrote {} shader bytes to {}", code.len(), fout));
+
+    match dxbc::ShaderInfo::parse(code) {
+        Ok(info) => {
+            let mut summary = info.to_yaml();
+            if let Some(missing) = layout_missing {
+                summary += &format!("layout_missing: [{}]\n", missing.join(", "));
+            }
+            let fout = snap_dir.to_owned()  + "/" + snap_prefix + suffix + ".yaml";
+            let mut file = std::fs::File::create(&fout)?;
+            file.write_all(summary.as_bytes())?;
+            util::write_log_file(&format!("wrote shader summary to {}", fout));
+        },
+        Err(e) => {
+            util::write_log_file(&format!("Warning: failed to parse shader {}: {}", fout, e));
+        }
+    }
+    Ok(true)
+}
+
+/// For D3D11 snapshots, parse a shader saved by `take_snapshot`; `suffix` is `_vshader` or
+/// `_pshader`.  Returns None if there is no saved shader or it can't be parsed.
+pub fn snapped_shader_info(snap_dir:&str, snap_prefix:&str, suffix:&str) -> Option<dxbc::ShaderInfo> {
+    let file = snap_dir.to_owned()  + "/" + snap_prefix + suffix + ".dxbc";
+    let code = std::fs::read(&file).ok()?;
+    dxbc::ShaderInfo::parse(&code).ok()
+}
+
+/// For D3D11 snapshots, check whether the vertex shader saved by `take_snapshot` reads blend
+/// indices or weights, which means it is probably GPU animated.  Returns None if there is no
+/// saved shader or it can't be parsed.
+pub fn snapped_vshader_has_skinning(snap_dir:&str, snap_prefix:&str) -> Option<bool> {
+    let info = snapped_shader_info(snap_dir, snap_prefix, "_vshader")?;
+    Some(info.inputs?.has_skinning_inputs())
+}
+
+/// Inputs of the current vertex shader that the input layout `layout` does not provide.  Returns
+/// None if this can't be checked: the shader bytecode is only kept when precopying data, and the
+/// layout must be known to the context.
+pub fn layout_missing_vs_inputs(rs:&DX11RenderState, layout:usize) -> Option<Vec<String>> {
+    let code = rs.device_shader_bytecode.get(&rs.current_vs)?;
+    let inputs = dxbc::ShaderInfo::parse(code).ok()?.inputs?;
+    let vf = rs.context_input_layouts_by_ptr.get(&layout)?;
+    Some(vf.missing_shader_inputs(&inputs))
+}
+
 pub fn take_snapshot(device:&mut DevicePointer, snap_dir:&str, snap_prefix:&str) -> (bool,bool) {
     unsafe {
         match device {
@@ -99,8 +156,38 @@ pub fn take_snapshot(device:&mut DevicePointer, snap_dir:&str, snap_prefix:&str)
                 (gotpix, gotvert)
             },
             DevicePointer::D3D11(_device) => {
-                util::write_log_file("shader capture not implemented for D3D11");
-                (false,false)
+                // copy out the code so that the lock isn't held while writing
+                let (vcode, pcode, layout_missing) = match dev_state_d3d11_read() {
+                    Some((_lck, state)) => {
+                        let get = |ptr:usize| if ptr == 0 {
+                            None
+                        } else {
+                            state.rs.device_shader_bytecode.get(&ptr).cloned()
+                        };
+                        // check that the layout satisfies the shader inputs; if it doesn't the
+                        // snapshot is probably of the wrong draw call
+                        let layout_missing = layout_missing_vs_inputs(&state.rs, state.rs.current_input_layout as usize);
+                        (get(state.rs.current_vs), get(state.rs.current_ps), layout_missing)
+                    },
+                    None => (None, None, None),
+                };
+                if let Some(missing) = layout_missing.as_ref().filter(|m| !m.is_empty()) {
+                    util::write_log_file(&format!("Warning: current input layout is missing vertex shader inputs: {:?}", missing));
+                }
+                let save = |code:Option<Vec<u8>>, suffix:&str, layout_missing:Option<&[String]>| match code {
+                    None => {
+                        util::write_log_file(&format!("no bytecode for current shader ({}), data precopy may be disabled", suffix));
+                        false
+                    },
+                    Some(code) => save_shader_d3d11(&code, snap_dir, snap_prefix, suffix, layout_missing).unwrap_or_else(|e| {
+                        util::write_log_file(&format!("failed to save shader: {:?}", e));
+                        false
+                    }),
+                };
+                let gotpix = save(pcode, "_pshader", None);
+                let gotvert = save(vcode, "_vshader", layout_missing.as_deref());
+
+                (gotpix, gotvert)
             },
         }
     }
@@ -1,11 +1,15 @@
 use winapi::ctypes::c_void;
 use winapi::shared::basetsd::SIZE_T;
 use winapi::shared::guiddef::REFIID;
-use winapi::shared::minwindef::{UINT, INT, ULONG};
+use winapi::shared::minwindef::{UINT, INT, ULONG, FLOAT};
 
 use winapi::um::d3d11::{ID3D11Buffer, ID3D11InputLayout, D3D11_INPUT_ELEMENT_DESC,
     ID3D11Device, D3D11_PRIMITIVE_TOPOLOGY, ID3D11ShaderResourceView, D3D11_BUFFER_DESC,
-    D3D11_SUBRESOURCE_DATA, ID3D11Resource, D3D11_TEXTURE2D_DESC, ID3D11Texture2D};
+    D3D11_SUBRESOURCE_DATA, ID3D11Resource, D3D11_TEXTURE2D_DESC, ID3D11Texture2D,
+    ID3D11ClassLinkage, ID3D11ClassInstance, ID3D11VertexShader, ID3D11PixelShader, D3D11_BOX,
+    D3D11_MAP, D3D11_MAPPED_SUBRESOURCE, D3D11_BLEND_DESC, ID3D11BlendState, D3D11_RASTERIZER_DESC,
+    ID3D11RasterizerState, D3D11_DEPTH_STENCIL_DESC, ID3D11DepthStencilState, D3D11_SAMPLER_DESC,
+    ID3D11SamplerState};
 use winapi::um::d3d11::ID3D11DeviceContext;
 use winapi::um::unknwnbase::IUnknown;
 use winapi::um::winnt::HRESULT;
@@ -42,6 +46,60 @@ pub type CreateTexture2DFn = unsafe extern "system" fn(
     ppTexture2D: *mut *mut ID3D11Texture2D,
 ) -> HRESULT;
 
+pub type CreateVertexShaderFn = unsafe extern "system" fn(
+    THIS: *mut ID3D11Device,
+    pShaderBytecode: *const c_void,
+    BytecodeLength: SIZE_T,
+    pClassLinkage: *mut ID3D11ClassLinkage,
+    ppVertexShader: *mut *mut ID3D11VertexShader,
+) -> HRESULT;
+
+pub type CreatePixelShaderFn = unsafe extern "system" fn(
+    THIS: *mut ID3D11Device,
+    pShaderBytecode: *const c_void,
+    BytecodeLength: SIZE_T,
+    pClassLinkage: *mut ID3D11ClassLinkage,
+    ppPixelShader: *mut *mut ID3D11PixelShader,
+) -> HRESULT;
+
+pub type CreateBlendStateFn = unsafe extern "system" fn(
+    THIS: *mut ID3D11Device,
+    pBlendStateDesc: *const D3D11_BLEND_DESC,
+    ppBlendState: *mut *mut ID3D11BlendState,
+) -> HRESULT;
+
+pub type CreateRasterizerStateFn = unsafe extern "system" fn(
+    THIS: *mut ID3D11Device,
+    pRasterizerDesc: *const D3D11_RASTERIZER_DESC,
+    ppRasterizerState: *mut *mut ID3D11RasterizerState,
+) -> HRESULT;
+
+pub type CreateDepthStencilStateFn = unsafe extern "system" fn(
+    THIS: *mut ID3D11Device,
+    pDepthStencilDesc: *const D3D11_DEPTH_STENCIL_DESC,
+    ppDepthStencilState: *mut *mut ID3D11DepthStencilState,
+) -> HRESULT;
+
+pub type CreateSamplerStateFn = unsafe extern "system" fn(
+    THIS: *mut ID3D11Device,
+    pSamplerDesc: *const D3D11_SAMPLER_DESC,
+    ppSamplerState: *mut *mut ID3D11SamplerState,
+) -> HRESULT;
+
+pub type VSSetShaderFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pVertexShader: *mut ID3D11VertexShader,
+    ppClassInstances: *const *mut ID3D11ClassInstance,
+    NumClassInstances: UINT,
+) -> ();
+
+pub type PSSetShaderFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pPixelShader: *mut ID3D11PixelShader,
+    ppClassInstances: *const *mut ID3D11ClassInstance,
+    NumClassInstances: UINT,
+) -> ();
+
 pub type IASetVertexBuffersFn = unsafe extern "system" fn(
     THIS: *mut ID3D11DeviceContext,
     StartSlot: UINT,
@@ -57,6 +115,57 @@ pub type VSSetConstantBuffersFn = unsafe extern "system" fn (
     NumBuffers: UINT,
     ppConstantBuffers: *const *mut ID3D11Buffer,
 ) -> ();
+pub type PSSetConstantBuffersFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    StartSlot: UINT,
+    NumBuffers: UINT,
+    ppConstantBuffers: *const *mut ID3D11Buffer,
+) -> ();
+pub type UpdateSubresourceFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pDstResource: *mut ID3D11Resource,
+    DstSubresource: UINT,
+    pDstBox: *const D3D11_BOX,
+    pSrcData: *const c_void,
+    SrcRowPitch: UINT,
+    SrcDepthPitch: UINT,
+) -> ();
+pub type MapFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pResource: *mut ID3D11Resource,
+    Subresource: UINT,
+    MapType: D3D11_MAP,
+    MapFlags: UINT,
+    pMappedResource: *mut D3D11_MAPPED_SUBRESOURCE,
+) -> HRESULT;
+pub type UnmapFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pResource: *mut ID3D11Resource,
+    Subresource: UINT,
+) -> ();
+// winapi declares the blend factor as a reference, but it is allowed to be null, so this is
+// a pointer and the vtable entry is transmuted.
+pub type OMSetBlendStateFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pBlendState: *mut ID3D11BlendState,
+    BlendFactor: *const [FLOAT; 4],
+    SampleMask: UINT,
+) -> ();
+pub type OMSetDepthStencilStateFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pDepthStencilState: *mut ID3D11DepthStencilState,
+    StencilRef: UINT,
+) -> ();
+pub type RSSetStateFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    pRasterizerState: *mut ID3D11RasterizerState,
+) -> ();
+pub type PSSetSamplersFn = unsafe extern "system" fn (
+    THIS: *mut ID3D11DeviceContext,
+    StartSlot: UINT,
+    NumSamplers: UINT,
+    ppSamplers: *const *mut ID3D11SamplerState,
+) -> ();
 pub type IASetInputLayoutFn = unsafe extern "system" fn (
     THIS: *mut ID3D11DeviceContext,
     pInputLayout: *mut ID3D11InputLayout,
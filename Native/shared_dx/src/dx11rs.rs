@@ -1,7 +1,8 @@
 use std::{fmt::{Display, Formatter, Error}, ffi::CStr, time::SystemTime};
 
 use fnv::FnvHashMap;
-use winapi::um::{d3d11::{ID3D11InputLayout, D3D11_INPUT_ELEMENT_DESC, D3D11_PRIMITIVE_TOPOLOGY}, d3dcommon::D3D_PRIMITIVE_TOPOLOGY_UNDEFINED};
+use winapi::um::{d3d11::{ID3D11InputLayout, D3D11_INPUT_ELEMENT_DESC, D3D11_PRIMITIVE_TOPOLOGY,
+    D3D11_BLEND_DESC, D3D11_RASTERIZER_DESC, D3D11_DEPTH_STENCIL_DESC, D3D11_SAMPLER_DESC}, d3dcommon::D3D_PRIMITIVE_TOPOLOGY_UNDEFINED};
 
 
 /// Container for a vertex format.  Contains a list of elements used by the format and its size in bytes.
@@ -102,6 +103,23 @@ impl VertexFormat {
     pub fn has_extra_semantics(old: SemanticMask, new: SemanticMask) -> bool {
         (new & !old) != 0
     }
+
+    /// The (semantic name, index) pairs that this layout provides.
+    pub fn provided_semantics(&self) -> Vec<(String, u32)> {
+        self.layout.iter()
+            .filter(|elem| !elem.SemanticName.is_null())
+            .map(|elem| {
+                let name = unsafe { CStr::from_ptr(elem.SemanticName) }.to_string_lossy().into_owned();
+                (name, elem.SemanticIndex)
+            })
+            .collect()
+    }
+
+    /// Inputs of a vertex shader (e.g. `TEXCOORD1`) that this layout does not provide.  If
+    /// this isn't empty, the layout can't be drawn with the shader.
+    pub fn missing_shader_inputs(&self, vs_inputs: &dxbc::Signature) -> Vec<String> {
+        vs_inputs.missing_inputs(&self.provided_semantics())
+    }
 }
 
 impl Display for VertexFormat {
@@ -120,7 +138,7 @@ impl Display for VertexFormat {
 }
 
 pub struct DX11RenderState {
-    /// Current vertex buffer properties, vector of (buf index,byte width,stride).
+    /// Current vertex buffer properties, vector of (slot,byte width,stride).
     pub vb_state: Vec<(u32,u32,u32)>,
     /// Number of layouts in `device_input_layouts_by_ptr`
     pub num_input_layouts: std::sync::atomic::AtomicUsize,
@@ -154,6 +172,39 @@ pub struct DX11RenderState {
     /// Controls when vertex data is removed
     pub device_vertex_buffer_createtime: Vec<(usize,SystemTime)>,
     pub device_vertex_buffer_totalsize_nextlog: (usize,usize),
+    /// When snapshotting this stores the bytecode of vertex and pixel shaders by shader pointer,
+    /// since it can't be retrieved from the shader object.
+    pub device_shader_bytecode: FnvHashMap<usize, Vec<u8>>,
+    /// The last vertex shader that was set on the context via VSSetShader.
+    pub current_vs: usize,
+    /// The last pixel shader that was set on the context via PSSetShader.
+    pub current_ps: usize,
+    /// When snapshotting this keeps a copy of each constant buffer, since they can't be read
+    /// back either.  The copy starts with the initial data (or zeros) and is updated by
+    /// UpdateSubresource and Map/Unmap, and removed when the buffer is released.
+    pub device_constant_buffer_data: FnvHashMap<usize, Vec<u8>>,
+    /// Constant buffers that are currently mapped, with the pointer to the mapped memory.  The
+    /// data is copied out when the buffer is unmapped.
+    pub mapped_constant_buffers: FnvHashMap<usize, usize>,
+    /// Constant buffers bound to the vertex shader, indexed by slot (0 if the slot is empty).
+    pub vs_constant_buffers: Vec<usize>,
+    /// Constant buffers bound to the pixel shader, indexed by slot (0 if the slot is empty).
+    pub ps_constant_buffers: Vec<usize>,
+    /// When snapshotting these store the descriptions of the state objects created on the
+    /// device, by state pointer.  D3D11 returns the same object for identical descriptions
+    /// so there aren't many of these.
+    pub device_blend_states: FnvHashMap<usize, D3D11_BLEND_DESC>,
+    pub device_rasterizer_states: FnvHashMap<usize, D3D11_RASTERIZER_DESC>,
+    pub device_depth_stencil_states: FnvHashMap<usize, D3D11_DEPTH_STENCIL_DESC>,
+    pub device_sampler_states: FnvHashMap<usize, D3D11_SAMPLER_DESC>,
+    /// The last blend state set via OMSetBlendState, with its blend factor and sample mask.
+    pub current_blend_state: (usize, [f32; 4], u32),
+    /// The last rasterizer state set via RSSetState.
+    pub current_rasterizer_state: usize,
+    /// The last depth stencil state set via OMSetDepthStencilState, with its stencil ref.
+    pub current_depth_stencil_state: (usize, u32),
+    /// Samplers bound to the pixel shader, indexed by slot (0 if the slot is empty).
+    pub ps_samplers: Vec<usize>,
 }
 
 impl DX11RenderState {
@@ -172,6 +223,21 @@ impl DX11RenderState {
             device_vertex_buffer_data: FnvHashMap::with_capacity_and_hasher(1600, Default::default()),
             device_vertex_buffer_createtime: Vec::new(),
             device_vertex_buffer_totalsize_nextlog: (0,0),
+            device_shader_bytecode: FnvHashMap::with_capacity_and_hasher(256, Default::default()),
+            current_vs: 0,
+            current_ps: 0,
+            device_constant_buffer_data: FnvHashMap::with_capacity_and_hasher(256, Default::default()),
+            mapped_constant_buffers: FnvHashMap::default(),
+            vs_constant_buffers: Vec::new(),
+            ps_constant_buffers: Vec::new(),
+            device_blend_states: FnvHashMap::default(),
+            device_rasterizer_states: FnvHashMap::default(),
+            device_depth_stencil_states: FnvHashMap::default(),
+            device_sampler_states: FnvHashMap::default(),
+            current_blend_state: (0, [1.0; 4], 0xffffffff),
+            current_rasterizer_state: 0,
+            current_depth_stencil_state: (0, 0),
+            ps_samplers: Vec::new(),
         }
     }
 
@@ -8,9 +8,6 @@
 #[macro_use]
 extern crate winapi;
 
-#[macro_use]
-extern crate lazy_static;
-
 pub mod defs_dx11;
 pub mod types_dx11;
 pub mod defs_dx9;
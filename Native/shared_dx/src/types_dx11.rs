@@ -5,7 +5,13 @@ pub struct HookDirect3D11Device {
     pub real_create_buffer: CreateBufferFn,
     pub real_create_texture_2d: CreateTexture2DFn,
     pub real_query_interface: QueryInterfaceFn,
-    pub real_create_input_layout: CreateInputLayoutFn
+    pub real_create_input_layout: CreateInputLayoutFn,
+    pub real_create_vertex_shader: CreateVertexShaderFn,
+    pub real_create_pixel_shader: CreatePixelShaderFn,
+    pub real_create_blend_state: CreateBlendStateFn,
+    pub real_create_rasterizer_state: CreateRasterizerStateFn,
+    pub real_create_depth_stencil_state: CreateDepthStencilStateFn,
+    pub real_create_sampler_state: CreateSamplerStateFn,
 }
 #[derive(Clone, Copy)]
 pub struct HookDirect3D11Context {
@@ -23,6 +29,16 @@ pub struct HookDirect3D11Context {
     pub real_ia_set_input_layout: IASetInputLayoutFn,
     pub real_ia_set_primitive_topology: IASetPrimitiveTopologyFn,
     pub real_ps_set_shader_resources: PSSetShaderResourcesFn,
+    pub real_vs_set_shader: VSSetShaderFn,
+    pub real_ps_set_shader: PSSetShaderFn,
+    pub real_ps_setconstantbuffers: PSSetConstantBuffersFn,
+    pub real_update_subresource: UpdateSubresourceFn,
+    pub real_map: MapFn,
+    pub real_unmap: UnmapFn,
+    pub real_om_set_blend_state: OMSetBlendStateFn,
+    pub real_om_set_depth_stencil_state: OMSetDepthStencilStateFn,
+    pub real_rs_set_state: RSSetStateFn,
+    pub real_ps_set_samplers: PSSetSamplersFn,
 }
 #[derive(Clone, Copy)]
 pub struct HookDirect3D11 {
@@ -1,177 +1,9 @@
 use crate::error::{HookError, Result};
-use std::time::{SystemTime};
-use fnv::{FnvHashMap};
 
-const LOG_TIME:bool = true;
-
-use std::cell::RefCell;
-
-lazy_static! {
-    static ref LOG_FILE_NAME: std::sync::Mutex<String> = std::sync::Mutex::new(String::new());
-    static ref LOG_INIT_TIME: std::sync::Mutex<SystemTime> = std::sync::Mutex::new(SystemTime::now());
-
-    pub static ref LOG_EXCL_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
-}
-
-thread_local! {
-    // create a map that maps string hash codes to a count of the time that string has been logged
-    static LOG_COUNT: RefCell<FnvHashMap<String, u32>> = RefCell::new(FnvHashMap::with_capacity_and_hasher(5000, Default::default()));
-    static LOG_LIMIT: RefCell<u32> = RefCell::new(100);
-}
+pub use mm_core::log::{get_log_file_path, set_log_limit, reset_log_counts, write_log_file, LOG_EXCL_LOCK};
 
 pub fn set_log_file_path(path: &str, name: &str) -> Result<()> {
-    let lock = LOG_FILE_NAME.lock();
-    match lock {
-        Err(e) => Err(HookError::WinApiError(format!("lock error: {}", e))),
-        Ok(mut fname) => {
-            let mut p = path.to_owned();
-            p.push_str(name);
-            *fname = p;
-            Ok(())
-        }
-    }
-}
-
-/// Return the log file path or "" if there was an error.  This function will temporarily lock
-/// a global mutex protecting access to the variable.
-pub fn get_log_file_path() -> String {
-    let lock = LOG_FILE_NAME.lock();
-    match lock {
-        Err(e) => {
-            eprintln!(
-                "ModelMod: derp, can't write log file due to lock error: {}",
-                e
-            );
-            "".to_owned()
-        }
-        Ok(fname) => {
-            (*fname).to_owned()
-        }
-    }
-}
-
-pub fn set_log_limit(limit: u32) {
-    LOG_LIMIT.with(|log_limit| *log_limit.borrow_mut() = limit);
-}
-enum LimResult {
-    Log,
-    DontLog,
-    DontLogAndFYI(String),
-}
-fn log_limit(s:&str) -> LimResult {
-    let limit = LOG_LIMIT.with(|log_limit| *log_limit.borrow());
-
-    LOG_COUNT.with(|log_once| {
-        let mut map = log_once.borrow_mut();
-        let count = map.get(s);
-        match count {
-            None => {
-                map.insert(s.to_owned(), 1);
-                LimResult::Log
-            },
-            Some(c) if *c > limit => {
-                LimResult::DontLog
-            },
-            Some(c) => {
-                if *c == limit {
-                    let fyi = format!("performance warning: message '{}' has been logged {} times; it won't be repeated", s, limit);
-                    map.get_mut(s).map(|c| *c += 1);
-                    LimResult::DontLogAndFYI(fyi)
-                } else {
-                    map.get_mut(s).map(|c| *c += 1);
-                    LimResult::Log
-                }
-            }
-        }
-    })
-
-}
-
-/// Reset rate limits on all log messages to zero (allowing them to be logged again).
-pub fn reset_log_counts() {
-    LOG_COUNT.with(|log_once| {
-        let mut map = log_once.borrow_mut();
-        for count in map.values_mut() {
-            *count = 0;
-        }
-    });
-    write_log_file("reset log limit counts");
-}
-
-pub fn write_log_file(msg: &str) {
-    use std::env::temp_dir;
-    use std::fs::OpenOptions;
-    use std::io::Write;
-
-    let alt_msg = match log_limit(msg) {
-        LimResult::Log => {
-            None
-        },
-        LimResult::DontLog => {
-            return;
-        },
-        LimResult::DontLogAndFYI(s) => {
-            Some(s)
-        }
-    };
-
-    let lock = LOG_FILE_NAME.lock();
-    match lock {
-        Err(e) => {
-            eprintln!(
-                "ModelMod: derp, can't write log file due to lock error: {}",
-                e
-            );
-        }
-        Ok(mut fname) => {
-            if (*fname).is_empty() {
-                let mut td = temp_dir();
-                println!("no log path, writing log to {:?}", td);
-                td.push("ModelMod.log");
-                match td.as_path().to_str() {
-                    None => {
-                        eprintln!("ModelMod: error getting temp path");
-                        return;
-                    }
-                    Some(p) => {
-                        *fname = p.to_owned();
-                    }
-                }
-            }
-
-            // set log time
-            let time_ms =
-                if LOG_TIME {
-                    match LOG_INIT_TIME.lock() {
-                        Ok(start) => {
-                            let since_start =
-                                SystemTime::now().duration_since(*start)
-                                .unwrap_or_else(|_| std::time::Duration::from_millis(0));
-                            let in_ms = since_start.as_secs() * 1000 +
-                            since_start.subsec_nanos() as u64 / 1_000_000;
-                            in_ms as u32
-                        },
-                        Err(_) => 0_u32
-                    }
-                } else {
-                    0
-                };
-
-            let tid = std::thread::current().id();
-
-            let w = || -> std::io::Result<()> {
-                let mut f = OpenOptions::new().create(true).append(true).open(&*fname)?;
-                if let Some(what) = alt_msg {
-                    writeln!(f, "{:?}/{}ms: {}\r", tid, time_ms, what)?;
-                } else {
-                    writeln!(f, "{:?}/{}ms: {}\r", tid, time_ms, msg)?;
-                }
-                Ok(())
-            };
-
-            w().unwrap_or_else(|e| eprintln!("ModelMod: log file write error: {}", e));
-        }
-    };
+    mm_core::log::set_log_file_path(path, name).map_err(HookError::WinApiError)
 }
 
 pub trait ReleaseDrop {
@@ -273,57 +105,4 @@ mod tests {
         }
 
     }
-
-    #[test]
-    fn test_logging_and_limit() {
-        let _loglock = LOG_EXCL_LOCK.lock().unwrap();
-
-        let testfile = "__testutil__test_log_limit.txt";
-        std::fs::remove_file(testfile).ok();
-        set_log_limit(25);
-
-        set_log_file_path("", testfile).expect("doh");
-        for _i in 0..30 {
-            write_log_file("spam");
-            write_log_file("spam");
-            write_log_file("spam");
-            write_log_file("humbug");
-        }
-
-        use std::io::Read;
-        let mut f = std::fs::File::open(testfile).expect("doh");
-        let mut s = String::new();
-        f.read_to_string(&mut s).expect("doh");
-        let lines: Vec<&str> = s.lines().collect();
-
-        let spam = lines.iter().filter(|l| l.contains("spam")).count();
-        let humbug = lines.iter().filter(|l| l.contains("humbug")).count();
-        assert_eq!(spam, 26);
-        assert_eq!(humbug, 26);
-
-        let mut linei = lines.iter();
-        let aftercolon = |s: &str| {
-            let cidx = s.find(": ").expect("doh");
-            s.split_at(cidx + ": ".len() ).1.trim().to_owned()
-        };
-
-        for i in 0..8 {
-            assert_eq!(aftercolon(linei.next().unwrap()), "spam", "line {}", i);
-            assert_eq!(aftercolon(linei.next().unwrap()), "spam", "line {}", i);
-            assert_eq!(aftercolon(linei.next().unwrap()), "spam", "line {}", i);
-            assert_eq!(aftercolon(linei.next().unwrap()), "humbug", "line {}", i);
-        }
-        assert_eq!(aftercolon(linei.next().unwrap()), "spam");
-        let fyi = "performance warning: message 'spam' has been logged 25 times; it won't be repeated";
-        assert_eq!(aftercolon(linei.next().unwrap()), fyi);
-        // then this many humbug lines followed by its spam warning
-        for i in 0..17 {
-            assert_eq!(aftercolon(linei.next().unwrap()), "humbug", "{}", i);
-        }
-        let fyi = "performance warning: message 'humbug' has been logged 25 times; it won't be repeated";
-        assert_eq!(aftercolon(linei.next().unwrap()), fyi);
-
-
-
-    }
-}
\ No newline at end of file
+}
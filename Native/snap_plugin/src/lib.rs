@@ -1,2 +1,2 @@
 mod snap_plugin;
-pub use snap_plugin::*;
\ No newline at end of file
+pub use snap_plugin::*;
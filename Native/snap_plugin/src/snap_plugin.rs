@@ -1,73 +1,283 @@
-use shared_dx::defs::LPDIRECT3DDEVICE9;
-use shared_dx::error::Result;
-use shared_dx::error::HookError;
-use snaplib::anim_frame::AnimFrame;
+//! C ABI for snapshot plugins.
+//!
+//! A plugin is a dll listed in the `plugins` section of the snap config.  It exports the
+//! functions named by the `*_SYMBOL` constants, with the signatures of the matching `*Fn` types.
+//! All structures passed across the boundary are `#[repr(C)]` and only contain plain data and
+//! raw pointers, so plugins can be written in any language; errors are reported by returning
+//! `PluginStatus::FAILED` and filling in the `PluginError` buffer.  Codes like the status and
+//! device type are plain integers rather than rust enums, since a value the other side doesn't
+//! know about would be undefined behavior in an enum.
+//!
+//! The host calls `init` once after loading, `capture` for each draw call that is snapshotted,
+//! `process` once per frame while a snapshot is active, and `shutdown` before unloading.
+//! Plugins must not unwind across these functions.
 
-use util;
+use std::ffi::c_void;
+use std::os::raw::c_char;
 
-const PLUGIN_VER:u32 = 1;
+/// Version of this ABI.  Increment this whenever a structure or function signature changes;
+/// the host refuses to load plugins built for a different version.
+/// (Version 1 was the original d3d9 only interface that returned rust `Result`s.)
+pub const PLUGIN_ABI_VERSION: u32 = 2;
 
-#[repr(C)] pub struct FrameCaptureState { private: [u8; 0] }
+pub const ABI_VERSION_SYMBOL: &str = "mm_snap_plugin_abi_version";
+pub const INIT_SYMBOL: &str = "mm_snap_plugin_init";
+pub const CAPTURE_SYMBOL: &str = "mm_snap_plugin_capture";
+pub const PROCESS_SYMBOL: &str = "mm_snap_plugin_process";
+pub const SHUTDOWN_SYMBOL: &str = "mm_snap_plugin_shutdown";
 
+/// Returned by the plugin functions (a `u32` in C).  The host treats unknown values as failures.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PluginStatus(pub u32);
+
+impl PluginStatus {
+    pub const OK: Self = Self(0);
+    pub const FAILED: Self = Self(1);
+}
+
+pub const PLUGIN_ERROR_LEN: usize = 256;
+
+/// Error message buffer filled in by a plugin when it returns `PluginStatus::FAILED`.  The
+/// message is utf8 and `len` bytes long; it is not null terminated.
 #[repr(C)]
-#[derive(Debug)]
-pub enum PluginError {
-    FailedToCaptureState(String),
-    FailedToProcessState(String),
+pub struct PluginError {
+    pub len: u32,
+    pub message: [u8; PLUGIN_ERROR_LEN],
+}
+
+impl PluginError {
+    pub fn new() -> Self {
+        Self { len: 0, message: [0; PLUGIN_ERROR_LEN] }
+    }
+
+    /// Set the message, truncating it (on a char boundary) if it doesn't fit.
+    pub fn set(&mut self, msg: &str) {
+        let mut len = msg.len().min(PLUGIN_ERROR_LEN);
+        while !msg.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.message[0..len].copy_from_slice(&msg.as_bytes()[0..len]);
+        self.len = len as u32;
+    }
+
+    pub fn message(&self) -> String {
+        let len = (self.len as usize).min(PLUGIN_ERROR_LEN);
+        String::from_utf8_lossy(&self.message[0..len]).into_owned()
+    }
 }
 
-impl std::convert::From<PluginError> for HookError {
-    fn from(error: PluginError) -> Self {
-        HookError::SnapshotPluginError(format!("{:?}", error))
+impl Default for PluginError {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub type GetVersionFn = extern "C" fn () -> u32;
-pub type InitFn = extern "C" fn () -> std::result::Result<(), PluginError>;
-pub type AnimFrameCaptureFn = extern "C" fn (device: LPDIRECT3DDEVICE9) -> std::result::Result<*mut FrameCaptureState, PluginError>;
-pub type AnimFrameProcessFn = extern "C" fn (cap_state: *mut FrameCaptureState, frame: *mut AnimFrame, fsize:u32) -> std::result::Result<(), PluginError>;
+/// The device type of a draw (a `u32` in C).  Plugins should ignore draws with a type they
+/// don't know about, since later hosts may add more.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceType(pub u32);
+
+impl DeviceType {
+    pub const D3D9: Self = Self(9);
+    pub const D3D11: Self = Self(11);
+}
+
+impl std::fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::D3D9 => write!(f, "D3D9"),
+            Self::D3D11 => write!(f, "D3D11"),
+            Self(other) => write!(f, "unknown device type {}", other),
+        }
+    }
+}
+
+/// Passed to `init`.
+#[repr(C)]
+pub struct HostInfo {
+    pub abi_version: u32,
+}
+
+/// Passed to `capture` for each snapshotted draw call.  The pointers are only valid for the
+/// duration of the call.
+#[repr(C)]
+pub struct DrawInfo {
+    pub device_type: DeviceType,
+    /// `IDirect3DDevice9` or `ID3D11Device`
+    pub device: *mut c_void,
+    pub prim_count: u32,
+    pub vert_count: u32,
+    /// Frame number, counted from when the hook was loaded
+    pub frame: u64,
+    /// Null terminated utf8 snapshot directory and file prefix of this draw's snapshot files.
+    /// These are empty strings if the snapshot failed.
+    pub snap_dir: *const c_char,
+    pub snap_prefix: *const c_char,
+}
+
+/// Passed to `process` once per frame while a snapshot is active.
+#[repr(C)]
+pub struct FrameInfo {
+    pub frame: u64,
+    /// Time since the snapshot started
+    pub snap_elapsed_ms: u64,
+}
+
+pub type AbiVersionFn = unsafe extern "C" fn() -> u32;
+pub type InitFn = unsafe extern "C" fn(host: *const HostInfo, err: *mut PluginError) -> PluginStatus;
+pub type CaptureFn = unsafe extern "C" fn(draw: *const DrawInfo, err: *mut PluginError) -> PluginStatus;
+pub type ProcessFn = unsafe extern "C" fn(frame: *const FrameInfo, err: *mut PluginError) -> PluginStatus;
+pub type ShutdownFn = unsafe extern "C" fn();
 
+/// The entry points of a loaded plugin.  This does not own the library; the host must keep it
+/// loaded for as long as the plugin is in use.
 pub struct SnapPlugin {
-    capture_fn:AnimFrameCaptureFn,
-    process_fn:AnimFrameProcessFn,
+    pub name: String,
+    init_fn: InitFn,
+    capture_fn: CaptureFn,
+    process_fn: ProcessFn,
+    shutdown_fn: ShutdownFn,
 }
+
 impl SnapPlugin {
-    fn anim_frame_capture(&mut self, device: LPDIRECT3DDEVICE9) -> Result<*mut FrameCaptureState> {
-        let res = unsafe { (self.capture_fn)(device) }?;
-        Ok(res)
+    /// Resolve the entry points using `lookup`, which returns the address of an exported
+    /// symbol, and check the plugin's ABI version.  `init` is not called.
+    ///
+    /// # Safety
+    /// The addresses returned by `lookup` must be functions with the signatures of the
+    /// corresponding `*Fn` types.
+    pub unsafe fn from_symbols<F>(name: &str, mut lookup: F) -> Result<Self, String>
+    where F: FnMut(&str) -> Option<*const c_void> {
+        let mut get = |sym: &str| {
+            lookup(sym).filter(|p| !p.is_null())
+                .ok_or_else(|| format!("plugin {}: {} not found", name, sym))
+        };
+        let abi_version = std::mem::transmute::<*const c_void, AbiVersionFn>(get(ABI_VERSION_SYMBOL)?);
+        let ver = abi_version();
+        if ver != PLUGIN_ABI_VERSION {
+            return Err(format!("plugin {}: has ABI version {}, we need {}", name, ver, PLUGIN_ABI_VERSION));
+        }
+        // all functions must be available before init is called
+        Ok(Self {
+            name: name.to_owned(),
+            init_fn: std::mem::transmute::<*const c_void, InitFn>(get(INIT_SYMBOL)?),
+            capture_fn: std::mem::transmute::<*const c_void, CaptureFn>(get(CAPTURE_SYMBOL)?),
+            process_fn: std::mem::transmute::<*const c_void, ProcessFn>(get(PROCESS_SYMBOL)?),
+            shutdown_fn: std::mem::transmute::<*const c_void, ShutdownFn>(get(SHUTDOWN_SYMBOL)?),
+        })
+    }
+
+    fn call<F>(&self, what: &str, f: F) -> Result<(), String>
+    where F: FnOnce(*mut PluginError) -> PluginStatus {
+        let mut err = PluginError::new();
+        match f(&mut err) {
+            PluginStatus::OK => Ok(()),
+            PluginStatus::FAILED => Err(format!("plugin {}: {} failed: {}", self.name, what, err.message())),
+            PluginStatus(other) => Err(format!("plugin {}: {} returned unknown status {}", self.name, what, other)),
+        }
+    }
+
+    pub fn init(&self) -> Result<(), String> {
+        let host = HostInfo { abi_version: PLUGIN_ABI_VERSION };
+        self.call("init", |err| unsafe { (self.init_fn)(&host, err) })
+    }
+
+    pub fn capture(&self, draw: &DrawInfo) -> Result<(), String> {
+        self.call("capture", |err| unsafe { (self.capture_fn)(draw, err) })
     }
-    fn anim_frame_process(&mut self, cap_state: *mut FrameCaptureState, frame: &mut AnimFrame) -> Result<()> {
-        let fsize:u32 = std::mem::size_of::<AnimFrame>() as u32;
-        let res = unsafe { (self.process_fn)(cap_state, frame, fsize) } ?;
-        Ok(res)
+
+    pub fn process(&self, frame: &FrameInfo) -> Result<(), String> {
+        self.call("process", |err| unsafe { (self.process_fn)(frame, err) })
+    }
+
+    pub fn shutdown(&self) {
+        unsafe { (self.shutdown_fn)() }
     }
 }
-pub fn load(path:&str) -> Result<SnapPlugin> {
-    let h = util::load_lib(path)?;
-
-    let load = || {
-        unsafe {
-            let getver:GetVersionFn = std::mem::transmute(util::get_proc_address(h, "get_version")?);
-            let ver = getver();
-            if ver != PLUGIN_VER {
-                return Err(HookError::SnapshotFailed(format!("Can't load plugin {}: has old version {}, we need {}", path, ver, PLUGIN_VER)));
-            }
-            // all functions must be available before we call init
-            let capture_fn:AnimFrameCaptureFn = std::mem::transmute(util::get_proc_address(h, "anim_frame_capture")?);
-            let process_fn:AnimFrameProcessFn = std::mem::transmute(util::get_proc_address(h, "anim_frame_process")?);
-
-            let init:InitFn = std::mem::transmute(util::get_proc_address(h, "init")?);
-            init()?;
-            Ok(SnapPlugin{
-                capture_fn,
-                process_fn,
-            })
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn abi_version() -> u32 { PLUGIN_ABI_VERSION }
+    unsafe extern "C" fn old_abi_version() -> u32 { 1 }
+    unsafe extern "C" fn init(host: *const HostInfo, _err: *mut PluginError) -> PluginStatus {
+        assert_eq!((*host).abi_version, PLUGIN_ABI_VERSION);
+        PluginStatus::OK
+    }
+    unsafe extern "C" fn capture(draw: *const DrawInfo, err: *mut PluginError) -> PluginStatus {
+        if (*draw).prim_count == 0 {
+            (*err).set("no prims");
+            return PluginStatus::FAILED;
+        }
+        PluginStatus::OK
+    }
+    unsafe extern "C" fn process(frame: *const FrameInfo, _err: *mut PluginError) -> PluginStatus {
+        // a status the host doesn't know about
+        if (*frame).frame == 0 {
+            return PluginStatus(7);
         }
-    };
+        PluginStatus::OK
+    }
+    unsafe extern "C" fn shutdown() {}
 
-    let res = load();
-    if res.is_err() {
-        util::unload_lib(h)?;
+    fn lookup(sym: &str) -> Option<*const c_void> {
+        Some(match sym {
+            ABI_VERSION_SYMBOL => abi_version as *const c_void,
+            INIT_SYMBOL => init as *const c_void,
+            CAPTURE_SYMBOL => capture as *const c_void,
+            PROCESS_SYMBOL => process as *const c_void,
+            SHUTDOWN_SYMBOL => shutdown as *const c_void,
+            _ => return None,
+        })
     }
-    res
-}
\ No newline at end of file
+
+    #[test]
+    fn test_from_symbols() {
+        let plugin = unsafe { SnapPlugin::from_symbols("test", lookup) }.unwrap();
+        plugin.init().unwrap();
+        let draw = |prim_count| DrawInfo {
+            device_type: DeviceType::D3D11,
+            device: std::ptr::null_mut(),
+            prim_count,
+            vert_count: 3,
+            frame: 1,
+            snap_dir: c"".as_ptr(),
+            snap_prefix: c"".as_ptr(),
+        };
+        plugin.capture(&draw(1)).unwrap();
+        assert_eq!(plugin.capture(&draw(0)).unwrap_err(), "plugin test: capture failed: no prims");
+        plugin.process(&FrameInfo { frame: 2, snap_elapsed_ms: 16 }).unwrap();
+        assert_eq!(plugin.process(&FrameInfo { frame: 0, snap_elapsed_ms: 0 }).unwrap_err(),
+            "plugin test: process returned unknown status 7");
+        plugin.shutdown();
+
+        let missing = unsafe { SnapPlugin::from_symbols("test", |s| if s == SHUTDOWN_SYMBOL { None } else { lookup(s) }) };
+        assert!(missing.err().unwrap().contains(SHUTDOWN_SYMBOL));
+        let old = unsafe { SnapPlugin::from_symbols("test", |s| {
+            if s == ABI_VERSION_SYMBOL { Some(old_abi_version as *const c_void) } else { lookup(s) }
+        })};
+        assert!(old.err().unwrap().contains("ABI version 1"));
+    }
+
+    #[test]
+    fn test_device_type_display() {
+        assert_eq!(DeviceType::D3D9.to_string(), "D3D9");
+        assert_eq!(DeviceType::D3D11.to_string(), "D3D11");
+        assert_eq!(DeviceType(12).to_string(), "unknown device type 12");
+    }
+
+    #[test]
+    fn test_error_truncation() {
+        let mut err = PluginError::new();
+        err.set("failed");
+        assert_eq!(err.message(), "failed");
+        // multibyte char straddling the end of the buffer is dropped
+        let long = "x".repeat(PLUGIN_ERROR_LEN - 1) + "é";
+        err.set(&long);
+        assert_eq!(err.len as usize, PLUGIN_ERROR_LEN - 1);
+        assert_eq!(err.message(), "x".repeat(PLUGIN_ERROR_LEN - 1));
+    }
+}
@@ -0,0 +1,68 @@
+//! Sample snapshot plugin.  For each snapshotted draw it writes
+//! `<snap_dir>/<snap_prefix>_sample_plugin.txt` with the draw's counts.  Copy this to start a
+//! new plugin.  Note that stdout and stderr usually go nowhere in a game process, so report
+//! problems through the `PluginError` the host passes in.
+
+// the entry points are only called by the host; the contract is documented in snap_plugin
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use snap_plugin::*;
+
+unsafe fn c_str(s: *const c_char) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(s).to_string_lossy().into_owned()
+}
+
+fn write_draw(draw: &DrawInfo) -> Result<(), String> {
+    let (dir, prefix) = unsafe { (c_str(draw.snap_dir), c_str(draw.snap_prefix)) };
+    if dir.is_empty() || prefix.is_empty() {
+        // snapshot failed, nothing to annotate
+        return Ok(());
+    }
+    let out = format!("{}/{}_sample_plugin.txt", dir, prefix);
+    let text = format!("device: {}\nframe: {}\nprims: {}\nverts: {}\n",
+        draw.device_type, draw.frame, draw.prim_count, draw.vert_count);
+    std::fs::write(&out, text).map_err(|e| format!("failed to write {}: {}", out, e))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mm_snap_plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mm_snap_plugin_init(_host: *const HostInfo, _err: *mut PluginError) -> PluginStatus {
+    PluginStatus::OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mm_snap_plugin_capture(draw: *const DrawInfo, err: *mut PluginError) -> PluginStatus {
+    let res = match draw.as_ref() {
+        None => Err("null draw info".to_owned()),
+        Some(draw) => write_draw(draw),
+    };
+    match res {
+        Ok(()) => PluginStatus::OK,
+        Err(e) => {
+            if let Some(err) = err.as_mut() {
+                err.set(&e);
+            }
+            PluginStatus::FAILED
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mm_snap_plugin_process(_frame: *const FrameInfo, _err: *mut PluginError) -> PluginStatus {
+    // nothing to do per frame
+    PluginStatus::OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mm_snap_plugin_shutdown() {
+}
@@ -0,0 +1,56 @@
+//! Loads the sample plugin dll with libloading and drives it the way the hook does, to check
+//! that the ABI works across a real library boundary.
+
+use std::ffi::{c_void, CString};
+use std::path::PathBuf;
+
+use snap_plugin::*;
+
+/// The cdylib is written to the target directory, next to the deps directory this test runs from.
+fn plugin_path() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let name = libloading::library_filename("snap_plugin_sample");
+    [deps.to_path_buf(), deps.parent().unwrap().to_path_buf()].into_iter()
+        .map(|d| d.join(&name))
+        .find(|p| p.is_file())
+        .unwrap_or_else(|| panic!("{:?} not found near {}", name, deps.display()))
+}
+
+#[test]
+fn test_load_sample_plugin() {
+    let lib = unsafe { libloading::Library::new(plugin_path()) }.unwrap();
+    let plugin = unsafe {
+        SnapPlugin::from_symbols("snap_plugin_sample", |sym| {
+            lib.get::<unsafe extern "C" fn()>(sym.as_bytes()).ok().map(|f| *f as *const c_void)
+        })
+    }.unwrap();
+    plugin.init().unwrap();
+
+    let dir = std::env::temp_dir().join(format!("mm_snap_plugin_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let snap_dir = CString::new(dir.to_string_lossy().as_bytes()).unwrap();
+    let snap_prefix = CString::new("snap_0").unwrap();
+    let draw = DrawInfo {
+        device_type: DeviceType::D3D11,
+        device: std::ptr::null_mut(),
+        prim_count: 12,
+        vert_count: 24,
+        frame: 100,
+        snap_dir: snap_dir.as_ptr(),
+        snap_prefix: snap_prefix.as_ptr(),
+    };
+    plugin.capture(&draw).unwrap();
+    let text = std::fs::read_to_string(dir.join("snap_0_sample_plugin.txt")).unwrap();
+    assert!(text.contains("device: D3D11"), "{}", text);
+    assert!(text.contains("prims: 12\nverts: 24"), "{}", text);
+
+    // errors come back through the error buffer
+    let bad_dir = CString::new(dir.join("missing").to_string_lossy().as_bytes()).unwrap();
+    let err = plugin.capture(&DrawInfo { snap_dir: bad_dir.as_ptr(), ..draw }).unwrap_err();
+    assert!(err.starts_with("plugin snap_plugin_sample: capture failed: failed to write"), "{}", err);
+
+    plugin.process(&FrameInfo { frame: 101, snap_elapsed_ms: 16 }).unwrap();
+    plugin.shutdown();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -44,43 +44,9 @@ where T: Serialize {
     Ok(())
 }
 
-#[derive(Serialize)]
-#[repr(C)]
-pub struct AnimFrame {
-    pub snapped_at: std::time::SystemTime,
-    pub floats: std::collections::BTreeMap<UINT, Vec4<f32>>,
-    pub transform1: Option<Vec4<f32>>,
-    // Note, if these are re-enabled at some point, that will break compatibility on deserialization with any old animation snapshot files.
-    // (will need to use a time stamp or some other means to determine what version a file is, or maybe just read the first few bytes
-    // to determine if its has a version, which I suppose should be checked in animframefile below since that represents a whole file)
-    // pub transform2: Option<Vec4<f32>>,
-    // pub transform3: Option<Vec4<f32>>,
-    // pub transform4: Option<Vec4<f32>>,
-}
-
-#[derive(Serialize)]
-pub struct AnimFrameFile {
-    pub frames:Vec<AnimFrame>
-}
-
-impl AnimFrameFile {
-    pub fn new() -> Self {
-        Self {
-            frames: vec![]
-        }
-    }
-
-    pub fn write_to_file(&self, name:&str) -> Result<()> {
-        let s = bincode::serialize(self).map_err(|e| {
-            HookError::SerdeError(format!("Serialization error: {:?}", e))
-        })?;
-
-        use std::io::Write;
-        let mut file = std::fs::File::create(name)?;
-        file.write_all(&s)?;
-        Ok(())
-    }
-}
+// The frame file types live in the anim_frames crate so that captures can be read without
+// pulling in the hook.
+pub use anim_frames::{AnimFrame, AnimFrameFile};
 
 pub fn write_to_file(name:&str, constants:&ConstantGroup) -> Result<()> {
     let file = GroupFile {
@@ -3,15 +3,34 @@ use std::time::SystemTime;
 use shared_dx::error::Result;
 
 use constant_tracking;
+
+/// Player position and heading at the time of an anim capture.  Some games feed world space
+/// bones to the shader, and this is needed to map them back into object space.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerTransform {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub rot: f32,
+}
+impl PlayerTransform {
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.rot]
+    }
+}
+
 pub struct AnimConstants {
     pub snapped_at: SystemTime,
     pub prim_count: UINT,
     pub vert_count: UINT,
     pub constants: constant_tracking::ConstantGroup,
+    /// D3D11 only: copy of the bone palette constant buffer.  Empty for D3D9, which uses
+    /// `constants` instead.
+    pub cbuffer: Vec<u8>,
     pub sequence: usize,
     pub frame: u64,
     pub capture_count: u32,
-    pub player_transform: Result<String>,
+    pub player_transform: Result<PlayerTransform>,
     pub snap_on_count: u32,
     // currently these matrices are not captured because they are identity
     // worldmat: D3DMATRIX,
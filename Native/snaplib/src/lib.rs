@@ -4,3 +4,5 @@ extern crate serde_yaml;
 pub mod anim_snap_state;
 pub mod anim_frame;
 pub mod snap_config;
+pub mod render_state_d3d11;
+pub mod snap_manifest;
@@ -0,0 +1,428 @@
+//! D3D11 render state as written to a snapshot's `_rstate.yaml`.  The D3D11 state objects are
+//! converted into serializable structs with readable enum names, and a couple of summary flags
+//! are included so that it is easy to tell at a glance how the mesh was drawn.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use winapi::um::d3d11::*;
+
+fn blend_name(b: D3D11_BLEND) -> String {
+    match b {
+        D3D11_BLEND_ZERO => "ZERO",
+        D3D11_BLEND_ONE => "ONE",
+        D3D11_BLEND_SRC_COLOR => "SRC_COLOR",
+        D3D11_BLEND_INV_SRC_COLOR => "INV_SRC_COLOR",
+        D3D11_BLEND_SRC_ALPHA => "SRC_ALPHA",
+        D3D11_BLEND_INV_SRC_ALPHA => "INV_SRC_ALPHA",
+        D3D11_BLEND_DEST_ALPHA => "DEST_ALPHA",
+        D3D11_BLEND_INV_DEST_ALPHA => "INV_DEST_ALPHA",
+        D3D11_BLEND_DEST_COLOR => "DEST_COLOR",
+        D3D11_BLEND_INV_DEST_COLOR => "INV_DEST_COLOR",
+        D3D11_BLEND_SRC_ALPHA_SAT => "SRC_ALPHA_SAT",
+        D3D11_BLEND_BLEND_FACTOR => "BLEND_FACTOR",
+        D3D11_BLEND_INV_BLEND_FACTOR => "INV_BLEND_FACTOR",
+        D3D11_BLEND_SRC1_COLOR => "SRC1_COLOR",
+        D3D11_BLEND_INV_SRC1_COLOR => "INV_SRC1_COLOR",
+        D3D11_BLEND_SRC1_ALPHA => "SRC1_ALPHA",
+        D3D11_BLEND_INV_SRC1_ALPHA => "INV_SRC1_ALPHA",
+        _ => return format!("UNKNOWN({})", b),
+    }.to_owned()
+}
+
+fn blend_op_name(op: D3D11_BLEND_OP) -> String {
+    match op {
+        D3D11_BLEND_OP_ADD => "ADD",
+        D3D11_BLEND_OP_SUBTRACT => "SUBTRACT",
+        D3D11_BLEND_OP_REV_SUBTRACT => "REV_SUBTRACT",
+        D3D11_BLEND_OP_MIN => "MIN",
+        D3D11_BLEND_OP_MAX => "MAX",
+        _ => return format!("UNKNOWN({})", op),
+    }.to_owned()
+}
+
+fn comparison_name(f: D3D11_COMPARISON_FUNC) -> String {
+    match f {
+        D3D11_COMPARISON_NEVER => "NEVER",
+        D3D11_COMPARISON_LESS => "LESS",
+        D3D11_COMPARISON_EQUAL => "EQUAL",
+        D3D11_COMPARISON_LESS_EQUAL => "LESS_EQUAL",
+        D3D11_COMPARISON_GREATER => "GREATER",
+        D3D11_COMPARISON_NOT_EQUAL => "NOT_EQUAL",
+        D3D11_COMPARISON_GREATER_EQUAL => "GREATER_EQUAL",
+        D3D11_COMPARISON_ALWAYS => "ALWAYS",
+        _ => return format!("UNKNOWN({})", f),
+    }.to_owned()
+}
+
+fn stencil_op_name(op: D3D11_STENCIL_OP) -> String {
+    match op {
+        D3D11_STENCIL_OP_KEEP => "KEEP",
+        D3D11_STENCIL_OP_ZERO => "ZERO",
+        D3D11_STENCIL_OP_REPLACE => "REPLACE",
+        D3D11_STENCIL_OP_INCR_SAT => "INCR_SAT",
+        D3D11_STENCIL_OP_DECR_SAT => "DECR_SAT",
+        D3D11_STENCIL_OP_INVERT => "INVERT",
+        D3D11_STENCIL_OP_INCR => "INCR",
+        D3D11_STENCIL_OP_DECR => "DECR",
+        _ => return format!("UNKNOWN({})", op),
+    }.to_owned()
+}
+
+fn address_name(m: D3D11_TEXTURE_ADDRESS_MODE) -> String {
+    match m {
+        D3D11_TEXTURE_ADDRESS_WRAP => "WRAP",
+        D3D11_TEXTURE_ADDRESS_MIRROR => "MIRROR",
+        D3D11_TEXTURE_ADDRESS_CLAMP => "CLAMP",
+        D3D11_TEXTURE_ADDRESS_BORDER => "BORDER",
+        D3D11_TEXTURE_ADDRESS_MIRROR_ONCE => "MIRROR_ONCE",
+        _ => return format!("UNKNOWN({})", m),
+    }.to_owned()
+}
+
+fn filter_name(f: D3D11_FILTER) -> String {
+    match f {
+        D3D11_FILTER_MIN_MAG_MIP_POINT => "MIN_MAG_MIP_POINT",
+        D3D11_FILTER_MIN_MAG_POINT_MIP_LINEAR => "MIN_MAG_POINT_MIP_LINEAR",
+        D3D11_FILTER_MIN_POINT_MAG_LINEAR_MIP_POINT => "MIN_POINT_MAG_LINEAR_MIP_POINT",
+        D3D11_FILTER_MIN_POINT_MAG_MIP_LINEAR => "MIN_POINT_MAG_MIP_LINEAR",
+        D3D11_FILTER_MIN_LINEAR_MAG_MIP_POINT => "MIN_LINEAR_MAG_MIP_POINT",
+        D3D11_FILTER_MIN_LINEAR_MAG_POINT_MIP_LINEAR => "MIN_LINEAR_MAG_POINT_MIP_LINEAR",
+        D3D11_FILTER_MIN_MAG_LINEAR_MIP_POINT => "MIN_MAG_LINEAR_MIP_POINT",
+        D3D11_FILTER_MIN_MAG_MIP_LINEAR => "MIN_MAG_MIP_LINEAR",
+        D3D11_FILTER_ANISOTROPIC => "ANISOTROPIC",
+        D3D11_FILTER_COMPARISON_MIN_MAG_MIP_POINT => "COMPARISON_MIN_MAG_MIP_POINT",
+        D3D11_FILTER_COMPARISON_MIN_MAG_MIP_LINEAR => "COMPARISON_MIN_MAG_MIP_LINEAR",
+        D3D11_FILTER_COMPARISON_ANISOTROPIC => "COMPARISON_ANISOTROPIC",
+        _ => return format!("0x{:x}", f),
+    }.to_owned()
+}
+
+#[derive(Serialize)]
+pub struct RenderTargetBlend {
+    pub target: usize,
+    pub blend_enable: bool,
+    pub src_blend: String,
+    pub dest_blend: String,
+    pub blend_op: String,
+    pub src_blend_alpha: String,
+    pub dest_blend_alpha: String,
+    pub blend_op_alpha: String,
+    pub write_mask: u8,
+}
+
+#[derive(Serialize)]
+pub struct BlendState {
+    pub alpha_to_coverage: bool,
+    pub independent_blend: bool,
+    pub blend_factor: [f32; 4],
+    pub sample_mask: u32,
+    /// Only target 0 is listed unless independent blending is enabled
+    pub render_targets: Vec<RenderTargetBlend>,
+}
+
+impl BlendState {
+    pub fn from_desc(desc: &D3D11_BLEND_DESC, blend_factor: [f32; 4], sample_mask: u32) -> Self {
+        let num_targets = if desc.IndependentBlendEnable != 0 { desc.RenderTarget.len() } else { 1 };
+        let render_targets = desc.RenderTarget.iter().take(num_targets).enumerate().map(|(target, rt)| {
+            RenderTargetBlend {
+                target,
+                blend_enable: rt.BlendEnable != 0,
+                src_blend: blend_name(rt.SrcBlend),
+                dest_blend: blend_name(rt.DestBlend),
+                blend_op: blend_op_name(rt.BlendOp),
+                src_blend_alpha: blend_name(rt.SrcBlendAlpha),
+                dest_blend_alpha: blend_name(rt.DestBlendAlpha),
+                blend_op_alpha: blend_op_name(rt.BlendOpAlpha),
+                write_mask: rt.RenderTargetWriteMask,
+            }
+        }).collect();
+        BlendState {
+            alpha_to_coverage: desc.AlphaToCoverageEnable != 0,
+            independent_blend: desc.IndependentBlendEnable != 0,
+            blend_factor,
+            sample_mask,
+            render_targets,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RasterizerState {
+    pub fill_mode: String,
+    pub cull_mode: String,
+    pub front_counter_clockwise: bool,
+    pub depth_bias: i32,
+    pub depth_bias_clamp: f32,
+    pub slope_scaled_depth_bias: f32,
+    pub depth_clip_enable: bool,
+    pub scissor_enable: bool,
+    pub multisample_enable: bool,
+    pub antialiased_line_enable: bool,
+}
+
+impl RasterizerState {
+    pub fn from_desc(desc: &D3D11_RASTERIZER_DESC) -> Self {
+        RasterizerState {
+            fill_mode: match desc.FillMode {
+                D3D11_FILL_WIREFRAME => "WIREFRAME".to_owned(),
+                D3D11_FILL_SOLID => "SOLID".to_owned(),
+                m => format!("UNKNOWN({})", m),
+            },
+            cull_mode: match desc.CullMode {
+                D3D11_CULL_NONE => "NONE".to_owned(),
+                D3D11_CULL_FRONT => "FRONT".to_owned(),
+                D3D11_CULL_BACK => "BACK".to_owned(),
+                m => format!("UNKNOWN({})", m),
+            },
+            front_counter_clockwise: desc.FrontCounterClockwise != 0,
+            depth_bias: desc.DepthBias,
+            depth_bias_clamp: desc.DepthBiasClamp,
+            slope_scaled_depth_bias: desc.SlopeScaledDepthBias,
+            depth_clip_enable: desc.DepthClipEnable != 0,
+            scissor_enable: desc.ScissorEnable != 0,
+            multisample_enable: desc.MultisampleEnable != 0,
+            antialiased_line_enable: desc.AntialiasedLineEnable != 0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StencilOps {
+    pub fail: String,
+    pub depth_fail: String,
+    pub pass: String,
+    pub func: String,
+}
+
+impl StencilOps {
+    fn from_desc(desc: &D3D11_DEPTH_STENCILOP_DESC) -> Self {
+        StencilOps {
+            fail: stencil_op_name(desc.StencilFailOp),
+            depth_fail: stencil_op_name(desc.StencilDepthFailOp),
+            pass: stencil_op_name(desc.StencilPassOp),
+            func: comparison_name(desc.StencilFunc),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DepthStencilState {
+    pub depth_enable: bool,
+    pub depth_write: bool,
+    pub depth_func: String,
+    pub stencil_enable: bool,
+    pub stencil_ref: u32,
+    pub stencil_read_mask: u8,
+    pub stencil_write_mask: u8,
+    pub front_face: StencilOps,
+    pub back_face: StencilOps,
+}
+
+impl DepthStencilState {
+    pub fn from_desc(desc: &D3D11_DEPTH_STENCIL_DESC, stencil_ref: u32) -> Self {
+        DepthStencilState {
+            depth_enable: desc.DepthEnable != 0,
+            depth_write: desc.DepthWriteMask == D3D11_DEPTH_WRITE_MASK_ALL,
+            depth_func: comparison_name(desc.DepthFunc),
+            stencil_enable: desc.StencilEnable != 0,
+            stencil_ref,
+            stencil_read_mask: desc.StencilReadMask,
+            stencil_write_mask: desc.StencilWriteMask,
+            front_face: StencilOps::from_desc(&desc.FrontFace),
+            back_face: StencilOps::from_desc(&desc.BackFace),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SamplerState {
+    pub filter: String,
+    pub address_u: String,
+    pub address_v: String,
+    pub address_w: String,
+    pub mip_lod_bias: f32,
+    pub max_anisotropy: u32,
+    pub comparison_func: String,
+    pub border_color: [f32; 4],
+    pub min_lod: f32,
+    pub max_lod: f32,
+}
+
+impl SamplerState {
+    pub fn from_desc(desc: &D3D11_SAMPLER_DESC) -> Self {
+        SamplerState {
+            filter: filter_name(desc.Filter),
+            address_u: address_name(desc.AddressU),
+            address_v: address_name(desc.AddressV),
+            address_w: address_name(desc.AddressW),
+            mip_lod_bias: desc.MipLODBias,
+            max_anisotropy: desc.MaxAnisotropy,
+            comparison_func: comparison_name(desc.ComparisonFunc),
+            border_color: desc.BorderColor,
+            min_lod: desc.MinLOD,
+            max_lod: desc.MaxLOD,
+        }
+    }
+}
+
+/// Render state bound when a D3D11 snapshot was taken.  A state that is None was either not
+/// set by the game (so the D3D11 default was in use) or was created before the hooks were
+/// installed.
+#[derive(Serialize)]
+pub struct D3D11RenderStateMap {
+    /// True if blending is enabled on the first render target
+    pub alpha_blended: bool,
+    /// True if back faces are not culled
+    pub two_sided: bool,
+    pub blend: Option<BlendState>,
+    pub rasterizer: Option<RasterizerState>,
+    pub depth_stencil: Option<DepthStencilState>,
+    /// Pixel shader samplers by slot
+    pub samplers: BTreeMap<u32, SamplerState>,
+}
+
+impl D3D11RenderStateMap {
+    pub fn new(blend: Option<BlendState>, rasterizer: Option<RasterizerState>,
+        depth_stencil: Option<DepthStencilState>, samplers: BTreeMap<u32, SamplerState>) -> Self {
+        // the defaults are no blending and back face culling
+        let alpha_blended = blend.as_ref()
+            .and_then(|b| b.render_targets.first())
+            .map(|rt| rt.blend_enable)
+            .unwrap_or(false);
+        let two_sided = rasterizer.as_ref()
+            .map(|r| r.cull_mode == "NONE")
+            .unwrap_or(false);
+        D3D11RenderStateMap {
+            alpha_blended,
+            two_sided,
+            blend,
+            rasterizer,
+            depth_stencil,
+            samplers,
+        }
+    }
+
+    pub fn has_state(&self) -> bool {
+        self.blend.is_some() || self.rasterizer.is_some() || self.depth_stencil.is_some()
+            || !self.samplers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blend_desc(independent: bool, enabled_targets: &[usize]) -> D3D11_BLEND_DESC {
+        let mut desc: D3D11_BLEND_DESC = unsafe { std::mem::zeroed() };
+        desc.IndependentBlendEnable = independent as i32;
+        for rt in desc.RenderTarget.iter_mut() {
+            rt.SrcBlend = D3D11_BLEND_ONE;
+            rt.DestBlend = D3D11_BLEND_ZERO;
+            rt.BlendOp = D3D11_BLEND_OP_ADD;
+            rt.SrcBlendAlpha = D3D11_BLEND_ONE;
+            rt.DestBlendAlpha = D3D11_BLEND_ZERO;
+            rt.BlendOpAlpha = D3D11_BLEND_OP_ADD;
+            rt.RenderTargetWriteMask = 0xf;
+        }
+        for &t in enabled_targets {
+            let rt = &mut desc.RenderTarget[t];
+            rt.BlendEnable = 1;
+            rt.SrcBlend = D3D11_BLEND_SRC_ALPHA;
+            rt.DestBlend = D3D11_BLEND_INV_SRC_ALPHA;
+        }
+        desc
+    }
+
+    fn rasterizer_desc(cull: D3D11_CULL_MODE) -> D3D11_RASTERIZER_DESC {
+        let mut desc: D3D11_RASTERIZER_DESC = unsafe { std::mem::zeroed() };
+        desc.FillMode = D3D11_FILL_SOLID;
+        desc.CullMode = cull;
+        desc.DepthClipEnable = 1;
+        desc
+    }
+
+    #[test]
+    fn test_enum_names() {
+        assert_eq!(blend_name(D3D11_BLEND_INV_SRC_ALPHA), "INV_SRC_ALPHA");
+        assert_eq!(blend_name(D3D11_BLEND_INV_SRC1_ALPHA), "INV_SRC1_ALPHA");
+        assert_eq!(blend_name(0), "UNKNOWN(0)");
+        assert_eq!(blend_op_name(D3D11_BLEND_OP_REV_SUBTRACT), "REV_SUBTRACT");
+        assert_eq!(blend_op_name(99), "UNKNOWN(99)");
+        assert_eq!(comparison_name(D3D11_COMPARISON_LESS_EQUAL), "LESS_EQUAL");
+        assert_eq!(comparison_name(D3D11_COMPARISON_ALWAYS), "ALWAYS");
+        assert_eq!(stencil_op_name(D3D11_STENCIL_OP_INCR_SAT), "INCR_SAT");
+        assert_eq!(stencil_op_name(0), "UNKNOWN(0)");
+        assert_eq!(address_name(D3D11_TEXTURE_ADDRESS_MIRROR_ONCE), "MIRROR_ONCE");
+        assert_eq!(filter_name(D3D11_FILTER_ANISOTROPIC), "ANISOTROPIC");
+        assert_eq!(filter_name(D3D11_FILTER_COMPARISON_MIN_MAG_MIP_LINEAR), "COMPARISON_MIN_MAG_MIP_LINEAR");
+        // filters are bit fields, so unknown ones are shown in hex
+        assert_eq!(filter_name(0x1ab), "0x1ab");
+    }
+
+    #[test]
+    fn test_state_from_desc() {
+        let b = BlendState::from_desc(&blend_desc(false, &[0]), [1.0; 4], 0xffffffff);
+        assert_eq!(b.render_targets.len(), 1);
+        let rt = &b.render_targets[0];
+        assert!(rt.blend_enable);
+        assert_eq!((rt.src_blend.as_str(), rt.dest_blend.as_str(), rt.blend_op.as_str()),
+            ("SRC_ALPHA", "INV_SRC_ALPHA", "ADD"));
+        assert_eq!(rt.write_mask, 0xf);
+        let b = BlendState::from_desc(&blend_desc(true, &[]), [1.0; 4], 0xffffffff);
+        assert_eq!(b.render_targets.len(), 8);
+        assert_eq!(b.render_targets[7].target, 7);
+
+        let r = RasterizerState::from_desc(&rasterizer_desc(D3D11_CULL_FRONT));
+        assert_eq!((r.fill_mode.as_str(), r.cull_mode.as_str()), ("SOLID", "FRONT"));
+        assert!(r.depth_clip_enable);
+        assert_eq!(RasterizerState::from_desc(&rasterizer_desc(7)).cull_mode, "UNKNOWN(7)");
+
+        let mut desc: D3D11_DEPTH_STENCIL_DESC = unsafe { std::mem::zeroed() };
+        desc.DepthEnable = 1;
+        desc.DepthWriteMask = D3D11_DEPTH_WRITE_MASK_ALL;
+        desc.DepthFunc = D3D11_COMPARISON_LESS;
+        desc.FrontFace.StencilPassOp = D3D11_STENCIL_OP_REPLACE;
+        let ds = DepthStencilState::from_desc(&desc, 3);
+        assert!(ds.depth_enable && ds.depth_write);
+        assert_eq!(ds.depth_func, "LESS");
+        assert_eq!(ds.stencil_ref, 3);
+        assert_eq!(ds.front_face.pass, "REPLACE");
+        desc.DepthWriteMask = D3D11_DEPTH_WRITE_MASK_ZERO;
+        assert!(!DepthStencilState::from_desc(&desc, 0).depth_write);
+    }
+
+    #[test]
+    fn test_derived_flags() {
+        // nothing recorded means the d3d11 defaults: no blending, back faces culled
+        let rs = D3D11RenderStateMap::new(None, None, None, BTreeMap::new());
+        assert!(!rs.alpha_blended && !rs.two_sided);
+        assert!(!rs.has_state());
+
+        let blend = |independent, targets: &[usize]|
+            Some(BlendState::from_desc(&blend_desc(independent, targets), [1.0; 4], 0xffffffff));
+        let raster = |cull| Some(RasterizerState::from_desc(&rasterizer_desc(cull)));
+
+        let rs = D3D11RenderStateMap::new(blend(false, &[0]), raster(D3D11_CULL_NONE), None, BTreeMap::new());
+        assert!(rs.alpha_blended && rs.two_sided);
+        assert!(rs.has_state());
+        let rs = D3D11RenderStateMap::new(blend(false, &[]), raster(D3D11_CULL_BACK), None, BTreeMap::new());
+        assert!(!rs.alpha_blended && !rs.two_sided);
+        let rs = D3D11RenderStateMap::new(None, raster(D3D11_CULL_FRONT), None, BTreeMap::new());
+        assert!(!rs.two_sided);
+        // only the first render target counts
+        let rs = D3D11RenderStateMap::new(blend(true, &[1]), None, None, BTreeMap::new());
+        assert!(!rs.alpha_blended);
+
+        // samplers alone count as state
+        let mut desc: D3D11_SAMPLER_DESC = unsafe { std::mem::zeroed() };
+        desc.Filter = D3D11_FILTER_MIN_MAG_MIP_LINEAR;
+        desc.AddressU = D3D11_TEXTURE_ADDRESS_WRAP;
+        let mut samplers = BTreeMap::new();
+        samplers.insert(0, SamplerState::from_desc(&desc));
+        let rs = D3D11RenderStateMap::new(None, None, None, samplers);
+        assert!(rs.has_state());
+        assert_eq!(rs.samplers[&0].filter, "MIN_MAG_MIP_LINEAR");
+        assert_eq!(rs.samplers[&0].address_u, "WRAP");
+    }
+}
@@ -31,6 +31,82 @@ impl AutoSnapMesh {
         }
     }
 }
+/// Inclusive range of counts; either end may be left open.
+#[derive(Deserialize,Serialize,Clone,Copy,Debug,PartialEq,Default)]
+#[serde(default)]
+pub struct CountRange {
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+impl CountRange {
+    pub fn contains(&self, v:u32) -> bool {
+        self.min.map(|min| v >= min).unwrap_or(true) && self.max.map(|max| v <= max).unwrap_or(true)
+    }
+}
+
+/// A mesh matches a rule if it matches every field that is set.  A rule with no fields set
+/// matches everything.
+#[derive(Deserialize,Serialize,Clone,Debug,PartialEq,Default)]
+#[serde(default)]
+pub struct SnapFilterRule {
+    pub prims: Option<CountRange>,
+    pub verts: Option<CountRange>,
+    pub mesh: Option<AutoSnapMesh>,
+    /// Only known for D3D11 with data precopy; a rule with a checksum never matches a mesh
+    /// whose checksum isn't known.
+    pub vb_checksum: Option<u32>,
+}
+impl SnapFilterRule {
+    pub fn matches(&self, prims:UINT, verts:UINT, vb_checksum:Option<u32>) -> bool {
+        self.prims.map(|r| r.contains(prims)).unwrap_or(true)
+            && self.verts.map(|r| r.contains(verts)).unwrap_or(true)
+            && self.mesh.map(|m| m.prims == prims && m.verts == verts).unwrap_or(true)
+            && self.vb_checksum.map(|c| vb_checksum == Some(c)).unwrap_or(true)
+    }
+}
+
+/// Rules for which of the meshes drawn with the selected texture are snapped.
+#[derive(Deserialize,Serialize,Clone,Debug,PartialEq,Default)]
+#[serde(default)]
+pub struct SnapFilters {
+    pub min_prims: Option<u32>,
+    /// If not empty, only meshes matching at least one of these are snapped
+    pub include: Vec<SnapFilterRule>,
+    /// Meshes matching any of these are not snapped, even if they are included
+    pub exclude: Vec<SnapFilterRule>,
+}
+impl SnapFilters {
+    pub fn is_empty(&self) -> bool {
+        self.min_prims.is_none() && self.include.is_empty() && self.exclude.is_empty()
+    }
+    /// Ok if the mesh should be snapped, otherwise the reason it was filtered out.
+    pub fn check(&self, prims:UINT, verts:UINT, vb_checksum:Option<u32>) -> std::result::Result<(), String> {
+        if let Some(min) = self.min_prims.filter(|min| prims < *min) {
+            return Err(format!("prim count {} is less than min_prims {}", prims, min));
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|r| r.matches(prims, verts, vb_checksum)) {
+            return Err("does not match any include rule".to_owned());
+        }
+        if let Some(rule) = self.exclude.iter().find(|r| r.matches(prims, verts, vb_checksum)) {
+            return Err(format!("matches exclude rule {:?}", rule));
+        }
+        Ok(())
+    }
+}
+
+/// Where anim snapshots get the player transform from.
+#[derive(Deserialize,Serialize,Clone,Debug,PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum PlayerTransformSource {
+    /// No transform; bones are written as captured
+    Identity,
+    /// Read x, y, z, rotation as four floats from the captured vertex constants, at a byte
+    /// offset into the bone palette (D3D11) or the float registers (D3D9, 16 bytes per register)
+    ConstantOffset { offset: u32 },
+    /// Call `GetActivePlayerTransform` in the dll at `extdll_path`
+    ExtDll,
+}
+
 #[derive(Deserialize,Clone,Serialize)]
 pub struct SnapConfig {
     pub snap_ms: u32,
@@ -45,6 +121,15 @@ pub struct SnapConfig {
     pub clear_sd_on_reset: bool,
     #[serde(default)]
     pub extdll_path: String,
+    /// D3D11 anim snapshots: vertex shader constant buffer slot that holds the bone palette.
+    /// If not set, the largest buffer bound to the vertex shader is used.
+    #[serde(default)]
+    pub anim_cbuffer_slot: Option<u32>,
+    /// If not set, the ext dll is used when `extdll_path` is set, otherwise identity.
+    #[serde(default)]
+    pub player_transform: Option<PlayerTransformSource>,
+    #[serde(default)]
+    pub filters: SnapFilters,
 }
 impl fmt::Display for SnapConfig {
     // This trait requires `fmt` with this exact signature.
@@ -63,6 +148,7 @@ impl fmt::Display for SnapConfig {
         writeln!(f, "  pconsts_to_capture: {}", self.pconsts_to_capture)?;
         if self.snap_anim {
             writeln!(f, "  max sequences: {}", self.max_const_sequences())?;
+            writeln!(f, "  anim_cbuffer_slot: {:?}", self.anim_cbuffer_slot)?;
         }
         match self.autosnap.as_ref() {
             None => writeln!(f, "  no autosnap meshes")?,
@@ -75,6 +161,10 @@ impl fmt::Display for SnapConfig {
         }
         writeln!(f, "  plugins: {:?}", self.plugins)?;
         writeln!(f, "  snap_extdll_path: {}", self.extdll_path)?;
+        writeln!(f, "  player_transform: {:?}", self.player_transform_source())?;
+        if !self.filters.is_empty() {
+            writeln!(f, "  filters: {:?}", self.filters)?;
+        }
 
         writeln!(f, "}}")
     }
@@ -93,6 +183,16 @@ impl SnapConfig {
             plugins: None,
             clear_sd_on_reset: false,
             extdll_path: String::new(),
+            anim_cbuffer_slot: None,
+            player_transform: None,
+            filters: SnapFilters::default(),
+        }
+    }
+    pub fn player_transform_source(&self) -> PlayerTransformSource {
+        match self.player_transform.as_ref() {
+            Some(src) => src.clone(),
+            None if !self.extdll_path.trim().is_empty() => PlayerTransformSource::ExtDll,
+            None => PlayerTransformSource::Identity,
         }
     }
     pub fn max_const_sequences(&self) -> usize {
@@ -176,4 +276,101 @@ impl SnapConfig {
     //     file.write_all(&s.as_bytes())?;
     //     Ok(())
     // }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: Option<u32>, max: Option<u32>) -> Option<CountRange> {
+        Some(CountRange { min, max })
+    }
+
+    #[test]
+    fn test_count_range() {
+        let r = CountRange { min: Some(10), max: Some(20) };
+        assert!(!r.contains(9));
+        assert!(r.contains(10) && r.contains(15) && r.contains(20));
+        assert!(!r.contains(21));
+
+        // open ended
+        assert!(CountRange { min: Some(10), max: None }.contains(u32::MAX));
+        assert!(!CountRange { min: Some(10), max: None }.contains(9));
+        assert!(CountRange { min: None, max: Some(20) }.contains(0));
+        assert!(!CountRange { min: None, max: Some(20) }.contains(21));
+        assert!(CountRange::default().contains(0) && CountRange::default().contains(u32::MAX));
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        // an empty rule matches everything
+        assert!(SnapFilterRule::default().matches(1, 3, None));
+
+        let rule = SnapFilterRule { prims: range(Some(100), Some(200)), verts: range(None, Some(500)),
+            ..Default::default() };
+        assert!(rule.matches(100, 500, None));
+        assert!(rule.matches(200, 0, Some(1)));
+        assert!(!rule.matches(99, 300, None));
+        assert!(!rule.matches(201, 300, None));
+        assert!(!rule.matches(150, 501, None));
+
+        let rule = SnapFilterRule { mesh: Some(AutoSnapMesh::new(12, 24)), ..Default::default() };
+        assert!(rule.matches(12, 24, None));
+        assert!(!rule.matches(12, 25, None));
+
+        // every field that is set has to match
+        let rule = SnapFilterRule { prims: range(Some(10), None), mesh: Some(AutoSnapMesh::new(5, 24)),
+            ..Default::default() };
+        assert!(!rule.matches(5, 24, None));
+    }
+
+    #[test]
+    fn test_rule_checksum() {
+        let rule = SnapFilterRule { vb_checksum: Some(0xabcd), ..Default::default() };
+        assert!(rule.matches(1, 3, Some(0xabcd)));
+        assert!(!rule.matches(1, 3, Some(0x1234)));
+        // the checksum isn't known on d3d9, so a checksum rule never matches there
+        assert!(!rule.matches(1, 3, None));
+        // but rules without a checksum don't care
+        assert!(SnapFilterRule { prims: range(Some(1), None), ..Default::default() }.matches(1, 3, None));
+    }
+
+    #[test]
+    fn test_filters_check() {
+        // empty filters accept everything
+        let filters = SnapFilters::default();
+        assert!(filters.is_empty());
+        assert!(filters.check(0, 0, None).is_ok());
+        assert!(filters.check(u32::MAX, u32::MAX, Some(1)).is_ok());
+
+        let filters = SnapFilters { min_prims: Some(10), ..Default::default() };
+        assert!(!filters.is_empty());
+        assert!(filters.check(9, 100, None).is_err());
+        assert!(filters.check(10, 100, None).is_ok());
+
+        let big = SnapFilterRule { prims: range(Some(1000), None), ..Default::default() };
+        let mid = SnapFilterRule { prims: range(Some(500), Some(2000)), ..Default::default() };
+        let cs = SnapFilterRule { vb_checksum: Some(7), ..Default::default() };
+
+        // only meshes matching an include rule are snapped
+        let filters = SnapFilters { include: vec![big.clone(), cs.clone()], ..Default::default() };
+        assert!(filters.check(1000, 10, None).is_ok());
+        assert!(filters.check(10, 10, Some(7)).is_ok());
+        assert!(filters.check(999, 10, None).is_err());
+        assert!(filters.check(999, 10, Some(8)).is_err());
+
+        // exclude wins over include
+        let filters = SnapFilters { include: vec![big.clone()], exclude: vec![mid.clone()], ..Default::default() };
+        assert!(filters.check(1500, 10, None).unwrap_err().starts_with("matches exclude rule"));
+        assert!(filters.check(2001, 10, None).is_ok());
+        assert_eq!(filters.check(100, 10, None), Err("does not match any include rule".to_owned()));
+
+        // with only exclude rules everything else is snapped
+        let filters = SnapFilters { exclude: vec![cs], ..Default::default() };
+        assert!(filters.check(1, 3, Some(7)).is_err());
+        assert!(filters.check(1, 3, None).is_ok());
+
+        // min_prims is checked first
+        let filters = SnapFilters { min_prims: Some(600), include: vec![mid], ..Default::default() };
+        assert!(filters.check(550, 10, None).unwrap_err().contains("min_prims"));
+    }
+}
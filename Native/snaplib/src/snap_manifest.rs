@@ -0,0 +1,160 @@
+//! Machine readable summary of a snapshot session, so that tools can find what was captured
+//! without scraping the log.  Written to the snapshot directory at the end of each session as
+//! `MMSnapshotManifest_<start time in unix ms>.yaml`.
+
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+use shared_dx::error::Result;
+
+use crate::anim_frame::write_obj_to_file;
+
+/// Increment if fields are removed or change meaning.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// One snapshotted draw call.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct MeshEntry {
+    pub prim_count: u32,
+    pub vert_count: u32,
+    /// Frame number, counted from when the hook was loaded
+    pub frame: u64,
+    /// Checksum of the vertex buffer, if the data was available natively (D3D11 with data
+    /// precopy).  The algorithm is given by `SnapManifest::vb_checksum_algorithm`.
+    pub vb_checksum: Option<u32>,
+    /// Prefix of this mesh's files in the snapshot directory; empty if the snapshot failed
+    /// before files were written.
+    pub file_prefix: String,
+    /// Texture file names, relative to the snapshot directory
+    pub textures: Vec<String>,
+    pub vertex_shader: bool,
+    pub pixel_shader: bool,
+    pub vertex_constants: bool,
+    pub pixel_constants: bool,
+    /// Best guess for the profile's `vec_encoding`, if the normals are stored as pairs of
+    /// 16 bit ints.
+    pub vec_encoding: Option<String>,
+    /// Errors encountered while snapshotting this mesh.  The mesh may be incomplete or missing
+    /// if there are any.
+    pub errors: Vec<String>,
+}
+
+impl MeshEntry {
+    pub fn new(prim_count: u32, vert_count: u32, frame: u64) -> Self {
+        Self {
+            prim_count,
+            vert_count,
+            frame,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapManifest {
+    pub version: u32,
+    /// "d3d9" or "d3d11"
+    pub device: String,
+    pub directory: String,
+    pub started_at: SystemTime,
+    pub duration_ms: u64,
+    pub vb_checksum_algorithm: String,
+    /// The `vec_encoding` guessed for the most meshes
+    pub vec_encoding: Option<String>,
+    pub meshes: Vec<MeshEntry>,
+}
+
+/// The encoding guessed for the most meshes; ties go to the alphabetically first.
+pub fn most_common_vec_encoding(meshes: &[MeshEntry]) -> Option<String> {
+    let mut counts: std::collections::BTreeMap<&str, usize> = std::collections::BTreeMap::new();
+    for enc in meshes.iter().filter_map(|m| m.vec_encoding.as_deref()) {
+        *counts.entry(enc).or_insert(0) += 1;
+    }
+    // max_by_key returns the last max, so iterate in reverse to prefer the first name
+    counts.into_iter().rev().max_by_key(|(_, n)| *n).map(|(enc, _)| enc.to_owned())
+}
+
+impl SnapManifest {
+    pub fn file_name(&self) -> String {
+        let start_ms = self.started_at.duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        format!("MMSnapshotManifest_{}.yaml", start_ms)
+    }
+
+    /// Write to the snapshot directory, returning the file path.
+    pub fn write(&self) -> Result<String> {
+        let out = format!("{}/{}", self.directory, self.file_name());
+        write_obj_to_file(&out, false, self)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn mesh(vec_encoding: Option<&str>) -> MeshEntry {
+        MeshEntry { vec_encoding: vec_encoding.map(str::to_owned), ..MeshEntry::new(12, 24, 100) }
+    }
+
+    fn manifest(dir: &str) -> SnapManifest {
+        let mut m = MeshEntry::new(12, 24, 100);
+        m.vb_checksum = Some(0xdeadbeef);
+        m.file_prefix = "snap_0".to_owned();
+        m.textures = vec!["snap_0_texture0.dds".to_owned()];
+        m.vertex_shader = true;
+        m.vec_encoding = Some("octa".to_owned());
+        SnapManifest {
+            version: MANIFEST_VERSION,
+            device: "d3d11".to_owned(),
+            directory: dir.to_owned(),
+            started_at: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+            duration_ms: 250,
+            vb_checksum_algorithm: "crc32".to_owned(),
+            vec_encoding: Some("octa".to_owned()),
+            meshes: vec![m, MeshEntry { errors: vec!["no textures".to_owned()], ..MeshEntry::new(1, 3, 101) }],
+        }
+    }
+
+    #[test]
+    fn test_most_common_vec_encoding() {
+        assert_eq!(most_common_vec_encoding(&[]), None);
+        assert_eq!(most_common_vec_encoding(&[mesh(None), mesh(None)]), None);
+        let meshes = [mesh(Some("packed")), mesh(None), mesh(Some("octa")), mesh(Some("packed"))];
+        assert_eq!(most_common_vec_encoding(&meshes).as_deref(), Some("packed"));
+        // ties go to the alphabetically first, regardless of order
+        let meshes = [mesh(Some("spheremap")), mesh(Some("octa")), mesh(Some("packed")), mesh(Some("octa")),
+            mesh(Some("spheremap"))];
+        assert_eq!(most_common_vec_encoding(&meshes).as_deref(), Some("octa"));
+        let meshes = [mesh(Some("packed")), mesh(Some("octa"))];
+        assert_eq!(most_common_vec_encoding(&meshes).as_deref(), Some("octa"));
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(manifest("").file_name(), "MMSnapshotManifest_1700000000123.yaml");
+        let before_epoch = SnapManifest { started_at: SystemTime::UNIX_EPOCH - Duration::from_secs(1), ..manifest("") };
+        assert_eq!(before_epoch.file_name(), "MMSnapshotManifest_0.yaml");
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        // tools depend on this format; if this fails because the version changed, make sure
+        // the change was intended
+        assert_eq!(MANIFEST_VERSION, 1);
+
+        let dir = std::env::temp_dir().join(format!("mm_snap_manifest_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let m = manifest(&dir.to_string_lossy());
+        let path = m.write().unwrap();
+        assert!(path.ends_with("MMSnapshotManifest_1700000000123.yaml"), "{}", path);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(text.starts_with("version: 1\n"), "{}", text);
+        let read: SnapManifest = serde_yaml::from_str(&text).unwrap();
+        assert_eq!(read, m);
+    }
+}
@@ -0,0 +1,126 @@
+//! Replays a draw trace recorded by the hook (Ctrl-F9, see `mm_core::draw_trace`) against a
+//! mod DB and reports which mod would have rendered each draw.
+//!
+//! Usage: `trace_replay <ModIndex.yaml> <trace.mmdt> [--prefs <game.prefs.yaml>] [--all] [--summary]`
+//!
+//! By default every draw that has mods for its geometry is printed, followed by a
+//! per-geometry summary.  `--all` also prints draws with no mods, `--summary` only prints the
+//! summary.  `--prefs` applies the variant selections and disabled mods from a prefs file,
+//! otherwise the first variant is selected for everything.
+
+mod mod_db;
+mod replay;
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use mm_core::draw_trace;
+
+use crate::replay::{Outcome, Replayer, Summary};
+
+fn usage() -> ! {
+    eprintln!("usage: trace_replay <ModIndex.yaml> <trace.mmdt> [--prefs <game.prefs.yaml>] [--all] [--summary]");
+    exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut files = vec![];
+    let mut prefs: Option<PathBuf> = None;
+    let mut print_all = false;
+    let mut summary_only = false;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--prefs" => {
+                i += 1;
+                prefs = Some(args.get(i).unwrap_or_else(|| usage()).into());
+            },
+            "--all" => print_all = true,
+            "--summary" => summary_only = true,
+            "-h" | "--help" => usage(),
+            a if a.starts_with("--") => {
+                eprintln!("unknown option: {}", a);
+                usage();
+            },
+            a => files.push(PathBuf::from(a)),
+        }
+        i += 1;
+    }
+    if files.len() != 2 {
+        usage();
+    }
+
+    let (mstate, warnings) = match mod_db::load_mod_db(&files[0]) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error: failed to load mod db: {}", e);
+            exit(1);
+        }
+    };
+    for w in warnings {
+        eprintln!("warning: {}", w);
+    }
+    let nmods: usize = mstate.mods.values().map(|v| v.len()).sum();
+    println!("loaded {} mods for {} geometries from {}", nmods, mstate.mods.len(), files[0].display());
+
+    let mut replayer = Replayer::new(mstate);
+    if let Some(prefs) = prefs {
+        let mstate = replayer.mod_state();
+        match mod_prefs::load_and_apply_variants_from(&prefs, &mstate.mods, &mut mstate.selected_variant) {
+            Some((applied, total)) => println!("applied {} of {} variant selections from {}", applied, total, prefs.display()),
+            None => {
+                eprintln!("error: failed to read prefs file {}", prefs.display());
+                exit(1);
+            }
+        }
+        mstate.disabled_mods = mod_prefs::load_disabled_mods_from(&prefs).unwrap_or_default();
+        if !mstate.disabled_mods.is_empty() {
+            println!("disabled mods: {}", mstate.disabled_mods.join(", "));
+        }
+    }
+
+    let reader = match draw_trace::open_trace_file(&files[1]) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error: failed to open trace {}: {}", files[1].display(), e);
+            exit(1);
+        }
+    };
+
+    let mut summary = Summary::default();
+    let mut draws = 0;
+    let mut frames = 0;
+    let mut last_frame = None;
+    for rec in reader {
+        let rec = match rec {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("error: trace read failed after {} draws: {}", draws, e);
+                break;
+            }
+        };
+        draws += 1;
+        if last_frame != Some(rec.frame) {
+            frames += 1;
+            last_frame = Some(rec.frame);
+        }
+        let outcome = replayer.replay(&rec);
+        let print = !summary_only && (print_all || outcome != Outcome::NoMod);
+        if print {
+            let vb = rec.vb_checksum.map(|c| format!("{:08X}", c)).unwrap_or_else(|| "--------".to_owned());
+            println!("frame {:>8} prims {:>7} verts {:>7} vb {} layout {:032X} -> {}",
+                rec.frame, rec.prim_count, rec.vert_count, vb, rec.semantic_mask, outcome);
+        }
+        summary.add(&rec, &outcome);
+    }
+
+    println!();
+    println!("{} draws in {} frames; {} modded geometries drawn", draws, frames, summary.geoms.len());
+    for ((prims, verts), gs) in summary.sorted() {
+        println!("{}p/{}v: {} draws, selection changed {} times", prims, verts, gs.draws, gs.changes);
+        for (outcome, count) in gs.outcomes.iter() {
+            println!("    {:>7}  {}", count, outcome);
+        }
+    }
+}
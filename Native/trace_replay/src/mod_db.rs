@@ -0,0 +1,313 @@
+//! Builds a `LoadedModState` from a mod index file, without the managed code.  This follows
+//! the rules of the managed `ModDB.loadIndexObjects` (mods and references are found by base
+//! name anywhere below the index file) and then sets up the native mod list the same way
+//! `mod_load::setup_mod_data` does.  Only the data that selection uses is loaded; meshes are
+//! only parsed if a reference doesn't declare its expected prim and vert counts.
+
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping, Value};
+
+use mm_core::interop::{ModData, ModType};
+use mm_core::native_mod::{mod_key, ModD3DState, NativeModData};
+use mm_core::{new_fnv_map, sort_mods, LoadedModState, LoadedModsMap, ModsByNameMap};
+
+/// Selection doesn't look at the renderer data, so the replay doesn't have any.
+pub type ReplayModState = LoadedModState<()>;
+
+/// Case insensitive key lookup, like the managed yaml helpers.
+fn get<'a>(map: &'a Mapping, key: &str) -> Option<&'a Value> {
+    map.iter()
+        .find(|(k, _)| k.as_str().map(|k| k.eq_ignore_ascii_case(key)).unwrap_or(false))
+        .map(|(_, v)| v)
+}
+
+fn get_str(map: &Mapping, key: &str) -> Option<String> {
+    match get(map, key)? {
+        Value::String(s) => Some(s.trim().to_owned()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn get_u32(map: &Mapping, key: &str) -> Option<u32> {
+    match get(map, key)? {
+        Value::Number(n) => n.as_u64().map(|n| n as u32),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn load_yaml(path: &Path) -> Result<Mapping, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    match serde_yaml::from_str::<Value>(&text) {
+        Ok(Value::Mapping(m)) => Ok(m),
+        Ok(_) => Err(format!("{}: expected a yaml mapping", path.display())),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn find_yaml_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_yaml_files(&path, out);
+        } else if path.extension().map(|e| e.eq_ignore_ascii_case("yaml")).unwrap_or(false) {
+            out.push(path);
+        }
+    }
+}
+
+fn find_by_base_name<'a>(files: &'a [PathBuf], name: &str) -> Option<&'a PathBuf> {
+    files.iter().find(|f| {
+        f.file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| s.eq_ignore_ascii_case(name))
+            .unwrap_or(false)
+    })
+}
+
+fn parse_mod_type(s: &str) -> Option<ModType> {
+    match s.to_lowercase().as_str() {
+        // cpuadditive doesn't exist anymore, but the managed code treats it as gpuadditive
+        "cpuadditive" | "gpuadditive" => Some(ModType::GPUAdditive),
+        "cpureplacement" => Some(ModType::CPUReplacement),
+        "gpureplacement" => Some(ModType::GPUReplacement),
+        "deletion" => Some(ModType::Deletion),
+        _ => None,
+    }
+}
+
+/// VBChecksum is a hex string with an optional `0x` prefix.
+fn parse_vb_checksum(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let s = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    u32::from_str_radix(s, 16).ok()
+}
+
+/// Return the (prim, vert) counts for the named reference.
+fn ref_counts(files: &[PathBuf], ref_name: &str) -> Result<(u32, u32), String> {
+    let path = find_by_base_name(files, ref_name)
+        .ok_or_else(|| format!("reference '{}' not found", ref_name))?;
+    let node = load_yaml(path)?;
+    if let (Some(p), Some(v)) = (get_u32(&node, "ExpectedPrimCount"), get_u32(&node, "ExpectedVertCount")) {
+        return Ok((p, v));
+    }
+    // no expected counts, so need to load the mesh
+    let mesh_path = get_str(&node, "MeshPath")
+        .ok_or_else(|| format!("{}: no MeshPath", path.display()))?;
+    let mesh_path = path.parent().unwrap_or(Path::new("")).join(mesh_path);
+    let mesh = mmobj::load_mmobj(&mesh_path).map_err(|e| e.to_string())?;
+    Ok((mesh.prim_count() as u32, mesh.positions.len() as u32))
+}
+
+fn new_mod(midx: i32, name: String, mod_type: ModType, ref_prims: u32, ref_verts: u32) -> NativeModData<()> {
+    let mut mod_data = ModData::new();
+    mod_data.numbers.mod_type = mod_type as i32;
+    mod_data.numbers.ref_prim_count = ref_prims as i32;
+    mod_data.numbers.ref_vert_count = ref_verts as i32;
+    NativeModData {
+        midx,
+        mod_data,
+        // everything is considered loaded so that the replay shows what would render
+        d3d_data: ModD3DState::Loaded(()),
+        is_parent: false,
+        parent_mod_names: vec![],
+        last_frame_render: 0,
+        name,
+        fill_attempts: 0,
+    }
+}
+
+/// Load the mods listed in the index file.  Mods that can't be loaded are skipped with a
+/// warning, warnings are returned along with the mod state.
+pub fn load_mod_db(index_path: &Path) -> Result<(ReplayModState, Vec<String>), String> {
+    let index = load_yaml(index_path)?;
+    let is_index = get_str(&index, "type").map(|t| t.eq_ignore_ascii_case("index")).unwrap_or(false);
+    if !is_index {
+        return Err(format!("{}: expected 'type: \"Index\"'", index_path.display()));
+    }
+    let mod_names: Vec<String> = match get(&index, "mods") {
+        Some(Value::Sequence(seq)) => seq.iter()
+            .filter_map(|m| m.as_mapping())
+            .filter(|m| get(m, "active").and_then(|a| a.as_bool()).unwrap_or(true))
+            .filter_map(|m| get_str(m, "name"))
+            .collect(),
+        _ => return Err(format!("{}: 'mods' sequence not found", index_path.display())),
+    };
+
+    let mut files = vec![];
+    find_yaml_files(index_path.parent().unwrap_or(Path::new(".")), &mut files);
+    files.sort();
+
+    let mut warnings = vec![];
+    let mut loaded_mods: LoadedModsMap<()> = new_fnv_map(mod_names.len() * 2);
+    let mut mods_by_name: ModsByNameMap = new_fnv_map(mod_names.len() * 2);
+    let mut all_parent_mods: Vec<String> = vec![];
+    let mut midx = 0;
+    let mut add_mod = |nmod: NativeModData<()>, loaded_mods: &mut LoadedModsMap<()>, warnings: &mut Vec<String>| {
+        let key = mod_key(nmod.mod_data.numbers.ref_vert_count as u32, nmod.mod_data.numbers.ref_prim_count as u32);
+        if mods_by_name.contains_key(&nmod.name) {
+            warnings.push(format!("duplicate mod name: ignoring dup: {}", nmod.name));
+        } else {
+            mods_by_name.insert(nmod.name.clone(), key);
+        }
+        loaded_mods.entry(key).or_default().push(nmod);
+    };
+
+    for name in mod_names.iter() {
+        let path = match find_by_base_name(&files, name) {
+            Some(p) => p,
+            None => {
+                warnings.push(format!("no mod file found for mod named '{}'", name));
+                continue;
+            }
+        };
+        let node = match load_yaml(path) {
+            Ok(n) => n,
+            Err(e) => {
+                warnings.push(e);
+                continue;
+            }
+        };
+        // names are case insensitive
+        let mod_name = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name).to_lowercase();
+        let type_str = get_str(&node, "modtype").or_else(|| get_str(&node, "meshtype")).unwrap_or_default();
+        let mod_type = match parse_mod_type(&type_str) {
+            Some(ModType::CPUReplacement) | None => {
+                warnings.push(format!("{}: unsupported mod type '{}'", mod_name, type_str));
+                continue;
+            },
+            Some(t) => t,
+        };
+
+        if let ModType::Deletion = mod_type {
+            // one mod for each deleted prim/vert combo, named like `setup_mod_data` does
+            let geom = match get(&node, "delGeometry") {
+                Some(Value::Sequence(seq)) => seq.clone(),
+                _ => vec![],
+            };
+            for g in geom.iter().filter_map(|g| g.as_mapping()) {
+                match (get_u32(g, "pc"), get_u32(g, "vc")) {
+                    (Some(pc), Some(vc)) => {
+                        let nmod = new_mod(midx, format!("{}_{}_{}", mod_name, pc, vc), ModType::Deletion, pc, vc);
+                        midx += 1;
+                        add_mod(nmod, &mut loaded_mods, &mut warnings);
+                    },
+                    _ => warnings.push(format!("{}: bad delGeometry entry", mod_name)),
+                }
+            }
+            continue;
+        }
+
+        let ref_name = match get_str(&node, "ref") {
+            Some(r) if !r.is_empty() => r,
+            _ => {
+                warnings.push(format!("{}: mod type {} requires a reference", mod_name, type_str));
+                continue;
+            }
+        };
+        let (ref_prims, ref_verts) = match ref_counts(&files, &ref_name) {
+            Ok(c) => c,
+            Err(e) => {
+                warnings.push(format!("{}: {}", mod_name, e));
+                continue;
+            }
+        };
+
+        let mut nmod = new_mod(midx, mod_name.clone(), mod_type, ref_prims, ref_verts);
+        midx += 1;
+        // the mod's own counts are only used for logging by the hook, the ref counts are fine
+        nmod.mod_data.numbers.prim_count = ref_prims as i32;
+        nmod.mod_data.numbers.vert_count = ref_verts as i32;
+        if let Some(cs) = get_str(&node, "VBChecksum").filter(|s| !s.is_empty()) {
+            match parse_vb_checksum(&cs) {
+                Some(v) => {
+                    nmod.mod_data.vb_checksum = v;
+                    nmod.mod_data.vb_checksum_set = true;
+                },
+                None => warnings.push(format!("{}: could not parse VBChecksum {:?} as hex; ignoring", mod_name, cs)),
+            }
+        }
+        let parents = get_str(&node, "ParentModName").unwrap_or_default();
+        nmod.parent_mod_names = NativeModData::<()>::split_parent_string(&parents)
+            .iter().map(|p| p.to_lowercase()).collect();
+        all_parent_mods.extend(nmod.parent_mod_names.iter().cloned());
+        add_mod(nmod, &mut loaded_mods, &mut warnings);
+    }
+
+    all_parent_mods.sort();
+    all_parent_mods.dedup();
+    for parent in all_parent_mods {
+        let found = mods_by_name.get(&parent)
+            .and_then(|key| loaded_mods.get_mut(key))
+            .and_then(|nmodv| nmodv.iter_mut().find(|nmod| nmod.name == parent));
+        match found {
+            Some(nmod) => nmod.is_parent = true,
+            None => warnings.push(format!("mod referenced as parent failed to load: {}", parent)),
+        }
+    }
+
+    sort_mods(&mut loaded_mods);
+
+    Ok((ReplayModState {
+        mods: loaded_mods,
+        mods_by_name,
+        selected_variant: new_fnv_map(16),
+        disabled_mods: vec![],
+        last_selected: None,
+    }, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_data(rel: &str) -> PathBuf {
+        let mut p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        p.push("../../TestData");
+        p.push(rel);
+        p
+    }
+
+    #[test]
+    fn test_load_test_data_index() {
+        let (mstate, warnings) = load_mod_db(&test_data("ModIndex.yaml")).expect("load failed");
+        // MonolithMod and DelMod are listed; Test.yaml isn't in the index
+        assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+        let mono = &mstate.mods[&mod_key(8, 12)];
+        assert_eq!(mono.len(), 1);
+        assert_eq!(mono[0].name, "monolithmod");
+        assert_eq!(mono[0].mod_data.numbers.mod_type, ModType::GPUReplacement as i32);
+        assert!(!mono[0].mod_data.vb_checksum_set);
+
+        let del = &mstate.mods[&mod_key(200, 100)];
+        assert_eq!(del[0].name, "delmod_100_200");
+        assert_eq!(del[0].mod_data.numbers.mod_type, ModType::Deletion as i32);
+        assert!(mstate.mods.contains_key(&mod_key(300, 150)));
+        assert_eq!(mstate.mods_by_name.len(), 3);
+    }
+
+    #[test]
+    fn test_load_subdir_index() {
+        // mods and refs are in subdirectories below the index
+        let (mstate, warnings) = load_mod_db(&test_data("Mods/ModIndex.yaml")).expect("load failed");
+        assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+        let crate_mod = &mstate.mods[&mod_key(765, 586)];
+        assert_eq!(crate_mod[0].name, "ft_cratemod");
+        assert_eq!(mstate.mods_by_name.len(), 3);
+    }
+
+    #[test]
+    fn test_vb_checksum_parse() {
+        assert_eq!(parse_vb_checksum("0xDEADbeef"), Some(0xDEADBEEF));
+        assert_eq!(parse_vb_checksum(" 1234 "), Some(0x1234));
+        assert_eq!(parse_vb_checksum("bogus"), None);
+    }
+}
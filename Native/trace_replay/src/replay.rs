@@ -0,0 +1,204 @@
+//! Runs recorded draws through `preselect`/`select`, the same way the draw hooks do.
+
+use fnv::FnvHashMap;
+
+use mm_core::draw_trace::DrawTraceRecord;
+use mm_core::interop::ModType;
+use mm_core::native_mod::mod_key;
+use mm_core::{preselect, select, BoundVB, VBChecksumStatus};
+
+use crate::mod_db::ReplayModState;
+
+/// What would have happened for a single draw.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// No mods for this geometry, `preselect` rejected it.
+    NoMod,
+    /// There are mods for this geometry but `select` didn't pick any (e.g. parent not active,
+    /// VB checksum mismatch).
+    NotSelected,
+    /// These mods were rendered.
+    Rendered(Vec<String>),
+    /// The geometry was deleted by the named mod.
+    Deleted(String),
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::NoMod => write!(f, "(no mod)"),
+            Outcome::NotSelected => write!(f, "(not selected)"),
+            Outcome::Rendered(names) => write!(f, "{}", names.join(", ")),
+            Outcome::Deleted(name) => write!(f, "deleted by {}", name),
+        }
+    }
+}
+
+/// Selection state that the draw hooks keep in `GLOBAL_STATE`: the bound VB "pointer" and the
+/// checksum map.  The trace only has the checksum, so the replay binds a fake VB that has it.
+pub struct Replayer {
+    mstate: ReplayModState,
+    vb_checksums: FnvHashMap<usize, VBChecksumStatus>,
+}
+
+const REPLAY_VB: usize = 1;
+
+impl Replayer {
+    pub fn new(mstate: ReplayModState) -> Self {
+        Self { mstate, vb_checksums: FnvHashMap::default() }
+    }
+
+    pub fn mod_state(&mut self) -> &mut ReplayModState {
+        &mut self.mstate
+    }
+
+    pub fn replay(&mut self, rec: &DrawTraceRecord) -> Outcome {
+        if !preselect(&mut self.mstate, rec.prim_count, rec.vert_count) {
+            return Outcome::NoMod;
+        }
+        let bound = match rec.vb_checksum {
+            Some(cs) => {
+                self.vb_checksums.insert(REPLAY_VB, VBChecksumStatus::Checksum(cs));
+                BoundVB { ptr: REPLAY_VB, checksums: Some(&self.vb_checksums) }
+            },
+            None => BoundVB::empty(),
+        };
+        let selected = match select(&mut self.mstate, rec.prim_count, rec.vert_count, rec.frame, &bound) {
+            Some(s) => s,
+            None => return Outcome::NotSelected,
+        };
+        // same order of checks as `check_and_render_mod`: a deletion mod stops the render
+        let mut names = vec![];
+        for nmod in selected.as_slice() {
+            if nmod.mod_data.numbers.mod_type == ModType::Deletion as i32 {
+                return Outcome::Deleted(nmod.name.clone());
+            }
+            names.push(nmod.name.clone());
+        }
+        Outcome::Rendered(names)
+    }
+}
+
+/// Per-geometry results for the summary.
+#[derive(Debug, Default)]
+pub struct GeomSummary {
+    pub draws: usize,
+    /// Number of draws for each distinct outcome.
+    pub outcomes: Vec<(Outcome, usize)>,
+    /// Number of times the outcome differed from the previous draw of this geometry.  A high
+    /// count usually means the mod is flickering.
+    pub changes: usize,
+    last: Option<Outcome>,
+}
+
+/// Accumulates `GeomSummary` for each (prim, vert) that has mods.
+#[derive(Default)]
+pub struct Summary {
+    pub geoms: FnvHashMap<u32, ((u32, u32), GeomSummary)>,
+}
+
+impl Summary {
+    pub fn add(&mut self, rec: &DrawTraceRecord, outcome: &Outcome) {
+        if let Outcome::NoMod = outcome {
+            return;
+        }
+        let key = mod_key(rec.vert_count, rec.prim_count);
+        let (_, gs) = self.geoms.entry(key)
+            .or_insert_with(|| ((rec.prim_count, rec.vert_count), GeomSummary::default()));
+        gs.draws += 1;
+        match gs.outcomes.iter_mut().find(|(o, _)| o == outcome) {
+            Some((_, count)) => *count += 1,
+            None => gs.outcomes.push((outcome.clone(), 1)),
+        }
+        if gs.last.as_ref().map(|l| l != outcome).unwrap_or(false) {
+            gs.changes += 1;
+        }
+        gs.last = Some(outcome.clone());
+    }
+
+    /// Summaries sorted by (prim, vert).
+    pub fn sorted(&self) -> Vec<&((u32, u32), GeomSummary)> {
+        let mut v: Vec<_> = self.geoms.values().collect();
+        v.sort_by_key(|(pv, _)| *pv);
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mm_core::interop::ModData;
+    use mm_core::native_mod::{ModD3DState, NativeModData};
+    use mm_core::{new_fnv_map, LoadedModState};
+
+    fn nmod(name: &str, prims: u32, verts: u32, mod_type: ModType, vb: Option<u32>) -> NativeModData<()> {
+        let mut mod_data = ModData::new();
+        mod_data.numbers.mod_type = mod_type as i32;
+        mod_data.numbers.ref_prim_count = prims as i32;
+        mod_data.numbers.ref_vert_count = verts as i32;
+        if let Some(vb) = vb {
+            mod_data.vb_checksum = vb;
+            mod_data.vb_checksum_set = true;
+        }
+        NativeModData {
+            midx: 0,
+            mod_data,
+            d3d_data: ModD3DState::Loaded(()),
+            is_parent: false,
+            parent_mod_names: vec![],
+            last_frame_render: 0,
+            name: name.to_owned(),
+            fill_attempts: 0,
+        }
+    }
+
+    fn state(mods: Vec<NativeModData<()>>) -> ReplayModState {
+        let mut mstate = LoadedModState { mods: new_fnv_map(4), mods_by_name: new_fnv_map(4), selected_variant: new_fnv_map(4),
+            disabled_mods: vec![], last_selected: None };
+        for m in mods {
+            let key = mod_key(m.mod_data.numbers.ref_vert_count as u32, m.mod_data.numbers.ref_prim_count as u32);
+            mstate.mods_by_name.insert(m.name.clone(), key);
+            mstate.mods.entry(key).or_default().push(m);
+        }
+        mstate
+    }
+
+    fn draw(frame: u64, prim_count: u32, vert_count: u32, vb_checksum: Option<u32>) -> DrawTraceRecord {
+        DrawTraceRecord { frame, prim_count, vert_count, vb_checksum, semantic_mask: 0 }
+    }
+
+    #[test]
+    fn test_replay_outcomes() {
+        let mut r = Replayer::new(state(vec![
+            nmod("a", 10, 20, ModType::GPUReplacement, Some(0xAA)),
+            nmod("b", 10, 20, ModType::GPUReplacement, Some(0xBB)),
+            nmod("del", 30, 40, ModType::Deletion, None),
+        ]));
+        assert_eq!(r.replay(&draw(1, 1, 3, None)), Outcome::NoMod);
+        assert_eq!(r.replay(&draw(1, 10, 20, Some(0xAA))), Outcome::Rendered(vec!["a".to_owned()]));
+        assert_eq!(r.replay(&draw(1, 10, 20, Some(0xBB))), Outcome::Rendered(vec!["b".to_owned()]));
+        assert_eq!(r.replay(&draw(1, 10, 20, Some(0xCC))), Outcome::NotSelected);
+        assert_eq!(r.replay(&draw(1, 30, 40, None)), Outcome::Deleted("del".to_owned()));
+    }
+
+    #[test]
+    fn test_summary_changes() {
+        let mut r = Replayer::new(state(vec![
+            nmod("a", 10, 20, ModType::GPUReplacement, Some(0xAA)),
+        ]));
+        let mut summary = Summary::default();
+        let draws = [draw(1, 10, 20, Some(0xAA)), draw(2, 10, 20, Some(0xAB)),
+            draw(3, 10, 20, Some(0xAA)), draw(4, 10, 20, Some(0xAA)), draw(4, 1, 3, None)];
+        for d in draws.iter() {
+            let o = r.replay(d);
+            summary.add(d, &o);
+        }
+        let sorted = summary.sorted();
+        assert_eq!(sorted.len(), 1);
+        let ((p, v), gs) = sorted[0];
+        assert_eq!((*p, *v), (10, 20));
+        assert_eq!(gs.draws, 4);
+        assert_eq!(gs.changes, 2);
+        assert_eq!(gs.outcomes, vec![(Outcome::Rendered(vec!["a".to_owned()]), 3), (Outcome::NotSelected, 1)]);
+    }
+}
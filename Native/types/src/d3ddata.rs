@@ -1,10 +1,15 @@
 
 use winapi::shared::d3d9::*;
+use winapi::shared::dxgiformat::{DXGI_FORMAT, DXGI_FORMAT_UNKNOWN};
 use winapi::um::d3d11::{ID3D11Buffer, ID3D11InputLayout, ID3D11Texture2D, ID3D11Resource, ID3D11ShaderResourceView};
 use shared_dx::dx11rs::SemanticMask;
 
 pub struct ModD3DData9 {
     pub vb: *mut IDirect3DVertexBuffer9,
+    /// Index buffer, null if the mod is drawn without one.
+    pub ib: *mut IDirect3DIndexBuffer9,
+    /// Number of vertices in `vb`, needed for indexed draws.
+    pub vert_count: u32,
     pub decl: *mut IDirect3DVertexDeclaration9,
     pub textures: [LPDIRECT3DTEXTURE9; 4],
 }
@@ -15,6 +20,9 @@ impl Clone for ModD3DData9 {
             if !self.vb.is_null() {
                 (*self.vb).AddRef();
             }
+            if !self.ib.is_null() {
+                (*self.ib).AddRef();
+            }
             if !self.decl.is_null() {
                 (*self.decl).AddRef();
             }
@@ -28,6 +36,8 @@ impl Clone for ModD3DData9 {
 
         Self {
             vb: self.vb,
+            ib: self.ib,
+            vert_count: self.vert_count,
             decl: self.decl,
             // Clones the textures array with each element having AddRef called if non-null
             textures: self.textures,
@@ -41,6 +51,8 @@ impl ModD3DData9 {
 
         Self {
             vb: null_mut(),
+            ib: null_mut(),
+            vert_count: 0,
             decl: null_mut(),
             textures: [null_mut(); 4],
         }
@@ -51,6 +63,10 @@ impl ModD3DData9 {
             (*self.vb).Release();
             self.vb = std::ptr::null_mut();
         }
+        if !self.ib.is_null() {
+            (*self.ib).Release();
+            self.ib = std::ptr::null_mut();
+        }
         if !self.decl.is_null() {
             (*self.decl).Release();
             self.decl = std::ptr::null_mut();
@@ -73,6 +89,11 @@ impl Drop for ModD3DData9 {
 
 pub struct ModD3DData11 {
     pub vb: *mut ID3D11Buffer,
+    /// Index buffer, null if the mod is drawn without one.
+    pub ib: *mut ID3D11Buffer,
+    /// Format of `ib` (R16 or R32 uint).
+    pub ib_format: DXGI_FORMAT,
+    pub index_count: u32,
     pub vlayout: *mut ID3D11InputLayout,
     /// Semantic-name/index bitmask of the layout that was used to fill `vb`.
     /// Zero means "not tracked" — the refill check treats that as "don't
@@ -91,6 +112,9 @@ impl Clone for ModD3DData11 {
             if !self.vb.is_null() {
                 (*self.vb).AddRef();
             }
+            if !self.ib.is_null() {
+                (*self.ib).AddRef();
+            }
             if !self.vlayout.is_null() {
                 (*self.vlayout).AddRef();
             }
@@ -110,6 +134,9 @@ impl Clone for ModD3DData11 {
 
         Self {
             vb: self.vb,
+            ib: self.ib,
+            ib_format: self.ib_format,
+            index_count: self.index_count,
             vlayout: self.vlayout,
             vlayout_semantic_mask: self.vlayout_semantic_mask,
             textures: self.textures,
@@ -127,6 +154,9 @@ impl ModD3DData11 {
 
         Self {
             vb: null_mut(),
+            ib: null_mut(),
+            ib_format: DXGI_FORMAT_UNKNOWN,
+            index_count: 0,
             vlayout: null_mut(),
             vlayout_semantic_mask: 0,
             textures: [null_mut(); 4],
@@ -142,6 +172,9 @@ impl ModD3DData11 {
 
         Self {
             vb: null_mut(),
+            ib: null_mut(),
+            ib_format: DXGI_FORMAT_UNKNOWN,
+            index_count: 0,
             vlayout: layout,
             vlayout_semantic_mask: 0,
             textures: [null_mut(); 4],
@@ -159,6 +192,11 @@ impl ModD3DData11 {
                 //if rc == 0 { util::write_log_file("releasing vb on d3d11 data");}
                 self.vb = std::ptr::null_mut();
             }
+            if !self.ib.is_null() {
+                let _rc = (*self.ib).Release();
+                self.ib = std::ptr::null_mut();
+            }
+            self.index_count = 0;
             if !self.vlayout.is_null() {
                 let _rc = (*self.vlayout).Release();
                 //if rc == 0 { util::write_log_file("releasing vlayout on d3d11 data");}
@@ -207,3 +245,19 @@ impl ModD3DData {
         }
     }
 }
+
+// `ModD3DData` holds raw d3d resource pointers which are not `Send` by default.  We need
+// `Send` on the loaded mod state so that the global `LOADED_MODS` mutex can be `Sync`, which
+// lets multiple threads (render thread, deferred load threads) access it through the lock.
+// The mod state itself lives in `mm_core` and is generic over this type, so the impl goes here.
+//
+// SAFETY: this relies on how the pointers are used, which the compiler can't check:
+//  * The D3D11 pointers are all device children (buffers, layouts, textures, views), which
+//    D3D11 allows to be used and released from any thread.  Device contexts are not free
+//    threaded, so a context must never be stored in here.
+//  * The D3D9 pointers are only created and used on the render thread.  The load threads
+//    only handle d3d11 devices (see `load_thread::load_resource`), so D3D9 data never
+//    actually moves between threads.
+//  * Outside of the load threads' private copies, the data is only reached through the
+//    `LOADED_MODS` lock, so it is never used by two threads at once.
+unsafe impl Send for ModD3DData {}
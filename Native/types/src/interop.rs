@@ -6,88 +6,8 @@ pub use winapi::shared::d3d9::*;
 pub use winapi::shared::d3d9types::*;
 use winapi::um::d3d11::D3D11_INPUT_ELEMENT_DESC;
 
-pub enum ModType {
-    None = 0,
-    GPUAdditive,
-    CPUReplacement,
-    GPUReplacement,
-    GPUPertubation,
-    Deletion,
-}
-
-const MAX_TEX_PATH_LEN: usize = 8192;
-const MAX_MOD_NAME_LEN: usize = 1024;
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone)]
-pub struct ModNumbers {
-    pub mod_type: i32,
-    pub prim_type: i32,
-    pub vert_count: i32,
-    pub prim_count: i32,
-    pub index_count: i32,
-    pub ref_vert_count: i32,
-    pub ref_prim_count: i32,
-    pub decl_size_bytes: i32,
-    pub vert_size_bytes: i32,
-    pub index_elem_size_bytes: i32,
-}
-
-const MAX_SNAPPROFILE_STRING: usize = 256;
-const MAX_TRANSFORM_SIZE: usize = 8;
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone)]
-pub struct ModSnapProfile {
-    pub valid: bool,
-
-    pub name: [WCHAR; MAX_SNAPPROFILE_STRING],
-
-    pub pos_xfrm_length: i32,
-    pub pos_xfrm: [[WCHAR; MAX_SNAPPROFILE_STRING]; MAX_TRANSFORM_SIZE],
-
-    pub uv_xfrm_length: i32,
-    pub uv_xfrm: [[WCHAR; MAX_SNAPPROFILE_STRING]; MAX_TRANSFORM_SIZE],
-
-    pub flip_tangent: bool,
-
-    pub vec_encoding: [WCHAR; MAX_SNAPPROFILE_STRING],
-
-    pub blend_index_in_color1: bool,
-
-    pub blend_weight_in_color2: bool,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone)]
-/// Contains information and data associated with a mod, but _not_ any D3D resources.
-/// This structure is passed to/from managed code so must have a defined layout, and can
-/// only contain types that can be marshalled over the interop boundary.
-pub struct ModData {
-    pub numbers: ModNumbers,
-    pub update_tangent_space: i32,
-    pub texPath0: [WCHAR; MAX_TEX_PATH_LEN],
-    pub texPath1: [WCHAR; MAX_TEX_PATH_LEN],
-    pub texPath2: [WCHAR; MAX_TEX_PATH_LEN],
-    pub texPath3: [WCHAR; MAX_TEX_PATH_LEN],
-    pub modName: [WCHAR; MAX_MOD_NAME_LEN],
-    pub parentModName: [WCHAR; MAX_MOD_NAME_LEN],
-    pub _pixelShaderPath: [WCHAR; MAX_TEX_PATH_LEN], // not used
-    pub mod_snap_profile: ModSnapProfile,
-    pub data_available: bool,
-    /// Vertex-buffer CRC32 that this mod requires of the bound stream-0 VB
-    /// in order to render. Only consulted when `vb_checksum_set` is true.
-    pub vb_checksum: u32,
-    /// Whether `vb_checksum` is a real constraint; if false the mod matches
-    /// any VB (legacy behavior).
-    pub vb_checksum_set: bool,
-}
-
-impl ModData {
-    pub fn new() -> Self {
-        unsafe { std::mem::zeroed() }
-    }
-}
+pub use mm_core::interop::{ModType, ModNumbers, ModSnapProfile, ModData};
+use mm_core::interop::MAX_TEX_PATH_LEN;
 
 #[cfg(target_pointer_width = "32")]
 const DX9_PAD_SIZE:usize = 11;
@@ -13,6 +13,7 @@
 ///     ExePath              REG_SZ    "C:\Games\foo.exe"
 ///     GameProfileReverseNormals        REG_DWORD
 ///     GameProfileUpdateTangents        REG_DWORD
+///     GameProfileTangentGenerator      REG_DWORD  0 = native (default), 1 = DirectXMesh
 ///     GameProfileDataPathName          REG_SZ
 ///     ...
 ///   Profile0001\
@@ -58,6 +59,10 @@ pub struct GameProfile {
     /// The system textures will be kept for at least 5 minutes after creation.  Once disposed the textures involved may be 
     /// un-snapshotable, but sometimes loading a new level (to trigger the game to produce a fresh systemmem texture) works to refresh them.
     pub snap_use_sysmemtexturetracking: bool,
+    /// Whether mod vertex and index buffers should be filled natively from the mod's mmobj file
+    /// (see `mod_load::native_mesh`) instead of by the managed `FillModData` callback.  Mods the
+    /// native fill can't handle still use the callback.  Default is false.
+    pub native_mmobj_load: bool,
 }
 
 pub const EMPTY_GAME_PROFILE:GameProfile = GameProfile {
@@ -66,6 +71,7 @@ pub const EMPTY_GAME_PROFILE:GameProfile = GameProfile {
     update_tangent_space: true,
     data_path_name: String::new(),
     snap_use_sysmemtexturetracking: false,
+    native_mmobj_load: false,
 };
 
 impl Default for GameProfile {
@@ -160,13 +166,17 @@ unsafe fn read_profile_from_key(profile_path: &str) -> GameProfile {
     let snap_use_sysmemtexturetracking = reg_query_dword(profile_path, "GameProfileSnapUseSysmemTextureTracking")
         .map(|v| v > 0)
         .unwrap_or(false);
+    let native_mmobj_load = reg_query_dword(profile_path, "GameProfileNativeMMObjLoad")
+        .map(|v| v > 0)
+        .unwrap_or(false);
 
     GameProfile {
         profile_key: profile_path.to_owned(),
         reverse_normals,
         update_tangent_space,
         data_path_name,
-        snap_use_sysmemtexturetracking
+        snap_use_sysmemtexturetracking,
+        native_mmobj_load,
     }
 }
 
@@ -0,0 +1,375 @@
+use crate::formats::*;
+use crate::half::{decode_minifloat, encode_minifloat, f16_to_f32, f32_to_f16};
+
+/// How each component of a format is stored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Component {
+    Float32,
+    Float16,
+    Unorm8,
+    Snorm8,
+    Uint8,
+    Sint8,
+    Unorm16,
+    Snorm16,
+    Uint16,
+    Sint16,
+    Uint32,
+    Sint32,
+}
+
+impl Component {
+    pub fn size(self) -> usize {
+        match self {
+            Component::Float32 | Component::Uint32 | Component::Sint32 => 4,
+            Component::Float16 | Component::Unorm16 | Component::Snorm16
+                | Component::Uint16 | Component::Sint16 => 2,
+            Component::Unorm8 | Component::Snorm8 | Component::Uint8 | Component::Sint8 => 1,
+        }
+    }
+
+    fn decode(self, b: &[u8]) -> f32 {
+        let u16le = || u16::from_le_bytes([b[0], b[1]]);
+        let u32le = || u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        match self {
+            Component::Float32 => f32::from_bits(u32le()),
+            Component::Float16 => f16_to_f32(u16le()),
+            Component::Unorm8 => b[0] as f32 / 255.0,
+            // both the minimum and the one above it are -1
+            Component::Snorm8 => (b[0] as i8 as f32 / 127.0).max(-1.0),
+            Component::Uint8 => b[0] as f32,
+            Component::Sint8 => b[0] as i8 as f32,
+            Component::Unorm16 => u16le() as f32 / 65535.0,
+            Component::Snorm16 => (u16le() as i16 as f32 / 32767.0).max(-1.0),
+            Component::Uint16 => u16le() as f32,
+            Component::Sint16 => u16le() as i16 as f32,
+            Component::Uint32 => u32le() as f32,
+            Component::Sint32 => u32le() as i32 as f32,
+        }
+    }
+
+    fn encode(self, f: f32, b: &mut [u8]) {
+        // `as` saturates and maps nan to 0, which is what we want for the integer formats
+        let unorm = |max: f32| (f.clamp(0.0, 1.0) * max).round();
+        let snorm = |max: f32| (f.clamp(-1.0, 1.0) * max).round();
+        match self {
+            Component::Float32 => b[0..4].copy_from_slice(&f.to_le_bytes()),
+            Component::Float16 => b[0..2].copy_from_slice(&f32_to_f16(f).to_le_bytes()),
+            Component::Unorm8 => b[0] = unorm(255.0) as u8,
+            Component::Snorm8 => b[0] = snorm(127.0) as i8 as u8,
+            Component::Uint8 => b[0] = f.round() as u8,
+            Component::Sint8 => b[0] = f.round() as i8 as u8,
+            Component::Unorm16 => b[0..2].copy_from_slice(&(unorm(65535.0) as u16).to_le_bytes()),
+            Component::Snorm16 => b[0..2].copy_from_slice(&(snorm(32767.0) as i16).to_le_bytes()),
+            Component::Uint16 => b[0..2].copy_from_slice(&(f.round() as u16).to_le_bytes()),
+            Component::Sint16 => b[0..2].copy_from_slice(&(f.round() as i16).to_le_bytes()),
+            Component::Uint32 => b[0..4].copy_from_slice(&(f.round() as u32).to_le_bytes()),
+            Component::Sint32 => b[0..4].copy_from_slice(&(f.round() as i32).to_le_bytes()),
+        }
+    }
+}
+
+/// Memory layout of a format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Layout {
+    /// `count` components of the same type, in xyzw order
+    Uniform { component: Component, count: usize },
+    /// 8 bit unorm components stored in bgra order; `alpha` is false for the X8 variant, whose
+    /// w reads as 1.
+    Bgra8 { alpha: bool },
+    /// A 32 bit word with 10 bit xyz and a 2 bit w, either unorm or uint
+    Rgb10A2 { normalized: bool },
+    /// A 32 bit word with 11 bit x and y and 10 bit z unsigned floats
+    Rg11B10Float,
+}
+
+/// Converts elements of one DXGI format to and from `[f32; 4]`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Codec {
+    pub format: DXGI_FORMAT,
+    pub name: &'static str,
+    pub layout: Layout,
+}
+
+/// Value of components that the format doesn't store.
+const DEFAULTS: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+impl Codec {
+    /// Size of one element in bytes
+    pub fn size(&self) -> usize {
+        match self.layout {
+            Layout::Uniform { component, count } => component.size() * count,
+            Layout::Bgra8 { .. } | Layout::Rgb10A2 { .. } | Layout::Rg11B10Float => 4,
+        }
+    }
+
+    /// Number of components stored
+    pub fn components(&self) -> usize {
+        match self.layout {
+            Layout::Uniform { count, .. } => count,
+            Layout::Bgra8 { alpha } => if alpha { 4 } else { 3 },
+            Layout::Rgb10A2 { .. } => 4,
+            Layout::Rg11B10Float => 3,
+        }
+    }
+
+    /// True if the components are unsigned normalized (0..1).  Vectors stored in these formats
+    /// are usually biased, i.e. `v * 0.5 + 0.5`.
+    pub fn is_unorm(&self) -> bool {
+        match self.layout {
+            Layout::Uniform { component, .. } =>
+                matches!(component, Component::Unorm8 | Component::Unorm16),
+            Layout::Bgra8 { .. } => true,
+            Layout::Rgb10A2 { normalized } => normalized,
+            Layout::Rg11B10Float => false,
+        }
+    }
+
+    fn check_len(&self, len: usize) -> Result<(), String> {
+        if len < self.size() {
+            return Err(format!("{}: need {} bytes, have {}", self.name, self.size(), len));
+        }
+        Ok(())
+    }
+
+    /// Decode the element at the start of `src`.
+    pub fn decode(&self, src: &[u8]) -> Result<[f32; 4], String> {
+        self.check_len(src.len())?;
+        let mut out = DEFAULTS;
+        match self.layout {
+            Layout::Uniform { component, count } => {
+                for (i, o) in out.iter_mut().enumerate().take(count) {
+                    *o = component.decode(&src[i * component.size()..]);
+                }
+            },
+            Layout::Bgra8 { alpha } => {
+                out[0] = src[2] as f32 / 255.0;
+                out[1] = src[1] as f32 / 255.0;
+                out[2] = src[0] as f32 / 255.0;
+                if alpha {
+                    out[3] = src[3] as f32 / 255.0;
+                }
+            },
+            Layout::Rgb10A2 { normalized } => {
+                let w = u32::from_le_bytes([src[0], src[1], src[2], src[3]]);
+                let (max3, max_a) = if normalized { (1023.0, 3.0) } else { (1.0, 1.0) };
+                for (i, o) in out.iter_mut().enumerate().take(3) {
+                    *o = ((w >> (i * 10)) & 0x3ff) as f32 / max3;
+                }
+                out[3] = (w >> 30) as f32 / max_a;
+            },
+            Layout::Rg11B10Float => {
+                let w = u32::from_le_bytes([src[0], src[1], src[2], src[3]]);
+                out[0] = decode_minifloat(w & 0x7ff, 6, false);
+                out[1] = decode_minifloat((w >> 11) & 0x7ff, 6, false);
+                out[2] = decode_minifloat(w >> 22, 5, false);
+            },
+        }
+        Ok(out)
+    }
+
+    /// Encode `v` into the start of `dst`.  Components the format doesn't store are ignored;
+    /// values out of range for the format are clamped.
+    pub fn encode(&self, v: &[f32; 4], dst: &mut [u8]) -> Result<(), String> {
+        self.check_len(dst.len())?;
+        match self.layout {
+            Layout::Uniform { component, count } => {
+                for (i, f) in v.iter().enumerate().take(count) {
+                    component.encode(*f, &mut dst[i * component.size()..]);
+                }
+            },
+            Layout::Bgra8 { alpha } => {
+                let unorm = |f: f32| (f.clamp(0.0, 1.0) * 255.0).round() as u8;
+                dst[0] = unorm(v[2]);
+                dst[1] = unorm(v[1]);
+                dst[2] = unorm(v[0]);
+                // x8 is undefined, so write something that reads back the same
+                dst[3] = if alpha { unorm(v[3]) } else { 255 };
+            },
+            Layout::Rgb10A2 { normalized } => {
+                let field = |f: f32, max: f32| if normalized {
+                    (f.clamp(0.0, 1.0) * max).round() as u32
+                } else {
+                    f.round().clamp(0.0, max) as u32
+                };
+                let w = field(v[0], 1023.0) | field(v[1], 1023.0) << 10 | field(v[2], 1023.0) << 20
+                    | field(v[3], 3.0) << 30;
+                dst[0..4].copy_from_slice(&w.to_le_bytes());
+            },
+            Layout::Rg11B10Float => {
+                // no infinity in the encoding that the hardware would expect for large values,
+                // so clamp to the largest finite value of each field
+                let x = encode_minifloat(v[0], 6, false).min(0x7bf);
+                let y = encode_minifloat(v[1], 6, false).min(0x7bf);
+                let z = encode_minifloat(v[2], 5, false).min(0x3df);
+                let w = x | y << 11 | z << 22;
+                dst[0..4].copy_from_slice(&w.to_le_bytes());
+            },
+        }
+        Ok(())
+    }
+}
+
+macro_rules! uniform {
+    ($fmt:ident, $comp:ident, $count:expr) => {
+        Codec {
+            format: $fmt,
+            name: stringify!($fmt),
+            layout: Layout::Uniform { component: Component::$comp, count: $count },
+        }
+    };
+}
+
+/// All formats with a codec.  sRGB formats are left out, since the conversion is done by
+/// texture sampling and never applies to vertex data.
+pub static CODECS: &[Codec] = &[
+    uniform!(DXGI_FORMAT_R32G32B32A32_FLOAT, Float32, 4),
+    uniform!(DXGI_FORMAT_R32G32B32A32_UINT, Uint32, 4),
+    uniform!(DXGI_FORMAT_R32G32B32A32_SINT, Sint32, 4),
+    uniform!(DXGI_FORMAT_R32G32B32_FLOAT, Float32, 3),
+    uniform!(DXGI_FORMAT_R32G32B32_UINT, Uint32, 3),
+    uniform!(DXGI_FORMAT_R32G32B32_SINT, Sint32, 3),
+    uniform!(DXGI_FORMAT_R16G16B16A16_FLOAT, Float16, 4),
+    uniform!(DXGI_FORMAT_R16G16B16A16_UNORM, Unorm16, 4),
+    uniform!(DXGI_FORMAT_R16G16B16A16_UINT, Uint16, 4),
+    uniform!(DXGI_FORMAT_R16G16B16A16_SNORM, Snorm16, 4),
+    uniform!(DXGI_FORMAT_R16G16B16A16_SINT, Sint16, 4),
+    uniform!(DXGI_FORMAT_R32G32_FLOAT, Float32, 2),
+    uniform!(DXGI_FORMAT_R32G32_UINT, Uint32, 2),
+    uniform!(DXGI_FORMAT_R32G32_SINT, Sint32, 2),
+    Codec { format: DXGI_FORMAT_R10G10B10A2_UNORM, name: "DXGI_FORMAT_R10G10B10A2_UNORM",
+        layout: Layout::Rgb10A2 { normalized: true } },
+    Codec { format: DXGI_FORMAT_R10G10B10A2_UINT, name: "DXGI_FORMAT_R10G10B10A2_UINT",
+        layout: Layout::Rgb10A2 { normalized: false } },
+    Codec { format: DXGI_FORMAT_R11G11B10_FLOAT, name: "DXGI_FORMAT_R11G11B10_FLOAT",
+        layout: Layout::Rg11B10Float },
+    uniform!(DXGI_FORMAT_R8G8B8A8_UNORM, Unorm8, 4),
+    uniform!(DXGI_FORMAT_R8G8B8A8_UINT, Uint8, 4),
+    uniform!(DXGI_FORMAT_R8G8B8A8_SNORM, Snorm8, 4),
+    uniform!(DXGI_FORMAT_R8G8B8A8_SINT, Sint8, 4),
+    uniform!(DXGI_FORMAT_R16G16_FLOAT, Float16, 2),
+    uniform!(DXGI_FORMAT_R16G16_UNORM, Unorm16, 2),
+    uniform!(DXGI_FORMAT_R16G16_UINT, Uint16, 2),
+    uniform!(DXGI_FORMAT_R16G16_SNORM, Snorm16, 2),
+    uniform!(DXGI_FORMAT_R16G16_SINT, Sint16, 2),
+    uniform!(DXGI_FORMAT_R32_FLOAT, Float32, 1),
+    uniform!(DXGI_FORMAT_R32_UINT, Uint32, 1),
+    uniform!(DXGI_FORMAT_R32_SINT, Sint32, 1),
+    uniform!(DXGI_FORMAT_R8G8_UNORM, Unorm8, 2),
+    uniform!(DXGI_FORMAT_R8G8_UINT, Uint8, 2),
+    uniform!(DXGI_FORMAT_R8G8_SNORM, Snorm8, 2),
+    uniform!(DXGI_FORMAT_R8G8_SINT, Sint8, 2),
+    uniform!(DXGI_FORMAT_R16_FLOAT, Float16, 1),
+    uniform!(DXGI_FORMAT_R16_UNORM, Unorm16, 1),
+    uniform!(DXGI_FORMAT_R16_UINT, Uint16, 1),
+    uniform!(DXGI_FORMAT_R16_SNORM, Snorm16, 1),
+    uniform!(DXGI_FORMAT_R16_SINT, Sint16, 1),
+    uniform!(DXGI_FORMAT_R8_UNORM, Unorm8, 1),
+    uniform!(DXGI_FORMAT_R8_UINT, Uint8, 1),
+    uniform!(DXGI_FORMAT_R8_SNORM, Snorm8, 1),
+    uniform!(DXGI_FORMAT_R8_SINT, Sint8, 1),
+    Codec { format: DXGI_FORMAT_B8G8R8A8_UNORM, name: "DXGI_FORMAT_B8G8R8A8_UNORM",
+        layout: Layout::Bgra8 { alpha: true } },
+    Codec { format: DXGI_FORMAT_B8G8R8X8_UNORM, name: "DXGI_FORMAT_B8G8R8X8_UNORM",
+        layout: Layout::Bgra8 { alpha: false } },
+];
+
+/// Look up the codec for a format.
+pub fn codec(format: DXGI_FORMAT) -> Result<&'static Codec, String> {
+    CODECS.iter().find(|c| c.format == format)
+        .ok_or_else(|| format!("no vertex codec for DXGI format {}", format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Values that every format can represent exactly, scaled to its range
+    fn sample(c: &Codec) -> [f32; 4] {
+        let n = c.components();
+        let mut v = DEFAULTS;
+        let vals: [f32; 4] = match c.layout {
+            Layout::Uniform { component, .. } => match component {
+                Component::Float32 | Component::Float16 => [1.5, -0.25, 1024.0, -3.0],
+                Component::Unorm8 => [0.0, 1.0, 51.0 / 255.0, 204.0 / 255.0],
+                Component::Unorm16 => [0.0, 1.0, 13107.0 / 65535.0, 0.5 + 0.5 / 65535.0],
+                Component::Snorm8 => [-1.0, 1.0, 0.0, 64.0 / 127.0],
+                Component::Snorm16 => [-1.0, 1.0, 0.0, -16384.0 / 32767.0],
+                Component::Uint8 | Component::Uint16 | Component::Uint32 => [0.0, 1.0, 7.0, 255.0],
+                Component::Sint8 | Component::Sint16 | Component::Sint32 => [-128.0, 1.0, 0.0, 127.0],
+            },
+            Layout::Bgra8 { .. } => [1.0, 0.0, 51.0 / 255.0, 204.0 / 255.0],
+            Layout::Rgb10A2 { normalized: true } => [0.0, 1.0, 341.0 / 1023.0, 2.0 / 3.0],
+            Layout::Rgb10A2 { normalized: false } => [0.0, 1023.0, 341.0, 2.0],
+            Layout::Rg11B10Float => [1.5, 0.25, 1024.0, 0.0],
+        };
+        v[0..n].copy_from_slice(&vals[0..n]);
+        v
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for c in CODECS.iter() {
+            let v = sample(c);
+            let mut buf = vec![0xcdu8; c.size() + 1];
+            c.encode(&v, &mut buf).unwrap();
+            assert_eq!(buf[c.size()], 0xcd, "{} wrote past its size", c.name);
+            let out = c.decode(&buf).unwrap();
+            for i in 0..4 {
+                assert!((out[i] - v[i]).abs() < 1e-6, "{}: {:?} -> {:?}", c.name, v, out);
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_bytes() {
+        let dec = |fmt, b: &[u8]| codec(fmt).unwrap().decode(b).unwrap();
+        assert_eq!(dec(DXGI_FORMAT_R32G32B32_FLOAT, &[0, 0, 0x80, 0x3f, 0, 0, 0, 0xc0, 0, 0, 0, 0]),
+            [1.0, -2.0, 0.0, 1.0]);
+        assert_eq!(dec(DXGI_FORMAT_R16G16_FLOAT, &[0x00, 0x3c, 0x00, 0xb8]), [1.0, -0.5, 0.0, 1.0]);
+        assert_eq!(dec(DXGI_FORMAT_R8G8B8A8_UNORM, &[0, 255, 0, 255]), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(dec(DXGI_FORMAT_R8G8B8A8_SNORM, &[0x81, 0x80, 0x7f, 0]), [-1.0, -1.0, 1.0, 0.0]);
+        assert_eq!(dec(DXGI_FORMAT_R16G16_SNORM, &[0x01, 0x80, 0xff, 0x7f]), [-1.0, 1.0, 0.0, 1.0]);
+        assert_eq!(dec(DXGI_FORMAT_R8G8B8A8_UINT, &[1, 2, 3, 4]), [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(dec(DXGI_FORMAT_B8G8R8A8_UNORM, &[255, 0, 0, 0]), [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(dec(DXGI_FORMAT_B8G8R8X8_UNORM, &[0, 0, 255, 0]), [1.0, 0.0, 0.0, 1.0]);
+        // x = 1023, y = 0, z = 0, w = 3
+        assert_eq!(dec(DXGI_FORMAT_R10G10B10A2_UNORM, &[0xff, 0x03, 0, 0xc0]), [1.0, 0.0, 0.0, 1.0]);
+        // x = 1.0 (15 << 6), y = 0, z = 2.0 (16 << 5)
+        let w: u32 = 15 << 6 | (16 << 5) << 22;
+        assert_eq!(dec(DXGI_FORMAT_R11G11B10_FLOAT, &w.to_le_bytes()), [1.0, 0.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn test_clamping() {
+        let enc = |fmt, v: [f32; 4]| {
+            let c = codec(fmt).unwrap();
+            let mut b = vec![0u8; c.size()];
+            c.encode(&v, &mut b).unwrap();
+            b
+        };
+        assert_eq!(enc(DXGI_FORMAT_R8G8B8A8_UNORM, [-1.0, 2.0, 0.5, f32::NAN]), [0, 255, 128, 0]);
+        assert_eq!(enc(DXGI_FORMAT_R8G8_SNORM, [-2.0, 2.0, 0.0, 0.0]), [0x81, 0x7f]);
+        assert_eq!(enc(DXGI_FORMAT_R8_UINT, [300.0, 0.0, 0.0, 0.0]), [255]);
+        assert_eq!(enc(DXGI_FORMAT_R16_SINT, [-40000.0, 0.0, 0.0, 0.0]), [0x00, 0x80]);
+        assert_eq!(enc(DXGI_FORMAT_R10G10B10A2_UINT, [2000.0, 0.0, 0.0, 7.0]),
+            (0x3ffu32 | 3 << 30).to_le_bytes());
+        let big = enc(DXGI_FORMAT_R11G11B10_FLOAT, [1e9, 0.0, 1e9, 0.0]);
+        let out = codec(DXGI_FORMAT_R11G11B10_FLOAT).unwrap().decode(&big).unwrap();
+        assert_eq!(out, [65024.0, 0.0, 64512.0, 1.0]);
+    }
+
+    #[test]
+    fn test_lookup_and_errors() {
+        assert_eq!(codec(DXGI_FORMAT_R16G16B16A16_SNORM).unwrap().size(), 8);
+        assert!(codec(DXGI_FORMAT_UNKNOWN).is_err());
+        assert!(codec(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB).is_err());
+        assert!(codec(DXGI_FORMAT_R32G32B32_FLOAT).unwrap().decode(&[0; 11]).is_err());
+        let mut short = [0u8; 3];
+        assert!(codec(DXGI_FORMAT_R8G8B8A8_UNORM).unwrap().encode(&[0.0; 4], &mut short).is_err());
+        // formats are unique
+        for (i, c) in CODECS.iter().enumerate() {
+            assert!(CODECS[i + 1..].iter().all(|o| o.format != c.format), "{}", c.name);
+        }
+    }
+}
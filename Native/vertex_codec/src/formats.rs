@@ -0,0 +1,53 @@
+//! The `DXGI_FORMAT` values that can appear in a vertex input layout.  These mirror winapi's
+//! `dxgiformat` module, which isn't available off windows.
+
+#![allow(non_camel_case_types)]
+
+pub type DXGI_FORMAT = u32;
+
+pub const DXGI_FORMAT_UNKNOWN: DXGI_FORMAT = 0;
+pub const DXGI_FORMAT_R32G32B32A32_FLOAT: DXGI_FORMAT = 2;
+pub const DXGI_FORMAT_R32G32B32A32_UINT: DXGI_FORMAT = 3;
+pub const DXGI_FORMAT_R32G32B32A32_SINT: DXGI_FORMAT = 4;
+pub const DXGI_FORMAT_R32G32B32_FLOAT: DXGI_FORMAT = 6;
+pub const DXGI_FORMAT_R32G32B32_UINT: DXGI_FORMAT = 7;
+pub const DXGI_FORMAT_R32G32B32_SINT: DXGI_FORMAT = 8;
+pub const DXGI_FORMAT_R16G16B16A16_FLOAT: DXGI_FORMAT = 10;
+pub const DXGI_FORMAT_R16G16B16A16_UNORM: DXGI_FORMAT = 11;
+pub const DXGI_FORMAT_R16G16B16A16_UINT: DXGI_FORMAT = 12;
+pub const DXGI_FORMAT_R16G16B16A16_SNORM: DXGI_FORMAT = 13;
+pub const DXGI_FORMAT_R16G16B16A16_SINT: DXGI_FORMAT = 14;
+pub const DXGI_FORMAT_R32G32_FLOAT: DXGI_FORMAT = 16;
+pub const DXGI_FORMAT_R32G32_UINT: DXGI_FORMAT = 17;
+pub const DXGI_FORMAT_R32G32_SINT: DXGI_FORMAT = 18;
+pub const DXGI_FORMAT_R10G10B10A2_UNORM: DXGI_FORMAT = 24;
+pub const DXGI_FORMAT_R10G10B10A2_UINT: DXGI_FORMAT = 25;
+pub const DXGI_FORMAT_R11G11B10_FLOAT: DXGI_FORMAT = 26;
+pub const DXGI_FORMAT_R8G8B8A8_UNORM: DXGI_FORMAT = 28;
+pub const DXGI_FORMAT_R8G8B8A8_UNORM_SRGB: DXGI_FORMAT = 29;
+pub const DXGI_FORMAT_R8G8B8A8_UINT: DXGI_FORMAT = 30;
+pub const DXGI_FORMAT_R8G8B8A8_SNORM: DXGI_FORMAT = 31;
+pub const DXGI_FORMAT_R8G8B8A8_SINT: DXGI_FORMAT = 32;
+pub const DXGI_FORMAT_R16G16_FLOAT: DXGI_FORMAT = 34;
+pub const DXGI_FORMAT_R16G16_UNORM: DXGI_FORMAT = 35;
+pub const DXGI_FORMAT_R16G16_UINT: DXGI_FORMAT = 36;
+pub const DXGI_FORMAT_R16G16_SNORM: DXGI_FORMAT = 37;
+pub const DXGI_FORMAT_R16G16_SINT: DXGI_FORMAT = 38;
+pub const DXGI_FORMAT_R32_FLOAT: DXGI_FORMAT = 41;
+pub const DXGI_FORMAT_R32_UINT: DXGI_FORMAT = 42;
+pub const DXGI_FORMAT_R32_SINT: DXGI_FORMAT = 43;
+pub const DXGI_FORMAT_R8G8_UNORM: DXGI_FORMAT = 49;
+pub const DXGI_FORMAT_R8G8_UINT: DXGI_FORMAT = 50;
+pub const DXGI_FORMAT_R8G8_SNORM: DXGI_FORMAT = 51;
+pub const DXGI_FORMAT_R8G8_SINT: DXGI_FORMAT = 52;
+pub const DXGI_FORMAT_R16_FLOAT: DXGI_FORMAT = 54;
+pub const DXGI_FORMAT_R16_UNORM: DXGI_FORMAT = 56;
+pub const DXGI_FORMAT_R16_UINT: DXGI_FORMAT = 57;
+pub const DXGI_FORMAT_R16_SNORM: DXGI_FORMAT = 58;
+pub const DXGI_FORMAT_R16_SINT: DXGI_FORMAT = 59;
+pub const DXGI_FORMAT_R8_UNORM: DXGI_FORMAT = 61;
+pub const DXGI_FORMAT_R8_UINT: DXGI_FORMAT = 62;
+pub const DXGI_FORMAT_R8_SNORM: DXGI_FORMAT = 63;
+pub const DXGI_FORMAT_R8_SINT: DXGI_FORMAT = 64;
+pub const DXGI_FORMAT_B8G8R8A8_UNORM: DXGI_FORMAT = 87;
+pub const DXGI_FORMAT_B8G8R8X8_UNORM: DXGI_FORMAT = 88;
@@ -0,0 +1,129 @@
+//! Conversions for the small float formats: 16 bit halfs and the unsigned 11 and 10 bit floats
+//! of `R11G11B10_FLOAT`.  All of them have a 5 bit exponent with a bias of 15; they differ in the
+//! mantissa width and whether there is a sign bit.
+
+const EXP_BITS: u32 = 5;
+const EXP_BIAS: i32 = 15;
+const EXP_MAX: u32 = (1 << EXP_BITS) - 1;
+
+/// Decode a small float with `mant_bits` of mantissa, with a sign bit above the exponent if
+/// `signed`.
+pub(crate) fn decode_minifloat(bits: u32, mant_bits: u32, signed: bool) -> f32 {
+    let mant = bits & ((1 << mant_bits) - 1);
+    let exp = (bits >> mant_bits) & EXP_MAX;
+    let negative = signed && (bits >> (mant_bits + EXP_BITS)) & 1 != 0;
+    let mag = if exp == 0 {
+        // subnormal
+        mant as f32 * 2f32.powi(1 - EXP_BIAS - mant_bits as i32)
+    } else if exp == EXP_MAX {
+        if mant == 0 { f32::INFINITY } else { f32::NAN }
+    } else {
+        (1.0 + mant as f32 / (1 << mant_bits) as f32) * 2f32.powi(exp as i32 - EXP_BIAS)
+    };
+    if negative { -mag } else { mag }
+}
+
+/// Round `full >> shift` to nearest, ties to even.
+fn shift_round(full: u32, shift: u32) -> u32 {
+    let kept = full >> shift;
+    let rem = full & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if rem > half || (rem == half && kept & 1 != 0) {
+        kept + 1
+    } else {
+        kept
+    }
+}
+
+/// Encode to a small float, rounding to nearest even.  Values too large become infinity; for
+/// unsigned formats negative values become zero.
+pub(crate) fn encode_minifloat(f: f32, mant_bits: u32, signed: bool) -> u32 {
+    let sign = if signed && f.is_sign_negative() { 1 << (mant_bits + EXP_BITS) } else { 0 };
+    if f.is_nan() {
+        return sign | (EXP_MAX << mant_bits) | 1;
+    }
+    if !signed && f <= 0.0 {
+        return 0;
+    }
+    let b = f.abs().to_bits();
+    let exp32 = (b >> 23) as i32;
+    let mant32 = b & 0x7f_ffff;
+    let inf = EXP_MAX << mant_bits;
+    if exp32 == 0xff {
+        return sign | inf;
+    }
+    if exp32 == 0 {
+        // f32 subnormals are far below the smallest small float
+        return sign;
+    }
+    let e = exp32 - 127;
+    let full = mant32 | 0x80_0000;
+    // overflow of the rounded mantissa carries into the exponent, which is what we want
+    let mag = if e >= 1 - EXP_BIAS {
+        (((e + EXP_BIAS) as u32) << mant_bits) + shift_round(mant32, 23 - mant_bits)
+    } else {
+        let shift = (-EXP_BIAS - mant_bits as i32 + 24 - e) as u32;
+        if shift > 24 {
+            0
+        } else {
+            shift_round(full, shift)
+        }
+    };
+    sign | mag.min(inf)
+}
+
+pub fn f16_to_f32(h: u16) -> f32 {
+    decode_minifloat(h as u32, 10, true)
+}
+
+pub fn f32_to_f16(f: f32) -> u16 {
+    encode_minifloat(f, 10, true) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half() {
+        for (h, f) in [(0x0000, 0.0), (0x3c00, 1.0), (0xc000, -2.0), (0x3400, 0.25),
+            (0x7bff, 65504.0), (0x0400, 6.1035156e-5), (0x0001, 5.9604645e-8), (0x7c00, f32::INFINITY)] {
+            assert_eq!(f16_to_f32(h), f, "{:#x}", h);
+            assert_eq!(f32_to_f16(f), h, "{}", f);
+        }
+        assert!(f16_to_f32(0x7e00).is_nan());
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        // rounding: 1 + 2^-11 is halfway between 1 and the next half, and ties to even
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(1e-10), 0);
+        // rounds up out of the subnormals
+        assert_eq!(f32_to_f16(6.1035156e-5 - 1e-9), 0x0400);
+    }
+
+    #[test]
+    fn test_half_round_trip() {
+        // every finite half survives a trip through f32
+        for h in 0..=0xffffu16 {
+            let f = f16_to_f32(h);
+            if f.is_finite() {
+                assert_eq!(f32_to_f16(f), h, "{:#x}", h);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsigned_small_floats() {
+        // 11 bit: 6 bit mantissa
+        assert_eq!(decode_minifloat(15 << 6, 6, false), 1.0);
+        assert_eq!(encode_minifloat(1.0, 6, false), 15 << 6);
+        assert_eq!(encode_minifloat(-1.0, 6, false), 0);
+        assert_eq!(decode_minifloat(encode_minifloat(0.5, 5, false), 5, false), 0.5);
+        assert_eq!(decode_minifloat((30 << 6) | 0x3f, 6, false), 65024.0);
+        for bits in 0..(31 << 5) {
+            assert_eq!(encode_minifloat(decode_minifloat(bits, 5, false), 5, false), bits);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Decoding and encoding of vertex elements in the DXGI formats used by D3D11 input layouts.
+//! Every element is converted to and from four f32s (`[x, y, z, w]`, with components that the
+//! format doesn't have reading as the D3D defaults of 0 for xyz and 1 for w), so code that
+//! processes vertex data by semantic doesn't need to care how a particular game stores it.
+//! Normalized formats map to 0..1 (unorm) or -1..1 (snorm) and integer formats keep their
+//! integer values.
+//!
+//! It has no platform dependencies so it can be tested anywhere; the format constants have
+//! the same values as winapi's `DXGI_FORMAT_*`.
+
+mod codec;
+mod formats;
+mod half;
+
+pub use crate::codec::*;
+pub use crate::formats::*;
+pub use crate::half::*;
@@ -16,6 +16,19 @@ open MonoGameHelpers
 let ``Mesh: mono game helpers``() =
     Assert.AreEqual(500us, floatToHalfUint16(halfUint16ToFloat(500us)), "float conversion failed")
 
+[<Test>]
+let ``Mesh: unique triangle verts``() =
+    let verts,indices = MeshUtil.uniqueTriVerts monolith.Triangles
+    Assert.AreEqual(19, verts.Length, "incorrect unique vert count")
+    Assert.AreEqual(monolith.Triangles.Length * 3, indices.Length, "incorrect index count")
+    let triVerts = monolith.Triangles |> Array.collect (fun tri -> tri.Verts)
+    indices |> Array.iteri (fun i idx -> Assert.AreEqual(triVerts.[i], verts.[idx], sprintf "index %d refers to wrong vert" i))
+
+    Assert.AreEqual(2, MeshUtil.indexElemSize 65535)
+    Assert.AreEqual(4, MeshUtil.indexElemSize 65536)
+    Assert.AreEqual(indices.Length * 2, (MeshUtil.indexBytes 2 indices).Length)
+    Assert.AreEqual(indices.Length * 4, (MeshUtil.indexBytes 4 indices).Length)
+
 [<Test>]
 let ``Mesh: write``() =
     let objPath = Path.Combine(Util.TestDataDir, "monolith.TestWrite.mmobj")
@@ -64,11 +64,12 @@ let ``ModDBInterop: module functions``() =
         Assert.AreEqual (mmod.ModType, (ModDBInterop.modTypeToInt GPUReplacement) , sprintf "incorrect mod type: %A" mmod)
         Assert.AreEqual (mmod.PrimType, 4 , sprintf "incorrect prim type: %A" mmod)
         Assert.AreEqual (mmod.PrimCount, 36 , sprintf "incorrect prim count: %A" mmod)
-        Assert.AreEqual (mmod.VertCount, 24 , sprintf "incorrect vert count: %A" mmod)
+        // mods are indexed, so this is the number of unique position/uv/normal combinations
+        Assert.AreEqual (mmod.VertCount, 57 , sprintf "incorrect vert count: %A" mmod)
         Assert.AreEqual (mmod.RefPrimCount, 12 , sprintf "incorrect ref prim count: %A" mmod)
         Assert.AreEqual (mmod.RefVertCount, 8 , sprintf "incorrect ref vert count: %A" mmod)
-        Assert.AreEqual (mmod.IndexCount, 0 , sprintf "incorrect index count: %A" mmod)
-        Assert.AreEqual (mmod.IndexElemSizeBytes, 0 , sprintf "incorrect index size: %A" mmod)
+        Assert.AreEqual (mmod.IndexCount, 108 , sprintf "incorrect index count: %A" mmod)
+        Assert.AreEqual (mmod.IndexElemSizeBytes, 2 , sprintf "incorrect index size: %A" mmod)
         Assert.AreEqual (mmod.DeclSizeBytes, 72 , sprintf "incorrect decl size: %A" mmod)
         Assert.AreEqual (mmod.VertSizeBytes, 92 , sprintf "incorrect vert size: %A" mmod)
         Assert.AreEqual (mmod.Tex0Path, "" , sprintf "incorrect tex0 path: %A" mmod)
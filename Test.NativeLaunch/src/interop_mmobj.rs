@@ -4,7 +4,7 @@ use std::{ffi::OsStr};
 
 use winapi::um::winnt::WCHAR;
 
-use crate::load_mmobj::{BlendPair, FaceVert, Float3, Float2, MMObj};
+use mmobj::{BlendPair, FaceVert, Float3, Float2, MMObj};
 
 const MAX_FILEPATH_LEN: usize = 8192;
 const MAX_SHORT_STRING_LEN: usize = 512;